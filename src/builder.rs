@@ -0,0 +1,559 @@
+use std::{
+    convert::TryFrom,
+    fmt::{Display, Formatter},
+};
+
+use crate::{
+    bytecode::{
+        branch_targets, branch_targets_mut, encode_instructions, Condition, EncodeError,
+        Instruction,
+    },
+    class::{CodeAttribute, Constant, ExceptionInfo},
+    descriptor::{FieldType, MethodDescriptor},
+    pool::ConstantPool,
+    string::JStr,
+};
+
+///
+/// A position in the code being built by a [`CodeBuilder`], which branches and exception handlers can refer to before it is placed
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Label(u32);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BuildError {
+    /// A label was branched to but never placed, or placed after the last instruction
+    UnplacedLabel(Label),
+    /// The instructions could not be encoded. Indices count the instructions in the order they were added
+    Encode(EncodeError),
+    /// The instruction at `index` refers to a constant that is not of the kind it expects, so its effect on the stack is unknown
+    InvalidConstant { index: usize },
+    /// The instruction at `index` pops more values than are on the stack
+    StackUnderflow { index: usize },
+    /// The instruction at `index` can be reached with different numbers of values on the stack
+    InconsistentStack { index: usize },
+    /// The stack or the local variables need more than 65535 slots
+    TooLarge,
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::UnplacedLabel(label) => write!(
+                f,
+                "label {} is used but not placed before an instruction",
+                label.0
+            ),
+            BuildError::Encode(e) => e.fmt(f),
+            BuildError::InvalidConstant { index } => write!(
+                f,
+                "instruction {} refers to a constant of the wrong kind",
+                index
+            ),
+            BuildError::StackUnderflow { index } => {
+                write!(f, "instruction {} pops from an empty stack", index)
+            }
+            BuildError::InconsistentStack { index } => write!(
+                f,
+                "instruction {} is reached with different stack depths",
+                index
+            ),
+            BuildError::TooLarge => f.write_str("code needs more than 65535 stack or local slots"),
+        }
+    }
+}
+
+impl std::error::Error for BuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BuildError::Encode(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<EncodeError> for BuildError {
+    fn from(e: EncodeError) -> Self {
+        BuildError::Encode(e)
+    }
+}
+
+///
+/// Assembles a [`CodeAttribute`] from instructions, with branches to [`Label`]s rather than offsets.
+///
+/// Labels are resolved, branches and switches are encoded, and `max_stack` and `max_locals` are computed when [`CodeBuilder::build`] is called.
+/// No StackMapTable is generated, so code for class files of version 50 and later that branches needs one added by the caller
+pub struct CodeBuilder<'a> {
+    pool: &'a mut ConstantPool,
+    /// The instructions, each labeled by its index, with the number of a [`Label`] as each branch target
+    insns: Vec<(u32, Instruction)>,
+    /// The index of the instruction each label was placed before, by the number of the label
+    labels: Vec<Option<usize>>,
+    exceptions: Vec<(Label, Label, Label, u16)>,
+    max_stack: Option<u16>,
+    min_locals: u16,
+}
+
+impl<'a> CodeBuilder<'a> {
+    ///
+    /// Creates a builder for code that refers to constants in `pool`
+    pub fn new(pool: &'a mut ConstantPool) -> Self {
+        Self {
+            pool,
+            insns: Vec::new(),
+            labels: Vec::new(),
+            exceptions: Vec::new(),
+            max_stack: None,
+            min_locals: 0,
+        }
+    }
+
+    ///
+    /// The constant pool the code refers to, for adding the constants of instructions
+    pub fn pool(&mut self) -> &mut ConstantPool {
+        self.pool
+    }
+
+    ///
+    /// Creates a label that has not been placed yet
+    pub fn label(&mut self) -> Label {
+        self.labels.push(None);
+        Label(self.labels.len() as u32 - 1)
+    }
+
+    ///
+    /// Places `label` before the next instruction to be added, or at the end of the code if there is none.
+    ///
+    /// Placing a label again moves it. A label that was not created by this builder is left unplaced, so [`CodeBuilder::build`] reports branches to it
+    pub fn place(&mut self, label: Label) -> &mut Self {
+        if let Some(placed) = self.labels.get_mut(label.0 as usize) {
+            *placed = Some(self.insns.len());
+        }
+        self
+    }
+
+    ///
+    /// Adds an instruction that does not branch. Use [`CodeBuilder::goto`], [`CodeBuilder::branch`], and the switch methods for those that do.
+    ///
+    /// The targets of a branch added here are taken as label numbers, so a raw offset is reported as an unplaced label by [`CodeBuilder::build`]
+    pub fn push(&mut self, insn: Instruction) -> &mut Self {
+        self.insns.push((self.insns.len() as u32, insn));
+        self
+    }
+
+    pub fn goto(&mut self, target: Label) -> &mut Self {
+        self.push(Instruction::Goto(target.0))
+    }
+
+    pub fn branch(&mut self, cond: Condition, target: Label) -> &mut Self {
+        self.push(Instruction::If {
+            cond,
+            target: target.0,
+        })
+    }
+
+    ///
+    /// Adds a `tableswitch` that goes to `targets[i]` for the value `low + i`, and to `default` for any other value
+    pub fn table_switch(&mut self, low: i32, targets: &[Label], default: Label) -> &mut Self {
+        self.push(Instruction::TableSwitch {
+            default: default.0,
+            low,
+            targets: targets.iter().map(|label| label.0).collect(),
+        })
+    }
+
+    pub fn lookup_switch(&mut self, pairs: &[(i32, Label)], default: Label) -> &mut Self {
+        self.push(Instruction::LookupSwitch {
+            default: default.0,
+            pairs: pairs.iter().map(|&(key, label)| (key, label.0)).collect(),
+        })
+    }
+
+    ///
+    /// Adds an exception handler at `handler` for the instructions from `start` up to `end`, catching the Class constant `catch_type`, or everything if it is 0
+    pub fn try_catch(
+        &mut self,
+        start: Label,
+        end: Label,
+        handler: Label,
+        catch_type: u16,
+    ) -> &mut Self {
+        self.exceptions.push((start, end, handler, catch_type));
+        self
+    }
+
+    ///
+    /// Uses `max_stack` rather than computing it
+    pub fn set_max_stack(&mut self, max_stack: u16) -> &mut Self {
+        self.max_stack = Some(max_stack);
+        self
+    }
+
+    ///
+    /// Makes `max_locals` at least `slots`, such as the slots taken by `this` and the parameters, which the instructions need not use
+    pub fn reserve_locals(&mut self, slots: u16) -> &mut Self {
+        self.min_locals = self.min_locals.max(slots);
+        self
+    }
+
+    ///
+    /// Resolves the labels and encodes the instructions.
+    ///
+    /// Unless given by [`CodeBuilder::set_max_stack`], `max_stack` is the deepest the stack gets on any path through the code, with a handler starting with only the exception on the stack
+    pub fn build(self) -> Result<CodeAttribute, BuildError> {
+        let CodeBuilder {
+            pool,
+            mut insns,
+            labels,
+            exceptions,
+            max_stack: explicit_max_stack,
+            min_locals,
+        } = self;
+        let end = insns.len();
+        let resolve = |label: Label| {
+            labels
+                .get(label.0 as usize)
+                .copied()
+                .flatten()
+                .ok_or(BuildError::UnplacedLabel(label))
+        };
+
+        for (_, insn) in &mut insns {
+            for target in branch_targets_mut(insn) {
+                let index = resolve(Label(*target))?;
+                if index == end {
+                    return Err(BuildError::UnplacedLabel(Label(*target)));
+                }
+                *target = index as u32;
+            }
+        }
+        let mut handlers = Vec::with_capacity(exceptions.len());
+        for (start, stop, handler, catch_type) in exceptions {
+            let handler_index = resolve(handler)?;
+            if handler_index == end {
+                return Err(BuildError::UnplacedLabel(handler));
+            }
+            handlers.push((resolve(start)?, resolve(stop)?, handler_index, catch_type));
+        }
+
+        let max_stack = match explicit_max_stack {
+            Some(max_stack) => max_stack,
+            None => max_stack(&insns, &handlers, pool)?,
+        };
+        let max_locals = insns
+            .iter()
+            .filter_map(|(_, insn)| local_slots(insn))
+            .try_fold(min_locals, |max, end| {
+                u16::try_from(end).map(|end| max.max(end))
+            })
+            .map_err(|_| BuildError::TooLarge)?;
+
+        let encoded = encode_instructions(&insns)?;
+        let offset = |index: usize| {
+            encoded
+                .offsets
+                .get(index)
+                .map_or(encoded.code.len() as u16, |&offset| offset as u16)
+        };
+        Ok(CodeAttribute {
+            max_stack,
+            max_locals,
+            exceptions: handlers
+                .into_iter()
+                .map(|(start, end, handler, catch_type)| ExceptionInfo {
+                    start_pc: offset(start),
+                    end_pc: offset(end),
+                    handler_pc: offset(handler),
+                    catch_type,
+                })
+                .collect(),
+            code: encoded.code,
+            attributes: Vec::new(),
+        })
+    }
+}
+
+///
+/// One past the highest local variable slot an instruction uses, if it uses one
+fn local_slots(insn: &Instruction) -> Option<u32> {
+    match *insn {
+        Instruction::ILoad(index)
+        | Instruction::FLoad(index)
+        | Instruction::ALoad(index)
+        | Instruction::IStore(index)
+        | Instruction::FStore(index)
+        | Instruction::AStore(index)
+        | Instruction::Ret(index)
+        | Instruction::IInc { index, .. } => Some(index as u32 + 1),
+        Instruction::LLoad(index)
+        | Instruction::DLoad(index)
+        | Instruction::LStore(index)
+        | Instruction::DStore(index) => Some(index as u32 + 2),
+        _ => None,
+    }
+}
+
+fn type_slots(ty: &FieldType) -> u32 {
+    match ty {
+        FieldType::Long | FieldType::Double => 2,
+        _ => 1,
+    }
+}
+
+///
+/// The descriptor of the member or call site an instruction refers to
+fn member_descriptor(pool: &ConstantPool, index: u16) -> Option<&JStr> {
+    match pool.get(index)? {
+        Constant::InvokeDynamic { name_and_type, .. } => {
+            pool.name_and_type(*name_and_type).map(|(_, desc)| desc)
+        }
+        _ => pool.member_ref(index).map(|(_, _, desc)| desc),
+    }
+}
+
+///
+/// The number of stack slots an instruction pops and pushes, or None if it refers to a constant that is not of the kind it expects
+fn stack_effect(insn: &Instruction, pool: &ConstantPool) -> Option<(u32, u32)> {
+    use Instruction::*;
+    let field = |index: u16| {
+        let desc = member_descriptor(pool, index)?;
+        FieldType::parse(desc).ok().map(|ty| type_slots(&ty))
+    };
+    let method = |index: u16| {
+        let desc = member_descriptor(pool, index)?;
+        let desc = MethodDescriptor::parse(desc).ok()?;
+        Some((
+            desc.params.iter().map(type_slots).sum::<u32>(),
+            desc.ret.as_ref().map_or(0, type_slots),
+        ))
+    };
+    Some(match *insn {
+        Nop | Goto(_) | Ret(_) | IInc { .. } | Return => (0, 0),
+        AConstNull | IConst(_) | FConst(_) | BiPush(_) | SiPush(_) | Ldc(_) | ILoad(_)
+        | FLoad(_) | ALoad(_) | Jsr(_) | New(_) => (0, 1),
+        LConst(_) | DConst(_) | Ldc2W(_) | LLoad(_) | DLoad(_) => (0, 2),
+        IALoad | FALoad | AALoad | BALoad | CALoad | SALoad => (2, 1),
+        LALoad | DALoad => (2, 2),
+        IStore(_)
+        | FStore(_)
+        | AStore(_)
+        | Pop
+        | IReturn
+        | FReturn
+        | AReturn
+        | AThrow
+        | MonitorEnter
+        | MonitorExit
+        | TableSwitch { .. }
+        | LookupSwitch { .. } => (1, 0),
+        LStore(_) | DStore(_) | Pop2 | LReturn | DReturn => (2, 0),
+        IAStore | FAStore | AAStore | BAStore | CAStore | SAStore => (3, 0),
+        LAStore | DAStore => (4, 0),
+        Dup => (1, 2),
+        DupX1 => (2, 3),
+        DupX2 => (3, 4),
+        Dup2 => (2, 4),
+        Dup2X1 => (3, 5),
+        Dup2X2 => (4, 6),
+        Swap => (2, 2),
+        IAdd | FAdd | ISub | FSub | IMul | FMul | IDiv | FDiv | IRem | FRem | IShl | IShr
+        | IUShr | IAnd | IOr | IXor | FCmpL | FCmpG => (2, 1),
+        LAdd | DAdd | LSub | DSub | LMul | DMul | LDiv | DDiv | LRem | DRem | LAnd | LOr | LXor => {
+            (4, 2)
+        }
+        LShl | LShr | LUShr => (3, 2),
+        INeg | FNeg | I2F | F2I | I2B | I2C | I2S | NewArray(_) | ANewArray(_) | ArrayLength
+        | CheckCast(_) | InstanceOf(_) => (1, 1),
+        LNeg | DNeg | L2D | D2L => (2, 2),
+        I2L | I2D | F2L | F2D => (1, 2),
+        L2I | L2F | D2I | D2F => (2, 1),
+        LCmp | DCmpL | DCmpG => (4, 1),
+        If { cond, .. } => match cond {
+            Condition::Eq
+            | Condition::Ne
+            | Condition::Lt
+            | Condition::Ge
+            | Condition::Gt
+            | Condition::Le
+            | Condition::Null
+            | Condition::NonNull => (1, 0),
+            _ => (2, 0),
+        },
+        GetStatic(index) => (0, field(index)?),
+        PutStatic(index) => (field(index)?, 0),
+        GetField(index) => (1, field(index)?),
+        PutField(index) => (1 + field(index)?, 0),
+        InvokeVirtual(index) | InvokeSpecial(index) | InvokeInterface { index, .. } => {
+            let (params, ret) = method(index)?;
+            (params + 1, ret)
+        }
+        InvokeStatic(index) | InvokeDynamic(index) => method(index)?,
+        MultiANewArray { dims, .. } => (dims as u32, 1),
+    })
+}
+
+///
+/// Whether execution can continue to the next instruction after `insn`
+fn falls_through(insn: &Instruction) -> bool {
+    !matches!(
+        insn,
+        Instruction::Goto(_)
+            | Instruction::Ret(_)
+            | Instruction::TableSwitch { .. }
+            | Instruction::LookupSwitch { .. }
+            | Instruction::IReturn
+            | Instruction::LReturn
+            | Instruction::FReturn
+            | Instruction::DReturn
+            | Instruction::AReturn
+            | Instruction::Return
+            | Instruction::AThrow
+    )
+}
+
+///
+/// Records that the instruction at `index` is reached with `depth` slots on the stack, queueing it the first time
+fn reach(
+    depths: &mut [Option<u32>],
+    pending: &mut Vec<usize>,
+    index: usize,
+    depth: u32,
+) -> Result<(), BuildError> {
+    match depths[index] {
+        None => {
+            depths[index] = Some(depth);
+            pending.push(index);
+            Ok(())
+        }
+        Some(known) if known != depth => Err(BuildError::InconsistentStack { index }),
+        Some(_) => Ok(()),
+    }
+}
+
+///
+/// Follows every path through the code from the first instruction and each handler, with branch targets resolved to instruction indices
+fn max_stack(
+    insns: &[(u32, Instruction)],
+    handlers: &[(usize, usize, usize, u16)],
+    pool: &ConstantPool,
+) -> Result<u16, BuildError> {
+    let mut depths = vec![None; insns.len()];
+    let mut pending = Vec::new();
+    if !insns.is_empty() {
+        reach(&mut depths, &mut pending, 0, 0)?;
+    }
+    for &(_, _, handler, _) in handlers {
+        reach(&mut depths, &mut pending, handler, 1)?;
+    }
+
+    let mut max = 0;
+    while let Some(index) = pending.pop() {
+        let insn = &insns[index].1;
+        let depth = depths[index].unwrap_or(0);
+        let (pops, pushes) =
+            stack_effect(insn, pool).ok_or(BuildError::InvalidConstant { index })?;
+        let after = depth
+            .checked_sub(pops)
+            .ok_or(BuildError::StackUnderflow { index })?
+            + pushes;
+        max = max.max(depth).max(after);
+        for target in branch_targets(insn) {
+            reach(&mut depths, &mut pending, target as usize, after)?;
+        }
+        if falls_through(insn) && index + 1 < insns.len() {
+            // A subroutine pops the return address `jsr` pushes before it returns
+            let next = if let Instruction::Jsr(_) = insn {
+                depth
+            } else {
+                after
+            };
+            reach(&mut depths, &mut pending, index + 1, next)?;
+        }
+    }
+    u16::try_from(max).map_err(|_| BuildError::TooLarge)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::Instructions;
+
+    #[test]
+    fn small_loop_disassembles_back() {
+        let mut pool = ConstantPool::new();
+        let mut builder = CodeBuilder::new(&mut pool);
+        let head = builder.label();
+        let end = builder.label();
+        builder
+            .reserve_locals(1)
+            .push(Instruction::IConst(0))
+            .push(Instruction::IStore(1))
+            .place(head)
+            .push(Instruction::ILoad(1))
+            .push(Instruction::ILoad(0))
+            .branch(Condition::ICmpGe, end)
+            .push(Instruction::IInc { index: 1, value: 1 })
+            .goto(head)
+            .place(end)
+            .push(Instruction::Return);
+        let code = builder.build().unwrap();
+
+        assert_eq!(code.max_stack, 2);
+        assert_eq!(code.max_locals, 2);
+        let insns = Instructions::new(&code.code)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(
+            insns,
+            [
+                (0, Instruction::IConst(0)),
+                (1, Instruction::IStore(1)),
+                (2, Instruction::ILoad(1)),
+                (3, Instruction::ILoad(0)),
+                (
+                    4,
+                    Instruction::If {
+                        cond: Condition::ICmpGe,
+                        target: 13
+                    }
+                ),
+                (7, Instruction::IInc { index: 1, value: 1 }),
+                (10, Instruction::Goto(2)),
+                (13, Instruction::Return),
+            ]
+        );
+    }
+
+    #[test]
+    fn unplaced_label_is_an_error() {
+        let mut pool = ConstantPool::new();
+        let mut builder = CodeBuilder::new(&mut pool);
+        let nowhere = builder.label();
+        builder.goto(nowhere);
+        assert_eq!(
+            builder.build().unwrap_err(),
+            BuildError::UnplacedLabel(nowhere)
+        );
+    }
+
+    #[test]
+    fn unknown_labels_are_errors() {
+        let mut other_pool = ConstantPool::new();
+        let mut other = CodeBuilder::new(&mut other_pool);
+        let foreign = (0..3).map(|_| other.label()).last().unwrap();
+
+        let mut pool = ConstantPool::new();
+        let mut builder = CodeBuilder::new(&mut pool);
+        builder.push(Instruction::Goto(5));
+        assert_eq!(
+            builder.build().unwrap_err(),
+            BuildError::UnplacedLabel(Label(5))
+        );
+
+        let mut builder = CodeBuilder::new(&mut pool);
+        builder.place(foreign).push(Instruction::Nop).goto(foreign);
+        assert_eq!(
+            builder.build().unwrap_err(),
+            BuildError::UnplacedLabel(foreign)
+        );
+    }
+}
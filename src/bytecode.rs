@@ -0,0 +1,1987 @@
+use std::{
+    convert::{TryFrom, TryInto},
+    fmt::{Display, Formatter},
+    iter::FusedIterator,
+};
+
+use crate::{
+    class::{
+        Attribute, CodeAttribute, Constant, StackMapFrame, TypeAnnotation, TypeAnnotationTarget,
+        VerificationInfo,
+    },
+    pool::ConstantPool,
+    string::JStr,
+};
+
+///
+/// The condition tested by a conditional branch
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Condition {
+    /// `ifeq`: an int is zero
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le,
+    /// `if_icmpeq`: two ints are equal
+    ICmpEq,
+    ICmpNe,
+    ICmpLt,
+    ICmpGe,
+    ICmpGt,
+    ICmpLe,
+    /// `if_acmpeq`: two references are the same
+    ACmpEq,
+    ACmpNe,
+    Null,
+    NonNull,
+}
+
+impl Condition {
+    fn from_opcode(opcode: u8) -> Option<Self> {
+        Some(match opcode {
+            0x99 => Condition::Eq,
+            0x9a => Condition::Ne,
+            0x9b => Condition::Lt,
+            0x9c => Condition::Ge,
+            0x9d => Condition::Gt,
+            0x9e => Condition::Le,
+            0x9f => Condition::ICmpEq,
+            0xa0 => Condition::ICmpNe,
+            0xa1 => Condition::ICmpLt,
+            0xa2 => Condition::ICmpGe,
+            0xa3 => Condition::ICmpGt,
+            0xa4 => Condition::ICmpLe,
+            0xa5 => Condition::ACmpEq,
+            0xa6 => Condition::ACmpNe,
+            0xc6 => Condition::Null,
+            0xc7 => Condition::NonNull,
+            _ => return None,
+        })
+    }
+
+    pub fn opcode(self) -> u8 {
+        match self {
+            Condition::Eq => 0x99,
+            Condition::Ne => 0x9a,
+            Condition::Lt => 0x9b,
+            Condition::Ge => 0x9c,
+            Condition::Gt => 0x9d,
+            Condition::Le => 0x9e,
+            Condition::ICmpEq => 0x9f,
+            Condition::ICmpNe => 0xa0,
+            Condition::ICmpLt => 0xa1,
+            Condition::ICmpGe => 0xa2,
+            Condition::ICmpGt => 0xa3,
+            Condition::ICmpLe => 0xa4,
+            Condition::ACmpEq => 0xa5,
+            Condition::ACmpNe => 0xa6,
+            Condition::Null => 0xc6,
+            Condition::NonNull => 0xc7,
+        }
+    }
+
+    ///
+    /// Returns the condition that holds exactly when `self` does not
+    pub fn negate(self) -> Self {
+        match self {
+            Condition::Eq => Condition::Ne,
+            Condition::Ne => Condition::Eq,
+            Condition::Lt => Condition::Ge,
+            Condition::Ge => Condition::Lt,
+            Condition::Gt => Condition::Le,
+            Condition::Le => Condition::Gt,
+            Condition::ICmpEq => Condition::ICmpNe,
+            Condition::ICmpNe => Condition::ICmpEq,
+            Condition::ICmpLt => Condition::ICmpGe,
+            Condition::ICmpGe => Condition::ICmpLt,
+            Condition::ICmpGt => Condition::ICmpLe,
+            Condition::ICmpLe => Condition::ICmpGt,
+            Condition::ACmpEq => Condition::ACmpNe,
+            Condition::ACmpNe => Condition::ACmpEq,
+            Condition::Null => Condition::NonNull,
+            Condition::NonNull => Condition::Null,
+        }
+    }
+}
+
+///
+/// A decoded JVM instruction.
+///
+/// Instructions with several encodings are normalized: `iload_0` is `ILoad(0)`, the `wide` forms of loads, stores, `ret`, and `iinc` are the same as the narrow forms, `ldc_w` is `Ldc`, and `goto_w` is `Goto`.
+/// Branch targets are absolute offsets into the method's code, and constant pool operands are indices into the class's pool
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Instruction {
+    Nop,
+    AConstNull,
+    /// `iconst_m1` through `iconst_5`
+    IConst(i8),
+    LConst(i8),
+    FConst(u8),
+    DConst(u8),
+    BiPush(i8),
+    SiPush(i16),
+    /// `ldc` or `ldc_w`
+    Ldc(u16),
+    Ldc2W(u16),
+    ILoad(u16),
+    LLoad(u16),
+    FLoad(u16),
+    DLoad(u16),
+    ALoad(u16),
+    IALoad,
+    LALoad,
+    FALoad,
+    DALoad,
+    AALoad,
+    BALoad,
+    CALoad,
+    SALoad,
+    IStore(u16),
+    LStore(u16),
+    FStore(u16),
+    DStore(u16),
+    AStore(u16),
+    IAStore,
+    LAStore,
+    FAStore,
+    DAStore,
+    AAStore,
+    BAStore,
+    CAStore,
+    SAStore,
+    Pop,
+    Pop2,
+    Dup,
+    DupX1,
+    DupX2,
+    Dup2,
+    Dup2X1,
+    Dup2X2,
+    Swap,
+    IAdd,
+    LAdd,
+    FAdd,
+    DAdd,
+    ISub,
+    LSub,
+    FSub,
+    DSub,
+    IMul,
+    LMul,
+    FMul,
+    DMul,
+    IDiv,
+    LDiv,
+    FDiv,
+    DDiv,
+    IRem,
+    LRem,
+    FRem,
+    DRem,
+    INeg,
+    LNeg,
+    FNeg,
+    DNeg,
+    IShl,
+    LShl,
+    IShr,
+    LShr,
+    IUShr,
+    LUShr,
+    IAnd,
+    LAnd,
+    IOr,
+    LOr,
+    IXor,
+    LXor,
+    /// `iinc` or `wide iinc`. The narrow form holds a u8 index and an i8 value
+    IInc {
+        index: u16,
+        value: i16,
+    },
+    I2L,
+    I2F,
+    I2D,
+    L2I,
+    L2F,
+    L2D,
+    F2I,
+    F2L,
+    F2D,
+    D2I,
+    D2L,
+    D2F,
+    I2B,
+    I2C,
+    I2S,
+    LCmp,
+    FCmpL,
+    FCmpG,
+    DCmpL,
+    DCmpG,
+    If {
+        cond: Condition,
+        target: u32,
+    },
+    /// `goto` or `goto_w`
+    Goto(u32),
+    /// `jsr` or `jsr_w`. Only allowed before class file version 51
+    Jsr(u32),
+    /// `ret` or `wide ret`. Only allowed before class file version 51
+    Ret(u16),
+    TableSwitch {
+        default: u32,
+        low: i32,
+        /// The targets for `low`, `low + 1`, and so on
+        targets: Vec<u32>,
+    },
+    LookupSwitch {
+        default: u32,
+        pairs: Vec<(i32, u32)>,
+    },
+    IReturn,
+    LReturn,
+    FReturn,
+    DReturn,
+    AReturn,
+    Return,
+    GetStatic(u16),
+    PutStatic(u16),
+    GetField(u16),
+    PutField(u16),
+    InvokeVirtual(u16),
+    InvokeSpecial(u16),
+    InvokeStatic(u16),
+    InvokeInterface {
+        index: u16,
+        count: u8,
+    },
+    InvokeDynamic(u16),
+    New(u16),
+    /// `newarray`, with the `atype` of the primitive element type
+    NewArray(u8),
+    ANewArray(u16),
+    ArrayLength,
+    AThrow,
+    CheckCast(u16),
+    InstanceOf(u16),
+    MonitorEnter,
+    MonitorExit,
+    MultiANewArray {
+        class: u16,
+        dims: u8,
+    },
+}
+
+impl Instruction {
+    ///
+    /// Returns the constant pool index this instruction refers to, if any
+    pub fn constant_index(&self) -> Option<u16> {
+        match self {
+            Instruction::Ldc(idx)
+            | Instruction::Ldc2W(idx)
+            | Instruction::GetStatic(idx)
+            | Instruction::PutStatic(idx)
+            | Instruction::GetField(idx)
+            | Instruction::PutField(idx)
+            | Instruction::InvokeVirtual(idx)
+            | Instruction::InvokeSpecial(idx)
+            | Instruction::InvokeStatic(idx)
+            | Instruction::InvokeInterface { index: idx, .. }
+            | Instruction::InvokeDynamic(idx)
+            | Instruction::New(idx)
+            | Instruction::ANewArray(idx)
+            | Instruction::CheckCast(idx)
+            | Instruction::InstanceOf(idx)
+            | Instruction::MultiANewArray { class: idx, .. } => Some(*idx),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The code ended in the middle of the instruction at `pc`
+    UnexpectedEnd {
+        pc: u32,
+    },
+    InvalidOpcode {
+        pc: u32,
+        opcode: u8,
+    },
+    /// `wide` was followed by an opcode it cannot modify
+    InvalidWideOpcode {
+        pc: u32,
+        opcode: u8,
+    },
+    /// A branch would leave the range of code offsets
+    InvalidBranchTarget {
+        pc: u32,
+        target: i64,
+    },
+    /// A `tableswitch` has `high < low`
+    InvalidSwitchRange {
+        pc: u32,
+        low: i32,
+        high: i32,
+    },
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DecodeError::UnexpectedEnd { pc } => {
+                write!(f, "code ends in the middle of the instruction at {}", pc)
+            }
+            DecodeError::InvalidOpcode { pc, opcode } => {
+                write!(f, "invalid opcode {:#04x} at {}", opcode, pc)
+            }
+            DecodeError::InvalidWideOpcode { pc, opcode } => {
+                write!(f, "opcode {:#04x} cannot follow wide at {}", opcode, pc)
+            }
+            DecodeError::InvalidBranchTarget { pc, target } => {
+                write!(f, "branch at {} targets {}", pc, target)
+            }
+            DecodeError::InvalidSwitchRange { pc, low, high } => {
+                write!(
+                    f,
+                    "tableswitch at {} has low {} above high {}",
+                    pc, low, high
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+///
+/// An iterator over the instructions of a method's code, and the offset each starts at.
+///
+/// After an error, the iterator is exhausted
+#[derive(Clone, Debug)]
+pub struct Instructions<'a> {
+    code: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Instructions<'a> {
+    pub fn new(code: &'a [u8]) -> Self {
+        Self { code, pos: 0 }
+    }
+
+    fn array<const N: usize>(&mut self, pc: u32) -> Result<[u8; N], DecodeError> {
+        let bytes = self
+            .code
+            .get(self.pos..self.pos + N)
+            .ok_or(DecodeError::UnexpectedEnd { pc })?;
+        self.pos += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self, pc: u32) -> Result<u8, DecodeError> {
+        Ok(self.array::<1>(pc)?[0])
+    }
+
+    fn u16(&mut self, pc: u32) -> Result<u16, DecodeError> {
+        Ok(u16::from_be_bytes(self.array(pc)?))
+    }
+
+    fn i32(&mut self, pc: u32) -> Result<i32, DecodeError> {
+        Ok(i32::from_be_bytes(self.array(pc)?))
+    }
+
+    fn target(pc: u32, offset: i32) -> Result<u32, DecodeError> {
+        let target = pc as i64 + offset as i64;
+        u32::try_from(target).map_err(|_| DecodeError::InvalidBranchTarget { pc, target })
+    }
+
+    fn decode(&mut self) -> Result<Instruction, DecodeError> {
+        let pc = self.pos as u32;
+        let opcode = self.u8(pc)?;
+        Ok(match opcode {
+            0x00 => Instruction::Nop,
+            0x01 => Instruction::AConstNull,
+            0x02..=0x08 => Instruction::IConst(opcode as i8 - 0x03),
+            0x09..=0x0a => Instruction::LConst(opcode as i8 - 0x09),
+            0x0b..=0x0d => Instruction::FConst(opcode - 0x0b),
+            0x0e..=0x0f => Instruction::DConst(opcode - 0x0e),
+            0x10 => Instruction::BiPush(self.u8(pc)? as i8),
+            0x11 => Instruction::SiPush(self.u16(pc)? as i16),
+            0x12 => Instruction::Ldc(self.u8(pc)? as u16),
+            0x13 => Instruction::Ldc(self.u16(pc)?),
+            0x14 => Instruction::Ldc2W(self.u16(pc)?),
+            0x15..=0x19 | 0x36..=0x3a | 0xa9 => {
+                let index = self.u8(pc)? as u16;
+                Self::local(opcode, index)
+            }
+            0x1a..=0x2d => {
+                let index = ((opcode - 0x1a) % 4) as u16;
+                Self::local(0x15 + (opcode - 0x1a) / 4, index)
+            }
+            0x2e => Instruction::IALoad,
+            0x2f => Instruction::LALoad,
+            0x30 => Instruction::FALoad,
+            0x31 => Instruction::DALoad,
+            0x32 => Instruction::AALoad,
+            0x33 => Instruction::BALoad,
+            0x34 => Instruction::CALoad,
+            0x35 => Instruction::SALoad,
+            0x3b..=0x4e => {
+                let index = ((opcode - 0x3b) % 4) as u16;
+                Self::local(0x36 + (opcode - 0x3b) / 4, index)
+            }
+            0x4f => Instruction::IAStore,
+            0x50 => Instruction::LAStore,
+            0x51 => Instruction::FAStore,
+            0x52 => Instruction::DAStore,
+            0x53 => Instruction::AAStore,
+            0x54 => Instruction::BAStore,
+            0x55 => Instruction::CAStore,
+            0x56 => Instruction::SAStore,
+            0x57 => Instruction::Pop,
+            0x58 => Instruction::Pop2,
+            0x59 => Instruction::Dup,
+            0x5a => Instruction::DupX1,
+            0x5b => Instruction::DupX2,
+            0x5c => Instruction::Dup2,
+            0x5d => Instruction::Dup2X1,
+            0x5e => Instruction::Dup2X2,
+            0x5f => Instruction::Swap,
+            0x60 => Instruction::IAdd,
+            0x61 => Instruction::LAdd,
+            0x62 => Instruction::FAdd,
+            0x63 => Instruction::DAdd,
+            0x64 => Instruction::ISub,
+            0x65 => Instruction::LSub,
+            0x66 => Instruction::FSub,
+            0x67 => Instruction::DSub,
+            0x68 => Instruction::IMul,
+            0x69 => Instruction::LMul,
+            0x6a => Instruction::FMul,
+            0x6b => Instruction::DMul,
+            0x6c => Instruction::IDiv,
+            0x6d => Instruction::LDiv,
+            0x6e => Instruction::FDiv,
+            0x6f => Instruction::DDiv,
+            0x70 => Instruction::IRem,
+            0x71 => Instruction::LRem,
+            0x72 => Instruction::FRem,
+            0x73 => Instruction::DRem,
+            0x74 => Instruction::INeg,
+            0x75 => Instruction::LNeg,
+            0x76 => Instruction::FNeg,
+            0x77 => Instruction::DNeg,
+            0x78 => Instruction::IShl,
+            0x79 => Instruction::LShl,
+            0x7a => Instruction::IShr,
+            0x7b => Instruction::LShr,
+            0x7c => Instruction::IUShr,
+            0x7d => Instruction::LUShr,
+            0x7e => Instruction::IAnd,
+            0x7f => Instruction::LAnd,
+            0x80 => Instruction::IOr,
+            0x81 => Instruction::LOr,
+            0x82 => Instruction::IXor,
+            0x83 => Instruction::LXor,
+            0x84 => Instruction::IInc {
+                index: self.u8(pc)? as u16,
+                value: self.u8(pc)? as i8 as i16,
+            },
+            0x85 => Instruction::I2L,
+            0x86 => Instruction::I2F,
+            0x87 => Instruction::I2D,
+            0x88 => Instruction::L2I,
+            0x89 => Instruction::L2F,
+            0x8a => Instruction::L2D,
+            0x8b => Instruction::F2I,
+            0x8c => Instruction::F2L,
+            0x8d => Instruction::F2D,
+            0x8e => Instruction::D2I,
+            0x8f => Instruction::D2L,
+            0x90 => Instruction::D2F,
+            0x91 => Instruction::I2B,
+            0x92 => Instruction::I2C,
+            0x93 => Instruction::I2S,
+            0x94 => Instruction::LCmp,
+            0x95 => Instruction::FCmpL,
+            0x96 => Instruction::FCmpG,
+            0x97 => Instruction::DCmpL,
+            0x98 => Instruction::DCmpG,
+            0x99..=0xa6 | 0xc6..=0xc7 => Instruction::If {
+                cond: Condition::from_opcode(opcode).unwrap(),
+                target: Self::target(pc, self.u16(pc)? as i16 as i32)?,
+            },
+            0xa7 => Instruction::Goto(Self::target(pc, self.u16(pc)? as i16 as i32)?),
+            0xa8 => Instruction::Jsr(Self::target(pc, self.u16(pc)? as i16 as i32)?),
+            0xaa | 0xab => {
+                self.pos += (4 - self.pos % 4) % 4;
+                if self.pos > self.code.len() {
+                    return Err(DecodeError::UnexpectedEnd { pc });
+                }
+                let default = Self::target(pc, self.i32(pc)?)?;
+                if opcode == 0xaa {
+                    let low = self.i32(pc)?;
+                    let high = self.i32(pc)?;
+                    if high < low {
+                        return Err(DecodeError::InvalidSwitchRange { pc, low, high });
+                    }
+                    let count = (high as i64 - low as i64 + 1) as usize;
+                    if count > (self.code.len() - self.pos) / 4 {
+                        return Err(DecodeError::UnexpectedEnd { pc });
+                    }
+                    let targets = (0..count)
+                        .map(|_| Self::target(pc, self.i32(pc)?))
+                        .collect::<Result<_, _>>()?;
+                    Instruction::TableSwitch {
+                        default,
+                        low,
+                        targets,
+                    }
+                } else {
+                    let count = self.i32(pc)?;
+                    if count < 0 || count as usize > (self.code.len() - self.pos) / 8 {
+                        return Err(DecodeError::UnexpectedEnd { pc });
+                    }
+                    let pairs = (0..count)
+                        .map(|_| Ok((self.i32(pc)?, Self::target(pc, self.i32(pc)?)?)))
+                        .collect::<Result<_, _>>()?;
+                    Instruction::LookupSwitch { default, pairs }
+                }
+            }
+            0xac => Instruction::IReturn,
+            0xad => Instruction::LReturn,
+            0xae => Instruction::FReturn,
+            0xaf => Instruction::DReturn,
+            0xb0 => Instruction::AReturn,
+            0xb1 => Instruction::Return,
+            0xb2 => Instruction::GetStatic(self.u16(pc)?),
+            0xb3 => Instruction::PutStatic(self.u16(pc)?),
+            0xb4 => Instruction::GetField(self.u16(pc)?),
+            0xb5 => Instruction::PutField(self.u16(pc)?),
+            0xb6 => Instruction::InvokeVirtual(self.u16(pc)?),
+            0xb7 => Instruction::InvokeSpecial(self.u16(pc)?),
+            0xb8 => Instruction::InvokeStatic(self.u16(pc)?),
+            0xb9 => {
+                let index = self.u16(pc)?;
+                let count = self.u8(pc)?;
+                self.u8(pc)?;
+                Instruction::InvokeInterface { index, count }
+            }
+            0xba => {
+                let index = self.u16(pc)?;
+                self.u16(pc)?;
+                Instruction::InvokeDynamic(index)
+            }
+            0xbb => Instruction::New(self.u16(pc)?),
+            0xbc => Instruction::NewArray(self.u8(pc)?),
+            0xbd => Instruction::ANewArray(self.u16(pc)?),
+            0xbe => Instruction::ArrayLength,
+            0xbf => Instruction::AThrow,
+            0xc0 => Instruction::CheckCast(self.u16(pc)?),
+            0xc1 => Instruction::InstanceOf(self.u16(pc)?),
+            0xc2 => Instruction::MonitorEnter,
+            0xc3 => Instruction::MonitorExit,
+            0xc4 => {
+                let opcode = self.u8(pc)?;
+                match opcode {
+                    0x15..=0x19 | 0x36..=0x3a | 0xa9 => Self::local(opcode, self.u16(pc)?),
+                    0x84 => Instruction::IInc {
+                        index: self.u16(pc)?,
+                        value: self.u16(pc)? as i16,
+                    },
+                    _ => return Err(DecodeError::InvalidWideOpcode { pc, opcode }),
+                }
+            }
+            0xc5 => Instruction::MultiANewArray {
+                class: self.u16(pc)?,
+                dims: self.u8(pc)?,
+            },
+            0xc8 => Instruction::Goto(Self::target(pc, self.i32(pc)?)?),
+            0xc9 => Instruction::Jsr(Self::target(pc, self.i32(pc)?)?),
+            _ => return Err(DecodeError::InvalidOpcode { pc, opcode }),
+        })
+    }
+
+    ///
+    /// Builds a load, store, or `ret` from the opcode of its one-byte-index form
+    fn local(opcode: u8, index: u16) -> Instruction {
+        match opcode {
+            0x15 => Instruction::ILoad(index),
+            0x16 => Instruction::LLoad(index),
+            0x17 => Instruction::FLoad(index),
+            0x18 => Instruction::DLoad(index),
+            0x19 => Instruction::ALoad(index),
+            0x36 => Instruction::IStore(index),
+            0x37 => Instruction::LStore(index),
+            0x38 => Instruction::FStore(index),
+            0x39 => Instruction::DStore(index),
+            0x3a => Instruction::AStore(index),
+            0xa9 => Instruction::Ret(index),
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl<'a> Iterator for Instructions<'a> {
+    type Item = Result<(u32, Instruction), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.code.len() {
+            return None;
+        }
+        let pc = self.pos as u32;
+        match self.decode() {
+            Ok(insn) => Some(Ok((pc, insn))),
+            Err(e) => {
+                self.pos = self.code.len();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl<'a> FusedIterator for Instructions<'a> {}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodeError {
+    /// A branch of the instruction at `index` targets a label that no instruction has
+    UnknownLabel { index: usize, label: u32 },
+    /// An operand of the instruction at `index` cannot be encoded, such as `IConst(6)` or a `TableSwitch` with no targets
+    InvalidOperand { index: usize },
+    /// The code is longer than the 65535 bytes a Code attribute can hold
+    CodeTooLong { len: usize },
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EncodeError::UnknownLabel { index, label } => {
+                write!(
+                    f,
+                    "instruction {} branches to unknown label {}",
+                    index, label
+                )
+            }
+            EncodeError::InvalidOperand { index } => {
+                write!(
+                    f,
+                    "instruction {} has an operand that cannot be encoded",
+                    index
+                )
+            }
+            EncodeError::CodeTooLong { len } => {
+                write!(f, "code is {} bytes long, more than 65535", len)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+///
+/// An error from [`CodeAttribute::map_instructions`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RewriteError {
+    /// The original code could not be decoded
+    Decode(DecodeError),
+    /// The rewritten instructions could not be encoded. Indices count the rewritten instructions
+    Encode(EncodeError),
+    /// The conditional branch at `index` among the rewritten instructions is out of range, and the code has a StackMapTable.
+    ///
+    /// The opposite branch over a `goto_w` that replaces it would branch to the instruction after the `goto_w`, which needs a stack map frame that cannot be computed without the types at that point
+    WideConditionalBranch { index: usize },
+    /// Stack map frames of removed instructions were moved onto the frame at `offset` in the rewritten code, and their changes to the locals cannot be merged into one frame
+    CollapsedFrames { offset: u32 },
+}
+
+impl Display for RewriteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RewriteError::Decode(e) => e.fmt(f),
+            RewriteError::Encode(e) => e.fmt(f),
+            RewriteError::WideConditionalBranch { index } => write!(
+                f,
+                "instruction {} is a conditional branch that must be widened, which would need a new stack map frame",
+                index
+            ),
+            RewriteError::CollapsedFrames { offset } => write!(
+                f,
+                "the stack map frames of removed instructions cannot be merged into the frame at offset {}",
+                offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RewriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RewriteError::Decode(e) => Some(e),
+            RewriteError::Encode(e) => Some(e),
+            RewriteError::WideConditionalBranch { .. } | RewriteError::CollapsedFrames { .. } => {
+                None
+            }
+        }
+    }
+}
+
+impl From<DecodeError> for RewriteError {
+    fn from(e: DecodeError) -> Self {
+        RewriteError::Decode(e)
+    }
+}
+
+impl From<EncodeError> for RewriteError {
+    fn from(e: EncodeError) -> Self {
+        RewriteError::Encode(e)
+    }
+}
+
+///
+/// Bytecode produced by [`encode_instructions`]
+#[derive(Clone, Debug)]
+pub struct EncodedCode {
+    pub code: Vec<u8>,
+    /// The offset in `code` of each instruction, in the order they were given
+    pub offsets: Vec<u32>,
+    /// The indices of the conditional branches that were encoded as the opposite branch over a `goto_w`.
+    ///
+    /// The instruction after each `goto_w` is a new branch target, so a StackMapTable needs a frame there
+    pub wide_conditionals: Vec<usize>,
+}
+
+fn put_local(out: &mut Vec<u8>, opcode: u8, short_opcode: Option<u8>, index: u16) {
+    match (short_opcode, u8::try_from(index)) {
+        (Some(short), Ok(index @ 0..=3)) => out.push(short + index),
+        (_, Ok(index)) => out.extend_from_slice(&[opcode, index]),
+        (_, Err(_)) => {
+            out.extend_from_slice(&[0xc4, opcode]);
+            out.extend_from_slice(&index.to_be_bytes());
+        }
+    }
+}
+
+fn simple_opcode(insn: &Instruction) -> Option<u8> {
+    Some(match insn {
+        Instruction::Nop => 0x00,
+        Instruction::AConstNull => 0x01,
+        Instruction::IALoad => 0x2e,
+        Instruction::LALoad => 0x2f,
+        Instruction::FALoad => 0x30,
+        Instruction::DALoad => 0x31,
+        Instruction::AALoad => 0x32,
+        Instruction::BALoad => 0x33,
+        Instruction::CALoad => 0x34,
+        Instruction::SALoad => 0x35,
+        Instruction::IAStore => 0x4f,
+        Instruction::LAStore => 0x50,
+        Instruction::FAStore => 0x51,
+        Instruction::DAStore => 0x52,
+        Instruction::AAStore => 0x53,
+        Instruction::BAStore => 0x54,
+        Instruction::CAStore => 0x55,
+        Instruction::SAStore => 0x56,
+        Instruction::Pop => 0x57,
+        Instruction::Pop2 => 0x58,
+        Instruction::Dup => 0x59,
+        Instruction::DupX1 => 0x5a,
+        Instruction::DupX2 => 0x5b,
+        Instruction::Dup2 => 0x5c,
+        Instruction::Dup2X1 => 0x5d,
+        Instruction::Dup2X2 => 0x5e,
+        Instruction::Swap => 0x5f,
+        Instruction::IAdd => 0x60,
+        Instruction::LAdd => 0x61,
+        Instruction::FAdd => 0x62,
+        Instruction::DAdd => 0x63,
+        Instruction::ISub => 0x64,
+        Instruction::LSub => 0x65,
+        Instruction::FSub => 0x66,
+        Instruction::DSub => 0x67,
+        Instruction::IMul => 0x68,
+        Instruction::LMul => 0x69,
+        Instruction::FMul => 0x6a,
+        Instruction::DMul => 0x6b,
+        Instruction::IDiv => 0x6c,
+        Instruction::LDiv => 0x6d,
+        Instruction::FDiv => 0x6e,
+        Instruction::DDiv => 0x6f,
+        Instruction::IRem => 0x70,
+        Instruction::LRem => 0x71,
+        Instruction::FRem => 0x72,
+        Instruction::DRem => 0x73,
+        Instruction::INeg => 0x74,
+        Instruction::LNeg => 0x75,
+        Instruction::FNeg => 0x76,
+        Instruction::DNeg => 0x77,
+        Instruction::IShl => 0x78,
+        Instruction::LShl => 0x79,
+        Instruction::IShr => 0x7a,
+        Instruction::LShr => 0x7b,
+        Instruction::IUShr => 0x7c,
+        Instruction::LUShr => 0x7d,
+        Instruction::IAnd => 0x7e,
+        Instruction::LAnd => 0x7f,
+        Instruction::IOr => 0x80,
+        Instruction::LOr => 0x81,
+        Instruction::IXor => 0x82,
+        Instruction::LXor => 0x83,
+        Instruction::I2L => 0x85,
+        Instruction::I2F => 0x86,
+        Instruction::I2D => 0x87,
+        Instruction::L2I => 0x88,
+        Instruction::L2F => 0x89,
+        Instruction::L2D => 0x8a,
+        Instruction::F2I => 0x8b,
+        Instruction::F2L => 0x8c,
+        Instruction::F2D => 0x8d,
+        Instruction::D2I => 0x8e,
+        Instruction::D2L => 0x8f,
+        Instruction::D2F => 0x90,
+        Instruction::I2B => 0x91,
+        Instruction::I2C => 0x92,
+        Instruction::I2S => 0x93,
+        Instruction::LCmp => 0x94,
+        Instruction::FCmpL => 0x95,
+        Instruction::FCmpG => 0x96,
+        Instruction::DCmpL => 0x97,
+        Instruction::DCmpG => 0x98,
+        Instruction::IReturn => 0xac,
+        Instruction::LReturn => 0xad,
+        Instruction::FReturn => 0xae,
+        Instruction::DReturn => 0xaf,
+        Instruction::AReturn => 0xb0,
+        Instruction::Return => 0xb1,
+        Instruction::ArrayLength => 0xbe,
+        Instruction::AThrow => 0xbf,
+        Instruction::MonitorEnter => 0xc2,
+        Instruction::MonitorExit => 0xc3,
+        _ => return None,
+    })
+}
+
+///
+/// The opcode of an instruction whose only operand is a constant pool index
+fn constant_opcode(insn: &Instruction) -> Option<u8> {
+    Some(match insn {
+        Instruction::Ldc2W(_) => 0x14,
+        Instruction::GetStatic(_) => 0xb2,
+        Instruction::PutStatic(_) => 0xb3,
+        Instruction::GetField(_) => 0xb4,
+        Instruction::PutField(_) => 0xb5,
+        Instruction::InvokeVirtual(_) => 0xb6,
+        Instruction::InvokeSpecial(_) => 0xb7,
+        Instruction::InvokeStatic(_) => 0xb8,
+        Instruction::New(_) => 0xbb,
+        Instruction::ANewArray(_) => 0xbd,
+        Instruction::CheckCast(_) => 0xc0,
+        Instruction::InstanceOf(_) => 0xc1,
+        _ => return None,
+    })
+}
+
+///
+/// Appends the encoding of `insn`, which starts at `pc`, to `out`. `target` gives the offset of a branch target, and `wide` selects the long form of a branch.
+///
+/// Returns false if an operand cannot be encoded
+fn encode_instruction(
+    insn: &Instruction,
+    pc: u32,
+    wide: bool,
+    target: impl Fn(u32) -> u32,
+    out: &mut Vec<u8>,
+) -> bool {
+    let offset = |label| target(label) as i64 - pc as i64;
+    let put_i32 = |out: &mut Vec<u8>, v: i64| out.extend_from_slice(&(v as i32).to_be_bytes());
+    let put_i16 = |out: &mut Vec<u8>, v: i64| out.extend_from_slice(&(v as i16).to_be_bytes());
+    let pad = |out: &mut Vec<u8>| {
+        let len = out.len();
+        out.resize(len + (3 - pc as usize % 4), 0);
+    };
+    if let Some(opcode) = simple_opcode(insn) {
+        out.push(opcode);
+        return true;
+    }
+    if let (Some(opcode), Some(idx)) = (constant_opcode(insn), insn.constant_index()) {
+        out.push(opcode);
+        out.extend_from_slice(&idx.to_be_bytes());
+        return true;
+    }
+    match insn {
+        Instruction::IConst(v @ -1..=5) => out.push((0x03 + v) as u8),
+        Instruction::LConst(v @ 0..=1) => out.push(0x09 + *v as u8),
+        Instruction::FConst(v @ 0..=2) => out.push(0x0b + v),
+        Instruction::DConst(v @ 0..=1) => out.push(0x0e + v),
+        Instruction::BiPush(v) => out.extend_from_slice(&[0x10, *v as u8]),
+        Instruction::SiPush(v) => {
+            out.push(0x11);
+            out.extend_from_slice(&v.to_be_bytes());
+        }
+        Instruction::Ldc(idx) => match u8::try_from(*idx) {
+            Ok(idx) => out.extend_from_slice(&[0x12, idx]),
+            Err(_) => {
+                out.push(0x13);
+                out.extend_from_slice(&idx.to_be_bytes());
+            }
+        },
+        Instruction::ILoad(idx) => put_local(out, 0x15, Some(0x1a), *idx),
+        Instruction::LLoad(idx) => put_local(out, 0x16, Some(0x1e), *idx),
+        Instruction::FLoad(idx) => put_local(out, 0x17, Some(0x22), *idx),
+        Instruction::DLoad(idx) => put_local(out, 0x18, Some(0x26), *idx),
+        Instruction::ALoad(idx) => put_local(out, 0x19, Some(0x2a), *idx),
+        Instruction::IStore(idx) => put_local(out, 0x36, Some(0x3b), *idx),
+        Instruction::LStore(idx) => put_local(out, 0x37, Some(0x3f), *idx),
+        Instruction::FStore(idx) => put_local(out, 0x38, Some(0x43), *idx),
+        Instruction::DStore(idx) => put_local(out, 0x39, Some(0x47), *idx),
+        Instruction::AStore(idx) => put_local(out, 0x3a, Some(0x4b), *idx),
+        Instruction::Ret(idx) => put_local(out, 0xa9, None, *idx),
+        Instruction::IInc { index, value } => match (u8::try_from(*index), i8::try_from(*value)) {
+            (Ok(index), Ok(value)) => out.extend_from_slice(&[0x84, index, value as u8]),
+            _ => {
+                out.extend_from_slice(&[0xc4, 0x84]);
+                out.extend_from_slice(&index.to_be_bytes());
+                out.extend_from_slice(&value.to_be_bytes());
+            }
+        },
+        Instruction::If { cond, target } if wide => {
+            // The opposite branch skips over a goto_w to the target
+            out.extend_from_slice(&[cond.negate().opcode(), 0, 8, 0xc8]);
+            put_i32(out, offset(*target) - 3);
+        }
+        Instruction::If { cond, target } => {
+            out.push(cond.opcode());
+            put_i16(out, offset(*target));
+        }
+        Instruction::Goto(target) | Instruction::Jsr(target) => {
+            let jsr = matches!(insn, Instruction::Jsr(_));
+            if wide {
+                out.push(if jsr { 0xc9 } else { 0xc8 });
+                put_i32(out, offset(*target));
+            } else {
+                out.push(if jsr { 0xa8 } else { 0xa7 });
+                put_i16(out, offset(*target));
+            }
+        }
+        Instruction::TableSwitch {
+            default,
+            low,
+            targets,
+        } => {
+            let high = match i32::try_from(*low as i64 + targets.len() as i64 - 1) {
+                Ok(high) if !targets.is_empty() => high,
+                _ => return false,
+            };
+            out.push(0xaa);
+            pad(out);
+            put_i32(out, offset(*default));
+            out.extend_from_slice(&low.to_be_bytes());
+            out.extend_from_slice(&high.to_be_bytes());
+            for target in targets {
+                put_i32(out, offset(*target));
+            }
+        }
+        Instruction::LookupSwitch { default, pairs } => {
+            out.push(0xab);
+            pad(out);
+            put_i32(out, offset(*default));
+            put_i32(out, pairs.len() as i64);
+            for (key, target) in pairs {
+                out.extend_from_slice(&key.to_be_bytes());
+                put_i32(out, offset(*target));
+            }
+        }
+        Instruction::InvokeInterface { index, count } => {
+            out.push(0xb9);
+            out.extend_from_slice(&index.to_be_bytes());
+            out.extend_from_slice(&[*count, 0]);
+        }
+        Instruction::InvokeDynamic(idx) => {
+            out.push(0xba);
+            out.extend_from_slice(&idx.to_be_bytes());
+            out.extend_from_slice(&[0, 0]);
+        }
+        Instruction::NewArray(atype) => out.extend_from_slice(&[0xbc, *atype]),
+        Instruction::MultiANewArray { class, dims } => {
+            out.push(0xc5);
+            out.extend_from_slice(&class.to_be_bytes());
+            out.push(*dims);
+        }
+        _ => return false,
+    }
+    true
+}
+
+///
+/// The labels an instruction branches to
+pub(crate) fn branch_targets_mut(insn: &mut Instruction) -> Vec<&mut u32> {
+    match insn {
+        Instruction::If { target, .. } | Instruction::Goto(target) | Instruction::Jsr(target) => {
+            vec![target]
+        }
+        Instruction::TableSwitch {
+            default, targets, ..
+        } => std::iter::once(default).chain(targets.iter_mut()).collect(),
+        Instruction::LookupSwitch { default, pairs } => std::iter::once(default)
+            .chain(pairs.iter_mut().map(|(_, target)| target))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub(crate) fn branch_targets(insn: &Instruction) -> Vec<u32> {
+    match insn {
+        Instruction::If { target, .. } | Instruction::Goto(target) | Instruction::Jsr(target) => {
+            vec![*target]
+        }
+        Instruction::TableSwitch {
+            default, targets, ..
+        } => std::iter::once(*default)
+            .chain(targets.iter().copied())
+            .collect(),
+        Instruction::LookupSwitch { default, pairs } => std::iter::once(*default)
+            .chain(pairs.iter().map(|&(_, target)| target))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+///
+/// Encodes a sequence of instructions as bytecode.
+///
+/// Each instruction is paired with a label, and branch targets refer to labels rather than offsets: a target is the first instruction with that label.
+/// The instructions yielded by [`Instructions`] can be encoded as they are, since their offsets serve as labels.
+///
+/// Each instruction gets its shortest encoding. A `goto` or `jsr` whose target is out of the range of a 16 bit offset becomes a `goto_w` or `jsr_w`,
+/// and a conditional branch becomes the opposite branch over a `goto_w` to its target, as listed in [`EncodedCode::wide_conditionals`]
+pub fn encode_instructions(insns: &[(u32, Instruction)]) -> Result<EncodedCode, EncodeError> {
+    let mut labels = std::collections::HashMap::new();
+    for (index, (label, _)) in insns.iter().enumerate() {
+        labels.entry(*label).or_insert(index);
+    }
+    for (index, (_, insn)) in insns.iter().enumerate() {
+        for label in branch_targets(insn) {
+            if !labels.contains_key(&label) {
+                return Err(EncodeError::UnknownLabel { index, label });
+            }
+        }
+    }
+
+    // Branches are only ever widened, so this settles after at most one pass per branch
+    let mut wide = vec![false; insns.len()];
+    let mut offsets = vec![0; insns.len()];
+    let mut scratch = Vec::new();
+    loop {
+        let mut pc = 0u32;
+        for (index, (_, insn)) in insns.iter().enumerate() {
+            offsets[index] = pc;
+            scratch.clear();
+            if !encode_instruction(insn, pc, wide[index], |_| pc, &mut scratch) {
+                return Err(EncodeError::InvalidOperand { index });
+            }
+            pc = pc.saturating_add(scratch.len() as u32);
+        }
+        let mut changed = false;
+        for (index, (_, insn)) in insns.iter().enumerate() {
+            if let Instruction::If { target, .. }
+            | Instruction::Goto(target)
+            | Instruction::Jsr(target) = insn
+            {
+                let offset = offsets[labels[target]] as i64 - offsets[index] as i64;
+                if !wide[index] && i16::try_from(offset).is_err() {
+                    wide[index] = true;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let mut code = Vec::new();
+    for (index, (_, insn)) in insns.iter().enumerate() {
+        encode_instruction(
+            insn,
+            offsets[index],
+            wide[index],
+            |label| offsets[labels[&label]],
+            &mut code,
+        );
+    }
+    if code.len() > u16::MAX as usize {
+        return Err(EncodeError::CodeTooLong { len: code.len() });
+    }
+    let wide_conditionals = insns
+        .iter()
+        .enumerate()
+        .filter(|&(index, (_, insn))| wide[index] && matches!(insn, Instruction::If { .. }))
+        .map(|(index, _)| index)
+        .collect();
+    Ok(EncodedCode {
+        code,
+        offsets,
+        wide_conditionals,
+    })
+}
+
+///
+/// The constant pool operand of an instruction, resolved through the pool
+#[derive(Clone, Debug)]
+pub enum ResolvedOperand<'a> {
+    /// The class of `new`, `anewarray`, `checkcast`, `instanceof`, or `multianewarray`
+    Class(&'a JStr),
+    /// The field or method of a field access or invocation
+    Member {
+        class: &'a JStr,
+        name: &'a JStr,
+        descriptor: &'a JStr,
+    },
+    /// The call site of `invokedynamic`. `bootstrap_method` indexes the BootstrapMethods attribute
+    Dynamic {
+        bootstrap_method: u16,
+        name: &'a JStr,
+        descriptor: &'a JStr,
+    },
+    /// The constant loaded by `ldc`, `ldc_w`, or `ldc2_w`
+    Constant(&'a Constant),
+    /// The index does not refer to a constant of the kind the instruction expects
+    Invalid(u16),
+}
+
+#[derive(Clone, Debug)]
+pub struct ResolvedInstruction<'a> {
+    pub pc: u32,
+    pub insn: Instruction,
+    /// The resolved constant pool operand, for instructions that have one
+    pub operand: Option<ResolvedOperand<'a>>,
+}
+
+fn resolve_operand<'a>(insn: &Instruction, pool: &'a ConstantPool) -> Option<ResolvedOperand<'a>> {
+    let idx = insn.constant_index()?;
+    let resolved = match insn {
+        Instruction::Ldc(_) | Instruction::Ldc2W(_) => pool.get(idx).map(ResolvedOperand::Constant),
+        Instruction::New(_)
+        | Instruction::ANewArray(_)
+        | Instruction::CheckCast(_)
+        | Instruction::InstanceOf(_)
+        | Instruction::MultiANewArray { .. } => pool.class_name(idx).map(ResolvedOperand::Class),
+        Instruction::InvokeDynamic(_) => match pool.get(idx) {
+            Some(Constant::InvokeDynamic {
+                bootstrap_attrs,
+                name_and_type,
+            }) => pool
+                .name_and_type(*name_and_type)
+                .map(|(name, descriptor)| ResolvedOperand::Dynamic {
+                    bootstrap_method: *bootstrap_attrs,
+                    name,
+                    descriptor,
+                }),
+            _ => None,
+        },
+        _ => pool
+            .member_ref(idx)
+            .map(|(class, name, descriptor)| ResolvedOperand::Member {
+                class,
+                name,
+                descriptor,
+            }),
+    };
+    Some(resolved.unwrap_or(ResolvedOperand::Invalid(idx)))
+}
+
+///
+/// Maps offsets in code that was rewritten by [`CodeAttribute::map_instructions`] to offsets in the new code
+struct OffsetMap {
+    /// The old offset and new offset of each instruction that was replaced by at least one instruction, in order
+    starts: Vec<(u32, u32)>,
+    len: u32,
+}
+
+impl OffsetMap {
+    ///
+    /// The new offset of the first instruction at or after `old`, or the end of the new code if there is none
+    fn get(&self, old: u32) -> u32 {
+        let idx = self.starts.partition_point(|&(pc, _)| pc < old);
+        self.starts.get(idx).map_or(self.len, |&(_, new)| new)
+    }
+
+    fn get_u16(&self, old: u16) -> u16 {
+        // The new code is at most 65535 bytes long
+        self.get(old as u32) as u16
+    }
+
+    ///
+    /// Maps the range of `length` bytes starting at `start`
+    fn range(&self, start: &mut u16, length: &mut u16) {
+        let end = self.get(*start as u32 + *length as u32);
+        *start = self.get_u16(*start);
+        *length = (end - *start as u32) as u16;
+    }
+
+    fn verification_info(&self, info: &mut VerificationInfo) {
+        if let VerificationInfo::Uninitialized { offset } = info {
+            *offset = self.get_u16(*offset);
+        }
+    }
+
+    ///
+    /// Moves each frame of a StackMapTable to the new offset of the instruction it was at, switching to the extended form of a frame where its offset delta no longer fits.
+    ///
+    /// The frame of a removed instruction lands on the same offset as the next frame. Only the last frame at an offset is kept, with the changes the frames before it made to the locals folded in
+    fn stack_map_table(&self, frames: &mut Vec<StackMapFrame>) -> Result<(), RewriteError> {
+        let mut old = None::<u32>;
+        let mut moved: Vec<(u32, StackMapFrame)> = Vec::with_capacity(frames.len());
+        for mut frame in frames.drain(..) {
+            match &mut frame {
+                StackMapFrame::SameLocals1StackFrame { info, .. }
+                | StackMapFrame::SameLocals1StackFrameExtended { info, .. } => {
+                    self.verification_info(info)
+                }
+                StackMapFrame::Append { items, .. } => items
+                    .iter_mut()
+                    .for_each(|info| self.verification_info(info)),
+                StackMapFrame::Full { locals, stack, .. } => locals
+                    .iter_mut()
+                    .chain(stack.iter_mut())
+                    .for_each(|info| self.verification_info(info)),
+                _ => {}
+            }
+            let delta = frame.offset_delta() as u32;
+            let offset = old.map_or(delta, |old| old + delta + 1);
+            old = Some(offset);
+            let offset = self.get(offset);
+            match moved.last() {
+                Some(&(last, _)) if last == offset => {
+                    let (_, first) = moved.pop().unwrap();
+                    let merged = merge_frames(first, frame)
+                        .ok_or(RewriteError::CollapsedFrames { offset })?;
+                    moved.push((offset, merged));
+                }
+                _ => moved.push((offset, frame)),
+            }
+        }
+
+        let mut new = None::<u32>;
+        for (offset, frame) in moved {
+            // Offsets only increase once the frames at the same offset are merged
+            let delta = new.map_or(offset, |new| offset - new - 1) as u16;
+            new = Some(offset);
+            frames.push(match frame {
+                StackMapFrame::Same { .. } | StackMapFrame::SameExtended { .. } if delta < 64 => {
+                    StackMapFrame::Same {
+                        offset_delta: delta as u8,
+                    }
+                }
+                StackMapFrame::Same { .. } | StackMapFrame::SameExtended { .. } => {
+                    StackMapFrame::SameExtended {
+                        offset_delta: delta,
+                    }
+                }
+                StackMapFrame::SameLocals1StackFrame { info, .. }
+                | StackMapFrame::SameLocals1StackFrameExtended { info, .. }
+                    if delta < 64 =>
+                {
+                    StackMapFrame::SameLocals1StackFrame {
+                        offset_delta: delta as u8,
+                        info,
+                    }
+                }
+                StackMapFrame::SameLocals1StackFrame { info, .. }
+                | StackMapFrame::SameLocals1StackFrameExtended { info, .. } => {
+                    StackMapFrame::SameLocals1StackFrameExtended {
+                        offset_delta: delta,
+                        info,
+                    }
+                }
+                StackMapFrame::ChopFrame { chop, .. } => StackMapFrame::ChopFrame {
+                    chop,
+                    offset_delta: delta,
+                },
+                StackMapFrame::Append { items, .. } => StackMapFrame::Append {
+                    offset_delta: delta,
+                    items,
+                },
+                StackMapFrame::Full { locals, stack, .. } => StackMapFrame::Full {
+                    offset_delta: delta,
+                    locals,
+                    stack,
+                },
+            });
+        }
+        Ok(())
+    }
+
+    fn type_annotation(&self, annotation: &mut TypeAnnotation) {
+        match &mut annotation.target {
+            TypeAnnotationTarget::LocalVariableType(table)
+            | TypeAnnotationTarget::ResourceVariableType(table) => {
+                for entry in table {
+                    self.range(&mut entry.start_pc, &mut entry.length);
+                }
+            }
+            TypeAnnotationTarget::InstanceOfType(offset)
+            | TypeAnnotationTarget::NewType(offset)
+            | TypeAnnotationTarget::NewReferenceType(offset)
+            | TypeAnnotationTarget::MethodReferenceType(offset)
+            | TypeAnnotationTarget::CastType { offset, .. }
+            | TypeAnnotationTarget::GenericConstructorTypeArgument { offset, .. }
+            | TypeAnnotationTarget::GenericMethodTypeArgument { offset, .. }
+            | TypeAnnotationTarget::GenericConstructorReferenceTypeArgument { offset, .. }
+            | TypeAnnotationTarget::GenericMethodReferenceTypeArgument { offset, .. } => {
+                *offset = self.get_u16(*offset)
+            }
+            _ => {}
+        }
+    }
+
+    fn attribute(&self, attr: &mut Attribute) -> Result<(), RewriteError> {
+        match attr {
+            Attribute::StackMapTable(frames) => self.stack_map_table(frames)?,
+            Attribute::LineNumberTable(lines) => {
+                for line in lines {
+                    line.start_pc = self.get_u16(line.start_pc);
+                }
+            }
+            Attribute::LocalVariableTable(vars) => {
+                for var in vars {
+                    self.range(&mut var.start_pc, &mut var.length);
+                }
+            }
+            Attribute::LocalVariableTypeTable(vars) => {
+                for var in vars {
+                    self.range(&mut var.start_pc, &mut var.length);
+                }
+            }
+            Attribute::RuntimeVisibleTypeAnnotations(annotations)
+            | Attribute::RuntimeInvisibleTypeAnnotations(annotations) => {
+                for annotation in annotations {
+                    self.type_annotation(annotation);
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+///
+/// How a stack map frame changes the locals of the frame before it
+enum LocalsChange {
+    Same,
+    Chop(u8),
+    Append(Vec<VerificationInfo>),
+    Full(Vec<VerificationInfo>),
+}
+
+impl LocalsChange {
+    ///
+    /// The change made by applying `self`, then `next`, if a single frame can express it
+    fn then(self, next: LocalsChange) -> Option<LocalsChange> {
+        Some(match (self, next) {
+            (_, LocalsChange::Full(locals)) => LocalsChange::Full(locals),
+            (LocalsChange::Same, next) => next,
+            (change, LocalsChange::Same) => change,
+            (LocalsChange::Full(mut locals), LocalsChange::Chop(chop)) => {
+                let len = locals.len().checked_sub(chop as usize)?;
+                locals.truncate(len);
+                LocalsChange::Full(locals)
+            }
+            (LocalsChange::Full(mut locals), LocalsChange::Append(items)) => {
+                locals.extend(items);
+                LocalsChange::Full(locals)
+            }
+            (LocalsChange::Chop(a), LocalsChange::Chop(b)) if a + b <= 3 => {
+                LocalsChange::Chop(a + b)
+            }
+            (LocalsChange::Append(mut items), LocalsChange::Chop(chop)) => {
+                match items.len().checked_sub(chop as usize) {
+                    Some(0) => LocalsChange::Same,
+                    Some(len) => {
+                        items.truncate(len);
+                        LocalsChange::Append(items)
+                    }
+                    None => LocalsChange::Chop(chop - items.len() as u8),
+                }
+            }
+            (LocalsChange::Append(mut items), LocalsChange::Append(more))
+                if items.len() + more.len() <= 3 =>
+            {
+                items.extend(more);
+                LocalsChange::Append(items)
+            }
+            _ => return None,
+        })
+    }
+}
+
+///
+/// Merges two frames at the same offset into one with the stack of `second`, and the locals `second` has when it follows `first`.
+///
+/// Returns None if no single frame can express the result without knowing the locals before `first`, such as a chop followed by an append
+fn merge_frames(first: StackMapFrame, second: StackMapFrame) -> Option<StackMapFrame> {
+    fn split(frame: StackMapFrame) -> (LocalsChange, Vec<VerificationInfo>) {
+        match frame {
+            StackMapFrame::Same { .. } | StackMapFrame::SameExtended { .. } => {
+                (LocalsChange::Same, Vec::new())
+            }
+            StackMapFrame::SameLocals1StackFrame { info, .. }
+            | StackMapFrame::SameLocals1StackFrameExtended { info, .. } => {
+                (LocalsChange::Same, vec![info])
+            }
+            StackMapFrame::ChopFrame { chop, .. } => (LocalsChange::Chop(chop), Vec::new()),
+            StackMapFrame::Append { items, .. } => (LocalsChange::Append(items), Vec::new()),
+            StackMapFrame::Full { locals, stack, .. } => (LocalsChange::Full(locals), stack),
+        }
+    }
+
+    let (first, _) = split(first);
+    let (second, mut stack) = split(second);
+    Some(match (first.then(second)?, stack.len()) {
+        (LocalsChange::Same, 0) => StackMapFrame::Same { offset_delta: 0 },
+        (LocalsChange::Same, 1) => StackMapFrame::SameLocals1StackFrame {
+            offset_delta: 0,
+            info: stack.pop().unwrap(),
+        },
+        (LocalsChange::Chop(chop), 0) => StackMapFrame::ChopFrame {
+            chop,
+            offset_delta: 0,
+        },
+        (LocalsChange::Append(items), 0) => StackMapFrame::Append {
+            offset_delta: 0,
+            items,
+        },
+        (LocalsChange::Full(locals), _) => StackMapFrame::Full {
+            offset_delta: 0,
+            locals,
+            stack,
+        },
+        _ => return None,
+    })
+}
+
+///
+/// Size and complexity measures of a method's code, as returned by [`CodeAttribute::metrics`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct CodeMetrics {
+    /// The length of the bytecode, in bytes
+    pub code_len: usize,
+    pub instruction_count: usize,
+    pub max_stack: u16,
+    /// The number of decisions made by conditional branches: one for each `if` instruction, and one for each case of a `tableswitch` or `lookupswitch` besides the default
+    pub branch_count: usize,
+    /// The number of entries in the exception table
+    pub handler_count: usize,
+}
+
+impl CodeMetrics {
+    ///
+    /// The cyclomatic complexity of the code, one more than the number of decisions. Exception handlers are not counted
+    pub fn cyclomatic_complexity(&self) -> usize {
+        self.branch_count + 1
+    }
+}
+
+impl CodeAttribute {
+    ///
+    /// Measures the size and complexity of the code, decoding every instruction
+    pub fn metrics(&self) -> Result<CodeMetrics, DecodeError> {
+        let mut metrics = CodeMetrics {
+            code_len: self.code.len(),
+            max_stack: self.max_stack,
+            handler_count: self.exceptions.len(),
+            ..CodeMetrics::default()
+        };
+        for insn in self.instructions() {
+            let (_, insn) = insn?;
+            metrics.instruction_count += 1;
+            metrics.branch_count += match insn {
+                Instruction::If { .. } => 1,
+                Instruction::TableSwitch { targets, .. } => targets.len(),
+                Instruction::LookupSwitch { pairs, .. } => pairs.len(),
+                _ => 0,
+            };
+        }
+        Ok(metrics)
+    }
+
+    ///
+    /// Decodes the bytecode of this attribute
+    pub fn instructions(&self) -> Instructions<'_> {
+        Instructions::new(&self.code)
+    }
+
+    ///
+    /// Replaces each instruction with the instructions `f` returns for it, given its offset, and re-encodes the code.
+    ///
+    /// The offset is a u32 rather than the u16 of the class file format, like the offsets from [`CodeAttribute::instructions`] and the branch targets of [`Instruction`], so that it can be used as a target without conversion.
+    ///
+    /// Branch targets in the returned instructions are offsets in the original code. A branch to a replaced instruction goes to the first instruction that replaced it, or the next remaining instruction if it was removed.
+    /// The offsets in the exception table, StackMapTable, LineNumberTable, LocalVariableTable, LocalVariableTypeTable, and type annotations of the code are moved in the same way.
+    /// `max_stack`, `max_locals`, and the types in the StackMapTable are left for the caller to update.
+    ///
+    /// If the code has a StackMapTable, a conditional branch whose target moves out of the range of a 16 bit offset is an error, since widening it needs a new frame.
+    /// Frames of removed instructions are merged into the next frame, which is an error if no single frame can express the merged locals.
+    ///
+    /// On error, the code is left unchanged
+    pub fn map_instructions(
+        &mut self,
+        mut f: impl FnMut(u32, Instruction) -> Vec<Instruction>,
+    ) -> Result<(), RewriteError> {
+        let decoded = self.instructions().collect::<Result<Vec<_>, _>>()?;
+        let groups: Vec<_> = decoded
+            .into_iter()
+            .map(|(pc, insn)| (pc, f(pc, insn)))
+            .collect();
+        let kept: Vec<u32> = groups
+            .iter()
+            .filter(|(_, group)| !group.is_empty())
+            .map(|&(pc, _)| pc)
+            .collect();
+
+        let mut insns = Vec::new();
+        let mut firsts = Vec::with_capacity(kept.len());
+        for (pc, group) in groups {
+            if !group.is_empty() {
+                firsts.push(insns.len());
+            }
+            for mut insn in group {
+                for target in branch_targets_mut(&mut insn) {
+                    // Targets that are not instructions are left for the encoder to reject
+                    if let Some(&label) = kept.get(kept.partition_point(|&pc| pc < *target)) {
+                        *target = label;
+                    }
+                }
+                insns.push((pc, insn));
+            }
+        }
+        let encoded = encode_instructions(&insns)?;
+        let has_stack_map = self
+            .attributes
+            .iter()
+            .any(|attr| matches!(attr, Attribute::StackMapTable(_)));
+        if let (true, Some(&index)) = (has_stack_map, encoded.wide_conditionals.first()) {
+            return Err(RewriteError::WideConditionalBranch { index });
+        }
+
+        let map = OffsetMap {
+            starts: kept
+                .into_iter()
+                .zip(firsts.into_iter().map(|index| encoded.offsets[index]))
+                .collect(),
+            len: encoded.code.len() as u32,
+        };
+        let mut attributes = self.attributes.clone();
+        for attr in &mut attributes {
+            map.attribute(attr)?;
+        }
+        self.attributes = attributes;
+        self.code = encoded.code;
+        for handler in &mut self.exceptions {
+            handler.start_pc = map.get_u16(handler.start_pc);
+            handler.end_pc = map.get_u16(handler.end_pc);
+            handler.handler_pc = map.get_u16(handler.handler_pc);
+        }
+        Ok(())
+    }
+
+    ///
+    /// Whether this code uses the `jsr`, `jsr_w`, or `ret` instructions of subroutines, which class files of version 51 and later must not contain.
+    ///
+    /// Code that cannot be decoded is considered up to the first error
+    pub fn uses_subroutines(&self) -> bool {
+        self.instructions()
+            .map_while(Result::ok)
+            .any(|(_, insn)| matches!(insn, Instruction::Jsr(_) | Instruction::Ret(_)))
+    }
+
+    ///
+    /// Decodes the bytecode of this attribute, resolving each instruction's constant pool operand through `pool`.
+    ///
+    /// Iteration stops at the first instruction that cannot be decoded. Use [`CodeAttribute::instructions`] to find out why
+    pub fn instructions_resolved<'a>(
+        &'a self,
+        pool: &'a ConstantPool,
+    ) -> impl Iterator<Item = ResolvedInstruction<'a>> + 'a {
+        self.instructions()
+            .map_while(Result::ok)
+            .map(move |(pc, insn)| ResolvedInstruction {
+                pc,
+                operand: resolve_operand(&insn, pool),
+                insn,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    fn member(operand: &Option<ResolvedOperand>) -> (String, String, String) {
+        match operand {
+            Some(ResolvedOperand::Member {
+                class,
+                name,
+                descriptor,
+            }) => (class.to_string(), name.to_string(), descriptor.to_string()),
+            other => panic!("expected a member, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn metrics_of_a_loop_with_one_conditional() {
+        let class = testutil::read(testutil::FIXTURE);
+        let code = testutil::method(&class, "loop").code().unwrap();
+        let metrics = code.metrics().unwrap();
+        assert_eq!(
+            metrics,
+            CodeMetrics {
+                code_len: 21,
+                instruction_count: 15,
+                max_stack: 2,
+                branch_count: 1,
+                handler_count: 0,
+            }
+        );
+        assert_eq!(metrics.cyclomatic_complexity(), 2);
+
+        // Three cases besides the default
+        let code = testutil::method(&class, "classify").code().unwrap();
+        assert_eq!(code.metrics().unwrap().cyclomatic_complexity(), 4);
+        // The early return splits the range of each of the two handlers in two
+        let code = testutil::method(&class, "guarded").code().unwrap();
+        assert_eq!(code.metrics().unwrap().handler_count, 4);
+    }
+
+    #[test]
+    fn instructions_resolve_member_operands() {
+        let class = testutil::read(testutil::FIXTURE_ANONYMOUS);
+        let code = testutil::method(&class, "run").code().unwrap();
+        let insns: Vec<_> = code.instructions_resolved(&class.consts).collect();
+        assert_eq!(insns.len(), 6);
+
+        assert!(matches!(insns[0].insn, Instruction::GetStatic(_)));
+        assert_eq!(insns[0].pc, 0);
+        assert_eq!(
+            member(&insns[0].operand),
+            (
+                "java/lang/System".to_string(),
+                "out".to_string(),
+                "Ljava/io/PrintStream;".to_string()
+            )
+        );
+        assert!(insns[1].operand.is_none());
+        assert_eq!(member(&insns[3].operand).1, "name");
+        assert_eq!(insns[4].pc, 10);
+        assert_eq!(member(&insns[4].operand).1, "println");
+    }
+
+    #[test]
+    fn ldc_resolves_to_the_constant() {
+        let class = testutil::read(testutil::FIXTURE);
+        let code = testutil::method(&class, "handlers").code().unwrap();
+        let strings: Vec<String> = code
+            .instructions_resolved(&class.consts)
+            .filter_map(|insn| match insn.operand {
+                Some(ResolvedOperand::Constant(Constant::String(st))) => {
+                    Some(testutil::utf8(&class, *st))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(strings, ["io", "state"]);
+    }
+
+    #[test]
+    fn legacy_subroutines_and_wide_forms_decode() {
+        let code = [
+            0xc9, 0, 0, 0, 12, // 0: jsr_w 12
+            0xc4, 0x84, 0x01, 0x00, 0xfc, 0x18, // 5: wide iinc 256, -1000
+            0xb1, // 11: return
+            0xc4, 0x3a, 0x01, 0x2c, // 12: wide astore 300
+            0xc4, 0xa9, 0x01, 0x2c, // 16: wide ret 300
+            0xa8, 0xff, 0xec, // 20: jsr 0
+            0xa9, 0x05, // 23: ret 5
+        ];
+        let insns: Vec<(u32, Instruction)> =
+            Instructions::new(&code).collect::<Result<_, _>>().unwrap();
+        assert_eq!(
+            insns,
+            [
+                (0, Instruction::Jsr(12)),
+                (
+                    5,
+                    Instruction::IInc {
+                        index: 256,
+                        value: -1000
+                    }
+                ),
+                (11, Instruction::Return),
+                (12, Instruction::AStore(300)),
+                (16, Instruction::Ret(300)),
+                (20, Instruction::Jsr(0)),
+                (23, Instruction::Ret(5)),
+            ]
+        );
+
+        // The wide forms are kept when re-encoding, and the jsr_w shrinks to a jsr
+        let encoded = encode_instructions(&insns).unwrap();
+        assert_eq!(&encoded.code[..3], [0xa8, 0, 10]);
+        assert_eq!(&encoded.code[3..18], &code[5..20]);
+        assert_eq!(&encoded.code[18..], [0xa8, 0xff, 0xee, 0xa9, 0x05]);
+    }
+
+    #[test]
+    fn wide_requires_a_local_variable_opcode() {
+        let code = [0x00, 0xc4, 0x60, 0x00, 0x01];
+        let err = Instructions::new(&code).find_map(Result::err);
+        assert_eq!(
+            err,
+            Some(DecodeError::InvalidWideOpcode {
+                pc: 1,
+                opcode: 0x60
+            })
+        );
+    }
+
+    ///
+    /// `first`, then enough `nop`s to take the `return` at label 100000 out of the range of a 16 bit offset
+    fn far_branch(first: Instruction) -> Vec<(u32, Instruction)> {
+        let mut insns = vec![(0, first)];
+        insns.extend((1..=40000).map(|label| (label, Instruction::Nop)));
+        insns.push((100000, Instruction::Return));
+        insns
+    }
+
+    fn decode(code: &[u8]) -> Vec<(u32, Instruction)> {
+        Instructions::new(code).collect::<Result<_, _>>().unwrap()
+    }
+
+    #[test]
+    fn far_goto_becomes_goto_w() {
+        let encoded = encode_instructions(&far_branch(Instruction::Goto(100000))).unwrap();
+        assert_eq!(encoded.code[0], 0xc8);
+        assert_eq!(encoded.code.len(), 5 + 40000 + 1);
+        assert_eq!(encoded.offsets[1], 5);
+        assert_eq!(decode(&encoded.code)[0], (0, Instruction::Goto(40005)));
+        assert!(encoded.wide_conditionals.is_empty());
+
+        // Backwards, from the end of the code to its start
+        let mut insns = far_branch(Instruction::Nop);
+        insns.insert(insns.len() - 1, (50000, Instruction::Goto(0)));
+        let encoded = encode_instructions(&insns).unwrap();
+        let decoded = decode(&encoded.code);
+        assert_eq!(decoded[decoded.len() - 2], (40001, Instruction::Goto(0)));
+        assert_eq!(encoded.code[40001], 0xc8);
+
+        // A branch just in range keeps the short form
+        let mut insns = vec![(0, Instruction::Goto(100000))];
+        insns.extend((1..=32764).map(|label| (label, Instruction::Nop)));
+        insns.push((100000, Instruction::Return));
+        let encoded = encode_instructions(&insns).unwrap();
+        assert_eq!(&encoded.code[..3], [0xa7, 0x7f, 0xff]);
+    }
+
+    #[test]
+    fn far_conditional_branch_is_inverted_over_goto_w() {
+        let branch = Instruction::If {
+            cond: Condition::Eq,
+            target: 100000,
+        };
+        let encoded = encode_instructions(&far_branch(branch)).unwrap();
+        assert_eq!(encoded.wide_conditionals, [0]);
+        assert_eq!(encoded.offsets[1], 8);
+        let decoded = decode(&encoded.code);
+        assert_eq!(
+            decoded[..2],
+            [
+                (
+                    0,
+                    Instruction::If {
+                        cond: Condition::Ne,
+                        target: 8
+                    }
+                ),
+                (3, Instruction::Goto(40008)),
+            ]
+        );
+    }
+
+    #[test]
+    fn widening_a_conditional_branch_needs_a_frame() {
+        let mut code = CodeAttribute {
+            max_stack: 1,
+            max_locals: 1,
+            // iload_0; ifeq 5; return; return
+            code: vec![0x1a, 0x99, 0, 4, 0xb1, 0xb1],
+            exceptions: Vec::new(),
+            attributes: vec![Attribute::StackMapTable(vec![StackMapFrame::Same {
+                offset_delta: 5,
+            }])],
+        };
+        let pad = |pc, insn| {
+            if pc == 4 {
+                let mut insns = vec![Instruction::Nop; 40000];
+                insns.push(insn);
+                insns
+            } else {
+                vec![insn]
+            }
+        };
+        let original = code.code.clone();
+        assert_eq!(
+            code.map_instructions(pad),
+            Err(RewriteError::WideConditionalBranch { index: 1 })
+        );
+        assert_eq!(code.code, original);
+
+        code.attributes.clear();
+        code.map_instructions(pad).unwrap();
+        assert_eq!(&code.code[1..9], [0x9a, 0, 8, 0xc8, 0, 0, 0x9c, 0x46]);
+    }
+
+    fn frame_offsets(code: &CodeAttribute) -> Vec<u32> {
+        let mut offsets = Vec::new();
+        for attr in &code.attributes {
+            if let Attribute::StackMapTable(frames) = attr {
+                for frame in frames {
+                    let delta = frame.offset_delta() as u32;
+                    offsets.push(offsets.last().map_or(delta, |last| last + delta + 1));
+                }
+            }
+        }
+        offsets
+    }
+
+    #[test]
+    fn nop_before_every_return_moves_handlers_and_frames() {
+        let class = testutil::read(testutil::FIXTURE);
+        // Returns inside and after a try block, and from its catch blocks
+        let original = testutil::method(&class, "guarded").code().unwrap().clone();
+        let returns: Vec<u32> = decode(&original.code)
+            .into_iter()
+            .filter(|(_, insn)| *insn == Instruction::IReturn)
+            .map(|(pc, _)| pc)
+            .collect();
+        assert!(returns.len() > 1);
+        assert!(!original.exceptions.is_empty());
+        // Each instruction moves down by one for each return before it
+        let moved = |pc: u32| pc + returns.iter().filter(|&&ret| ret < pc).count() as u32;
+
+        let mut code = original.clone();
+        code.map_instructions(|_, insn| match insn {
+            Instruction::IReturn => vec![Instruction::Nop, Instruction::IReturn],
+            insn => vec![insn],
+        })
+        .unwrap();
+
+        assert_eq!(code.code.len(), original.code.len() + returns.len());
+        let decoded = decode(&code.code);
+        for (pc, insn) in decode(&original.code) {
+            let mut index = decoded
+                .iter()
+                .position(|&(new, _)| new == moved(pc))
+                .unwrap();
+            if insn == Instruction::IReturn {
+                // The nop takes the place of the return, so branches to the return reach it first
+                assert_eq!(decoded[index].1, Instruction::Nop);
+                index += 1;
+            }
+            // Branch targets follow the instructions they pointed at
+            let mut expected = insn;
+            for target in branch_targets_mut(&mut expected) {
+                *target = moved(*target);
+            }
+            assert_eq!(decoded[index].1, expected);
+        }
+
+        assert_eq!(code.exceptions.len(), original.exceptions.len());
+        for (new, old) in code.exceptions.iter().zip(&original.exceptions) {
+            assert_eq!(new.start_pc as u32, moved(old.start_pc as u32));
+            assert_eq!(new.end_pc as u32, moved(old.end_pc as u32));
+            assert_eq!(new.handler_pc as u32, moved(old.handler_pc as u32));
+            assert_eq!(new.catch_type, old.catch_type);
+        }
+
+        let frames = frame_offsets(&original);
+        assert!(!frames.is_empty());
+        assert_eq!(
+            frame_offsets(&code),
+            frames.into_iter().map(moved).collect::<Vec<_>>()
+        );
+    }
+
+    ///
+    /// `iconst_0; ifeq 6; nop; nop; return`, with a frame at each `nop` and at the `return`
+    fn frames_at_nops(first: StackMapFrame, second: StackMapFrame) -> CodeAttribute {
+        CodeAttribute {
+            max_stack: 1,
+            max_locals: 2,
+            code: vec![0x03, 0x99, 0, 5, 0x00, 0x00, 0xb1],
+            exceptions: Vec::new(),
+            attributes: vec![Attribute::StackMapTable(vec![
+                first,
+                second,
+                StackMapFrame::Same { offset_delta: 0 },
+            ])],
+        }
+    }
+
+    fn remove_first_nop(pc: u32, insn: Instruction) -> Vec<Instruction> {
+        if pc == 4 {
+            Vec::new()
+        } else {
+            vec![insn]
+        }
+    }
+
+    #[test]
+    fn frames_of_removed_instructions_are_merged() {
+        let mut code = frames_at_nops(
+            StackMapFrame::Append {
+                offset_delta: 4,
+                items: vec![VerificationInfo::Integer, VerificationInfo::Float],
+            },
+            StackMapFrame::ChopFrame {
+                chop: 1,
+                offset_delta: 0,
+            },
+        );
+        code.map_instructions(remove_first_nop).unwrap();
+        assert_eq!(code.code, [0x03, 0x99, 0, 4, 0x00, 0xb1]);
+        match &code.attributes[..] {
+            [Attribute::StackMapTable(frames)] => {
+                assert!(matches!(
+                    &frames[..],
+                    [
+                        StackMapFrame::Append { offset_delta: 4, items },
+                        StackMapFrame::Same { offset_delta: 0 },
+                    ] if matches!(items[..], [VerificationInfo::Integer])
+                ));
+            }
+            attrs => panic!("expected a StackMapTable, got {:?}", attrs),
+        }
+
+        // A chop followed by an append needs the locals before the chop
+        let mut code = frames_at_nops(
+            StackMapFrame::ChopFrame {
+                chop: 1,
+                offset_delta: 4,
+            },
+            StackMapFrame::Append {
+                offset_delta: 0,
+                items: vec![VerificationInfo::Integer],
+            },
+        );
+        let original = code.clone();
+        assert_eq!(
+            code.map_instructions(remove_first_nop),
+            Err(RewriteError::CollapsedFrames { offset: 4 })
+        );
+        assert_eq!(code.code, original.code);
+        assert_eq!(frame_offsets(&code), [4, 5, 6]);
+    }
+}
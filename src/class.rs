@@ -1,11 +1,24 @@
-use crate::string::JString;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use crate::{
+    bytecode::DecodeError,
+    descriptor::{DescriptorError, FieldType, MethodDescriptor},
+    flags::{InnerClassAccessFlags, ParameterAccessFlags},
+    pool::{ConstantPool, CpIndex},
+    refs::RefKind,
+    signature::{ClassSignature, FieldSignature, MethodSignature, SignatureError},
+    string::{JStr, JString},
+    write::WriteError,
+};
 
 #[derive(Clone, Debug)]
 pub enum Constant {
     Utf8(JString),
     Int(i32),
+    /// Read and written by its bit pattern, so NaN payloads and the sign of zero survive a round trip
     Float(f32),
     Long(i64),
+    /// Read and written by its bit pattern, so NaN payloads and the sign of zero survive a round trip
     Double(f64),
     LongOrDoubleHigh,
     Class(u16),
@@ -43,11 +56,83 @@ pub enum Constant {
     Package(u16),
 }
 
+impl Constant {
+    ///
+    /// Resolves a MethodHandle constant to its kind and the field or method it refers to.
+    ///
+    /// Returns None if this is not a MethodHandle, its kind is unknown, or it refers to a constant its kind does not allow, such as a `REF_invokeStatic` handle to a FieldRef.
+    /// `REF_newInvokeSpecial` must refer to an `<init>` method, and the other method kinds must not refer to `<init>` or `<clinit>`
+    pub fn resolve_method_handle<'a>(
+        &self,
+        pool: &'a ConstantPool,
+    ) -> Option<ResolvedMethodHandle<'a>> {
+        let (kind, reference) = match self {
+            Constant::MethodHandle { kind, reference } => {
+                (MethodHandleKind::from_u8(*kind)?, *reference)
+            }
+            _ => return None,
+        };
+        let target = pool.get(reference)?;
+        let valid_target = match kind {
+            MethodHandleKind::GetField
+            | MethodHandleKind::GetStatic
+            | MethodHandleKind::PutField
+            | MethodHandleKind::PutStatic => matches!(target, Constant::FieldRef { .. }),
+            MethodHandleKind::InvokeVirtual | MethodHandleKind::NewInvokeSpecial => {
+                matches!(target, Constant::MethodRef { .. })
+            }
+            // Interface methods are only allowed from version 52, which the constant cannot check
+            MethodHandleKind::InvokeStatic | MethodHandleKind::InvokeSpecial => matches!(
+                target,
+                Constant::MethodRef { .. } | Constant::InterfaceMethodRef { .. }
+            ),
+            MethodHandleKind::InvokeInterface => {
+                matches!(target, Constant::InterfaceMethodRef { .. })
+            }
+        };
+        if !valid_target {
+            return None;
+        }
+        let (class, name, descriptor) = pool.member_ref(reference)?;
+        let valid_name = match kind {
+            MethodHandleKind::NewInvokeSpecial => name.as_bytes() == b"<init>",
+            MethodHandleKind::InvokeVirtual
+            | MethodHandleKind::InvokeStatic
+            | MethodHandleKind::InvokeSpecial
+            | MethodHandleKind::InvokeInterface => {
+                !matches!(name.as_bytes(), b"<init>" | b"<clinit>")
+            }
+            _ => true,
+        };
+        if !valid_name {
+            return None;
+        }
+        Some(ResolvedMethodHandle {
+            kind,
+            class,
+            name,
+            descriptor,
+        })
+    }
+
+    ///
+    /// Resolves and parses the descriptor of a MethodType constant. Returns None if this is not a MethodType or its descriptor does not resolve
+    pub fn resolve_method_type<'a>(
+        &self,
+        pool: &'a ConstantPool,
+    ) -> Option<Result<MethodDescriptor<'a>, DescriptorError>> {
+        match self {
+            Constant::MethodType(desc) => pool.utf8(*desc).map(MethodDescriptor::parse),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct ClassFile {
     pub min: u16,
     pub maj: u16,
-    pub consts: Vec<Constant>,
+    pub consts: ConstantPool,
     pub acc: u16,
     pub this: u16,
     pub supercl: u16,
@@ -57,12 +142,452 @@ pub struct ClassFile {
     pub attributes: Vec<Attribute>,
 }
 
+impl ClassFile {
+    ///
+    /// Iterates over every Utf8 constant with its index, which includes names, descriptors, and the text of string literals
+    pub fn utf8_constants(&self) -> impl Iterator<Item = (u16, &JStr)> + '_ {
+        self.consts
+            .entries()
+            .filter_map(|(index, constant)| match constant {
+                Constant::Utf8(st) => Some((index, &**st)),
+                _ => None,
+            })
+    }
+
+    ///
+    /// Replaces the text of every Utf8 constant with what `interner` returns for it.
+    ///
+    /// Passing the same interner for every class of a jar lets it canonicalize text across all of them, for example by handing out a single shrunk copy of each distinct string.
+    /// The interner must return equivalent text, since nothing else in the class is updated
+    pub fn intern_utf8_with(&mut self, mut interner: impl FnMut(&JStr) -> JString) {
+        for constant in &mut self.consts {
+            if let Constant::Utf8(st) = constant {
+                *st = interner(st);
+            }
+        }
+    }
+
+    ///
+    /// Iterates over the methods of the class, pairing the name of each with its parsed descriptor.
+    ///
+    /// Methods whose name or descriptor does not resolve to a Utf8 constant are skipped
+    pub fn method_descriptors(
+        &self,
+    ) -> impl Iterator<Item = (&JStr, Result<MethodDescriptor<'_>, DescriptorError>)> + '_ {
+        self.methods.iter().filter_map(move |method| {
+            let name = self.consts.utf8(method.name)?;
+            let descriptor = self.consts.utf8(method.descriptor)?;
+            Some((name, MethodDescriptor::parse(descriptor)))
+        })
+    }
+
+    ///
+    /// Iterates over the fields of the class, pairing the name of each with its parsed type.
+    ///
+    /// Fields whose name or descriptor does not resolve to a Utf8 constant are skipped
+    pub fn field_descriptors(
+        &self,
+    ) -> impl Iterator<Item = (&JStr, Result<FieldType<'_>, DescriptorError>)> + '_ {
+        self.fields.iter().filter_map(move |field| {
+            let name = self.consts.utf8(field.name)?;
+            let descriptor = self.consts.utf8(field.descriptor)?;
+            Some((name, FieldType::parse(descriptor)))
+        })
+    }
+
+    ///
+    /// Collects the internal names of every class this class refers to: through Class constants, the descriptors of its fields and methods, and the descriptors of the fields and methods it references.
+    ///
+    /// Array classes contribute their element class, if any. Malformed descriptors are skipped.
+    pub fn referenced_classes(&self) -> BTreeSet<&JStr> {
+        fn add_descriptor<'a>(set: &mut BTreeSet<&'a JStr>, desc: &'a JStr) {
+            if desc.as_bytes().first() == Some(&b'(') {
+                if let Ok(desc) = MethodDescriptor::parse(desc) {
+                    set.extend(desc.class_names());
+                }
+            } else if let Ok(Some(name)) = FieldType::parse(desc).map(|ty| ty.class_name()) {
+                set.insert(name);
+            }
+        }
+
+        let mut set = BTreeSet::new();
+        for constant in &self.consts {
+            match constant {
+                Constant::Class(name) => {
+                    if let Some(name) = self.consts.utf8(*name) {
+                        if name.as_bytes().first() == Some(&b'[') {
+                            add_descriptor(&mut set, name);
+                        } else {
+                            set.insert(name);
+                        }
+                    }
+                }
+                Constant::FieldRef { name_and_type, .. }
+                | Constant::MethodRef { name_and_type, .. }
+                | Constant::InterfaceMethodRef { name_and_type, .. } => {
+                    if let Some((_, desc)) = self.consts.name_and_type(*name_and_type) {
+                        add_descriptor(&mut set, desc);
+                    }
+                }
+                _ => {}
+            }
+        }
+        for desc in self
+            .fields
+            .iter()
+            .map(|f| f.descriptor)
+            .chain(self.methods.iter().map(|m| m.descriptor))
+        {
+            if let Some(desc) = self.consts.utf8(desc) {
+                add_descriptor(&mut set, desc);
+            }
+        }
+        set
+    }
+
+    ///
+    /// Renames the class `from` to `to` wherever this class refers to it: in Class constants (including array classes), descriptors, and signatures.
+    ///
+    /// Utf8 constants are never modified in place, since they may be shared with uses that must keep their text, such as a String constant.
+    /// Instead, each reference is redirected to a Utf8 holding the renamed text, which is added to the pool if not already present. Malformed descriptors and signatures are left alone.
+    ///
+    /// Fails with [`WriteError::ConstantPoolOverflow`] if the pool has no room for the renamed text, in which case no reference is changed
+    pub fn rename_class(&mut self, from: &JStr, to: &JStr) -> Result<(), WriteError> {
+        fn rename(kind: RefKind, text: &JStr, from: &JStr, to: &JStr) -> Option<JString> {
+            let renamed = match kind {
+                RefKind::ClassName if text == from => to.to_owned(),
+                RefKind::ClassName | RefKind::Descriptor
+                    if text.as_bytes().first() == Some(&b'(') =>
+                {
+                    let mut desc = MethodDescriptor::parse(text).ok()?;
+                    desc.rename_class(from, to);
+                    desc.to_descriptor()
+                }
+                RefKind::ClassName | RefKind::Descriptor => {
+                    let mut ty = FieldType::parse(text).ok()?;
+                    ty.rename_class(from, to);
+                    ty.to_descriptor()
+                }
+                RefKind::Signature if text.as_bytes().contains(&b'(') => {
+                    let mut sig = MethodSignature::parse(text).ok()?;
+                    sig.rename_class(from, to);
+                    sig.to_signature()
+                }
+                RefKind::Signature => {
+                    if let Ok(mut sig) = FieldSignature::parse(text) {
+                        sig.rename_class(from, to);
+                        sig.to_signature()
+                    } else {
+                        let mut sig = ClassSignature::parse(text).ok()?;
+                        sig.rename_class(from, to);
+                        sig.to_signature()
+                    }
+                }
+                _ => return None,
+            };
+            Some(renamed).filter(|renamed| **renamed != *text)
+        }
+
+        let mut renamed = BTreeMap::new();
+        let consts = &self.consts;
+        self.for_each_ref(|_, kind, &idx| {
+            if let RefKind::ClassName | RefKind::Descriptor | RefKind::Signature = kind {
+                renamed
+                    .entry((idx, kind))
+                    .or_insert_with(|| rename(kind, consts.utf8(idx)?, from, to));
+            }
+        });
+
+        let mut existing = HashMap::new();
+        for (idx, text) in self.utf8_constants() {
+            existing.entry(text.to_owned()).or_insert(idx);
+        }
+        let mut redirects = HashMap::new();
+        for (key, text) in renamed {
+            if let Some(text) = text {
+                let idx = match existing.get(&text) {
+                    Some(&idx) => idx,
+                    None => {
+                        let idx = self.consts.push(Constant::Utf8(text.clone()))?;
+                        existing.insert(text, idx);
+                        idx
+                    }
+                };
+                redirects.insert(key, idx);
+            }
+        }
+
+        self.for_each_ref_mut(|_, kind, idx| {
+            if let Some(&new) = redirects.get(&(*idx, kind)) {
+                *idx = new;
+            }
+        });
+        Ok(())
+    }
+
+    ///
+    /// Finds the (name, descriptor) pairs shared by more than one field, or by more than one method, which the JVM rejects.
+    ///
+    /// Each pair is reported once, in the order its second occurrence appears. Members whose name or descriptor cannot be resolved are skipped
+    pub fn find_duplicate_members(&self) -> Vec<(JString, JString)> {
+        let mut dups = Vec::new();
+        let fields: Vec<_> = self.fields.iter().map(|f| (f.name, f.descriptor)).collect();
+        let methods: Vec<_> = self
+            .methods
+            .iter()
+            .map(|m| (m.name, m.descriptor))
+            .collect();
+        for members in [fields, methods] {
+            let mut seen = HashMap::new();
+            for (name, descriptor) in members {
+                if let (Some(name), Some(descriptor)) =
+                    (self.consts.utf8(name), self.consts.utf8(descriptor))
+                {
+                    let count = seen.entry((name, descriptor)).or_insert(0u32);
+                    *count += 1;
+                    if *count == 2 {
+                        dups.push((name.to_owned(), descriptor.to_owned()));
+                    }
+                }
+            }
+        }
+        dups
+    }
+
+    ///
+    /// Sorts the fields and the methods of the class by their [`sort_key`](MethodInfo::sort_key), so that classes with the same members are written in the same order.
+    ///
+    /// The order of members has no meaning to the JVM. Members with the same key, which only happens if they are duplicates or their names do not resolve, keep their relative order
+    pub fn sort_members(&mut self) {
+        let ClassFile {
+            consts,
+            fields,
+            methods,
+            ..
+        } = self;
+        fields.sort_by_cached_key(|field| field.sort_key(consts));
+        methods.sort_by_cached_key(|method| method.sort_key(consts));
+    }
+
+    ///
+    /// Resolves the entries of the InnerClasses attribute, if present, in the order they appear
+    pub fn inner_classes(&self) -> Vec<ResolvedInnerClass<'_>> {
+        self.attributes
+            .iter()
+            .filter_map(|attr| match attr {
+                Attribute::InnerClasses(classes) => Some(classes),
+                _ => None,
+            })
+            .flatten()
+            .map(|info| ResolvedInnerClass {
+                inner_class: CpIndex(info.inner_class_info).class_name(&self.consts),
+                outer_class: CpIndex(info.outer_class_info).class_name(&self.consts),
+                inner_name: CpIndex(info.inner_name).utf8(&self.consts),
+                flags: InnerClassAccessFlags::from_bits_truncate(info.inner_flags),
+            })
+            .collect()
+    }
+
+    ///
+    /// Resolves the components of the Record attribute, if present, in the order they appear
+    pub fn record_components(&self) -> Vec<ResolvedRecordComponent<'_>> {
+        self.attributes
+            .iter()
+            .filter_map(|attr| match attr {
+                Attribute::Record(components) => Some(components),
+                _ => None,
+            })
+            .flatten()
+            .map(|component| ResolvedRecordComponent {
+                name: self.consts.utf8(component.name),
+                descriptor: self.consts.utf8(component.descriptor),
+                signature: component.signature(&self.consts),
+            })
+            .collect()
+    }
+
+    ///
+    /// The names of the attributes of the class, without interpreting them. Unresolved attributes are included by the name in the pool.
+    ///
+    /// The attributes of fields and methods are not included. See [`FieldInfo::attribute_names`] and [`MethodInfo::attribute_names`]
+    pub fn attribute_names(&self) -> Vec<&JStr> {
+        attribute_names(&self.attributes, &self.consts)
+    }
+
+    ///
+    /// Resolves the entries of the BootstrapMethods attribute of the class, in order, so they can be indexed by the `bootstrap_attrs` of Dynamic and InvokeDynamic constants
+    pub fn bootstrap_methods(&self) -> Vec<ResolvedBootstrapMethod<'_>> {
+        let pool = &self.consts;
+        self.attributes
+            .iter()
+            .filter_map(|attr| match attr {
+                Attribute::BootstrapMethods(methods) => Some(methods),
+                _ => None,
+            })
+            .flatten()
+            .map(|method| ResolvedBootstrapMethod {
+                handle: pool.method_handle(method.href),
+                args: method
+                    .args
+                    .iter()
+                    .map(|&arg| {
+                        let resolved = match pool.get(arg) {
+                            Some(Constant::Int(v)) => Some(BootstrapArgument::Int(*v)),
+                            Some(Constant::Float(v)) => Some(BootstrapArgument::Float(*v)),
+                            Some(Constant::Long(v)) => Some(BootstrapArgument::Long(*v)),
+                            Some(Constant::Double(v)) => Some(BootstrapArgument::Double(*v)),
+                            Some(Constant::String(st)) => {
+                                pool.utf8(*st).map(BootstrapArgument::String)
+                            }
+                            Some(Constant::Class(_)) => {
+                                pool.class_name(arg).map(BootstrapArgument::Class)
+                            }
+                            Some(Constant::MethodType(desc)) => {
+                                pool.utf8(*desc).map(BootstrapArgument::MethodType)
+                            }
+                            Some(Constant::MethodHandle { .. }) => {
+                                pool.method_handle(arg).map(BootstrapArgument::MethodHandle)
+                            }
+                            Some(Constant::Dynamic {
+                                bootstrap_attrs,
+                                name_and_type,
+                            }) => pool
+                                .name_and_type(*name_and_type)
+                                .map(|(name, descriptor)| BootstrapArgument::Dynamic {
+                                    bootstrap_method: *bootstrap_attrs,
+                                    name,
+                                    descriptor,
+                                }),
+                            _ => None,
+                        };
+                        resolved.unwrap_or(BootstrapArgument::Invalid(arg))
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    ///
+    /// Whether the major version of the class is at least `major`, such as one of the `JAVA_*` constants in [`consts`].
+    ///
+    /// For example, StackMapTable attributes are required from [`consts::JAVA_6`], and NestHost attributes are allowed from [`consts::JAVA_11`]
+    pub fn version_at_least(&self, major: u16) -> bool {
+        self.maj >= major
+    }
+
+    ///
+    /// Whether the class depends on the preview features of its Java release, which is marked by a minor version of [`consts::PREVIEW_FEATURES`]
+    pub fn is_preview(&self) -> bool {
+        self.min == consts::PREVIEW_FEATURES
+    }
+
+    ///
+    /// Resolves the SourceFile attribute, if present
+    pub fn source_file(&self) -> Option<&JStr> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::SourceFile(name) => self.consts.utf8(*name),
+            _ => None,
+        })
+    }
+
+    ///
+    /// Sets the SourceFile attribute to `name`, adding it to the pool if needed.
+    ///
+    /// An existing SourceFile attribute is replaced in place, and any further ones are removed.
+    /// Fails, leaving the class unchanged, if the name must be added to a pool that is full
+    pub fn set_source_file(&mut self, name: &JStr) -> Result<(), WriteError> {
+        let name = self.consts.intern_utf8(name)?;
+        let mut found = false;
+        self.attributes.retain_mut(|attr| match attr {
+            Attribute::SourceFile(idx) if !found => {
+                *idx = name;
+                found = true;
+                true
+            }
+            Attribute::SourceFile(_) => false,
+            _ => true,
+        });
+        if !found {
+            self.attributes.push(Attribute::SourceFile(name));
+        }
+        Ok(())
+    }
+
+    ///
+    /// Resolves the ModuleMainClass attribute of a module-info class to the internal name of the main class, if present
+    pub fn module_main_class(&self) -> Option<&JStr> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::ModuleMainClass(class) => self.consts.class_name(*class),
+            _ => None,
+        })
+    }
+
+    ///
+    /// Resolves the ModulePackages attribute of a module-info class to the internal names of the packages of the module, or an empty list if it is absent.
+    ///
+    /// Entries that do not resolve to a Package constant are skipped
+    pub fn module_packages(&self) -> Vec<&JStr> {
+        self.attributes
+            .iter()
+            .filter_map(|attr| match attr {
+                Attribute::ModulePackage(packages) => Some(packages),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|&package| self.consts.package_name(package))
+            .collect()
+    }
+
+    ///
+    /// Removes debugging attributes (see [`Attribute::is_debug_info`]) from the class, its methods, and their Code attributes.
+    ///
+    /// The constants those attributes referred to are left in the pool, so every other index stays valid. Use [`ClassFile::strip_debug_and_prune_constants`] to remove them as well
+    pub fn strip_debug(&mut self) {
+        self.attributes.retain(|attr| !attr.is_debug_info());
+        for method in &mut self.methods {
+            method.attributes.retain(|attr| !attr.is_debug_info());
+            for attr in &mut method.attributes {
+                if let Attribute::Code(code) = attr {
+                    code.attributes.retain(|attr| !attr.is_debug_info());
+                }
+            }
+        }
+    }
+
+    ///
+    /// Removes debugging attributes like [`ClassFile::strip_debug`], then removes the constants that are no longer referred to with [`ClassFile::garbage_collect_constants`].
+    ///
+    /// This renumbers the constant pool. If the bytecode of a method cannot be decoded, the debugging attributes are still removed, but the pool is left unchanged and the error is returned
+    pub fn strip_debug_and_prune_constants(&mut self) -> Result<(), DecodeError> {
+        self.strip_debug();
+        self.garbage_collect_constants()
+    }
+}
+
 pub mod consts {
     pub const MAGIC: u32 = 0xCAFEBABE;
     pub const MIN_VERSION: u16 = 45;
     pub const MAX_VERSION: u16 = 60;
     pub const PREVIEW_FEATURES: u16 = 0xffff;
 
+    // The major version of the class files of each release of Java
+    pub const JAVA_1_1: u16 = 45;
+    pub const JAVA_1_2: u16 = 46;
+    pub const JAVA_1_3: u16 = 47;
+    pub const JAVA_1_4: u16 = 48;
+    pub const JAVA_5: u16 = 49;
+    pub const JAVA_6: u16 = 50;
+    pub const JAVA_7: u16 = 51;
+    pub const JAVA_8: u16 = 52;
+    pub const JAVA_9: u16 = 53;
+    pub const JAVA_10: u16 = 54;
+    pub const JAVA_11: u16 = 55;
+    pub const JAVA_12: u16 = 56;
+    pub const JAVA_13: u16 = 57;
+    pub const JAVA_14: u16 = 58;
+    pub const JAVA_15: u16 = 59;
+    pub const JAVA_16: u16 = 60;
+
     pub const ACC_CLASS_BITS: u16 = ACC_PUBLIC
         | ACC_FINAL
         | ACC_SUPER
@@ -135,7 +660,10 @@ pub enum Attribute {
     StackMapTable(Vec<StackMapFrame>),
     Exceptions(Vec<u16>),
     InnerClasses(Vec<InnerClassInfo>),
-    EnclosingMethod { class: u16, method: u16 },
+    EnclosingMethod {
+        class: u16,
+        method: u16,
+    },
     Synthetic,
     Signature(u16),
     SourceFile(u16),
@@ -160,10 +688,149 @@ pub enum Attribute {
     NestMembers(Vec<u16>),
     Record(Vec<RecordComponentInfo>),
     PermittedSubclasses(Vec<u16>),
+    /// lcjvm extension. Encoded as a u8 kind (0 for From, 1 for Removed, 2 for Unstable), a u16 version or feature index, and a u8 default flag
     Availability(Availability),
+    /// lcjvm extension. Encoded as a u16 index of the Utf8 name of the lang item
     LangItem(u16),
+    /// lcjvm extension. Encoded as a u16 constant pool index
     FillNativeMethod(u16),
-    Unresolved { name: u16, content: Vec<u8> },
+    Unresolved {
+        name: u16,
+        content: Vec<u8>,
+    },
+}
+
+impl Attribute {
+    ///
+    /// Returns the name of the attribute in the class file, or None for an [`Attribute::Unresolved`], which stores the constant pool index of its name instead
+    pub fn name(&self) -> Option<&'static str> {
+        Some(match self {
+            Attribute::ConstantValue(_) => "ConstantValue",
+            Attribute::Code(_) => "Code",
+            Attribute::StackMapTable(_) => "StackMapTable",
+            Attribute::Exceptions(_) => "Exceptions",
+            Attribute::InnerClasses(_) => "InnerClasses",
+            Attribute::EnclosingMethod { .. } => "EnclosingMethod",
+            Attribute::Synthetic => "Synthetic",
+            Attribute::Signature(_) => "Signature",
+            Attribute::SourceFile(_) => "SourceFile",
+            Attribute::SourceDebugExtension(_) => "SourceDebugExtension",
+            Attribute::LineNumberTable(_) => "LineNumberTable",
+            Attribute::LocalVariableTable(_) => "LocalVariableTable",
+            Attribute::LocalVariableTypeTable(_) => "LocalVariableTypeTable",
+            Attribute::Deprecated => "Deprecated",
+            Attribute::RuntimeVisibleAnnotations(_) => "RuntimeVisibleAnnotations",
+            Attribute::RuntimeInvisibleAnnotations(_) => "RuntimeInvisibleAnnotations",
+            Attribute::RuntimeVisibleParameterAnnotations(_) => {
+                "RuntimeVisibleParameterAnnotations"
+            }
+            Attribute::RuntimeInvisibleParameterAnnotations(_) => {
+                "RuntimeInvisibleParameterAnnotations"
+            }
+            Attribute::RuntimeVisibleTypeAnnotations(_) => "RuntimeVisibleTypeAnnotations",
+            Attribute::RuntimeInvisibleTypeAnnotations(_) => "RuntimeInvisibleTypeAnnotations",
+            Attribute::AnnotationDefault(_) => "AnnotationDefault",
+            Attribute::BootstrapMethods(_) => "BootstrapMethods",
+            Attribute::MethodParameters(_) => "MethodParameters",
+            Attribute::Module(_) => "Module",
+            Attribute::ModulePackage(_) => "ModulePackages",
+            Attribute::ModuleMainClass(_) => "ModuleMainClass",
+            Attribute::NestHost(_) => "NestHost",
+            Attribute::NestMembers(_) => "NestMembers",
+            Attribute::Record(_) => "Record",
+            Attribute::PermittedSubclasses(_) => "PermittedSubclasses",
+            Attribute::Availability(_) => "Availability",
+            Attribute::LangItem(_) => "LangItem",
+            Attribute::FillNativeMethod(_) => "FillNativeMethod",
+            Attribute::Unresolved { .. } => return None,
+        })
+    }
+
+    ///
+    /// Creates an attribute that lcjvm does not interpret, adding `name` to the pool if needed. `content` is written as is, after the attribute's name and length.
+    ///
+    /// Fails if `name` must be added to a pool that is full
+    pub fn unresolved(
+        name: &JStr,
+        content: Vec<u8>,
+        pool: &mut ConstantPool,
+    ) -> Result<Self, WriteError> {
+        Ok(Attribute::Unresolved {
+            name: pool.intern_utf8(name)?,
+            content,
+        })
+    }
+
+    ///
+    /// Returns the name of the attribute as it is written in the class file, resolving the name of an [`Attribute::Unresolved`] from `pool`
+    pub fn name_bytes<'a>(&'a self, pool: &'a ConstantPool) -> Option<&'a [u8]> {
+        match self {
+            Attribute::Unresolved { name, .. } => pool.utf8(*name).map(JStr::as_bytes),
+            _ => self.name().map(str::as_bytes),
+        }
+    }
+
+    ///
+    /// Like [`Attribute::name_bytes`], but as a JStr
+    pub fn resolve_name<'a>(&'a self, pool: &'a ConstantPool) -> Option<&'a JStr> {
+        match self {
+            Attribute::Unresolved { name, .. } => pool.utf8(*name),
+            // The names of the attributes lcjvm knows are ASCII
+            _ => self.name().and_then(|name| JStr::from_str(name).ok()),
+        }
+    }
+
+    ///
+    /// Returns the content of an [`Attribute::Unresolved`], or None for any other attribute
+    pub fn content(&self) -> Option<&[u8]> {
+        match self {
+            Attribute::Unresolved { content, .. } => Some(content),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Whether this attribute only carries debugging information, which the JVM does not need to load or run the class
+    pub fn is_debug_info(&self) -> bool {
+        matches!(
+            self,
+            Attribute::LineNumberTable(_)
+                | Attribute::LocalVariableTable(_)
+                | Attribute::LocalVariableTypeTable(_)
+                | Attribute::SourceFile(_)
+                | Attribute::SourceDebugExtension(_)
+        )
+    }
+
+    ///
+    /// Resolves an EnclosingMethod attribute to the name of the enclosing class and, if the class is enclosed by a method or constructor, that method's name and descriptor.
+    ///
+    /// Returns None if this is not an EnclosingMethod attribute or its indices do not resolve
+    pub fn enclosing_method<'a>(
+        &self,
+        pool: &'a ConstantPool,
+    ) -> Option<(&'a JStr, Option<(&'a JStr, &'a JStr)>)> {
+        match self {
+            Attribute::EnclosingMethod { class, method } => {
+                let class = pool.class_name(*class)?;
+                let method = CpIndex(*method).resolve(|method| pool.name_and_type(method))?;
+                Some((class, method))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// The names of `attrs` and the attributes of any Code attribute among them, skipping names that do not resolve
+fn attribute_names<'a>(attrs: &'a [Attribute], pool: &'a ConstantPool) -> Vec<&'a JStr> {
+    let mut names = Vec::new();
+    for attr in attrs {
+        names.extend(attr.resolve_name(pool));
+        if let Attribute::Code(code) = attr {
+            names.extend(attribute_names(&code.attributes, pool));
+        }
+    }
+    names
 }
 
 #[derive(Clone, Debug)]
@@ -192,13 +859,19 @@ pub struct ExceptionInfo {
 
 #[derive(Clone, Debug)]
 pub enum StackMapFrame {
-    Same,
-    SameLocals1StackFrame(VerificationInfo),
+    Same {
+        offset_delta: u8,
+    },
+    SameLocals1StackFrame {
+        offset_delta: u8,
+        info: VerificationInfo,
+    },
     SameLocals1StackFrameExtended {
-        offset_deleta: u16,
+        offset_delta: u16,
         info: VerificationInfo,
     },
     ChopFrame {
+        /// The number of locals removed, from 1 to 3. The frame type is `251 - chop`
         chop: u8,
         offset_delta: u16,
     },
@@ -216,6 +889,22 @@ pub enum StackMapFrame {
     },
 }
 
+impl StackMapFrame {
+    ///
+    /// The offset delta of the frame, whatever its form. The first frame is at `offset_delta`, and each later frame at `offset_delta + 1` past the one before it
+    pub fn offset_delta(&self) -> u16 {
+        match self {
+            StackMapFrame::Same { offset_delta }
+            | StackMapFrame::SameLocals1StackFrame { offset_delta, .. } => *offset_delta as u16,
+            StackMapFrame::SameLocals1StackFrameExtended { offset_delta, .. }
+            | StackMapFrame::ChopFrame { offset_delta, .. }
+            | StackMapFrame::SameExtended { offset_delta }
+            | StackMapFrame::Append { offset_delta, .. }
+            | StackMapFrame::Full { offset_delta, .. } => *offset_delta,
+        }
+    }
+}
+
 #[repr(u8)]
 #[derive(Clone, Debug)]
 pub enum VerificationInfo {
@@ -238,6 +927,107 @@ pub struct InnerClassInfo {
     pub inner_flags: u16,
 }
 
+///
+/// An [`InnerClassInfo`] with its indices resolved. Each name is None if its index is 0 or does not resolve
+#[derive(Clone, Debug)]
+pub struct ResolvedInnerClass<'a> {
+    pub inner_class: Option<&'a JStr>,
+    /// The class this class is a member of, or None for local and anonymous classes
+    pub outer_class: Option<&'a JStr>,
+    /// The simple name of the class, or None for anonymous classes
+    pub inner_name: Option<&'a JStr>,
+    pub flags: InnerClassAccessFlags,
+}
+
+///
+/// A [`RecordComponentInfo`] with its indices resolved. The name and descriptor are None if their indices do not resolve
+#[derive(Clone, Debug)]
+pub struct ResolvedRecordComponent<'a> {
+    pub name: Option<&'a JStr>,
+    pub descriptor: Option<&'a JStr>,
+    /// The parsed generic signature of the component, or None if it has no Signature attribute or its index does not resolve
+    pub signature: Option<Result<FieldSignature<'a>, SignatureError>>,
+}
+
+///
+/// The kind of a MethodHandle constant, which says how the handle accesses the field or method it refers to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MethodHandleKind {
+    /// `REF_getField`
+    GetField = 1,
+    GetStatic = 2,
+    PutField = 3,
+    PutStatic = 4,
+    InvokeVirtual = 5,
+    InvokeStatic = 6,
+    InvokeSpecial = 7,
+    /// `REF_newInvokeSpecial`, which creates an object and calls its constructor
+    NewInvokeSpecial = 8,
+    InvokeInterface = 9,
+}
+
+impl MethodHandleKind {
+    ///
+    /// Converts the `reference_kind` of a MethodHandle constant, returning None if it is not from 1 to 9
+    pub fn from_u8(kind: u8) -> Option<Self> {
+        Some(match kind {
+            1 => MethodHandleKind::GetField,
+            2 => MethodHandleKind::GetStatic,
+            3 => MethodHandleKind::PutField,
+            4 => MethodHandleKind::PutStatic,
+            5 => MethodHandleKind::InvokeVirtual,
+            6 => MethodHandleKind::InvokeStatic,
+            7 => MethodHandleKind::InvokeSpecial,
+            8 => MethodHandleKind::NewInvokeSpecial,
+            9 => MethodHandleKind::InvokeInterface,
+            _ => return None,
+        })
+    }
+}
+
+///
+/// A MethodHandle constant with the field or method it refers to resolved, as returned by [`Constant::resolve_method_handle`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ResolvedMethodHandle<'a> {
+    pub kind: MethodHandleKind,
+    pub class: &'a JStr,
+    pub name: &'a JStr,
+    pub descriptor: &'a JStr,
+}
+
+///
+/// A static argument of a bootstrap method, resolved from the loadable constant it refers to
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BootstrapArgument<'a> {
+    Int(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    String(&'a JStr),
+    /// A class, with its internal name
+    Class(&'a JStr),
+    /// A method type, with its descriptor
+    MethodType(&'a JStr),
+    MethodHandle(ResolvedMethodHandle<'a>),
+    /// A dynamically-computed constant. `bootstrap_method` indexes the BootstrapMethods attribute
+    Dynamic {
+        bootstrap_method: u16,
+        name: &'a JStr,
+        descriptor: &'a JStr,
+    },
+    /// The index does not resolve to a loadable constant
+    Invalid(u16),
+}
+
+///
+/// An entry of the BootstrapMethods attribute with its method handle and arguments resolved
+#[derive(Clone, Debug, PartialEq)]
+pub struct ResolvedBootstrapMethod<'a> {
+    /// The bootstrap method, or None if its index does not resolve to a MethodHandle constant
+    pub handle: Option<ResolvedMethodHandle<'a>>,
+    pub args: Vec<BootstrapArgument<'a>>,
+}
+
 #[derive(Clone, Debug)]
 pub struct LineNumberEntry {
     pub start_pc: u16,
@@ -269,6 +1059,30 @@ pub struct LocalVariableTypeInfo {
     pub index: u16,
 }
 
+impl LocalVariableTypeInfo {
+    ///
+    /// Resolves and parses the generic signature of the variable
+    pub fn signature<'a>(
+        &self,
+        pool: &'a ConstantPool,
+    ) -> Option<Result<FieldSignature<'a>, SignatureError>> {
+        pool.utf8(self.signature).map(FieldSignature::parse)
+    }
+}
+
+///
+/// A local variable from the LocalVariableTable of a method, with its generic signature from the LocalVariableTypeTable. Each name is None if its index does not resolve
+#[derive(Clone, Debug)]
+pub struct ResolvedLocalVariable<'a> {
+    pub start_pc: u16,
+    pub length: u16,
+    pub index: u16,
+    pub name: Option<&'a JStr>,
+    pub descriptor: Option<&'a JStr>,
+    /// The parsed generic signature of the variable, or None if the variable has no entry in the LocalVariableTypeTable or its signature does not resolve
+    pub signature: Option<Result<FieldSignature<'a>, SignatureError>>,
+}
+
 #[derive(Clone, Debug)]
 pub struct Annotation {
     pub class: u16,
@@ -390,6 +1204,20 @@ pub struct RecordComponentInfo {
     pub attributes: Vec<Attribute>,
 }
 
+impl RecordComponentInfo {
+    ///
+    /// Resolves and parses the Signature attribute of the component, which it has if its type is generic, such as `List<String>`
+    pub fn signature<'a>(
+        &self,
+        pool: &'a ConstantPool,
+    ) -> Option<Result<FieldSignature<'a>, SignatureError>> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::Signature(sig) => pool.utf8(*sig).map(FieldSignature::parse),
+            _ => None,
+        })
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct FieldInfo {
     pub acc: u16,
@@ -398,6 +1226,79 @@ pub struct FieldInfo {
     pub attributes: Vec<Attribute>,
 }
 
+///
+/// The initial value of a static field, given by its ConstantValue attribute
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConstantValue<'a> {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    String(&'a JStr),
+}
+
+fn sort_key(pool: &ConstantPool, name: u16, descriptor: u16) -> (JString, JString) {
+    let resolve = |idx| pool.utf8(idx).map(JStr::to_owned).unwrap_or_default();
+    (resolve(name), resolve(descriptor))
+}
+
+impl FieldInfo {
+    ///
+    /// The name and descriptor of the field, which [`ClassFile::sort_members`] orders by. Each is empty if its index does not resolve, so such fields sort first
+    pub fn sort_key(&self, pool: &ConstantPool) -> (JString, JString) {
+        sort_key(pool, self.name, self.descriptor)
+    }
+
+    ///
+    /// The names of the attributes of the field, without interpreting them. Unresolved attributes are included by the name in the pool
+    pub fn attribute_names<'a>(&'a self, pool: &'a ConstantPool) -> Vec<&'a JStr> {
+        attribute_names(&self.attributes, pool)
+    }
+
+    ///
+    /// Whether the field is marked synthetic, either by `ACC_SYNTHETIC` or by a Synthetic attribute, which compilers emitted before the flag existed
+    pub fn is_synthetic(&self) -> bool {
+        self.acc & consts::ACC_SYNTHETIC != 0
+            || self
+                .attributes
+                .iter()
+                .any(|attr| matches!(attr, Attribute::Synthetic))
+    }
+
+    ///
+    /// Whether the field has a Deprecated attribute. There is no access flag for deprecation
+    pub fn is_deprecated(&self) -> bool {
+        self.attributes
+            .iter()
+            .any(|attr| matches!(attr, Attribute::Deprecated))
+    }
+
+    ///
+    /// Resolves the ConstantValue attribute of the field.
+    ///
+    /// Returns None if the field is not static, since the JVM ignores the attribute on instance fields, or if the constant is not of the kind the descriptor requires:
+    /// an Int for `int`, `short`, `char`, `byte`, and `boolean` fields, and a String for `java/lang/String` fields
+    pub fn constant_value<'a>(&self, pool: &'a ConstantPool) -> Option<ConstantValue<'a>> {
+        if self.acc & consts::ACC_STATIC == 0 {
+            return None;
+        }
+        let idx = self.attributes.iter().find_map(|attr| match attr {
+            Attribute::ConstantValue(idx) => Some(*idx),
+            _ => None,
+        })?;
+        match (pool.utf8(self.descriptor)?.as_bytes(), pool.get(idx)?) {
+            (b"I" | b"S" | b"C" | b"B" | b"Z", Constant::Int(v)) => Some(ConstantValue::Int(*v)),
+            (b"J", Constant::Long(v)) => Some(ConstantValue::Long(*v)),
+            (b"F", Constant::Float(v)) => Some(ConstantValue::Float(*v)),
+            (b"D", Constant::Double(v)) => Some(ConstantValue::Double(*v)),
+            (b"Ljava/lang/String;", Constant::String(st)) => {
+                Some(ConstantValue::String(pool.utf8(*st)?))
+            }
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub struct MethodInfo {
     pub acc: u16,
@@ -405,3 +1306,1180 @@ pub struct MethodInfo {
     pub descriptor: u16,
     pub attributes: Vec<Attribute>,
 }
+
+impl MethodInfo {
+    ///
+    /// The name and descriptor of the method, which [`ClassFile::sort_members`] orders by. Each is empty if its index does not resolve, so such methods sort first
+    pub fn sort_key(&self, pool: &ConstantPool) -> (JString, JString) {
+        sort_key(pool, self.name, self.descriptor)
+    }
+
+    ///
+    /// The names of the attributes of the method, including those of its Code attribute, without interpreting them. Unresolved attributes are included by the name in the pool
+    pub fn attribute_names<'a>(&'a self, pool: &'a ConstantPool) -> Vec<&'a JStr> {
+        attribute_names(&self.attributes, pool)
+    }
+
+    ///
+    /// Whether the method is marked synthetic, either by `ACC_SYNTHETIC` or by a Synthetic attribute, which compilers emitted before the flag existed
+    pub fn is_synthetic(&self) -> bool {
+        self.acc & consts::ACC_SYNTHETIC != 0
+            || self
+                .attributes
+                .iter()
+                .any(|attr| matches!(attr, Attribute::Synthetic))
+    }
+
+    ///
+    /// Whether the method has a Deprecated attribute. There is no access flag for deprecation
+    pub fn is_deprecated(&self) -> bool {
+        self.attributes
+            .iter()
+            .any(|attr| matches!(attr, Attribute::Deprecated))
+    }
+
+    ///
+    /// Finds the Code attribute of the method, which abstract and native methods do not have.
+    ///
+    /// If there is more than one (see [`ClassFile::validate_code_attributes`]), the first is returned
+    pub fn code(&self) -> Option<&CodeAttribute> {
+        self.attributes.iter().find_map(|attr| match attr {
+            Attribute::Code(code) => Some(code),
+            _ => None,
+        })
+    }
+
+    ///
+    /// Like [`MethodInfo::code`], but allows modifying the code
+    pub fn code_mut(&mut self) -> Option<&mut CodeAttribute> {
+        self.attributes.iter_mut().find_map(|attr| match attr {
+            Attribute::Code(code) => Some(code),
+            _ => None,
+        })
+    }
+
+    ///
+    /// Resolves the entries of the MethodParameters attribute, if present, giving each parameter's name (None if unnamed) and flags
+    pub fn parameters<'a>(
+        &self,
+        pool: &'a ConstantPool,
+    ) -> Vec<(Option<&'a JStr>, ParameterAccessFlags)> {
+        self.attributes
+            .iter()
+            .filter_map(|attr| match attr {
+                Attribute::MethodParameters(params) => Some(params),
+                _ => None,
+            })
+            .flatten()
+            .map(|param| {
+                let name = CpIndex(param.name).utf8(pool);
+                (name, ParameterAccessFlags::from_bits_truncate(param.access))
+            })
+            .collect()
+    }
+
+    ///
+    /// Resolves the names of the classes in the Exceptions attribute (the `throws` clause), if present, in order.
+    ///
+    /// Entries that do not resolve to a Class constant are skipped
+    pub fn thrown_exceptions<'a>(&self, pool: &'a ConstantPool) -> Vec<&'a JStr> {
+        self.attributes
+            .iter()
+            .filter_map(|attr| match attr {
+                Attribute::Exceptions(classes) => Some(classes),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|&class| CpIndex(class).class_name(pool))
+            .collect()
+    }
+}
+
+impl CodeAttribute {
+    ///
+    /// Resolves the entries of the LocalVariableTable of the code, matching each to the entry of the LocalVariableTypeTable with the same `start_pc` and `index`, if any.
+    ///
+    /// Entries of the LocalVariableTypeTable that match no entry of the LocalVariableTable are ignored
+    pub fn local_variables<'a>(&self, pool: &'a ConstantPool) -> Vec<ResolvedLocalVariable<'a>> {
+        let typed: Vec<&LocalVariableTypeInfo> = self
+            .attributes
+            .iter()
+            .filter_map(|attr| match attr {
+                Attribute::LocalVariableTypeTable(vars) => Some(vars),
+                _ => None,
+            })
+            .flatten()
+            .collect();
+        self.attributes
+            .iter()
+            .filter_map(|attr| match attr {
+                Attribute::LocalVariableTable(vars) => Some(vars),
+                _ => None,
+            })
+            .flatten()
+            .map(|var| ResolvedLocalVariable {
+                start_pc: var.start_pc,
+                length: var.length,
+                index: var.index,
+                name: pool.utf8(var.name),
+                descriptor: pool.utf8(var.descriptor),
+                signature: typed
+                    .iter()
+                    .find(|ty| ty.start_pc == var.start_pc && ty.index == var.index)
+                    .and_then(|ty| ty.signature(pool)),
+            })
+            .collect()
+    }
+
+    ///
+    /// Resolves the type caught by each exception handler, in the order of the exception table.
+    ///
+    /// Yields None for handlers that catch everything (`catch_type` 0), as used for `finally` blocks, and for catch types that do not resolve to a Class constant
+    pub fn handler_types<'a>(
+        &'a self,
+        pool: &'a ConstantPool,
+    ) -> impl Iterator<Item = Option<&'a JStr>> + 'a {
+        self.exceptions
+            .iter()
+            .map(move |handler| CpIndex(handler.catch_type).class_name(pool))
+    }
+
+    ///
+    /// Sorts the entries of the LineNumberTable, LocalVariableTable, and LocalVariableTypeTable attributes of the code by `start_pc`.
+    ///
+    /// The sort is stable, so entries with the same `start_pc` keep their order
+    pub fn sort_by_pc(&mut self) {
+        for attr in &mut self.attributes {
+            match attr {
+                Attribute::LineNumberTable(lines) => lines.sort_by_key(|line| line.start_pc),
+                Attribute::LocalVariableTable(vars) => vars.sort_by_key(|var| var.start_pc),
+                Attribute::LocalVariableTypeTable(vars) => vars.sort_by_key(|var| var.start_pc),
+                _ => {}
+            }
+        }
+    }
+
+    ///
+    /// The offset in the code of each frame of the StackMapTable attributes, computed from the offset deltas.
+    ///
+    /// The offsets are not truncated to 16 bits, so a table that runs past the end of the code can be detected
+    pub fn stack_map_offsets(&self) -> Vec<u32> {
+        let mut offsets = Vec::new();
+        for attr in &self.attributes {
+            if let Attribute::StackMapTable(frames) = attr {
+                let mut prev = None::<u32>;
+                for frame in frames {
+                    let delta = frame.offset_delta() as u32;
+                    let offset = prev.map_or(delta, |prev| prev + delta + 1);
+                    offsets.push(offset);
+                    prev = Some(offset);
+                }
+            }
+        }
+        offsets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    fn names<'a>(set: impl IntoIterator<Item = &'a JStr>) -> Vec<String> {
+        set.into_iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn referenced_classes_include_descriptor_types() {
+        let class = testutil::read(testutil::FIXTURE);
+        assert_eq!(
+            names(class.referenced_classes()),
+            [
+                "Fixture",
+                "Fixture$1",
+                "Fixture$Inner",
+                "java/io/IOException",
+                "java/lang/IllegalStateException",
+                "java/lang/InterruptedException",
+                "java/lang/Object",
+                "java/lang/Runnable",
+                "java/lang/String",
+                "java/lang/Throwable",
+                "java/lang/invoke/CallSite",
+                "java/lang/invoke/LambdaMetafactory",
+                "java/lang/invoke/MethodHandle",
+                "java/lang/invoke/MethodHandles",
+                "java/lang/invoke/MethodHandles$Lookup",
+                "java/lang/invoke/MethodType",
+                "java/lang/invoke/StringConcatFactory",
+                "java/util/ArrayList",
+                "java/util/Collection",
+                "java/util/List",
+                "java/util/function/Supplier",
+            ]
+        );
+
+        // Point only mentions List and String in descriptors, never in a Class constant
+        let point = testutil::read(testutil::POINT);
+        let class_constants: Vec<String> = point
+            .consts
+            .entries()
+            .filter_map(|(index, _)| point.consts.class_name(index))
+            .map(|name| name.to_string())
+            .collect();
+        let referenced = names(point.referenced_classes());
+        for name in ["java/util/List", "java/lang/String"].iter() {
+            assert!(!class_constants.iter().any(|n| n == name));
+            assert!(referenced.iter().any(|n| n == name));
+        }
+    }
+
+    #[test]
+    fn rename_class_updates_descriptors_and_signatures() {
+        let mut class = testutil::read(testutil::USES);
+        let from = testutil::jstring("com/old/Foo");
+        class
+            .rename_class(&from, &testutil::jstring("com/new/Foo"))
+            .unwrap();
+
+        let follow = testutil::method(&class, "follow");
+        assert_eq!(
+            testutil::utf8(&class, follow.descriptor),
+            "(Lcom/new/Foo;)Lcom/new/Foo;"
+        );
+        let array = testutil::method(&class, "array");
+        assert_eq!(testutil::utf8(&class, array.descriptor), "()[Lcom/new/Foo;");
+        let all = testutil::field(&class, "all");
+        let signature = all.attributes.iter().find_map(|attr| match attr {
+            Attribute::Signature(sig) => Some(testutil::utf8(&class, *sig)),
+            _ => None,
+        });
+        assert_eq!(
+            signature.as_deref(),
+            Some("Ljava/util/List<Lcom/new/Foo;>;")
+        );
+
+        let referenced = names(class.referenced_classes());
+        assert!(referenced.iter().any(|name| name == "com/new/Foo"));
+        assert!(!referenced.iter().any(|name| name == "com/old/Foo"));
+
+        let written = class.write_to_vec().unwrap();
+        assert!(testutil::read(&written).validate().is_ok());
+    }
+
+    #[test]
+    fn strip_debug_removes_debug_attributes() {
+        fn has_debug_info(class: &ClassFile) -> bool {
+            let is_debug = |name: &&JStr| {
+                [
+                    "LineNumberTable",
+                    "LocalVariableTable",
+                    "LocalVariableTypeTable",
+                    "SourceFile",
+                ]
+                .iter()
+                .any(|debug| name.as_bytes() == debug.as_bytes())
+            };
+            class.attribute_names().iter().any(is_debug)
+                || class
+                    .methods
+                    .iter()
+                    .any(|method| method.attribute_names(&class.consts).iter().any(is_debug))
+        }
+
+        let original = testutil::read(testutil::FIXTURE);
+        assert!(has_debug_info(&original));
+
+        let mut stripped = original.clone();
+        stripped.strip_debug();
+        assert!(!has_debug_info(&stripped));
+        assert_eq!(stripped.consts.count(), original.consts.count());
+        let reread = testutil::read(&stripped.write_to_vec().unwrap());
+        assert!(reread.validate().is_ok());
+        assert!(!has_debug_info(&reread));
+
+        let mut pruned = original.clone();
+        pruned.strip_debug_and_prune_constants().unwrap();
+        assert!(!has_debug_info(&pruned));
+        assert!(pruned.consts.count() < original.consts.count());
+        assert!(
+            !pruned
+                .utf8_constants()
+                .any(|(_, st)| st.as_bytes() == b"LineNumberTable"
+                    || st.as_bytes() == b"Fixture.java")
+        );
+        let reread = testutil::read(&pruned.write_to_vec().unwrap());
+        assert!(reread.validate().is_ok());
+        assert_eq!(reread.methods.len(), original.methods.len());
+    }
+
+    #[test]
+    fn utf8_constants_yield_every_string_with_its_index() {
+        let class = testutil::read(testutil::IFACE);
+        let strings = class.utf8_constants().collect::<Vec<_>>();
+        assert!(strings.windows(2).all(|pair| pair[0].0 < pair[1].0));
+        for &(index, st) in &strings {
+            assert_eq!(class.consts.utf8(index), Some(st));
+        }
+        let utf8_count = class
+            .consts
+            .entries()
+            .filter(|(_, constant)| matches!(constant, Constant::Utf8(_)))
+            .count();
+        assert_eq!(strings.len(), utf8_count);
+
+        let text = strings
+            .iter()
+            .map(|(_, st)| st.as_bytes())
+            .collect::<Vec<_>>();
+        for expected in [
+            &b"Iface"[..],
+            b"run",
+            b"()V",
+            b"twice",
+            b"CONSTANT",
+            b"Iface.java",
+        ] {
+            assert!(text.contains(&expected), "{:?} is missing", expected);
+        }
+    }
+
+    #[test]
+    fn module_main_class_and_packages() {
+        let module = testutil::read(testutil::MODULE_INFO);
+        assert_eq!(
+            module.module_main_class().unwrap().as_bytes(),
+            b"com/example/app/Main"
+        );
+        let mut packages = module
+            .module_packages()
+            .into_iter()
+            .map(JStr::as_bytes)
+            .collect::<Vec<_>>();
+        packages.sort_unstable();
+        assert_eq!(packages, [&b"com/example/app"[..], b"com/example/util"]);
+
+        let class = testutil::read(testutil::FIXTURE);
+        assert!(class.module_main_class().is_none());
+        assert!(class.module_packages().is_empty());
+    }
+
+    #[test]
+    fn record_component_signatures() {
+        use crate::signature::{TypeArgument, TypeSignature};
+
+        let point = testutil::read(testutil::POINT);
+        let components = point.record_components();
+        assert_eq!(components.len(), 2);
+
+        let x = &components[0];
+        assert_eq!(x.name.unwrap().as_bytes(), b"x");
+        assert_eq!(x.descriptor.unwrap().as_bytes(), b"I");
+        assert!(x.signature.is_none());
+
+        let tags = &components[1];
+        assert_eq!(tags.name.unwrap().as_bytes(), b"tags");
+        assert_eq!(tags.descriptor.unwrap().as_bytes(), b"Ljava/util/List;");
+        let sig = tags.signature.clone().unwrap().unwrap();
+        assert_eq!(
+            sig.to_signature().as_bytes(),
+            b"Ljava/util/List<Ljava/lang/String;>;"
+        );
+        let list = match &sig.ty {
+            TypeSignature::Class(class) => &class.outer,
+            other => panic!("expected a class type, got {:?}", other),
+        };
+        assert_eq!(list.name.as_bytes(), b"java/util/List");
+        match &list.type_args[..] {
+            [TypeArgument::Exact(TypeSignature::Class(string))] => {
+                assert_eq!(string.outer.name.as_bytes(), b"java/lang/String")
+            }
+            other => panic!("expected one exact type argument, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn parsed_member_descriptors() {
+        let class = testutil::read(testutil::FIXTURE);
+        let jstr = |st: &'static str| JStr::from_str(st).unwrap();
+
+        let methods = class.method_descriptors().collect::<Vec<_>>();
+        assert_eq!(methods.len(), class.methods.len());
+        let params = methods
+            .iter()
+            .find(|(name, _)| name.as_bytes() == b"params")
+            .unwrap();
+        assert_eq!(
+            params.1,
+            Ok(MethodDescriptor {
+                params: vec![FieldType::Int, FieldType::Object(jstr("java/lang/String"))],
+                ret: None,
+            })
+        );
+        let names = methods
+            .iter()
+            .find(|(name, _)| name.as_bytes() == b"names")
+            .unwrap();
+        let list = FieldType::Object(jstr("java/util/List"));
+        assert_eq!(
+            names.1,
+            Ok(MethodDescriptor {
+                params: vec![list.clone()],
+                ret: Some(list),
+            })
+        );
+
+        let fields = class
+            .field_descriptors()
+            .map(|(name, ty)| (name.as_bytes(), ty.unwrap()))
+            .collect::<Vec<_>>();
+        assert!(fields.contains(&(&b"BIG"[..], FieldType::Long)));
+        assert!(fields.contains(&(&b"HALF"[..], FieldType::Double)));
+        assert!(fields.contains(&(&b"name"[..], FieldType::Object(jstr("java/lang/String")))));
+    }
+
+    #[test]
+    fn local_variables_carry_generic_signatures() {
+        let class = testutil::read(testutil::FIXTURE);
+        let code = testutil::method(&class, "names").code().unwrap();
+        let locals = code.local_variables(&class.consts);
+        let names = locals
+            .iter()
+            .map(|var| var.name.unwrap().as_bytes())
+            .collect::<Vec<_>>();
+        assert_eq!(names, [&b"this"[..], b"input", b"out"]);
+
+        let this = &locals[0];
+        assert_eq!(this.descriptor.unwrap().as_bytes(), b"LFixture;");
+        assert!(this.signature.is_none());
+
+        for var in &locals[1..] {
+            assert_eq!(var.descriptor.unwrap().as_bytes(), b"Ljava/util/List;");
+            let sig = var.signature.clone().unwrap().unwrap();
+            assert_eq!(
+                sig.to_signature().as_bytes(),
+                b"Ljava/util/List<Ljava/lang/String;>;"
+            );
+        }
+        assert_eq!(locals[1].index, 1);
+        assert_eq!(locals[2].index, 2);
+    }
+
+    #[test]
+    fn lambda_bootstrap_method_is_resolved() {
+        let class = testutil::read(testutil::FIXTURE);
+        let methods = class.bootstrap_methods();
+        let lambda = methods
+            .iter()
+            .find(|method| method.handle.unwrap().name.as_bytes() == b"metafactory")
+            .unwrap();
+        let handle = lambda.handle.unwrap();
+        assert_eq!(handle.kind, MethodHandleKind::InvokeStatic);
+        assert_eq!(
+            handle.class.as_bytes(),
+            b"java/lang/invoke/LambdaMetafactory"
+        );
+
+        match &lambda.args[..] {
+            [BootstrapArgument::MethodType(erased), BootstrapArgument::MethodHandle(target), BootstrapArgument::MethodType(instantiated)] =>
+            {
+                assert_eq!(erased.as_bytes(), b"()Ljava/lang/Object;");
+                assert_eq!(target.kind, MethodHandleKind::InvokeVirtual);
+                assert_eq!(target.class.as_bytes(), b"Fixture");
+                assert_eq!(target.name.as_bytes(), b"lambda$lambda$0");
+                assert_eq!(
+                    target.descriptor.as_bytes(),
+                    b"(Ljava/lang/String;)Ljava/lang/String;"
+                );
+                assert_eq!(instantiated.as_bytes(), b"()Ljava/lang/String;");
+            }
+            args => panic!("unexpected arguments {:?}", args),
+        }
+
+        // String concatenation passes its recipe as a String
+        assert!(methods.iter().any(|method| matches!(
+            &method.args[..],
+            [BootstrapArgument::String(recipe)] if recipe.as_bytes() == b"\x01\x01"
+        )));
+    }
+
+    #[test]
+    fn attribute_names_include_custom_attributes() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        let custom = Attribute::unresolved(
+            &testutil::jstring("com.example.Custom"),
+            vec![1, 2, 3],
+            &mut class.consts,
+        )
+        .unwrap();
+        let index = class
+            .methods
+            .iter()
+            .position(|method| class.consts.utf8(method.name).unwrap().as_bytes() == b"loop")
+            .unwrap();
+        class.methods[index].attributes.push(custom.clone());
+        class.attributes.push(custom);
+        let class = testutil::read(&class.write_to_vec().unwrap());
+
+        let bytes = |names: Vec<&JStr>| {
+            names
+                .into_iter()
+                .map(|name| name.as_bytes().to_vec())
+                .collect::<Vec<_>>()
+        };
+        let method = bytes(class.methods[index].attribute_names(&class.consts));
+        // The attributes of the Code attribute follow it
+        assert_eq!(
+            method,
+            [
+                &b"Code"[..],
+                b"LineNumberTable",
+                b"LocalVariableTable",
+                b"StackMapTable",
+                b"MethodParameters",
+                b"com.example.Custom",
+            ]
+        );
+        let names = bytes(class.attribute_names());
+        assert_eq!(names.last().unwrap(), b"com.example.Custom");
+        assert!(names.contains(&b"SourceFile".to_vec()));
+        assert!(!names.contains(&b"Code".to_vec()));
+    }
+
+    #[test]
+    fn interning_shares_text_across_classes() {
+        let mut pool: HashMap<JString, usize> = HashMap::new();
+        let mut interner = |st: &JStr| {
+            *pool.entry(st.to_owned()).or_insert(0) += 1;
+            let mut text = st.to_owned();
+            text.shrink_to_fit();
+            text
+        };
+        let mut outer = testutil::read(testutil::FIXTURE);
+        let mut inner = testutil::read(testutil::FIXTURE_INNER);
+        let before = (outer.write_to_vec().unwrap(), inner.write_to_vec().unwrap());
+        outer.intern_utf8_with(&mut interner);
+        inner.intern_utf8_with(&mut interner);
+
+        // Nothing but the storage of the text changed
+        assert_eq!(
+            (outer.write_to_vec().unwrap(), inner.write_to_vec().unwrap()),
+            before
+        );
+        for class in [&outer, &inner] {
+            for constant in &class.consts {
+                if let Constant::Utf8(st) = constant {
+                    assert_eq!(st.capacity(), st.len());
+                }
+            }
+        }
+        // Both classes handed their names to the same interner
+        for shared in [
+            "Fixture",
+            "Fixture$Inner",
+            "java/lang/Object",
+            "InnerClasses",
+        ] {
+            assert_eq!(pool[&testutil::jstring(shared)], 2, "{}", shared);
+        }
+        assert_eq!(
+            pool.values().sum::<usize>(),
+            outer.utf8_constants().count() + inner.utf8_constants().count()
+        );
+    }
+
+    #[test]
+    fn method_handle_and_method_type_constants() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        let (_, metafactory) = class
+            .consts
+            .entries()
+            .find(|(_, constant)| {
+                matches!(constant, Constant::MethodHandle { kind: 6, .. })
+                    && constant
+                        .resolve_method_handle(&class.consts)
+                        .unwrap()
+                        .name
+                        .as_bytes()
+                        == b"metafactory"
+            })
+            .unwrap();
+        let handle = metafactory.resolve_method_handle(&class.consts).unwrap();
+        assert_eq!(handle.kind, MethodHandleKind::InvokeStatic);
+        assert_eq!(
+            handle.class.as_bytes(),
+            b"java/lang/invoke/LambdaMetafactory"
+        );
+        let desc = MethodDescriptor::parse(handle.descriptor).unwrap();
+        assert_eq!(desc.params.len(), 6);
+        assert_eq!(
+            desc.ret,
+            Some(FieldType::Object(
+                JStr::from_str("java/lang/invoke/CallSite").unwrap()
+            ))
+        );
+
+        let erased = class
+            .consts
+            .iter()
+            .filter_map(|constant| constant.resolve_method_type(&class.consts))
+            .map(Result::unwrap)
+            .find(|desc| desc.params.is_empty() && desc.ret.is_some())
+            .unwrap();
+        assert_eq!(erased.to_string(), "() -> java.lang.Object");
+        assert!(metafactory.resolve_method_type(&class.consts).is_none());
+
+        // A handle whose kind does not match the constant it refers to
+        let field = class
+            .consts
+            .entries()
+            .find(|(_, constant)| matches!(constant, Constant::FieldRef { .. }))
+            .unwrap()
+            .0;
+        let bad = Constant::MethodHandle {
+            kind: MethodHandleKind::InvokeStatic as u8,
+            reference: field,
+        };
+        assert!(bad.resolve_method_handle(&class.consts).is_none());
+        let getter = Constant::MethodHandle {
+            kind: MethodHandleKind::GetField as u8,
+            reference: field,
+        };
+        assert_eq!(
+            getter.resolve_method_handle(&class.consts).unwrap().kind,
+            MethodHandleKind::GetField
+        );
+        let unknown = class
+            .consts
+            .push(Constant::MethodHandle {
+                kind: 10,
+                reference: field,
+            })
+            .unwrap();
+        assert!(class.consts.method_handle(unknown).is_none());
+    }
+
+    #[test]
+    fn sort_members_is_idempotent_and_consistent() {
+        let names = |class: &ClassFile| {
+            class
+                .methods
+                .iter()
+                .map(|method| class.consts.utf8(method.name).unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        let mut sorted = testutil::read(testutil::FIXTURE);
+        sorted.sort_members();
+        let order = names(&sorted);
+        assert!(order.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert_eq!(order[0], "<init>");
+        assert!(sorted
+            .fields
+            .windows(2)
+            .all(|pair| pair[0].sort_key(&sorted.consts) <= pair[1].sort_key(&sorted.consts)));
+
+        let written = sorted.write_to_vec().unwrap();
+        sorted.sort_members();
+        assert_eq!(sorted.write_to_vec().unwrap(), written);
+
+        // Starting from another order gives the same result
+        let mut reversed = testutil::read(testutil::FIXTURE);
+        reversed.methods.reverse();
+        reversed.fields.reverse();
+        reversed.sort_members();
+        assert_eq!(reversed.write_to_vec().unwrap(), written);
+        assert!(testutil::read(&written).validate().is_ok());
+
+        // A name that does not resolve sorts first
+        let last = reversed.methods.len() - 1;
+        reversed.methods[last].name = 0;
+        let (name, descriptor) = reversed.methods[last].sort_key(&reversed.consts);
+        assert!(name.as_bytes().is_empty());
+        assert!(!descriptor.as_bytes().is_empty());
+        reversed.sort_members();
+        assert_eq!(reversed.methods[0].name, 0);
+    }
+
+    #[test]
+    fn duplicate_members_are_reported_once() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        assert!(class.find_duplicate_members().is_empty());
+
+        let mut copy = testutil::method(&class, "loop").clone();
+        class.methods.push(copy.clone());
+        // The same name and descriptor, through Utf8 constants with different indices
+        copy.name = class
+            .consts
+            .push(Constant::Utf8(testutil::jstring("loop")))
+            .unwrap();
+        copy.descriptor = class
+            .consts
+            .push(Constant::Utf8(testutil::jstring("(I)I")))
+            .unwrap();
+        class.methods.push(copy);
+        let field = testutil::field(&class, "name").clone();
+        class.fields.push(field);
+
+        let dups: Vec<(String, String)> = class
+            .find_duplicate_members()
+            .iter()
+            .map(|(name, descriptor)| (name.to_string(), descriptor.to_string()))
+            .collect();
+        assert_eq!(
+            dups,
+            [
+                ("name".to_string(), "Ljava/lang/String;".to_string()),
+                ("loop".to_string(), "(I)I".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn enclosing_method_of_anonymous_class() {
+        fn enclosing(class: &ClassFile) -> Option<(String, Option<(String, String)>)> {
+            let (outer, method) = class
+                .attributes
+                .iter()
+                .find_map(|attr| attr.enclosing_method(&class.consts))?;
+            Some((
+                outer.to_string(),
+                method.map(|(name, descriptor)| (name.to_string(), descriptor.to_string())),
+            ))
+        }
+        fn set_method(class: &mut ClassFile, index: u16) {
+            for attr in &mut class.attributes {
+                if let Attribute::EnclosingMethod { method, .. } = attr {
+                    *method = index;
+                }
+            }
+        }
+
+        let mut class = testutil::read(testutil::FIXTURE_ANONYMOUS);
+        assert_eq!(
+            enclosing(&class),
+            Some((
+                "Fixture".to_string(),
+                Some((
+                    "anonymous".to_string(),
+                    "()Ljava/lang/Runnable;".to_string()
+                ))
+            ))
+        );
+
+        // As for a class declared in a field initializer
+        set_method(&mut class, 0);
+        let reread = testutil::read(&class.write_to_vec().unwrap());
+        assert_eq!(enclosing(&reread), Some(("Fixture".to_string(), None)));
+
+        let this = class.this;
+        set_method(&mut class, this);
+        assert_eq!(enclosing(&class), None);
+        assert_eq!(enclosing(&testutil::read(testutil::FIXTURE_INNER)), None);
+    }
+
+    #[test]
+    fn inner_classes_resolve_named_and_anonymous() {
+        let class = testutil::read(testutil::FIXTURE);
+        let inner = class.inner_classes();
+        assert_eq!(inner.len(), 3);
+
+        let anonymous = &inner[0];
+        assert_eq!(
+            anonymous.inner_class.map(JStr::to_string).as_deref(),
+            Some("Fixture$1")
+        );
+        assert_eq!(anonymous.outer_class, None);
+        assert_eq!(anonymous.inner_name, None);
+        assert!(anonymous.flags.is_empty());
+
+        let named = &inner[1];
+        assert_eq!(
+            named.inner_class.map(JStr::to_string).as_deref(),
+            Some("Fixture$Inner")
+        );
+        assert_eq!(
+            named.outer_class.map(JStr::to_string).as_deref(),
+            Some("Fixture")
+        );
+        assert_eq!(
+            named.inner_name.map(JStr::to_string).as_deref(),
+            Some("Inner")
+        );
+        assert_eq!(named.flags, InnerClassAccessFlags::PUBLIC);
+
+        let lookup = &inner[2];
+        assert_eq!(
+            lookup.inner_name.map(JStr::to_string).as_deref(),
+            Some("Lookup")
+        );
+        assert_eq!(
+            lookup.flags,
+            InnerClassAccessFlags::PUBLIC
+                | InnerClassAccessFlags::STATIC
+                | InnerClassAccessFlags::FINAL
+        );
+    }
+
+    #[test]
+    fn constant_values_resolve_by_descriptor() {
+        let class = testutil::read(testutil::FIXTURE);
+        let value = |name: &str| testutil::field(&class, name).constant_value(&class.consts);
+        assert!(matches!(value("ANSWER"), Some(ConstantValue::Int(42))));
+        assert!(
+            matches!(value("GREETING"), Some(ConstantValue::String(st)) if st.as_bytes() == b"hello")
+        );
+        assert!(matches!(value("BIG"), Some(ConstantValue::Long(v)) if v == 1 << 40));
+        assert!(matches!(value("HALF"), Some(ConstantValue::Double(v)) if v == 0.5));
+        assert!(
+            matches!(value("NUL"), Some(ConstantValue::String(st)) if st.as_bytes() == b"a\xc0\x80b")
+        );
+        assert!(value("name").is_none());
+
+        // A String constant on an int field does not match its descriptor
+        let mut mismatched = testutil::field(&class, "ANSWER").clone();
+        mismatched.descriptor = testutil::field(&class, "GREETING").descriptor;
+        assert!(mismatched.constant_value(&class.consts).is_none());
+        // Nor is the attribute used on an instance field
+        let mut instance = testutil::field(&class, "ANSWER").clone();
+        instance.acc &= !consts::ACC_STATIC;
+        assert!(instance.constant_value(&class.consts).is_none());
+    }
+
+    #[test]
+    fn method_parameters_resolve_names_and_flags() {
+        fn params(
+            class: &ClassFile,
+            method: &MethodInfo,
+        ) -> Vec<(Option<String>, ParameterAccessFlags)> {
+            method
+                .parameters(&class.consts)
+                .into_iter()
+                .map(|(name, flags)| (name.map(JStr::to_string), flags))
+                .collect()
+        }
+
+        let class = testutil::read(testutil::FIXTURE);
+        let mut method = testutil::method(&class, "params").clone();
+        assert_eq!(
+            params(&class, &method),
+            [
+                (Some("first".to_string()), ParameterAccessFlags::FINAL),
+                (Some("second".to_string()), ParameterAccessFlags::empty()),
+            ]
+        );
+        for attr in &mut method.attributes {
+            if let Attribute::MethodParameters(params) = attr {
+                params.push(ParameterInfo {
+                    name: 0,
+                    access: consts::ACC_SYNTHETIC,
+                });
+            }
+        }
+        assert_eq!(
+            params(&class, &method)[2],
+            (None, ParameterAccessFlags::SYNTHETIC)
+        );
+        assert!(params(&class, testutil::method(&class, "old")).is_empty());
+
+        let inner = testutil::read(testutil::FIXTURE_INNER);
+        assert_eq!(
+            params(&inner, testutil::method(&inner, "<init>")),
+            [(
+                Some("this$0".to_string()),
+                ParameterAccessFlags::FINAL | ParameterAccessFlags::MANDATED
+            )]
+        );
+    }
+
+    #[test]
+    fn deprecated_and_synthetic_from_attribute_or_flag() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        // javac marks deprecation only by the attribute, and synthetic members only by the flag
+        let old = testutil::method(&class, "old");
+        assert!(old.is_deprecated());
+        assert!(!old.is_synthetic());
+        assert_eq!(old.acc & consts::ACC_SYNTHETIC, 0);
+
+        let lambda = testutil::method(&class, "lambda$lambda$0");
+        assert!(lambda.is_synthetic());
+        assert!(!lambda
+            .attributes
+            .iter()
+            .any(|attr| matches!(attr, Attribute::Synthetic)));
+        assert!(!lambda.is_deprecated());
+
+        let inner = testutil::read(testutil::FIXTURE_INNER);
+        assert!(testutil::field(&inner, "this$0").is_synthetic());
+
+        // Pre-Java 5 compilers only emitted the attribute
+        let index = class
+            .methods
+            .iter()
+            .position(|method| testutil::utf8(&class, method.name) == "loop")
+            .unwrap();
+        assert!(!class.methods[index].is_synthetic());
+        class.methods[index].attributes.push(Attribute::Synthetic);
+        assert!(class.methods[index].is_synthetic());
+    }
+
+    #[test]
+    fn set_then_read_source_file() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        assert_eq!(
+            class.source_file().unwrap(),
+            &*testutil::jstring("Fixture.java")
+        );
+        let count = |class: &ClassFile| {
+            class
+                .attributes
+                .iter()
+                .filter(|attr| matches!(attr, Attribute::SourceFile(_)))
+                .count()
+        };
+
+        class
+            .set_source_file(&testutil::jstring("Renamed.kt"))
+            .unwrap();
+        assert_eq!(
+            class.source_file().unwrap(),
+            &*testutil::jstring("Renamed.kt")
+        );
+        assert_eq!(count(&class), 1);
+
+        let written = testutil::read(&class.write_to_vec().unwrap());
+        assert_eq!(
+            written.source_file().unwrap(),
+            &*testutil::jstring("Renamed.kt")
+        );
+
+        // A duplicate attribute is merged into the first one
+        class.attributes.push(Attribute::SourceFile(class.this));
+        class
+            .set_source_file(&testutil::jstring("Other.java"))
+            .unwrap();
+        assert_eq!(count(&class), 1);
+        assert_eq!(
+            class.source_file().unwrap(),
+            &*testutil::jstring("Other.java")
+        );
+
+        class.attributes.clear();
+        assert_eq!(class.source_file(), None);
+        class
+            .set_source_file(&testutil::jstring("New.java"))
+            .unwrap();
+        assert_eq!(
+            class.source_file().unwrap(),
+            &*testutil::jstring("New.java")
+        );
+    }
+
+    #[test]
+    fn made_up_attribute_round_trips() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        let content = vec![0xca, 0xfe, 0x00, 0xff, 0x01];
+        let attr = Attribute::unresolved(
+            &testutil::jstring("com.example.Vendor"),
+            content.clone(),
+            &mut class.consts,
+        )
+        .unwrap();
+        assert_eq!(attr.name(), None);
+        assert_eq!(
+            attr.name_bytes(&class.consts),
+            Some(&b"com.example.Vendor"[..])
+        );
+        assert_eq!(attr.content(), Some(&content[..]));
+        class.attributes.push(attr.clone());
+        class.methods[0].code_mut().unwrap().attributes.push(attr);
+
+        let bytes = class.write_to_vec().unwrap();
+        let read = testutil::read(&bytes);
+        let last = read.attributes.last().unwrap();
+        assert_eq!(
+            last.resolve_name(&read.consts).unwrap(),
+            &*testutil::jstring("com.example.Vendor")
+        );
+        assert_eq!(last.content(), Some(&content[..]));
+        let in_code = read.methods[0].code().unwrap().attributes.last().unwrap();
+        assert_eq!(in_code.content(), Some(&content[..]));
+        assert_eq!(read.write_to_vec().unwrap(), bytes);
+    }
+
+    #[test]
+    fn handler_types_resolve_catch_types() {
+        let class = testutil::read(testutil::FIXTURE);
+        let code = testutil::method(&class, "handlers").code().unwrap();
+        let types: Vec<_> = code
+            .handler_types(&class.consts)
+            .map(|ty| ty.map(|name| name.to_string()))
+            .collect();
+        // javac guards each catch block, and the try block itself, with a catch-all handler for the finally block
+        let some = |name: &str| Some(name.to_string());
+        assert_eq!(
+            types,
+            [
+                some("java/io/IOException"),
+                some("java/lang/IllegalStateException"),
+                None,
+                None,
+                None,
+            ]
+        );
+        assert_eq!(types.len(), code.exceptions.len());
+        assert_eq!(
+            testutil::method(&class, "loop")
+                .code()
+                .unwrap()
+                .handler_types(&class.consts)
+                .count(),
+            0
+        );
+    }
+
+    #[test]
+    fn version_at_least_boundaries() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        assert_eq!(class.maj, consts::JAVA_16);
+        assert!(class.version_at_least(consts::JAVA_16));
+        assert!(!class.version_at_least(consts::JAVA_16 + 1));
+
+        for (major, feature) in [
+            (consts::JAVA_6, "StackMapTable"),
+            (consts::JAVA_11, "NestHost"),
+        ] {
+            class.maj = major - 1;
+            assert!(
+                !class.version_at_least(major),
+                "{} at {}",
+                feature,
+                class.maj
+            );
+            class.maj = major;
+            assert!(
+                class.version_at_least(major),
+                "{} at {}",
+                feature,
+                class.maj
+            );
+            class.maj = major + 1;
+            assert!(
+                class.version_at_least(major),
+                "{} at {}",
+                feature,
+                class.maj
+            );
+        }
+
+        class.maj = consts::MIN_VERSION;
+        assert!(class.version_at_least(consts::JAVA_1_1));
+        assert!(!class.version_at_least(consts::JAVA_1_2));
+        // The minor version does not matter
+        class.min = consts::PREVIEW_FEATURES;
+        assert!(!class.version_at_least(consts::JAVA_1_2));
+    }
+
+    #[test]
+    fn sort_by_pc_is_stable() {
+        let class = testutil::read(testutil::FIXTURE);
+        let mut code = testutil::method(&class, "loop").code().unwrap().clone();
+        let line = |start_pc, line_number| LineNumberEntry {
+            start_pc,
+            line_number,
+        };
+        code.attributes = vec![Attribute::LineNumberTable(vec![
+            line(8, 3),
+            line(0, 1),
+            line(8, 2),
+            line(4, 5),
+            line(0, 4),
+        ])];
+        code.sort_by_pc();
+        match &code.attributes[..] {
+            [Attribute::LineNumberTable(lines)] => {
+                let lines: Vec<_> = lines
+                    .iter()
+                    .map(|line| (line.start_pc, line.line_number))
+                    .collect();
+                // Entries at the same pc keep their order
+                assert_eq!(lines, [(0, 1), (0, 4), (4, 5), (8, 3), (8, 2)]);
+            }
+            attrs => panic!("expected a LineNumberTable, got {:?}", attrs),
+        }
+
+        // javac sorts its LineNumberTable, but lists `this` last in the LocalVariableTable
+        let mut code = testutil::method(&class, "handlers").code().unwrap().clone();
+        let tables = |code: &CodeAttribute| {
+            let mut lines = Vec::new();
+            let mut vars = Vec::new();
+            for attr in &code.attributes {
+                match attr {
+                    Attribute::LineNumberTable(table) => {
+                        lines.extend(table.iter().map(|line| (line.start_pc, line.line_number)))
+                    }
+                    Attribute::LocalVariableTable(table) => {
+                        vars.extend(table.iter().map(|var| (var.start_pc, var.index)))
+                    }
+                    _ => {}
+                }
+            }
+            (lines, vars)
+        };
+        let (lines, vars) = tables(&code);
+        assert_eq!(vars, [(21, 1), (44, 1), (0, 0)]);
+        code.sort_by_pc();
+        assert_eq!(tables(&code), (lines, vec![(0, 0), (21, 1), (44, 1)]));
+    }
+
+    #[test]
+    fn code_is_found_for_concrete_methods_only() {
+        let iface = testutil::read(testutil::IFACE);
+        let run = testutil::method(&iface, "run");
+        assert!(run.acc & consts::ACC_ABSTRACT != 0);
+        assert!(run.code().is_none());
+        let twice = testutil::method(&iface, "twice");
+        let code = twice.code().unwrap();
+        assert!(std::ptr::eq(
+            code,
+            twice
+                .attributes
+                .iter()
+                .find_map(|attr| match attr {
+                    Attribute::Code(code) => Some(code),
+                    _ => None,
+                })
+                .unwrap()
+        ));
+
+        let mut class = testutil::read(testutil::FIXTURE);
+        let index = class
+            .methods
+            .iter()
+            .position(|method| testutil::utf8(&class, method.name) == "old")
+            .unwrap();
+        // `old` is empty, so its code is a single return
+        let code = class.methods[index].code_mut().unwrap();
+        assert_eq!(code.code, [0xb1]);
+        code.max_stack = 7;
+        assert_eq!(class.methods[index].code().unwrap().max_stack, 7);
+
+        let mut abstract_run = run.clone();
+        assert!(abstract_run.code_mut().is_none());
+    }
+
+    #[test]
+    fn thrown_exceptions_resolve_the_throws_clause() {
+        let class = testutil::read(testutil::FIXTURE);
+        assert_eq!(
+            names(testutil::method(&class, "io").thrown_exceptions(&class.consts)),
+            ["java/io/IOException", "java/lang/InterruptedException"]
+        );
+        assert_eq!(
+            names(testutil::method(&class, "handlers").thrown_exceptions(&class.consts)),
+            ["java/lang/InterruptedException"]
+        );
+        assert!(testutil::method(&class, "loop")
+            .thrown_exceptions(&class.consts)
+            .is_empty());
+    }
+}
@@ -0,0 +1,341 @@
+use std::fmt::{Display, Formatter};
+
+use crate::string::{JStr, JString};
+
+///
+/// The type of a field, parameter, or return value, as written in a descriptor
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum FieldType<'a> {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    /// A class or interface type, with the internal name of the class
+    Object(&'a JStr),
+    Array(Box<FieldType<'a>>),
+}
+
+///
+/// A parsed method descriptor. A `ret` of None is `void`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MethodDescriptor<'a> {
+    pub params: Vec<FieldType<'a>>,
+    pub ret: Option<FieldType<'a>>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DescriptorError {
+    UnexpectedEnd,
+    /// An unexpected byte was found at the given position
+    InvalidChar {
+        pos: usize,
+        byte: u8,
+    },
+    /// An array type had more than 255 dimensions
+    TooManyDimensions,
+    /// The descriptor continued past the end of the type
+    TrailingData {
+        pos: usize,
+    },
+}
+
+impl Display for DescriptorError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DescriptorError::UnexpectedEnd => f.write_str("unexpected end of descriptor"),
+            DescriptorError::InvalidChar { pos, byte } => {
+                write!(f, "unexpected byte {:#04x} at {}", byte, pos)
+            }
+            DescriptorError::TooManyDimensions => {
+                f.write_str("array type has more than 255 dimensions")
+            }
+            DescriptorError::TrailingData { pos } => {
+                write!(f, "unexpected data after descriptor at {}", pos)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DescriptorError {}
+
+struct DescriptorParser<'a> {
+    desc: &'a JStr,
+    pos: usize,
+}
+
+impl<'a> DescriptorParser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.desc.as_bytes().get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<u8, DescriptorError> {
+        let b = self.peek().ok_or(DescriptorError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn field_type(&mut self) -> Result<FieldType<'a>, DescriptorError> {
+        let mut dims = 0usize;
+        while self.peek() == Some(b'[') {
+            self.pos += 1;
+            dims += 1;
+        }
+        if dims > 255 {
+            return Err(DescriptorError::TooManyDimensions);
+        }
+
+        let pos = self.pos;
+        let mut ty = match self.next()? {
+            b'B' => FieldType::Byte,
+            b'C' => FieldType::Char,
+            b'D' => FieldType::Double,
+            b'F' => FieldType::Float,
+            b'I' => FieldType::Int,
+            b'J' => FieldType::Long,
+            b'S' => FieldType::Short,
+            b'Z' => FieldType::Boolean,
+            b'L' => {
+                let start = self.pos;
+                let len = self.desc.as_bytes()[start..]
+                    .iter()
+                    .position(|&b| b == b';')
+                    .ok_or(DescriptorError::UnexpectedEnd)?;
+                if len == 0 {
+                    return Err(DescriptorError::InvalidChar {
+                        pos: start,
+                        byte: b';',
+                    });
+                }
+                self.pos = start + len + 1;
+                // SAFETY:
+                // 'L' and ';' are ASCII, so the bytes between them start and end on character boundaries
+                FieldType::Object(unsafe {
+                    JStr::from_modified_utf8_unchecked(&self.desc.as_bytes()[start..start + len])
+                })
+            }
+            byte => return Err(DescriptorError::InvalidChar { pos, byte }),
+        };
+        for _ in 0..dims {
+            ty = FieldType::Array(Box::new(ty));
+        }
+        Ok(ty)
+    }
+
+    fn finish(&self) -> Result<(), DescriptorError> {
+        if self.pos == self.desc.len() {
+            Ok(())
+        } else {
+            Err(DescriptorError::TrailingData { pos: self.pos })
+        }
+    }
+}
+
+fn into_jstring(bytes: Vec<u8>) -> JString {
+    // SAFETY:
+    // Descriptors are encoded from ASCII punctuation and the class names they contain
+    unsafe { JString::from_modified_utf8_unchecked(bytes) }
+}
+
+impl<'a> FieldType<'a> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            FieldType::Byte => out.push(b'B'),
+            FieldType::Char => out.push(b'C'),
+            FieldType::Double => out.push(b'D'),
+            FieldType::Float => out.push(b'F'),
+            FieldType::Int => out.push(b'I'),
+            FieldType::Long => out.push(b'J'),
+            FieldType::Short => out.push(b'S'),
+            FieldType::Boolean => out.push(b'Z'),
+            FieldType::Object(name) => {
+                out.push(b'L');
+                out.extend_from_slice(name.as_bytes());
+                out.push(b';');
+            }
+            FieldType::Array(elem) => {
+                out.push(b'[');
+                elem.encode(out);
+            }
+        }
+    }
+
+    pub fn parse(desc: &'a JStr) -> Result<Self, DescriptorError> {
+        let mut parser = DescriptorParser { desc, pos: 0 };
+        let ty = parser.field_type()?;
+        parser.finish()?;
+        Ok(ty)
+    }
+
+    ///
+    /// Returns the class named by this type, looking through array types
+    pub fn class_name(&self) -> Option<&'a JStr> {
+        match self {
+            FieldType::Object(name) => Some(name),
+            FieldType::Array(elem) => elem.class_name(),
+            _ => None,
+        }
+    }
+
+    pub fn to_descriptor(&self) -> JString {
+        let mut out = Vec::new();
+        self.encode(&mut out);
+        into_jstring(out)
+    }
+
+    ///
+    /// The descriptor as a String, such as `[[I`, unlike the Java form given by `Display`. Characters a String cannot hold, such as unpaired surrogates, are replaced as when displaying a [`JStr`]
+    pub fn to_descriptor_string(&self) -> String {
+        self.to_descriptor().to_string()
+    }
+
+    ///
+    /// Replaces every reference to the class `from` with `to`
+    pub fn rename_class(&mut self, from: &JStr, to: &'a JStr) {
+        match self {
+            FieldType::Object(name) if *name == from => *name = to,
+            FieldType::Array(elem) => elem.rename_class(from, to),
+            _ => {}
+        }
+    }
+}
+
+impl<'a> MethodDescriptor<'a> {
+    pub fn parse(desc: &'a JStr) -> Result<Self, DescriptorError> {
+        let mut parser = DescriptorParser { desc, pos: 0 };
+        match parser.next()? {
+            b'(' => {}
+            byte => return Err(DescriptorError::InvalidChar { pos: 0, byte }),
+        }
+        let mut params = Vec::new();
+        while parser.peek() != Some(b')') {
+            params.push(parser.field_type()?);
+        }
+        parser.pos += 1;
+        let ret = if parser.peek() == Some(b'V') {
+            parser.pos += 1;
+            None
+        } else {
+            Some(parser.field_type()?)
+        };
+        parser.finish()?;
+        Ok(Self { params, ret })
+    }
+
+    ///
+    /// Iterates over the classes named by the parameter and return types
+    pub fn class_names(&self) -> impl Iterator<Item = &'a JStr> + '_ {
+        self.params
+            .iter()
+            .chain(self.ret.as_ref())
+            .filter_map(FieldType::class_name)
+    }
+
+    pub fn to_descriptor(&self) -> JString {
+        let mut out = vec![b'('];
+        for param in &self.params {
+            param.encode(&mut out);
+        }
+        out.push(b')');
+        match &self.ret {
+            Some(ret) => ret.encode(&mut out),
+            None => out.push(b'V'),
+        }
+        into_jstring(out)
+    }
+
+    ///
+    /// The descriptor as a String, such as `(I)Z`, unlike the Java form given by `Display`
+    pub fn to_descriptor_string(&self) -> String {
+        self.to_descriptor().to_string()
+    }
+
+    ///
+    /// Replaces every reference to the class `from` with `to`
+    pub fn rename_class(&mut self, from: &JStr, to: &'a JStr) {
+        for ty in self.params.iter_mut().chain(self.ret.iter_mut()) {
+            ty.rename_class(from, to);
+        }
+    }
+}
+
+///
+/// Formats the type as it is written in Java source, such as `int[]` or `java.lang.String`. [`FieldType::to_descriptor`] gives the descriptor instead
+impl<'a> Display for FieldType<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FieldType::Byte => f.write_str("byte"),
+            FieldType::Char => f.write_str("char"),
+            FieldType::Double => f.write_str("double"),
+            FieldType::Float => f.write_str("float"),
+            FieldType::Int => f.write_str("int"),
+            FieldType::Long => f.write_str("long"),
+            FieldType::Short => f.write_str("short"),
+            FieldType::Boolean => f.write_str("boolean"),
+            FieldType::Object(name) => {
+                for (i, part) in name.split('/').enumerate() {
+                    if i != 0 {
+                        f.write_str(".")?;
+                    }
+                    part.fmt(f)?;
+                }
+                Ok(())
+            }
+            FieldType::Array(elem) => write!(f, "{}[]", elem),
+        }
+    }
+}
+
+///
+/// Formats the descriptor like a Java lambda type, such as `(int, boolean) -> void`. [`MethodDescriptor::to_descriptor`] gives the descriptor instead
+impl<'a> Display for MethodDescriptor<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("(")?;
+        for (i, param) in self.params.iter().enumerate() {
+            if i != 0 {
+                f.write_str(", ")?;
+            }
+            param.fmt(f)?;
+        }
+        f.write_str(") -> ")?;
+        match &self.ret {
+            Some(ret) => ret.fmt(f),
+            None => f.write_str("void"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn jstr(st: &str) -> &JStr {
+        JStr::from_str(st).unwrap()
+    }
+
+    #[test]
+    fn java_form_and_descriptor_form() {
+        let array = FieldType::parse(jstr("[[I")).unwrap();
+        assert_eq!(array.to_string(), "int[][]");
+        assert_eq!(array.to_descriptor_string(), "[[I");
+
+        let string = FieldType::parse(jstr("[Ljava/lang/String;")).unwrap();
+        assert_eq!(string.to_string(), "java.lang.String[]");
+        assert_eq!(string.to_descriptor_string(), "[Ljava/lang/String;");
+
+        let method = MethodDescriptor::parse(jstr("(I)Z")).unwrap();
+        assert_eq!(method.to_string(), "(int) -> boolean");
+        assert_eq!(method.to_descriptor_string(), "(I)Z");
+
+        let method = MethodDescriptor::parse(jstr("(JLjava/util/List;)V")).unwrap();
+        assert_eq!(method.to_string(), "(long, java.util.List) -> void");
+        assert_eq!(method.to_descriptor_string(), "(JLjava/util/List;)V");
+        assert_eq!(
+            MethodDescriptor::parse(jstr("()V")).unwrap().to_string(),
+            "() -> void"
+        );
+    }
+}
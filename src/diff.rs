@@ -0,0 +1,389 @@
+use std::collections::HashMap;
+
+use crate::{
+    class::{Attribute, ClassFile, Constant, MethodInfo},
+    hash::{fnv1a, FNV_OFFSET},
+    parse::KNOWN_ATTRIBUTES,
+    pool::ConstantPool,
+    string::{JStr, JString},
+    write::WriteError,
+};
+
+///
+/// The differences between two versions of a class, as found by [`diff`].
+///
+/// Fields and methods are identified by their name and descriptor, and attributes by their name. Constant pool indices are compared by what they refer to, so a class whose pool was merely reordered has no differences.
+/// Each list is in the order the items appear in the class they were found in
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClassDiff {
+    pub added_fields: Vec<(JString, JString)>,
+    pub removed_fields: Vec<(JString, JString)>,
+    /// Fields whose access flags or attributes differ
+    pub changed_fields: Vec<(JString, JString)>,
+    pub added_methods: Vec<(JString, JString)>,
+    pub removed_methods: Vec<(JString, JString)>,
+    /// Methods whose access flags or attributes other than Code differ, such as their generic signature or thrown exceptions
+    pub changed_methods: Vec<(JString, JString)>,
+    /// Methods whose Code attribute differs, including methods that gained or lost one
+    pub changed_bodies: Vec<(JString, JString)>,
+    pub added_attributes: Vec<JString>,
+    pub removed_attributes: Vec<JString>,
+    pub changed_attributes: Vec<JString>,
+}
+
+impl ClassDiff {
+    ///
+    /// Whether no differences were found
+    pub fn is_empty(&self) -> bool {
+        *self == ClassDiff::default()
+    }
+}
+
+/// The name and descriptor of a member, with the fingerprints of its attributes other than Code and of its Code attributes
+type Member = ((JString, JString), (u64, u64));
+
+struct Fingerprinter<'a> {
+    class: &'a ClassFile,
+    hashes: Vec<u64>,
+    /// A pool holding only the names of attributes, so the attributes being fingerprinted can be written
+    names: &'a ConstantPool,
+}
+
+impl<'a> Fingerprinter<'a> {
+    fn new(class: &'a ClassFile, names: &'a ConstantPool) -> Self {
+        Self {
+            class,
+            hashes: class.constant_hashes(),
+            names,
+        }
+    }
+
+    ///
+    /// Hashes access flags and attributes. The constants they refer to are hashed by content, and everything else as it would be written.
+    ///
+    /// The names of custom attributes are hashed as constants, and their content as written
+    fn fingerprint(&self, acc: u16, attributes: &[&Attribute]) -> Result<u64, WriteError> {
+        let mut holder = ClassFile {
+            min: 0,
+            maj: 0,
+            consts: self.names.clone(),
+            acc: 0,
+            this: 0,
+            supercl: 0,
+            interfaces: Vec::new(),
+            fields: Vec::new(),
+            methods: vec![MethodInfo {
+                acc,
+                name: 0,
+                descriptor: 0,
+                attributes: attributes.iter().map(|&attr| attr.clone()).collect(),
+            }],
+            attributes: Vec::new(),
+        };
+        let mut hash = FNV_OFFSET;
+        holder.for_each_ref_mut(|_, _, idx| {
+            let constant = (*idx as usize)
+                .checked_sub(1)
+                .and_then(|slot| self.hashes.get(slot))
+                .copied()
+                .unwrap_or(*idx as u64);
+            hash = fnv1a(hash, &constant.to_be_bytes());
+            *idx = 0;
+        });
+        // Keep the names of custom attributes resolvable, so the holder is written like any other class
+        let custom = holder
+            .consts
+            .push(Constant::Utf8(JStr::from_str("Custom").unwrap().to_owned()))?;
+        name_custom_attributes(&mut holder.methods[0].attributes, custom);
+        let mut bytes = Vec::new();
+        holder.write(&mut bytes)?;
+        Ok(fnv1a(hash, &bytes))
+    }
+
+    fn attribute_name(&self, attr: &Attribute) -> Option<JString> {
+        match attr {
+            Attribute::Unresolved { name, .. } => self.class.consts.utf8(*name).map(JStr::to_owned),
+            _ => attr
+                .name()
+                .and_then(|name| JStr::from_str(name).ok())
+                .map(JStr::to_owned),
+        }
+    }
+
+    ///
+    /// Groups the attributes by name, in the order each name first appears, and fingerprints each group
+    fn attributes(&self, attributes: &[Attribute]) -> Result<Vec<(JString, u64)>, WriteError> {
+        let mut groups: Vec<(JString, Vec<&Attribute>)> = Vec::new();
+        for attr in attributes {
+            if let Some(name) = self.attribute_name(attr) {
+                match groups.iter_mut().find(|(group, _)| *group == name) {
+                    Some((_, attrs)) => attrs.push(attr),
+                    None => groups.push((name, vec![attr])),
+                }
+            }
+        }
+        groups
+            .into_iter()
+            .map(|(name, attrs)| Ok((name, self.fingerprint(0, &attrs)?)))
+            .collect()
+    }
+
+    ///
+    /// Resolves the name and descriptor of a member, and fingerprints its access flags and attributes other than Code, and its Code attributes
+    fn member(
+        &self,
+        acc: u16,
+        name: u16,
+        descriptor: u16,
+        attributes: &[Attribute],
+    ) -> Result<Option<Member>, WriteError> {
+        let (name, descriptor) = match (
+            self.class.consts.utf8(name),
+            self.class.consts.utf8(descriptor),
+        ) {
+            (Some(name), Some(descriptor)) => (name.to_owned(), descriptor.to_owned()),
+            _ => return Ok(None),
+        };
+        let (code, rest): (Vec<_>, Vec<_>) = attributes
+            .iter()
+            .partition(|attr| matches!(attr, Attribute::Code(_)));
+        Ok(Some((
+            (name, descriptor),
+            (self.fingerprint(acc, &rest)?, self.fingerprint(0, &code)?),
+        )))
+    }
+}
+
+///
+/// Points the names of custom attributes, including those in Code attributes and record components, at the constant `name`
+fn name_custom_attributes(attributes: &mut [Attribute], name: u16) {
+    for attr in attributes {
+        match attr {
+            Attribute::Unresolved { name: idx, .. } => *idx = name,
+            Attribute::Code(code) => name_custom_attributes(&mut code.attributes, name),
+            Attribute::Record(components) => {
+                for component in components {
+                    name_custom_attributes(&mut component.attributes, name);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+type Changes<K> = (Vec<K>, Vec<K>, Vec<K>);
+
+///
+/// Splits keyed fingerprints into the keys only in `b`, the keys only in `a`, and the keys whose fingerprints differ according to `differs`
+fn compare<K: Clone + Eq + std::hash::Hash, V>(
+    a: &[(K, V)],
+    b: &[(K, V)],
+    differs: impl Fn(&V, &V) -> bool,
+) -> Changes<K> {
+    let in_a: HashMap<_, _> = a.iter().map(|(key, value)| (key, value)).collect();
+    let in_b: HashMap<_, _> = b.iter().map(|(key, value)| (key, value)).collect();
+    let added = b
+        .iter()
+        .filter(|(key, _)| !in_a.contains_key(key))
+        .map(|(key, _)| key.clone())
+        .collect();
+    let removed = a
+        .iter()
+        .filter(|(key, _)| !in_b.contains_key(key))
+        .map(|(key, _)| key.clone())
+        .collect();
+    let changed = a
+        .iter()
+        .filter(|(key, value)| in_b.get(key).is_some_and(|other| differs(value, other)))
+        .map(|(key, _)| key.clone())
+        .collect();
+    (added, removed, changed)
+}
+
+///
+/// Compares two versions of a class.
+///
+/// Members whose name or descriptor cannot be resolved are skipped. If a class has several members with the same name and descriptor, only the last one is compared.
+///
+/// Returns an error if either class cannot be written
+pub fn diff(a: &ClassFile, b: &ClassFile) -> Result<ClassDiff, WriteError> {
+    let names = ConstantPool::from_constants(
+        KNOWN_ATTRIBUTES
+            .iter()
+            .map(|name| Constant::Utf8(JStr::from_str(name).unwrap().to_owned()))
+            .collect(),
+    );
+    let a = Fingerprinter::new(a, &names);
+    let b = Fingerprinter::new(b, &names);
+
+    let fields = |f: &Fingerprinter| -> Result<Vec<_>, WriteError> {
+        f.class
+            .fields
+            .iter()
+            .filter_map(|field| {
+                f.member(field.acc, field.name, field.descriptor, &field.attributes)
+                    .transpose()
+            })
+            .collect()
+    };
+    let (added_fields, removed_fields, changed_fields) =
+        compare(&fields(&a)?, &fields(&b)?, |a, b| a.0 != b.0);
+
+    let methods = |f: &Fingerprinter| -> Result<Vec<_>, WriteError> {
+        f.class
+            .methods
+            .iter()
+            .filter_map(|method| {
+                f.member(
+                    method.acc,
+                    method.name,
+                    method.descriptor,
+                    &method.attributes,
+                )
+                .transpose()
+            })
+            .collect()
+    };
+    let (methods_a, methods_b) = (methods(&a)?, methods(&b)?);
+    let (added_methods, removed_methods, changed_methods) =
+        compare(&methods_a, &methods_b, |a, b| a.0 != b.0);
+    let (_, _, changed_bodies) = compare(&methods_a, &methods_b, |a, b| a.1 != b.1);
+
+    let (added_attributes, removed_attributes, changed_attributes) = compare(
+        &a.attributes(&a.class.attributes)?,
+        &b.attributes(&b.class.attributes)?,
+        |a, b| a != b,
+    );
+
+    Ok(ClassDiff {
+        added_fields,
+        removed_fields,
+        changed_fields,
+        added_methods,
+        removed_methods,
+        changed_methods,
+        changed_bodies,
+        added_attributes,
+        removed_attributes,
+        changed_attributes,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    fn pair(name: &str, descriptor: &str) -> (JString, JString) {
+        (testutil::jstring(name), testutil::jstring(descriptor))
+    }
+
+    #[test]
+    fn reordered_pool_has_no_differences() {
+        let a = testutil::read(testutil::FIXTURE);
+        let mut b = a.clone();
+        b.canonicalize_constant_pool();
+        assert!(diff(&a, &b).unwrap().is_empty());
+    }
+
+    #[test]
+    fn added_method_and_changed_body() {
+        let a = testutil::read(testutil::FIXTURE);
+        let mut b = a.clone();
+
+        let mut added = testutil::method(&b, "old").clone();
+        added.attributes.clear();
+        added.name = b.consts.intern_utf8(&testutil::jstring("added")).unwrap();
+        b.methods.push(added);
+
+        let index = b
+            .methods
+            .iter()
+            .position(|method| testutil::utf8(&b, method.name) == "loop")
+            .unwrap();
+        let code = b.methods[index].code_mut().unwrap();
+        // iconst_0 becomes iconst_1
+        assert_eq!(code.code[0], 0x03);
+        code.code[0] = 0x04;
+        b.canonicalize_constant_pool();
+
+        assert_eq!(
+            diff(&a, &b).unwrap(),
+            ClassDiff {
+                added_methods: vec![pair("added", "()V")],
+                changed_bodies: vec![pair("loop", "(I)I")],
+                ..ClassDiff::default()
+            }
+        );
+        assert_eq!(
+            diff(&b, &a).unwrap(),
+            ClassDiff {
+                removed_methods: vec![pair("added", "()V")],
+                changed_bodies: vec![pair("loop", "(I)I")],
+                ..ClassDiff::default()
+            }
+        );
+    }
+
+    #[test]
+    fn changed_custom_attribute() {
+        let mut a = testutil::read(testutil::FIXTURE);
+        let name = a.consts.intern_utf8(&testutil::jstring("Custom")).unwrap();
+        a.attributes.push(Attribute::Unresolved {
+            name,
+            content: vec![1, 2, 3],
+        });
+        let index = a
+            .methods
+            .iter()
+            .position(|method| testutil::utf8(&a, method.name) == "loop")
+            .unwrap();
+        let code = a.methods[index].code_mut().unwrap();
+        code.attributes.push(Attribute::Unresolved {
+            name,
+            content: vec![4],
+        });
+        assert!(diff(&a, &a.clone()).unwrap().is_empty());
+
+        let mut b = a.clone();
+        if let Some(Attribute::Unresolved { content, .. }) = b.attributes.last_mut() {
+            content[2] = 4;
+        }
+        assert_eq!(
+            diff(&a, &b).unwrap(),
+            ClassDiff {
+                changed_attributes: vec![testutil::jstring("Custom")],
+                ..ClassDiff::default()
+            }
+        );
+
+        let mut b = a.clone();
+        let code = b.methods[index].code_mut().unwrap();
+        if let Some(Attribute::Unresolved { content, .. }) = code.attributes.last_mut() {
+            content[0] = 5;
+        }
+        assert_eq!(
+            diff(&a, &b).unwrap(),
+            ClassDiff {
+                changed_bodies: vec![pair("loop", "(I)I")],
+                ..ClassDiff::default()
+            }
+        );
+    }
+
+    #[test]
+    fn unwritable_class_is_an_error() {
+        let a = testutil::read(testutil::FIXTURE);
+        let mut b = a.clone();
+        b.attributes.push(Attribute::StackMapTable(vec![
+            crate::class::StackMapFrame::ChopFrame {
+                chop: 0,
+                offset_delta: 0,
+            },
+        ]));
+        assert!(matches!(
+            diff(&a, &b),
+            Err(WriteError::InvalidChopFrame { chop: 0 })
+        ));
+    }
+}
@@ -0,0 +1,122 @@
+use std::fmt::{Display, Formatter};
+
+use crate::{
+    bytecode::{DecodeError, EncodeError},
+    descriptor::DescriptorError,
+    parse::ParseError,
+    string::ModifiedUtf8Error,
+    write::WriteError,
+};
+
+///
+/// Any of the errors produced by this crate, so that applications can use one error type with `?`.
+///
+/// The original error is available from [`std::error::Error::source`]
+#[derive(Debug)]
+pub enum Error {
+    Io(std::io::Error),
+    Utf8(ModifiedUtf8Error),
+    Descriptor(DescriptorError),
+    Decode(DecodeError),
+    Encode(EncodeError),
+    Parse(ParseError),
+    Write(WriteError),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io(e) => e.fmt(f),
+            Error::Utf8(e) => e.fmt(f),
+            Error::Descriptor(e) => e.fmt(f),
+            Error::Decode(e) => e.fmt(f),
+            Error::Encode(e) => e.fmt(f),
+            Error::Parse(e) => e.fmt(f),
+            Error::Write(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io(e) => Some(e),
+            Error::Utf8(e) => Some(e),
+            Error::Descriptor(e) => Some(e),
+            Error::Decode(e) => Some(e),
+            Error::Encode(e) => Some(e),
+            Error::Parse(e) => Some(e),
+            Error::Write(e) => Some(e),
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<ModifiedUtf8Error> for Error {
+    fn from(e: ModifiedUtf8Error) -> Self {
+        Error::Utf8(e)
+    }
+}
+
+impl From<DescriptorError> for Error {
+    fn from(e: DescriptorError) -> Self {
+        Error::Descriptor(e)
+    }
+}
+
+impl From<DecodeError> for Error {
+    fn from(e: DecodeError) -> Self {
+        Error::Decode(e)
+    }
+}
+
+impl From<EncodeError> for Error {
+    fn from(e: EncodeError) -> Self {
+        Error::Encode(e)
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::Parse(e)
+    }
+}
+
+impl From<WriteError> for Error {
+    fn from(e: WriteError) -> Self {
+        Error::Write(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class::ClassFile;
+
+    fn read(bytes: &[u8]) -> Result<ClassFile, Error> {
+        Ok(ClassFile::read(&mut &bytes[..])?)
+    }
+
+    #[test]
+    fn source_chain_reaches_the_utf8_error() {
+        // A class whose only constant is a Utf8 holding the invalid byte FF
+        let bytes = b"\xca\xfe\xba\xbe\x00\x00\x00\x34\x00\x02\x01\x00\x01\xff";
+        let err = read(bytes).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::Parse(ParseError::InvalidUtf8 { index: 1, .. })
+        ));
+
+        let parse = std::error::Error::source(&err).unwrap();
+        assert!(parse.is::<ParseError>());
+        assert_eq!(parse.to_string(), err.to_string());
+        let utf8 = parse.source().unwrap();
+        assert!(utf8.is::<ModifiedUtf8Error>());
+        assert!(utf8.source().is_none());
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    fmt::{Debug, Formatter},
+    ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign},
+};
+
+use crate::class::consts;
+
+macro_rules! access_flags {
+    ($(#[$meta:meta])* $name:ident: $bits:ident { $($flag:ident = $value:ident),* $(,)? }) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, PartialEq, Eq, Hash, Default)]
+        pub struct $name(u16);
+
+        impl $name {
+            $(pub const $flag: Self = Self(consts::$value);)*
+
+            ///
+            /// Keeps only the bits that are meaningful for this kind of flags
+            pub const fn from_bits_truncate(bits: u16) -> Self {
+                Self(bits & consts::$bits)
+            }
+
+            ///
+            /// Returns None if any bit is set that is not meaningful for this kind of flags
+            pub const fn from_bits(bits: u16) -> Option<Self> {
+                if bits & !consts::$bits == 0 {
+                    Some(Self(bits))
+                } else {
+                    None
+                }
+            }
+
+            pub const fn empty() -> Self {
+                Self(0)
+            }
+
+            pub const fn bits(self) -> u16 {
+                self.0
+            }
+
+            pub const fn is_empty(self) -> bool {
+                self.0 == 0
+            }
+
+            ///
+            /// Whether every flag set in `other` is also set in `self`
+            pub const fn contains(self, other: Self) -> bool {
+                self.0 & other.0 == other.0
+            }
+
+            pub const fn intersects(self, other: Self) -> bool {
+                self.0 & other.0 != 0
+            }
+        }
+
+        impl BitOr for $name {
+            type Output = Self;
+
+            fn bitor(self, rhs: Self) -> Self {
+                Self(self.0 | rhs.0)
+            }
+        }
+
+        impl BitOrAssign for $name {
+            fn bitor_assign(&mut self, rhs: Self) {
+                self.0 |= rhs.0;
+            }
+        }
+
+        impl BitAnd for $name {
+            type Output = Self;
+
+            fn bitand(self, rhs: Self) -> Self {
+                Self(self.0 & rhs.0)
+            }
+        }
+
+        impl BitAndAssign for $name {
+            fn bitand_assign(&mut self, rhs: Self) {
+                self.0 &= rhs.0;
+            }
+        }
+
+        impl Debug for $name {
+            fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+                let mut first = true;
+                $(
+                    if self.contains(Self::$flag) {
+                        if !first {
+                            f.write_str(" | ")?;
+                        }
+                        first = false;
+                        f.write_str(stringify!($value))?;
+                    }
+                )*
+                if first {
+                    f.write_str("(empty)")?;
+                }
+                Ok(())
+            }
+        }
+    };
+}
+
+access_flags! {
+    ///
+    /// The access flags of a class
+    ClassAccessFlags: ACC_CLASS_BITS {
+        PUBLIC = ACC_PUBLIC,
+        FINAL = ACC_FINAL,
+        SUPER = ACC_SUPER,
+        INTERFACE = ACC_INTERFACE,
+        ABSTRACT = ACC_ABSTRACT,
+        SYNTHETIC = ACC_SYNTHETIC,
+        ANNOTATION = ACC_ANNOTATION,
+        ENUM = ACC_ENUM,
+        MODULE = ACC_MODULE,
+    }
+}
+
+access_flags! {
+    ///
+    /// The access flags of a field
+    FieldAccessFlags: ACC_FIELD_BITS {
+        PUBLIC = ACC_PUBLIC,
+        PRIVATE = ACC_PRIVATE,
+        PROTECTED = ACC_PROTECTED,
+        STATIC = ACC_STATIC,
+        FINAL = ACC_FINAL,
+        VOLATILE = ACC_VOLATILE,
+        TRANSIENT = ACC_TRANSIENT,
+        SYNTHETIC = ACC_SYNTHETIC,
+        ENUM = ACC_ENUM,
+    }
+}
+
+access_flags! {
+    ///
+    /// The access flags of a method
+    MethodAccessFlags: ACC_METHOD_BITS {
+        PUBLIC = ACC_PUBLIC,
+        PRIVATE = ACC_PRIVATE,
+        PROTECTED = ACC_PROTECTED,
+        STATIC = ACC_STATIC,
+        FINAL = ACC_FINAL,
+        SYNCHRONIZED = ACC_SYNCHRONIZED,
+        BRIDGE = ACC_BRIDGE,
+        VARARGS = ACC_VARARGS,
+        NATIVE = ACC_NATIVE,
+        ABSTRACT = ACC_ABSTRACT,
+        STRICT = ACC_STRICT,
+        SYNTHETIC = ACC_SYNTHETIC,
+    }
+}
+
+access_flags! {
+    ///
+    /// The flags of an entry in the InnerClasses attribute
+    InnerClassAccessFlags: ACC_INNER_CLASS_BITS {
+        PUBLIC = ACC_PUBLIC,
+        PRIVATE = ACC_PRIVATE,
+        PROTECTED = ACC_PROTECTED,
+        STATIC = ACC_STATIC,
+        FINAL = ACC_FINAL,
+        INTERFACE = ACC_INTERFACE,
+        ABSTRACT = ACC_ABSTRACT,
+        SYNTHETIC = ACC_SYNTHETIC,
+        ANNOTATION = ACC_ANNOTATION,
+        ENUM = ACC_ENUM,
+    }
+}
+
+access_flags! {
+    ///
+    /// The flags of a parameter in the MethodParameters attribute
+    ParameterAccessFlags: ACC_PARAMETER_BITS {
+        FINAL = ACC_FINAL,
+        SYNTHETIC = ACC_SYNTHETIC,
+        MANDATED = ACC_MANDATED,
+    }
+}
+
+access_flags! {
+    ///
+    /// The flags of a `requires` entry in the Module attribute
+    RequiresFlags: ACC_REQUIRES_BITS {
+        TRANSITIVE = ACC_TRANSITIVE,
+        STATIC_PHASE = ACC_STATIC_PHASE,
+        SYNTHETIC = ACC_SYNTHETIC,
+        MANDATED = ACC_MANDATED,
+    }
+}
+
+access_flags! {
+    ///
+    /// The flags of an `exports` or `opens` entry in the Module attribute
+    ExportsFlags: ACC_EXPORTS_BITS {
+        SYNTHETIC = ACC_SYNTHETIC,
+        MANDATED = ACC_MANDATED,
+    }
+}
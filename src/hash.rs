@@ -0,0 +1,322 @@
+use std::{cmp::Ordering, collections::HashMap};
+
+use crate::{
+    class::{ClassFile, Constant},
+    refs::Location,
+    write::WriteError,
+};
+
+pub(crate) const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+///
+/// FNV-1a, chosen over [`std::hash::Hasher`] implementations because its output never changes between Rust versions
+pub(crate) fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |hash, &b| (hash ^ b as u64).wrapping_mul(FNV_PRIME))
+}
+
+///
+/// The tag of a constant and the parts of it that are not constant pool indices
+fn scalars(constant: &Constant) -> (u8, Vec<u8>) {
+    match constant {
+        Constant::Utf8(st) => (1, st.as_bytes().to_vec()),
+        Constant::Int(v) => (3, v.to_be_bytes().to_vec()),
+        Constant::Float(v) => (4, v.to_bits().to_be_bytes().to_vec()),
+        Constant::Long(v) => (5, v.to_be_bytes().to_vec()),
+        Constant::Double(v) => (6, v.to_bits().to_be_bytes().to_vec()),
+        Constant::Class(_) => (7, Vec::new()),
+        Constant::String(_) => (8, Vec::new()),
+        Constant::FieldRef { .. } => (9, Vec::new()),
+        Constant::MethodRef { .. } => (10, Vec::new()),
+        Constant::InterfaceMethodRef { .. } => (11, Vec::new()),
+        Constant::NameAndType { .. } => (12, Vec::new()),
+        Constant::MethodHandle { kind, .. } => (15, vec![*kind]),
+        Constant::MethodType(_) => (16, Vec::new()),
+        Constant::Dynamic {
+            bootstrap_attrs, ..
+        } => (17, bootstrap_attrs.to_be_bytes().to_vec()),
+        Constant::InvokeDynamic {
+            bootstrap_attrs, ..
+        } => (18, bootstrap_attrs.to_be_bytes().to_vec()),
+        Constant::Module(_) => (19, Vec::new()),
+        Constant::Package(_) => (20, Vec::new()),
+        Constant::LongOrDoubleHigh => (0, Vec::new()),
+    }
+}
+
+///
+/// Hashes the parts of a constant that are not constant pool indices
+fn hash_scalars(constant: &Constant) -> u64 {
+    let (tag, bytes) = scalars(constant);
+    fnv1a(fnv1a(FNV_OFFSET, &[tag]), &bytes)
+}
+
+impl ClassFile {
+    ///
+    /// The indices each slot of the pool refers to, in order
+    fn constant_children(&self) -> Vec<Vec<u16>> {
+        let mut children = vec![Vec::new(); self.consts.as_slice().len()];
+        self.for_each_ref(|loc, _, &idx| {
+            if let Location::Constant(owner) = loc {
+                children[owner as usize - 1].push(idx);
+            }
+        });
+        children
+    }
+
+    ///
+    /// Compares the constants in slots `a` and `b` by their content and, recursively, the content of everything they refer to.
+    ///
+    /// This is the total order that [`ClassFile::constant_hashes`] approximates, used to tell apart constants whose hashes collide.
+    /// Indices that are out of range are compared as numbers, and a cycle compares equal once it has been followed `len` times
+    fn compare_constants(
+        &self,
+        children: &[Vec<u16>],
+        a: usize,
+        b: usize,
+        depth: usize,
+    ) -> Ordering {
+        let slots = self.consts.as_slice();
+        if a == b || depth > slots.len() {
+            return Ordering::Equal;
+        }
+        scalars(&slots[a])
+            .cmp(&scalars(&slots[b]))
+            .then_with(|| children[a].len().cmp(&children[b].len()))
+            .then_with(|| {
+                let slot = |idx: u16| {
+                    (idx as usize)
+                        .checked_sub(1)
+                        .filter(|&slot| slot < slots.len())
+                };
+                children[a]
+                    .iter()
+                    .zip(&children[b])
+                    .map(|(&x, &y)| match (slot(x), slot(y)) {
+                        (Some(x), Some(y)) => self.compare_constants(children, x, y, depth + 1),
+                        (x, y) => x.is_some().cmp(&y.is_some()).then(x.cmp(&y)),
+                    })
+                    .find(|ord| ord.is_ne())
+                    .unwrap_or(Ordering::Equal)
+            })
+    }
+
+    ///
+    /// Hashes each slot of the pool together with everything it refers to, so that equal constants hash equal wherever they are in the pool.
+    ///
+    /// Indices that are out of range or that form a cycle are hashed as the index itself
+    pub(crate) fn constant_hashes(&self) -> Vec<u64> {
+        let len = self.consts.as_slice().len();
+        let children = self.constant_children();
+
+        let mut hashes: Vec<Option<u64>> = vec![None; len];
+        let mut visiting = vec![false; len];
+        for root in 0..len {
+            let mut stack = vec![root];
+            while let Some(&slot) = stack.last() {
+                if hashes[slot].is_some() {
+                    stack.pop();
+                    continue;
+                }
+                visiting[slot] = true;
+                let pending = children[slot]
+                    .iter()
+                    .filter_map(|&idx| (idx as usize).checked_sub(1))
+                    .find(|&child| child < len && hashes[child].is_none() && !visiting[child]);
+                if let Some(child) = pending {
+                    stack.push(child);
+                    continue;
+                }
+                let hash = children[slot].iter().fold(
+                    hash_scalars(&self.consts.as_slice()[slot]),
+                    |hash, &idx| {
+                        let child = (idx as usize)
+                            .checked_sub(1)
+                            .and_then(|child| hashes.get(child).copied().flatten())
+                            .unwrap_or(idx as u64);
+                        fnv1a(hash, &child.to_be_bytes())
+                    },
+                );
+                hashes[slot] = Some(hash);
+                visiting[slot] = false;
+                stack.pop();
+            }
+        }
+        hashes.into_iter().map(Option::unwrap).collect()
+    }
+
+    ///
+    /// Computes a hash of the content of the class that does not depend on the order of the constant pool, for example to tell whether a regenerated class actually changed.
+    ///
+    /// The hash is the same across platforms and versions of Rust. Duplicate constants are treated as one. Everything else, including the order of members and attributes and any unused constants, affects the hash.
+    ///
+    /// Returns an error if the class cannot be written
+    pub fn content_hash(&self) -> Result<u64, WriteError> {
+        let hashes = self.constant_hashes();
+        let children = self.constant_children();
+        // Equal constants are interchangeable, so each is replaced by the first of its equals, which keeps `ldc` operands in range.
+        // Constants with the same hash are only merged if their content is equal, in case two different constants collide
+        let mut first: HashMap<u64, Vec<u16>> = HashMap::new();
+        let mut equal = HashMap::new();
+        for (idx, _) in self.consts.entries() {
+            let slot = idx as usize - 1;
+            let candidates = first.entry(hashes[slot]).or_default();
+            let existing = candidates.iter().copied().find(|&other| {
+                self.compare_constants(&children, other as usize - 1, slot, 0)
+                    .is_eq()
+            });
+            match existing {
+                Some(other) => {
+                    equal.insert(idx, other);
+                }
+                None => candidates.push(idx),
+            }
+        }
+        let mut order: Vec<u16> = first.values().flatten().copied().collect();
+        order.sort_by(|&a, &b| {
+            let (a, b) = (a as usize - 1, b as usize - 1);
+            hashes[a]
+                .cmp(&hashes[b])
+                .then_with(|| self.compare_constants(&children, a, b, 0))
+        });
+
+        let mut class = self.clone();
+        class.for_each_ref_mut(|_, _, idx| {
+            if let Some(&other) = equal.get(idx) {
+                *idx = other;
+            }
+        });
+        class.reorder_constants(&order);
+        let mut bytes = Vec::new();
+        class.write(&mut bytes)?;
+        Ok(fnv1a(FNV_OFFSET, &bytes))
+    }
+
+    ///
+    /// Reorders the constant pool into an order that depends only on the content of the constants, and redirects every reference, so that classes that differ only in the order of their pools are written identically.
+    ///
+    /// Constants are sorted by tag, then by their own content (such as the text of a Utf8), then by what they refer to. Operands of `ldc` instructions come first so that they still fit in a byte.
+    /// No constants are added or removed, and duplicates are kept next to each other
+    pub fn canonicalize_constant_pool(&mut self) {
+        let hashes = self.constant_hashes();
+        let mut order: Vec<u16> = self.consts.entries().map(|(idx, _)| idx).collect();
+        order.sort_by_cached_key(|&idx| {
+            let (tag, bytes) = scalars(&self.consts.as_slice()[idx as usize - 1]);
+            (tag, bytes, hashes[idx as usize - 1])
+        });
+        self.reorder_constants(&order);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn reordered_pool_hashes_the_same() {
+        for bytes in testutil::ALL {
+            let class = testutil::read(bytes);
+            let mut reordered = class.clone();
+            let mut order: Vec<u16> = class.consts.entries().map(|(idx, _)| idx).collect();
+            order.reverse();
+            reordered.reorder_constants(&order);
+            assert_ne!(reordered.write_to_vec().unwrap(), *bytes);
+            assert_eq!(
+                reordered.content_hash().unwrap(),
+                class.content_hash().unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn only_equal_constants_are_merged() {
+        let class = testutil::read(testutil::FIXTURE);
+        let hash = class.content_hash().unwrap();
+
+        let mut duplicate = class.clone();
+        duplicate
+            .consts
+            .push(Constant::Utf8(testutil::jstring("hello")))
+            .unwrap();
+        assert_eq!(duplicate.content_hash().unwrap(), hash);
+
+        let mut changed = class.clone();
+        changed
+            .consts
+            .push(Constant::Utf8(testutil::jstring("goodbye")))
+            .unwrap();
+        assert_ne!(changed.content_hash().unwrap(), hash);
+    }
+
+    #[test]
+    fn unwritable_class_has_no_hash() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        class
+            .consts
+            .push(Constant::Utf8(testutil::jstring(&"a".repeat(70000))))
+            .unwrap();
+        assert!(matches!(
+            class.content_hash(),
+            Err(WriteError::Utf8TooLong { len: 70000 })
+        ));
+    }
+
+    #[test]
+    fn compare_constants_follows_references() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        let a = class
+            .consts
+            .push(Constant::Utf8(testutil::jstring("a")))
+            .unwrap();
+        let b = class
+            .consts
+            .push(Constant::Utf8(testutil::jstring("b")))
+            .unwrap();
+        let class_a = class.consts.push(Constant::Class(a)).unwrap();
+        let class_b = class.consts.push(Constant::Class(b)).unwrap();
+        let class_a2 = class.consts.push(Constant::Class(a)).unwrap();
+        let children = class.constant_children();
+        let cmp =
+            |x: u16, y: u16| class.compare_constants(&children, x as usize - 1, y as usize - 1, 0);
+        assert_eq!(cmp(class_a, class_b), Ordering::Less);
+        assert_eq!(cmp(class_b, class_a), Ordering::Greater);
+        assert_eq!(cmp(class_a, class_a2), Ordering::Equal);
+        assert_eq!(cmp(a, class_a), Ordering::Less);
+    }
+
+    #[test]
+    fn differently_ordered_classes_canonicalize_identically() {
+        for bytes in testutil::ALL {
+            let class = testutil::read(bytes);
+            let order: Vec<u16> = class.consts.entries().map(|(idx, _)| idx).collect();
+
+            let mut reversed = class.clone();
+            reversed.reorder_constants(&order.iter().rev().copied().collect::<Vec<_>>());
+            let mut rotated = class.clone();
+            let (front, back) = order.split_at(order.len() / 3);
+            rotated.reorder_constants(&[back, front].concat());
+            assert_ne!(
+                reversed.write_to_vec().unwrap(),
+                rotated.write_to_vec().unwrap()
+            );
+
+            reversed.canonicalize_constant_pool();
+            rotated.canonicalize_constant_pool();
+            let canonical = reversed.write_to_vec().unwrap();
+            assert_eq!(canonical, rotated.write_to_vec().unwrap());
+
+            // The result is a valid class that canonicalizes to itself
+            let mut reread = testutil::read(&canonical);
+            assert_eq!(reread.validate_references(), Ok(()));
+            assert_eq!(
+                reread.content_hash().unwrap(),
+                class.content_hash().unwrap()
+            );
+            reread.canonicalize_constant_pool();
+            assert_eq!(reread.write_to_vec().unwrap(), canonical);
+        }
+    }
+}
@@ -1,4 +1,23 @@
 #![deny(unsafe_op_in_unsafe_fn)]
 
+pub mod builder;
+pub mod bytecode;
 pub mod class;
+pub mod descriptor;
+pub mod diff;
+mod error;
+pub mod flags;
+mod hash;
+pub mod parse;
+pub mod pool;
+mod reader;
+pub mod refs;
+pub mod signature;
 pub mod string;
+#[cfg(test)]
+mod testutil;
+pub mod validate;
+pub mod write;
+mod writer;
+
+pub use error::Error;
@@ -0,0 +1,1548 @@
+use std::{
+    fmt::{Display, Formatter},
+    io::Read,
+    ops::Range,
+};
+
+use crate::{
+    class::{
+        consts, Annotation, AnnotationElement, Attribute, Availability, BootstrapMethod, ClassFile,
+        CodeAttribute, Constant, ElementValue, ExceptionInfo, ExportInfo, FieldInfo,
+        InnerClassInfo, LineNumberEntry, LocalVariableInfo, LocalVariableLocationInfo,
+        LocalVariableTypeInfo, MethodInfo, ModuleInfo, ParameterInfo, ProvidesInfo,
+        RecordComponentInfo, RequireInfo, StackMapFrame, TypeAnnotation, TypeAnnotationTarget,
+        TypePathSegment, VerificationInfo,
+    },
+    pool::{ConstantPool, CpIndex},
+    reader::{reserve_checked, ClassReader},
+    string::{JStr, JString, ModifiedUtf8Error},
+};
+
+///
+/// Limits enforced while reading a class file, to bound the work done on untrusted input.
+///
+/// The defaults are the largest values the class file format can express, so they only reject files that are malformed anyways.
+#[derive(Clone, Debug)]
+pub struct ParseOptions {
+    /// The maximum `constant_pool_count`
+    pub max_constant_pool: usize,
+    /// The maximum length of the bytecode of a single Code attribute
+    pub max_code_length: usize,
+    /// The maximum number of attributes in a single attribute table
+    pub max_attributes: usize,
+    /// The maximum number of bytes read from the input
+    pub max_bytes: usize,
+    /// The maximum number of annotations and arrays an annotation element value can be nested in.
+    ///
+    /// Nested values are read recursively, so unlike the other limits this defaults to less than the format allows, to keep a forged annotation from overflowing the stack
+    pub max_nesting_depth: usize,
+    /// The maximum number of attribute tables an attribute table can be nested in, such as the attributes of a Code attribute in a Code attribute.
+    ///
+    /// Like [`ParseOptions::max_nesting_depth`], this keeps forged input from overflowing the stack. Each level takes more stack than a level of an annotation, so the default is lower
+    pub max_attribute_depth: usize,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            max_constant_pool: u16::MAX as usize,
+            max_code_length: u16::MAX as usize,
+            max_attributes: u16::MAX as usize,
+            max_bytes: usize::MAX,
+            max_nesting_depth: 256,
+            max_attribute_depth: 32,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ParseError {
+    Io(std::io::Error),
+    BadMagic(u32),
+    InvalidConstantTag {
+        index: u16,
+        tag: u8,
+    },
+    InvalidUtf8 {
+        index: u16,
+        err: ModifiedUtf8Error,
+    },
+    /// The name of an attribute is not a Utf8 constant
+    InvalidAttributeName(u16),
+    /// The content of an attribute does not match the format for its name
+    MalformedAttribute(&'static str),
+    /// One of the limits in [`ParseOptions`] was exceeded
+    LimitExceeded {
+        limit: &'static str,
+        value: usize,
+    },
+    /// The input ended while reading `expected`, in a read that started at byte `at`.
+    ///
+    /// The content of an attribute ends at its `attribute_length`, so an attribute whose length is too short for what it holds is reported here as well, with `expected` naming the attribute or the part of it being read
+    UnexpectedEof {
+        expected: &'static str,
+        at: u64,
+    },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Io(e) => e.fmt(f),
+            ParseError::BadMagic(magic) => write!(f, "bad magic number {:#010x}", magic),
+            ParseError::InvalidConstantTag { index, tag } => {
+                write!(f, "constant #{} has invalid tag {}", index, tag)
+            }
+            ParseError::InvalidUtf8 { index, err } => write!(
+                f,
+                "constant #{} is not valid Modified UTF-8 (valid up to byte {})",
+                index,
+                err.valid_up_to()
+            ),
+            ParseError::InvalidAttributeName(index) => {
+                write!(f, "attribute name #{} is not a Utf8 constant", index)
+            }
+            ParseError::MalformedAttribute(name) => write!(f, "malformed {} attribute", name),
+            ParseError::LimitExceeded { limit, value } => {
+                write!(f, "{} exceeded (found {})", limit, value)
+            }
+            ParseError::UnexpectedEof { expected, at } => write!(
+                f,
+                "unexpected end of input reading {} at byte {}",
+                expected, at
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Io(e) => Some(e),
+            ParseError::InvalidUtf8 { err, .. } => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ParseError {
+    fn from(e: std::io::Error) -> Self {
+        ParseError::Io(e)
+    }
+}
+
+fn read_constant<R: Read>(
+    r: &mut ClassReader<R>,
+    index: u16,
+    consts: &mut Vec<Constant>,
+) -> Result<(), ParseError> {
+    let tag = r.expect("constant").read_u8()?;
+    let constant = match tag {
+        1 => {
+            let len = r.read_u16()?;
+            Constant::Utf8(r.read_utf8(len as usize).map_err(|e| match e {
+                ParseError::InvalidUtf8 { err, .. } => ParseError::InvalidUtf8 { index, err },
+                e => e,
+            })?)
+        }
+        3 => Constant::Int(r.read_i32()?),
+        4 => Constant::Float(r.read_f32()?),
+        5 => Constant::Long(r.read_i64()?),
+        6 => Constant::Double(r.read_f64()?),
+        7 => Constant::Class(r.read_u16()?),
+        8 => Constant::String(r.read_u16()?),
+        9 => Constant::FieldRef {
+            class: r.read_u16()?,
+            name_and_type: r.read_u16()?,
+        },
+        10 => Constant::MethodRef {
+            class: r.read_u16()?,
+            name_and_type: r.read_u16()?,
+        },
+        11 => Constant::InterfaceMethodRef {
+            class: r.read_u16()?,
+            name_and_type: r.read_u16()?,
+        },
+        12 => Constant::NameAndType {
+            name: r.read_u16()?,
+            descriptor: r.read_u16()?,
+        },
+        15 => Constant::MethodHandle {
+            kind: r.read_u8()?,
+            reference: r.read_u16()?,
+        },
+        16 => Constant::MethodType(r.read_u16()?),
+        17 => Constant::Dynamic {
+            bootstrap_attrs: r.read_u16()?,
+            name_and_type: r.read_u16()?,
+        },
+        18 => Constant::InvokeDynamic {
+            bootstrap_attrs: r.read_u16()?,
+            name_and_type: r.read_u16()?,
+        },
+        19 => Constant::Module(r.read_u16()?),
+        20 => Constant::Package(r.read_u16()?),
+        tag => return Err(ParseError::InvalidConstantTag { index, tag }),
+    };
+    let wide = matches!(constant, Constant::Long(_) | Constant::Double(_));
+    consts.push(constant);
+    if wide {
+        consts.push(Constant::LongOrDoubleHigh);
+    }
+    Ok(())
+}
+
+fn read_verification_info<R: Read>(r: &mut ClassReader<R>) -> Result<VerificationInfo, ParseError> {
+    Ok(match r.read_u8()? {
+        0 => VerificationInfo::Top,
+        1 => VerificationInfo::Integer,
+        2 => VerificationInfo::Float,
+        3 => VerificationInfo::Double,
+        4 => VerificationInfo::Long,
+        5 => VerificationInfo::Null,
+        6 => VerificationInfo::UninitializedThis,
+        7 => VerificationInfo::Object {
+            class: r.read_u16()?,
+        },
+        8 => VerificationInfo::Uninitialized {
+            offset: r.read_u16()?,
+        },
+        _ => return Err(ParseError::MalformedAttribute("StackMapTable")),
+    })
+}
+
+fn read_verification_infos<R: Read>(
+    r: &mut ClassReader<R>,
+) -> Result<Vec<VerificationInfo>, ParseError> {
+    let count = r.read_u16()?;
+    r.table(count as usize, 1, read_verification_info)
+}
+
+fn read_stack_map_frame<R: Read>(r: &mut ClassReader<R>) -> Result<StackMapFrame, ParseError> {
+    Ok(match r.read_u8()? {
+        tag @ 0..=63 => StackMapFrame::Same { offset_delta: tag },
+        tag @ 64..=127 => StackMapFrame::SameLocals1StackFrame {
+            offset_delta: tag - 64,
+            info: read_verification_info(r)?,
+        },
+        247 => StackMapFrame::SameLocals1StackFrameExtended {
+            offset_delta: r.read_u16()?,
+            info: read_verification_info(r)?,
+        },
+        tag @ 248..=250 => StackMapFrame::ChopFrame {
+            chop: 251 - tag,
+            offset_delta: r.read_u16()?,
+        },
+        251 => StackMapFrame::SameExtended {
+            offset_delta: r.read_u16()?,
+        },
+        tag @ 252..=254 => StackMapFrame::Append {
+            offset_delta: r.read_u16()?,
+            items: r.table((tag - 251) as usize, 1, read_verification_info)?,
+        },
+        255 => StackMapFrame::Full {
+            offset_delta: r.read_u16()?,
+            locals: read_verification_infos(r)?,
+            stack: read_verification_infos(r)?,
+        },
+        _ => return Err(ParseError::MalformedAttribute("StackMapTable")),
+    })
+}
+
+/// Reads an element value nested in `depth` annotations and arrays, not counting the annotation the element belongs to
+fn read_element_value<R: Read>(
+    r: &mut ClassReader<R>,
+    depth: usize,
+) -> Result<ElementValue, ParseError> {
+    if depth > r.opts.max_nesting_depth {
+        return Err(ParseError::LimitExceeded {
+            limit: "max_nesting_depth",
+            value: depth,
+        });
+    }
+    Ok(match r.read_u8()? {
+        b'B' => ElementValue::Byte(r.read_u16()?),
+        b'C' => ElementValue::Char(r.read_u16()?),
+        b'D' => ElementValue::Double(r.read_u16()?),
+        b'F' => ElementValue::Float(r.read_u16()?),
+        b'I' => ElementValue::Int(r.read_u16()?),
+        b'J' => ElementValue::Long(r.read_u16()?),
+        b'S' => ElementValue::Short(r.read_u16()?),
+        b'Z' => ElementValue::Boolean(r.read_u16()?),
+        b's' => ElementValue::String(r.read_u16()?),
+        b'e' => ElementValue::Enum {
+            type_name: r.read_u16()?,
+            const_name: r.read_u16()?,
+        },
+        b'c' => ElementValue::Class(r.read_u16()?),
+        b'@' => ElementValue::Annotation(read_annotation(r, depth + 1)?),
+        b'[' => {
+            let count = r.read_u16()?;
+            ElementValue::Array(r.table(count as usize, 3, |r| read_element_value(r, depth + 1))?)
+        }
+        _ => return Err(ParseError::MalformedAttribute("annotation")),
+    })
+}
+
+fn read_annotation<R: Read>(
+    r: &mut ClassReader<R>,
+    depth: usize,
+) -> Result<Annotation, ParseError> {
+    let class = r.read_u16()?;
+    let count = r.read_u16()?;
+    let elements = r.table(count as usize, 5, |r| {
+        Ok(AnnotationElement {
+            name: r.read_u16()?,
+            value: read_element_value(r, depth)?,
+        })
+    })?;
+    Ok(Annotation { class, elements })
+}
+
+fn read_annotations<R: Read>(r: &mut ClassReader<R>) -> Result<Vec<Annotation>, ParseError> {
+    let count = r.read_u16()?;
+    r.table(count as usize, 4, |r| read_annotation(r, 0))
+}
+
+fn read_local_variable_locations<R: Read>(
+    r: &mut ClassReader<R>,
+) -> Result<Vec<LocalVariableLocationInfo>, ParseError> {
+    let count = r.read_u16()?;
+    r.table(count as usize, 6, |r| {
+        Ok(LocalVariableLocationInfo {
+            start_pc: r.read_u16()?,
+            length: r.read_u16()?,
+            index: r.read_u16()?,
+        })
+    })
+}
+
+fn read_type_annotation<R: Read>(r: &mut ClassReader<R>) -> Result<TypeAnnotation, ParseError> {
+    let target = match r.read_u8()? {
+        0x00 => TypeAnnotationTarget::ClassTypeParameter(r.read_u8()?),
+        0x01 => TypeAnnotationTarget::MethodTypeParameter(r.read_u8()?),
+        0x10 => TypeAnnotationTarget::SuperClass(r.read_u16()?),
+        0x11 => TypeAnnotationTarget::ClassTypeParameterBound {
+            param: r.read_u8()?,
+            bound: r.read_u8()?,
+        },
+        0x12 => TypeAnnotationTarget::MethodTypeParameterBound {
+            param: r.read_u8()?,
+            bound: r.read_u8()?,
+        },
+        0x13 => TypeAnnotationTarget::FieldType,
+        0x14 => TypeAnnotationTarget::MethodReturnType,
+        0x15 => TypeAnnotationTarget::RecieverType,
+        0x16 => TypeAnnotationTarget::FormalParameterType(r.read_u8()?),
+        0x17 => TypeAnnotationTarget::ThrowsType(r.read_u16()?),
+        0x40 => TypeAnnotationTarget::LocalVariableType(read_local_variable_locations(r)?),
+        0x41 => TypeAnnotationTarget::ResourceVariableType(read_local_variable_locations(r)?),
+        0x42 => TypeAnnotationTarget::CatchParameterType(r.read_u16()?),
+        0x43 => TypeAnnotationTarget::InstanceOfType(r.read_u16()?),
+        0x44 => TypeAnnotationTarget::NewType(r.read_u16()?),
+        0x45 => TypeAnnotationTarget::NewReferenceType(r.read_u16()?),
+        0x46 => TypeAnnotationTarget::MethodReferenceType(r.read_u16()?),
+        0x47 => TypeAnnotationTarget::CastType {
+            offset: r.read_u16()?,
+            type_var: r.read_u8()?,
+        },
+        0x48 => TypeAnnotationTarget::GenericConstructorTypeArgument {
+            offset: r.read_u16()?,
+            type_var: r.read_u8()?,
+        },
+        0x49 => TypeAnnotationTarget::GenericMethodTypeArgument {
+            offset: r.read_u16()?,
+            type_var: r.read_u8()?,
+        },
+        0x4a => TypeAnnotationTarget::GenericConstructorReferenceTypeArgument {
+            offset: r.read_u16()?,
+            type_var: r.read_u8()?,
+        },
+        0x4b => TypeAnnotationTarget::GenericMethodReferenceTypeArgument {
+            offset: r.read_u16()?,
+            type_var: r.read_u8()?,
+        },
+        _ => return Err(ParseError::MalformedAttribute("type annotation")),
+    };
+    let path_len = r.read_u8()?;
+    let path = r.table(path_len as usize, 2, |r| {
+        let kind = r.read_u8()?;
+        let arg = r.read_u8()?;
+        Ok(match kind {
+            0 => TypePathSegment::Array,
+            1 => TypePathSegment::NestedType,
+            2 => TypePathSegment::Wildcard,
+            3 => TypePathSegment::ParameterizedType(arg),
+            _ => return Err(ParseError::MalformedAttribute("type annotation")),
+        })
+    })?;
+    Ok(TypeAnnotation {
+        target,
+        path,
+        annotation: read_annotation(r, 0)?,
+    })
+}
+
+fn read_type_annotations<R: Read>(
+    r: &mut ClassReader<R>,
+) -> Result<Vec<TypeAnnotation>, ParseError> {
+    let count = r.read_u16()?;
+    r.table(count as usize, 6, read_type_annotation)
+}
+
+/// The names of the attributes that are parsed into something other than [`Attribute::Unresolved`]
+pub(crate) const KNOWN_ATTRIBUTES: [&str; 33] = [
+    "ConstantValue",
+    "Code",
+    "StackMapTable",
+    "Exceptions",
+    "InnerClasses",
+    "EnclosingMethod",
+    "Synthetic",
+    "Signature",
+    "SourceFile",
+    "SourceDebugExtension",
+    "LineNumberTable",
+    "LocalVariableTable",
+    "LocalVariableTypeTable",
+    "Deprecated",
+    "RuntimeVisibleAnnotations",
+    "RuntimeInvisibleAnnotations",
+    "RuntimeVisibleParameterAnnotations",
+    "RuntimeInvisibleParameterAnnotations",
+    "RuntimeVisibleTypeAnnotations",
+    "RuntimeInvisibleTypeAnnotations",
+    "AnnotationDefault",
+    "BootstrapMethods",
+    "MethodParameters",
+    "Module",
+    "ModulePackages",
+    "ModuleMainClass",
+    "NestHost",
+    "NestMembers",
+    "Record",
+    "PermittedSubclasses",
+    "Availability",
+    "LangItem",
+    "FillNativeMethod",
+];
+
+struct ClassDecoder<'a> {
+    consts: &'a ConstantPool,
+    opts: &'a ParseOptions,
+}
+
+impl<'a> ClassDecoder<'a> {
+    /// Reads the parts shared by fields and methods, recording their spans if `spans` is given
+    fn read_member<R: Read>(
+        &self,
+        r: &mut ClassReader<R>,
+        spans: Option<&mut Vec<MemberSpans>>,
+    ) -> Result<(u16, u16, u16, Vec<Attribute>), ParseError> {
+        let start = r.pos;
+        let acc = r.expect("access_flags").read_u16()?;
+        let name = r.expect("name_index").read_u16()?;
+        let descriptor = r.expect("descriptor_index").read_u16()?;
+        let mut attribute_spans = spans.as_ref().map(|_| Vec::new());
+        let attributes = self.read_spanned_attributes(r, attribute_spans.as_mut())?;
+        if let Some(spans) = spans {
+            spans.push(MemberSpans {
+                range: start..r.pos,
+                attributes: attribute_spans.unwrap_or_default(),
+            });
+        }
+        Ok((acc, name, descriptor, attributes))
+    }
+
+    fn read_field<R: Read>(
+        &self,
+        r: &mut ClassReader<R>,
+        spans: Option<&mut Vec<MemberSpans>>,
+    ) -> Result<FieldInfo, ParseError> {
+        let (acc, name, descriptor, attributes) = self.read_member(r, spans)?;
+        Ok(FieldInfo {
+            acc,
+            name,
+            descriptor,
+            attributes,
+        })
+    }
+
+    fn read_method<R: Read>(
+        &self,
+        r: &mut ClassReader<R>,
+        spans: Option<&mut Vec<MemberSpans>>,
+    ) -> Result<MethodInfo, ParseError> {
+        let (acc, name, descriptor, attributes) = self.read_member(r, spans)?;
+        Ok(MethodInfo {
+            acc,
+            name,
+            descriptor,
+            attributes,
+        })
+    }
+
+    fn read_spanned_attributes<R: Read>(
+        &self,
+        r: &mut ClassReader<R>,
+        spans: Option<&mut Vec<Range<usize>>>,
+    ) -> Result<Vec<Attribute>, ParseError> {
+        self.read_attribute_table(r, spans, |r| self.read_attribute(r))
+    }
+
+    fn read_attributes<R: Read>(
+        &self,
+        r: &mut ClassReader<R>,
+    ) -> Result<Vec<Attribute>, ParseError> {
+        self.read_spanned_attributes(r, None)
+    }
+
+    /// Reads an attribute table in the content of an attribute that is nested in `depth` attribute tables
+    fn read_nested_attributes(
+        &self,
+        r: &mut ClassReader<&[u8]>,
+        depth: usize,
+    ) -> Result<Vec<Attribute>, ParseError> {
+        if depth > self.opts.max_attribute_depth {
+            return Err(ParseError::LimitExceeded {
+                limit: "max_attribute_depth",
+                value: depth,
+            });
+        }
+        self.read_attribute_table(r, None, |r| {
+            let name = r.expect("attribute_name_index").read_u16()?;
+            let len = r.expect("attribute_length").read_u32()?;
+            let start = r.pos as u64;
+            // The content is already in memory, so nested attributes are read in place instead of copied
+            let content = r.expect("attribute info").read_slice(len as usize)?;
+            match self.known_attribute(name)? {
+                Some(known) => self.read_known_attribute(known, content, start, depth),
+                None => Ok(Attribute::Unresolved {
+                    name,
+                    content: content.to_vec(),
+                }),
+            }
+        })
+    }
+
+    fn read_attribute_table<R: Read>(
+        &self,
+        r: &mut ClassReader<R>,
+        mut spans: Option<&mut Vec<Range<usize>>>,
+        read_attribute: impl Fn(&mut ClassReader<R>) -> Result<Attribute, ParseError>,
+    ) -> Result<Vec<Attribute>, ParseError> {
+        let count = r
+            .expect("attributes_count")
+            .limited_count("max_attributes", self.opts.max_attributes)?;
+        r.table(count as usize, 6, |r| {
+            let start = r.pos;
+            let attr = read_attribute(r)?;
+            if let Some(spans) = spans.as_deref_mut() {
+                spans.push(start..r.pos);
+            }
+            Ok(attr)
+        })
+    }
+
+    fn read_attribute<R: Read>(&self, r: &mut ClassReader<R>) -> Result<Attribute, ParseError> {
+        let name = r.expect("attribute_name_index").read_u16()?;
+        let len = r.expect("attribute_length").read_u32()?;
+        let start = r.pos as u64;
+        let content = r.expect("attribute info").read_bytes(len as usize)?;
+        match self.known_attribute(name)? {
+            Some(known) => self.read_known_attribute(known, &content, start, 0),
+            None => Ok(Attribute::Unresolved { name, content }),
+        }
+    }
+
+    /// Finds the name of the attribute with the name at `name` in [`KNOWN_ATTRIBUTES`], or returns None if it is a custom attribute
+    fn known_attribute(&self, name: u16) -> Result<Option<&'static str>, ParseError> {
+        let attr_name = self
+            .consts
+            .utf8(name)
+            .ok_or(ParseError::InvalidAttributeName(name))?;
+        Ok(KNOWN_ATTRIBUTES
+            .iter()
+            .find(|known| known.as_bytes() == attr_name.as_bytes())
+            .copied())
+    }
+
+    /// Reads the content of an attribute that starts at byte `start` and is nested in `depth` attribute tables
+    fn read_known_attribute(
+        &self,
+        known: &'static str,
+        content: &[u8],
+        start: u64,
+        depth: usize,
+    ) -> Result<Attribute, ParseError> {
+        let mut body = ClassReader::with_len(content, self.opts, content.len());
+        body.expect(known);
+        // Only Code and Record attributes hold attribute tables. They are read here rather than in read_attribute_body, whose stack frame is large, to keep the frames of each level of nesting small
+        let attr = match known {
+            "Code" => self.read_code(&mut body, depth).map(Attribute::Code),
+            "Record" => self.read_record(&mut body, depth).map(Attribute::Record),
+            _ => self.read_attribute_body(known, &mut body),
+        }
+        .map_err(|e| match e {
+            // Positions in the body are relative to the start of the content
+            ParseError::UnexpectedEof { expected, at } => ParseError::UnexpectedEof {
+                expected,
+                at: start + at,
+            },
+            e => e,
+        })?;
+        if !body.inner.is_empty() {
+            return Err(ParseError::MalformedAttribute(known));
+        }
+        Ok(attr)
+    }
+
+    fn read_code(
+        &self,
+        r: &mut ClassReader<&[u8]>,
+        depth: usize,
+    ) -> Result<CodeAttribute, ParseError> {
+        let max_stack = r.read_u16()?;
+        let max_locals = r.read_u16()?;
+        let code_len = r.read_u32()? as usize;
+        if code_len > self.opts.max_code_length {
+            return Err(ParseError::LimitExceeded {
+                limit: "max_code_length",
+                value: code_len,
+            });
+        }
+        let code = r.read_bytes(code_len)?;
+        let handlers = r.read_u16()?;
+        let exceptions = r.table(handlers as usize, 8, |r| {
+            Ok(ExceptionInfo {
+                start_pc: r.read_u16()?,
+                end_pc: r.read_u16()?,
+                handler_pc: r.read_u16()?,
+                catch_type: r.read_u16()?,
+            })
+        })?;
+        Ok(CodeAttribute {
+            max_stack,
+            max_locals,
+            code,
+            exceptions,
+            attributes: self.read_nested_attributes(r, depth + 1)?,
+        })
+    }
+
+    fn read_record(
+        &self,
+        r: &mut ClassReader<&[u8]>,
+        depth: usize,
+    ) -> Result<Vec<RecordComponentInfo>, ParseError> {
+        let count = r.read_u16()?;
+        r.table(count as usize, 6, |r| {
+            Ok(RecordComponentInfo {
+                name: r.read_u16()?,
+                descriptor: r.read_u16()?,
+                attributes: self.read_nested_attributes(r, depth + 1)?,
+            })
+        })
+    }
+
+    fn read_module<R: Read>(&self, r: &mut ClassReader<R>) -> Result<ModuleInfo, ParseError> {
+        let name = r.read_u16()?;
+        let access = r.read_u16()?;
+        let version = r.read_u16()?;
+        let count = r.read_u16()?;
+        let requires = r.table(count as usize, 6, |r| {
+            Ok(RequireInfo {
+                requires: r.read_u16()?,
+                flags: r.read_u16()?,
+                version: r.read_u16()?,
+            })
+        })?;
+        let read_exports = |r: &mut ClassReader<R>| {
+            let count = r.read_u16()?;
+            r.table(count as usize, 6, |r| {
+                Ok(ExportInfo {
+                    exports: r.read_u16()?,
+                    flags: r.read_u16()?,
+                    to: r.read_u16s()?,
+                })
+            })
+        };
+        let exports = read_exports(r)?;
+        let opens = read_exports(r)?;
+        let uses = r.read_u16s()?;
+        let count = r.read_u16()?;
+        let provides = r.table(count as usize, 4, |r| {
+            Ok(ProvidesInfo {
+                provides: r.read_u16()?,
+                with: r.read_u16s()?,
+            })
+        })?;
+        Ok(ModuleInfo {
+            name,
+            access,
+            version,
+            requires,
+            exports,
+            opens,
+            uses,
+            provides,
+        })
+    }
+
+    /// Reads the content of an attribute that does not hold attribute tables
+    fn read_attribute_body(
+        &self,
+        name: &'static str,
+        r: &mut ClassReader<&[u8]>,
+    ) -> Result<Attribute, ParseError> {
+        Ok(match name {
+            "ConstantValue" => Attribute::ConstantValue(r.read_u16()?),
+            "StackMapTable" => {
+                let count = r.read_u16()?;
+                Attribute::StackMapTable(r.table(count as usize, 1, read_stack_map_frame)?)
+            }
+            "Exceptions" => Attribute::Exceptions(r.read_u16s()?),
+            "InnerClasses" => {
+                let count = r.read_u16()?;
+                Attribute::InnerClasses(r.table(count as usize, 8, |r| {
+                    Ok(InnerClassInfo {
+                        inner_class_info: r.read_u16()?,
+                        outer_class_info: r.read_u16()?,
+                        inner_name: r.read_u16()?,
+                        inner_flags: r.read_u16()?,
+                    })
+                })?)
+            }
+            "EnclosingMethod" => Attribute::EnclosingMethod {
+                class: r.read_u16()?,
+                method: r.read_u16()?,
+            },
+            "Synthetic" => Attribute::Synthetic,
+            "Signature" => Attribute::Signature(r.read_u16()?),
+            "SourceFile" => Attribute::SourceFile(r.read_u16()?),
+            "SourceDebugExtension" => {
+                let mut content = Vec::new();
+                r.inner.read_to_end(&mut content)?;
+                Attribute::SourceDebugExtension(
+                    JString::from_modified_utf8(content)
+                        .map_err(|_| ParseError::MalformedAttribute("SourceDebugExtension"))?,
+                )
+            }
+            "LineNumberTable" => {
+                let count = r.read_u16()?;
+                Attribute::LineNumberTable(r.table(count as usize, 4, |r| {
+                    Ok(LineNumberEntry {
+                        start_pc: r.read_u16()?,
+                        line_number: r.read_u16()?,
+                    })
+                })?)
+            }
+            "LocalVariableTable" => {
+                let count = r.read_u16()?;
+                Attribute::LocalVariableTable(r.table(count as usize, 10, |r| {
+                    Ok(LocalVariableInfo {
+                        start_pc: r.read_u16()?,
+                        length: r.read_u16()?,
+                        name: r.read_u16()?,
+                        descriptor: r.read_u16()?,
+                        index: r.read_u16()?,
+                    })
+                })?)
+            }
+            "LocalVariableTypeTable" => {
+                let count = r.read_u16()?;
+                Attribute::LocalVariableTypeTable(r.table(count as usize, 10, |r| {
+                    Ok(LocalVariableTypeInfo {
+                        start_pc: r.read_u16()?,
+                        length: r.read_u16()?,
+                        name: r.read_u16()?,
+                        signature: r.read_u16()?,
+                        index: r.read_u16()?,
+                    })
+                })?)
+            }
+            "Deprecated" => Attribute::Deprecated,
+            "RuntimeVisibleAnnotations" => {
+                Attribute::RuntimeVisibleAnnotations(read_annotations(r)?)
+            }
+            "RuntimeInvisibleAnnotations" => {
+                Attribute::RuntimeInvisibleAnnotations(read_annotations(r)?)
+            }
+            "RuntimeVisibleParameterAnnotations" | "RuntimeInvisibleParameterAnnotations" => {
+                let count = r.read_u8()?;
+                let params = r.table(count as usize, 2, read_annotations)?;
+                if name == "RuntimeVisibleParameterAnnotations" {
+                    Attribute::RuntimeVisibleParameterAnnotations(params)
+                } else {
+                    Attribute::RuntimeInvisibleParameterAnnotations(params)
+                }
+            }
+            "RuntimeVisibleTypeAnnotations" => {
+                Attribute::RuntimeVisibleTypeAnnotations(read_type_annotations(r)?)
+            }
+            "RuntimeInvisibleTypeAnnotations" => {
+                Attribute::RuntimeInvisibleTypeAnnotations(read_type_annotations(r)?)
+            }
+            "AnnotationDefault" => Attribute::AnnotationDefault(read_element_value(r, 0)?),
+            "BootstrapMethods" => {
+                let count = r.read_u16()?;
+                Attribute::BootstrapMethods(r.table(count as usize, 4, |r| {
+                    Ok(BootstrapMethod {
+                        href: r.read_u16()?,
+                        args: r.read_u16s()?,
+                    })
+                })?)
+            }
+            "MethodParameters" => {
+                let count = r.read_u8()?;
+                Attribute::MethodParameters(r.table(count as usize, 4, |r| {
+                    Ok(ParameterInfo {
+                        name: r.read_u16()?,
+                        access: r.read_u16()?,
+                    })
+                })?)
+            }
+            "Module" => Attribute::Module(self.read_module(r)?),
+            "ModulePackages" => Attribute::ModulePackage(r.read_u16s()?),
+            "ModuleMainClass" => Attribute::ModuleMainClass(r.read_u16()?),
+            "NestHost" => Attribute::NestHost(r.read_u16()?),
+            "NestMembers" => Attribute::NestMembers(r.read_u16s()?),
+            "PermittedSubclasses" => Attribute::PermittedSubclasses(r.read_u16s()?),
+            "Availability" => {
+                let kind = r.read_u8()?;
+                let val = r.read_u16()?;
+                let default = r.read_u8()? != 0;
+                Attribute::Availability(match kind {
+                    0 => Availability::From { ver: val, default },
+                    1 => Availability::Removed { ver: val, default },
+                    2 => Availability::Unstable {
+                        feature: val,
+                        default,
+                    },
+                    _ => return Err(ParseError::MalformedAttribute("Availability")),
+                })
+            }
+            "LangItem" => Attribute::LangItem(r.read_u16()?),
+            "FillNativeMethod" => Attribute::FillNativeMethod(r.read_u16()?),
+            "Code" | "Record" => unreachable!("{} is read by read_known_attribute", name),
+            _ => unreachable!("{} is not in KNOWN_ATTRIBUTES", name),
+        })
+    }
+}
+
+///
+/// Receives the parts of a class file as [`ClassFile::read_streaming`] parses them, so that fields and methods are never held in memory all at once.
+///
+/// Every method does nothing by default
+pub trait ClassVisitor {
+    ///
+    /// Called for each constant in index order once the whole pool has been read. The second slots of Longs and Doubles are skipped
+    fn visit_constant(&mut self, _index: u16, _constant: &Constant) {}
+
+    fn visit_field(&mut self, _field: &FieldInfo, _pool: &ConstantPool) {}
+
+    ///
+    /// Called for each method, before [`ClassVisitor::visit_code`] is called for its Code attribute
+    fn visit_method(&mut self, _method: &MethodInfo, _pool: &ConstantPool) {}
+
+    fn visit_code(&mut self, _method: &MethodInfo, _code: &CodeAttribute, _pool: &ConstantPool) {}
+
+    ///
+    /// Called for each attribute of the class itself, after every field and method
+    fn visit_attribute(&mut self, _attr: &Attribute, _pool: &ConstantPool) {}
+}
+
+fn read_constant_pool<R: Read>(
+    r: &mut ClassReader<R>,
+    mut spans: Option<&mut Vec<Range<usize>>>,
+) -> Result<(u16, u16, ConstantPool), ParseError> {
+    let magic = r.expect("magic").read_u32()?;
+    if magic != consts::MAGIC {
+        return Err(ParseError::BadMagic(magic));
+    }
+    let min = r.expect("minor_version").read_u16()?;
+    let maj = r.expect("major_version").read_u16()?;
+
+    let max = r.opts.max_constant_pool;
+    let count = r
+        .expect("constant_pool_count")
+        .limited_count("max_constant_pool", max)?;
+    // The smallest constants are 3 bytes long
+    let mut consts = reserve_checked(count as usize, r.remaining(), 3);
+    while consts.len() + 1 < count as usize {
+        let start = r.pos;
+        read_constant(r, consts.len() as u16 + 1, &mut consts)?;
+        if let Some(spans) = spans.as_deref_mut() {
+            spans.push(start..r.pos);
+            // The second slot of a Long or Double
+            spans.resize(consts.len(), r.pos..r.pos);
+        }
+    }
+    Ok((min, maj, ConstantPool::from_constants(consts)))
+}
+
+///
+/// Where in the input the parts of a class file were read from, as returned by [`ClassFile::read_with_spans`].
+///
+/// Each range includes the tag or header of the part it covers, and is relative to the first byte of the class file
+#[derive(Clone, Debug, Default)]
+pub struct ClassSpans {
+    /// The range of each slot of the constant pool, in the order of [`ConstantPool::as_slice`]. The second slot of a Long or Double is an empty range at the end of the constant
+    pub constants: Vec<Range<usize>>,
+    pub fields: Vec<MemberSpans>,
+    pub methods: Vec<MemberSpans>,
+    /// The range of each attribute of the class
+    pub attributes: Vec<Range<usize>>,
+}
+
+///
+/// Where in the input a field or method was read from
+#[derive(Clone, Debug, Default)]
+pub struct MemberSpans {
+    pub range: Range<usize>,
+    /// The range of each attribute of the member. Attributes nested inside these, such as those of a Code attribute, are not recorded
+    pub attributes: Vec<Range<usize>>,
+}
+
+///
+/// The part of a class file before its fields, as returned by [`ClassFile::read_header`]
+#[derive(Clone, Debug)]
+pub struct ClassHeader {
+    pub min: u16,
+    pub maj: u16,
+    pub consts: ConstantPool,
+    pub acc: u16,
+    pub this: u16,
+    pub supercl: u16,
+    pub interfaces: Vec<u16>,
+}
+
+impl ClassHeader {
+    ///
+    /// Resolves the internal name of the class
+    pub fn this_name(&self) -> Option<&JStr> {
+        self.consts.class_name(self.this)
+    }
+
+    ///
+    /// Resolves the internal name of the superclass, which is None for `java/lang/Object` and module-info classes
+    pub fn super_name(&self) -> Option<&JStr> {
+        CpIndex(self.supercl).class_name(&self.consts)
+    }
+
+    ///
+    /// Resolves the internal names of the direct superinterfaces, skipping any that do not resolve
+    pub fn interface_names(&self) -> impl Iterator<Item = &JStr> + '_ {
+        self.interfaces
+            .iter()
+            .filter_map(move |&index| self.consts.class_name(index))
+    }
+}
+
+fn read_header<R: Read>(
+    r: &mut ClassReader<R>,
+    spans: Option<&mut Vec<Range<usize>>>,
+) -> Result<ClassHeader, ParseError> {
+    let (min, maj, consts) = read_constant_pool(r, spans)?;
+    Ok(ClassHeader {
+        min,
+        maj,
+        consts,
+        acc: r.expect("access_flags").read_u16()?,
+        this: r.expect("this_class").read_u16()?,
+        supercl: r.expect("super_class").read_u16()?,
+        interfaces: r.expect("interfaces").read_u16s()?,
+    })
+}
+
+fn read_class<R: Read>(
+    r: &mut ClassReader<R>,
+    mut spans: Option<&mut ClassSpans>,
+) -> Result<ClassFile, ParseError> {
+    let ClassHeader {
+        min,
+        maj,
+        consts,
+        acc,
+        this,
+        supercl,
+        interfaces,
+    } = read_header(r, spans.as_deref_mut().map(|spans| &mut spans.constants))?;
+
+    let decoder = ClassDecoder {
+        consts: &consts,
+        opts: r.opts,
+    };
+    let count = r.expect("fields_count").read_u16()?;
+    let fields = r.table(count as usize, 8, |r| {
+        decoder.read_field(r, spans.as_deref_mut().map(|spans| &mut spans.fields))
+    })?;
+    let count = r.expect("methods_count").read_u16()?;
+    let methods = r.table(count as usize, 8, |r| {
+        decoder.read_method(r, spans.as_deref_mut().map(|spans| &mut spans.methods))
+    })?;
+    let attributes =
+        decoder.read_spanned_attributes(r, spans.map(|spans| &mut spans.attributes))?;
+
+    Ok(ClassFile {
+        min,
+        maj,
+        consts,
+        acc,
+        this,
+        supercl,
+        interfaces,
+        fields,
+        methods,
+        attributes,
+    })
+}
+
+impl ClassFile {
+    ///
+    /// Reads a class file with the default [`ParseOptions`]
+    pub fn read<R: Read>(r: &mut R) -> Result<ClassFile, ParseError> {
+        Self::read_with_options(r, &ParseOptions::default())
+    }
+
+    pub fn read_with_options<R: Read>(
+        r: &mut R,
+        opts: &ParseOptions,
+    ) -> Result<ClassFile, ParseError> {
+        read_class(&mut ClassReader::new(r, opts), None)
+    }
+
+    ///
+    /// Reads only the version, constant pool, access flags, `this_class`, `super_class`, and interfaces of a class file, with the default [`ParseOptions`].
+    ///
+    /// Reading stops before the fields, so this is much faster than [`ClassFile::read`] when only the name and supertypes of a class are needed, such as when scanning a classpath.
+    /// The rest of the input is left unread
+    pub fn read_header<R: Read>(r: &mut R) -> Result<ClassHeader, ParseError> {
+        read_header(&mut ClassReader::new(r, &ParseOptions::default()), None)
+    }
+
+    ///
+    /// Reads a class file with the default [`ParseOptions`], also returning the number of bytes it took up.
+    ///
+    /// Nothing past the end of the class file is read, so this can be used to read several classes from one stream, or to detect trailing data
+    pub fn read_with_len<R: Read>(r: &mut R) -> Result<(ClassFile, usize), ParseError> {
+        let opts = ParseOptions::default();
+        let mut r = ClassReader::new(r, &opts);
+        let class = read_class(&mut r, None)?;
+        Ok((class, r.pos))
+    }
+
+    ///
+    /// Reads a class file with the default [`ParseOptions`], also returning where in the input each constant, field, method, and attribute was read from.
+    ///
+    /// This allows a part of the file to be patched in place, without writing the whole class again
+    pub fn read_with_spans<R: Read>(r: &mut R) -> Result<(ClassFile, ClassSpans), ParseError> {
+        let mut spans = ClassSpans::default();
+        let class = read_class(
+            &mut ClassReader::new(r, &ParseOptions::default()),
+            Some(&mut spans),
+        )?;
+        Ok((class, spans))
+    }
+
+    ///
+    /// Reads a class file with the default [`ParseOptions`], passing its constants, fields, methods, and attributes to `visitor` as they are read instead of collecting them.
+    ///
+    /// The constant pool is still read in full first, since everything after it refers to it
+    pub fn read_streaming<R: Read, V: ClassVisitor>(
+        r: &mut R,
+        visitor: &mut V,
+    ) -> Result<(), ParseError> {
+        let opts = ParseOptions::default();
+        let mut r = ClassReader::new(r, &opts);
+        let (_, _, consts) = read_constant_pool(&mut r, None)?;
+        for (index, constant) in consts.entries() {
+            visitor.visit_constant(index, constant);
+        }
+
+        r.expect("access_flags").read_u16()?;
+        r.expect("this_class").read_u16()?;
+        r.expect("super_class").read_u16()?;
+        let count = r.expect("interfaces").read_u16()?;
+        r.read_bytes(count as usize * 2)?;
+
+        let decoder = ClassDecoder {
+            consts: &consts,
+            opts: &opts,
+        };
+        for _ in 0..r.expect("fields_count").read_u16()? {
+            visitor.visit_field(&decoder.read_field(&mut r, None)?, &consts);
+        }
+        for _ in 0..r.expect("methods_count").read_u16()? {
+            let method = decoder.read_method(&mut r, None)?;
+            visitor.visit_method(&method, &consts);
+            for attr in &method.attributes {
+                if let Attribute::Code(code) = attr {
+                    visitor.visit_code(&method, code, &consts);
+                }
+            }
+        }
+        for attr in decoder.read_attributes(&mut r)? {
+            visitor.visit_attribute(&attr, &consts);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{string::JStr, testutil};
+
+    fn read_with(bytes: &[u8], opts: &ParseOptions) -> Result<ClassFile, ParseError> {
+        ClassFile::read_with_options(&mut &bytes[..], opts)
+    }
+
+    #[test]
+    fn read_header_stops_before_fields() {
+        let mut input = testutil::FIXTURE;
+        let header = ClassFile::read_header(&mut input).unwrap();
+        assert_eq!(header.this_name().unwrap().as_bytes(), b"Fixture");
+        assert_eq!(header.super_name().unwrap().as_bytes(), b"java/lang/Object");
+        assert_eq!(header.interface_names().count(), 0);
+
+        let class = testutil::read(testutil::FIXTURE);
+        assert_eq!(header.this, class.this);
+        assert_eq!(header.consts.count(), class.consts.count());
+        // The rest of the input starts at fields_count
+        let fields_count = u16::from_be_bytes([input[0], input[1]]);
+        assert_eq!(usize::from(fields_count), class.fields.len());
+
+        let mut input = testutil::POINT;
+        let header = ClassFile::read_header(&mut input).unwrap();
+        assert_eq!(header.this_name().unwrap().as_bytes(), b"Point");
+        assert_eq!(header.super_name().unwrap().as_bytes(), b"java/lang/Record");
+    }
+
+    fn limit_exceeded(result: Result<ClassFile, ParseError>) -> &'static str {
+        match result {
+            Err(ParseError::LimitExceeded { limit, .. }) => limit,
+            other => panic!("expected LimitExceeded, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    ///
+    /// The fixture class, with a RuntimeVisibleAnnotations attribute whose single element is an int nested in `depth` arrays
+    fn nested_annotation_class(depth: usize) -> Vec<u8> {
+        let mut class = testutil::read(testutil::FIXTURE);
+        let name = class
+            .consts
+            .intern_utf8(JStr::from_str("RuntimeVisibleAnnotations").unwrap())
+            .unwrap();
+        let utf8 = class
+            .consts
+            .intern_utf8(JStr::from_str("value").unwrap())
+            .unwrap();
+        let mut content = vec![0, 1];
+        content.extend_from_slice(&utf8.to_be_bytes()); // type_index
+        content.extend_from_slice(&[0, 1]);
+        content.extend_from_slice(&utf8.to_be_bytes()); // element_name_index
+        for _ in 0..depth {
+            content.extend_from_slice(&[b'[', 0, 1]);
+        }
+        content.push(b'I');
+        content.extend_from_slice(&utf8.to_be_bytes());
+        class
+            .attributes
+            .push(Attribute::Unresolved { name, content });
+        class.write_to_vec().unwrap()
+    }
+
+    #[test]
+    fn forged_constant_pool_count_fails_at_end_of_input() {
+        let mut bytes = vec![0xca, 0xfe, 0xba, 0xbe, 0, 0, 0, 60, 0xff, 0xff];
+        match read_with(&bytes, &ParseOptions::default()) {
+            Err(ParseError::UnexpectedEof { expected, at }) => {
+                assert_eq!((expected, at), ("constant", 10))
+            }
+            other => panic!("expected UnexpectedEof, got {:?}", other.map(|_| ())),
+        }
+        // A Utf8 constant claiming to be 65535 bytes long, with only 2 of them present
+        bytes.extend_from_slice(&[1, 0xff, 0xff, b'a', b'b']);
+        assert_eq!(
+            eof(read_with(&bytes, &ParseOptions::default())),
+            ("constant", 13)
+        );
+    }
+
+    fn eof<T>(result: Result<T, ParseError>) -> (&'static str, u64) {
+        match result {
+            Err(ParseError::UnexpectedEof { expected, at }) => (expected, at),
+            Err(e) => panic!("expected UnexpectedEof, got {:?}", e),
+            Ok(_) => panic!("expected UnexpectedEof, got a class"),
+        }
+    }
+
+    #[test]
+    fn truncated_class_names_what_was_being_read() {
+        let (class, spans) = ClassFile::read_with_spans(&mut &testutil::FIXTURE[..]).unwrap();
+        assert!(class.interfaces.is_empty());
+        let pool_end = spans.constants.last().unwrap().end;
+        let method = &spans.methods[0];
+        let code = method.attributes[0].clone();
+        assert!(testutil::method(&class, "<init>").code().is_some());
+
+        for (len, expected, at) in [
+            (0, "magic", 0),
+            (6, "major_version", 6),
+            (9, "constant_pool_count", 8),
+            // The tag was read, but not the rest of the constant
+            (
+                spans.constants[3].start + 1,
+                "constant",
+                spans.constants[3].start + 1,
+            ),
+            (pool_end + 1, "access_flags", pool_end),
+            (pool_end + 3, "this_class", pool_end + 2),
+            (pool_end + 7, "interfaces", pool_end + 6),
+            (pool_end + 9, "fields_count", pool_end + 8),
+            (method.range.start + 3, "name_index", method.range.start + 2),
+            (code.start + 4, "attribute_length", code.start + 2),
+            (code.end - 1, "attribute info", code.start + 6),
+            (
+                spans.attributes[0].start,
+                "attribute_name_index",
+                spans.attributes[0].start,
+            ),
+        ] {
+            let truncated = &testutil::FIXTURE[..len];
+            assert_eq!(
+                eof(ClassFile::read(&mut &truncated[..])),
+                (expected, at as u64),
+                "truncated to {} bytes",
+                len
+            );
+            assert_eq!(
+                eof(ClassFile::read_streaming(
+                    &mut &truncated[..],
+                    &mut NoVisitor
+                )),
+                (expected, at as u64),
+                "streaming, truncated to {} bytes",
+                len
+            );
+        }
+    }
+
+    struct NoVisitor;
+
+    impl ClassVisitor for NoVisitor {}
+
+    #[test]
+    fn short_attribute_names_the_attribute() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        let name = class
+            .consts
+            .intern_utf8(JStr::from_str("SourceFile").unwrap())
+            .unwrap();
+        class
+            .attributes
+            .retain(|attr| !matches!(attr, Attribute::SourceFile(_)));
+        // A SourceFile attribute needs 2 bytes of content
+        class.attributes.push(Attribute::Unresolved {
+            name,
+            content: vec![0],
+        });
+        let bytes = class.write_to_vec().unwrap();
+        assert_eq!(
+            eof(read_with(&bytes, &ParseOptions::default())),
+            ("SourceFile", bytes.len() as u64 - 1)
+        );
+
+        // Inside a Code attribute, the position is still in the whole input
+        let (class, spans) = ClassFile::read_with_spans(&mut &testutil::FIXTURE[..]).unwrap();
+        let code_len = class.methods[0].code().unwrap().code.len() as u32;
+        let content = spans.methods[0].attributes[0].start + 6;
+        let mut bytes = testutil::FIXTURE.to_vec();
+        // max_stack, max_locals, and code_length, then all but the last byte of the bytecode
+        bytes[content - 4..content].copy_from_slice(&(8 + code_len - 1).to_be_bytes());
+        assert_eq!(
+            eof(read_with(&bytes, &ParseOptions::default())),
+            ("Code", content as u64 + 8)
+        );
+    }
+
+    #[test]
+    fn limits_are_enforced() {
+        let bytes = testutil::FIXTURE;
+        let opts = ParseOptions {
+            max_constant_pool: 16,
+            ..ParseOptions::default()
+        };
+        assert_eq!(limit_exceeded(read_with(bytes, &opts)), "max_constant_pool");
+        let opts = ParseOptions {
+            max_bytes: 100,
+            ..ParseOptions::default()
+        };
+        assert_eq!(limit_exceeded(read_with(bytes, &opts)), "max_bytes");
+        let opts = ParseOptions {
+            max_code_length: 4,
+            ..ParseOptions::default()
+        };
+        assert_eq!(limit_exceeded(read_with(bytes, &opts)), "max_code_length");
+        let opts = ParseOptions {
+            max_attributes: 0,
+            ..ParseOptions::default()
+        };
+        assert_eq!(limit_exceeded(read_with(bytes, &opts)), "max_attributes");
+        let opts = ParseOptions {
+            max_bytes: bytes.len(),
+            ..ParseOptions::default()
+        };
+        assert!(read_with(bytes, &opts).is_ok());
+    }
+
+    #[test]
+    fn deeply_nested_annotation_is_rejected() {
+        let opts = ParseOptions {
+            max_nesting_depth: 8,
+            ..ParseOptions::default()
+        };
+        let class = read_with(&nested_annotation_class(8), &opts).unwrap();
+        assert!(matches!(
+            class.attributes.last(),
+            Some(Attribute::RuntimeVisibleAnnotations(_))
+        ));
+        let bytes = nested_annotation_class(9);
+        assert_eq!(
+            limit_exceeded(read_with(&bytes, &opts)),
+            "max_nesting_depth"
+        );
+
+        // Deep enough to overflow the stack if it were read
+        let bytes = nested_annotation_class(1_000_000);
+        assert_eq!(
+            limit_exceeded(read_with(&bytes, &ParseOptions::default())),
+            "max_nesting_depth"
+        );
+    }
+
+    ///
+    /// The fixture class, with a Code attribute holding a Code attribute and so on, `depth` Code attributes deep
+    fn nested_code_class(depth: usize) -> Vec<u8> {
+        let mut class = testutil::read(testutil::FIXTURE);
+        let name = class
+            .consts
+            .intern_utf8(JStr::from_str("Code").unwrap())
+            .unwrap();
+        // max_stack, max_locals, code_length and exception_table_length of an empty Code attribute
+        let empty = [0u8; 10];
+        let mut content = Vec::new();
+        for level in 1..depth {
+            content.extend_from_slice(&empty);
+            content.extend_from_slice(&[0, 1]);
+            content.extend_from_slice(&name.to_be_bytes());
+            let len = (depth - level) * 18 - 6;
+            content.extend_from_slice(&(len as u32).to_be_bytes());
+        }
+        content.extend_from_slice(&empty);
+        content.extend_from_slice(&[0, 0]);
+        class
+            .attributes
+            .push(Attribute::Unresolved { name, content });
+        class.write_to_vec().unwrap()
+    }
+
+    #[test]
+    fn deeply_nested_code_is_rejected() {
+        let opts = ParseOptions {
+            max_attribute_depth: 8,
+            ..ParseOptions::default()
+        };
+        let class = read_with(&nested_code_class(8), &opts).unwrap();
+        let mut depth = 0;
+        let mut attrs = &class.attributes[class.attributes.len() - 1..];
+        while let [Attribute::Code(code)] = attrs {
+            depth += 1;
+            attrs = &code.attributes;
+        }
+        assert_eq!((depth, attrs.len()), (8, 0));
+        let bytes = nested_code_class(9);
+        assert_eq!(
+            limit_exceeded(read_with(&bytes, &opts)),
+            "max_attribute_depth"
+        );
+
+        // Deep enough to overflow the stack if it were read
+        let bytes = nested_code_class(20_000);
+        assert_eq!(
+            limit_exceeded(read_with(&bytes, &ParseOptions::default())),
+            "max_attribute_depth"
+        );
+    }
+
+    #[derive(Default)]
+    struct Counter {
+        constants: usize,
+        fields: usize,
+        methods: Vec<String>,
+        code: usize,
+        attributes: usize,
+    }
+
+    impl ClassVisitor for Counter {
+        fn visit_constant(&mut self, _index: u16, _constant: &Constant) {
+            self.constants += 1;
+        }
+
+        fn visit_field(&mut self, _field: &FieldInfo, _pool: &ConstantPool) {
+            self.fields += 1;
+        }
+
+        fn visit_method(&mut self, method: &MethodInfo, pool: &ConstantPool) {
+            self.methods
+                .push(pool.utf8(method.name).unwrap().to_string());
+        }
+
+        fn visit_code(
+            &mut self,
+            _method: &MethodInfo,
+            _code: &CodeAttribute,
+            _pool: &ConstantPool,
+        ) {
+            self.code += 1;
+        }
+
+        fn visit_attribute(&mut self, _attr: &Attribute, _pool: &ConstantPool) {
+            self.attributes += 1;
+        }
+    }
+
+    #[test]
+    fn streaming_visits_what_the_eager_parser_reads() {
+        for bytes in testutil::ALL {
+            let class = testutil::read(bytes);
+            let mut counter = Counter::default();
+            ClassFile::read_streaming(&mut &bytes[..], &mut counter).unwrap();
+            assert_eq!(counter.constants, class.consts.real_entries());
+            assert_eq!(counter.fields, class.fields.len());
+            let methods: Vec<String> = class
+                .methods
+                .iter()
+                .map(|method| testutil::utf8(&class, method.name))
+                .collect();
+            assert_eq!(counter.methods, methods);
+            assert_eq!(
+                counter.code,
+                class.methods.iter().filter(|m| m.code().is_some()).count()
+            );
+            assert_eq!(counter.attributes, class.attributes.len());
+        }
+    }
+
+    #[test]
+    fn spans_slice_the_source() {
+        let bytes = testutil::FIXTURE;
+        let (class, spans) = ClassFile::read_with_spans(&mut &bytes[..]).unwrap();
+        assert_eq!(spans.constants.len(), class.consts.as_slice().len());
+        assert_eq!(spans.fields.len(), class.fields.len());
+        assert_eq!(spans.methods.len(), class.methods.len());
+
+        let (hello, _) = class
+            .utf8_constants()
+            .find(|(_, st)| st.as_bytes() == b"hello")
+            .unwrap();
+        let range = spans.constants[hello as usize - 1].clone();
+        assert_eq!(&bytes[range.clone()], b"\x01\x00\x05hello");
+
+        // The second slot of a Long is an empty range where the Long ends
+        let (big, _) = class
+            .consts
+            .entries()
+            .find(|(_, constant)| matches!(constant, Constant::Long(_)))
+            .unwrap();
+        let long = spans.constants[big as usize - 1].clone();
+        assert_eq!(long.len(), 9);
+        assert_eq!(spans.constants[big as usize], long.end..long.end);
+
+        let method = &spans.methods[0];
+        assert_eq!(
+            bytes[method.range.start + 2..method.range.start + 4],
+            class.methods[0].name.to_be_bytes()
+        );
+        assert_eq!(spans.attributes.last().unwrap().end, bytes.len());
+
+        // Patch the constant in place
+        let mut patched = bytes.to_vec();
+        patched[range.start + 3..range.end].copy_from_slice(b"HELLO");
+        let class = testutil::read(&patched);
+        assert_eq!(testutil::utf8(&class, hello), "HELLO");
+    }
+
+    #[test]
+    fn concatenated_classes_read_one_at_a_time() {
+        let mut bytes = testutil::FIXTURE.to_vec();
+        bytes.extend_from_slice(testutil::POINT);
+        bytes.extend_from_slice(b"trailing");
+
+        let mut input = &bytes[..];
+        let (first, len) = ClassFile::read_with_len(&mut input).unwrap();
+        assert_eq!(len, testutil::FIXTURE.len());
+        assert_eq!(first.write_to_vec().unwrap(), testutil::FIXTURE);
+        let (second, len) = ClassFile::read_with_len(&mut input).unwrap();
+        assert_eq!(len, testutil::POINT.len());
+        assert_eq!(second.write_to_vec().unwrap(), testutil::POINT);
+        assert_eq!(input, b"trailing");
+    }
+
+    #[test]
+    fn float_constants_keep_their_exact_bits() {
+        // A signaling NaN with a payload, a quiet NaN with the sign bit set, and negative zero
+        let float_bits = [0x7f80_0001u32, 0xffc0_1234, 0x8000_0000];
+        let double_bits = [
+            0x7ff0_0000_0000_0001u64,
+            0xfff8_dead_beef_0000,
+            0x8000_0000_0000_0000,
+        ];
+        let mut class = testutil::read(testutil::FIXTURE);
+        let floats: Vec<u16> = float_bits
+            .iter()
+            .map(|&bits| {
+                class
+                    .consts
+                    .push(Constant::Float(f32::from_bits(bits)))
+                    .unwrap()
+            })
+            .collect();
+        let doubles: Vec<u16> = double_bits
+            .iter()
+            .map(|&bits| {
+                class
+                    .consts
+                    .push(Constant::Double(f64::from_bits(bits)))
+                    .unwrap()
+            })
+            .collect();
+        let bytes = class.write_to_vec().unwrap();
+        for bits in float_bits {
+            assert!(bytes
+                .windows(5)
+                .any(|w| w == [&[4][..], &bits.to_be_bytes()].concat()));
+        }
+        for bits in double_bits {
+            assert!(bytes
+                .windows(9)
+                .any(|w| w == [&[6][..], &bits.to_be_bytes()].concat()));
+        }
+
+        let read = testutil::read(&bytes);
+        for (&index, bits) in floats.iter().zip(float_bits) {
+            match read.consts.get(index) {
+                Some(Constant::Float(f)) => assert_eq!(f.to_bits(), bits),
+                other => panic!("expected a Float, got {:?}", other),
+            }
+        }
+        for (&index, bits) in doubles.iter().zip(double_bits) {
+            match read.consts.get(index) {
+                Some(Constant::Double(d)) => assert_eq!(d.to_bits(), bits),
+                other => panic!("expected a Double, got {:?}", other),
+            }
+        }
+        assert_eq!(read.write_to_vec().unwrap(), bytes);
+    }
+}
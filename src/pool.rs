@@ -0,0 +1,532 @@
+use std::{
+    convert::TryFrom,
+    fmt::{Display, Formatter},
+    num::NonZeroU16,
+};
+
+use crate::{
+    class::{Constant, ResolvedMethodHandle},
+    string::JStr,
+    write::WriteError,
+};
+
+/// The most slots a constant pool can have, since `constant_pool_count` is one more than that
+pub(crate) const MAX_SLOTS: usize = u16::MAX as usize - 1;
+
+///
+/// The constant pool of a class file.
+///
+/// Constants are addressed by their 1-based index in the class file. A Long or Double occupies two indices, the second of which holds a [`Constant::LongOrDoubleHigh`]
+#[derive(Clone, Debug, Default)]
+pub struct ConstantPool(Vec<Constant>);
+
+impl ConstantPool {
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
+    ///
+    /// Wraps a list of constants, where `consts[0]` is the constant at index 1
+    pub fn from_constants(consts: Vec<Constant>) -> Self {
+        Self(consts)
+    }
+
+    pub fn into_constants(self) -> Vec<Constant> {
+        self.0
+    }
+
+    pub fn as_slice(&self) -> &[Constant] {
+        &self.0
+    }
+
+    ///
+    /// The `constant_pool_count` of the class file: one more than the highest index, counting both slots of each Long and Double.
+    ///
+    /// Saturates at 65535 for a pool too large to be written
+    pub fn count(&self) -> u16 {
+        u16::try_from(self.0.len() + 1).unwrap_or(u16::MAX)
+    }
+
+    ///
+    /// The number of constants in the pool, counting each Long and Double once
+    pub fn real_entries(&self) -> usize {
+        self.entries().count()
+    }
+
+    ///
+    /// Iterates over every slot of the pool in index order, including the second slots of Longs and Doubles
+    pub fn iter(&self) -> std::slice::Iter<'_, Constant> {
+        self.0.iter()
+    }
+
+    ///
+    /// Iterates over the constants of the pool along with their indices, skipping the second slots of Longs and Doubles.
+    ///
+    /// Constants past index 65535, which cannot be referred to, are not visited
+    pub fn entries(&self) -> impl Iterator<Item = (u16, &Constant)> + '_ {
+        (1..=u16::MAX)
+            .zip(&self.0)
+            .filter(|(_, constant)| !matches!(constant, Constant::LongOrDoubleHigh))
+    }
+
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Constant> {
+        self.0.iter_mut()
+    }
+
+    pub fn get(&self, index: u16) -> Option<&Constant> {
+        self.0.get((index as usize).checked_sub(1)?)
+    }
+
+    pub fn get_mut(&mut self, index: u16) -> Option<&mut Constant> {
+        self.0.get_mut((index as usize).checked_sub(1)?)
+    }
+
+    ///
+    /// Appends a constant to the pool, returning its index. A Long or Double is followed by its second slot.
+    ///
+    /// Fails with [`WriteError::ConstantPoolOverflow`], leaving the pool unchanged, if the pool would have more than the 65534 slots that can be indexed
+    pub fn push(&mut self, constant: Constant) -> Result<u16, WriteError> {
+        let wide = matches!(constant, Constant::Long(_) | Constant::Double(_));
+        let slots = self.0.len() + if wide { 2 } else { 1 };
+        if slots > MAX_SLOTS {
+            return Err(WriteError::ConstantPoolOverflow { slots });
+        }
+        self.0.push(constant);
+        let index = self.0.len() as u16;
+        if wide {
+            self.0.push(Constant::LongOrDoubleHigh);
+        }
+        Ok(index)
+    }
+
+    ///
+    /// Replaces the constant at `index` with `new`, returning the old constant.
+    ///
+    /// Only a constant taking the same number of slots can be replaced, since a Long or Double replacing a single-slot constant (or the other way around) would shift every later index
+    pub fn replace(&mut self, index: u16, new: Constant) -> Result<Constant, ReplaceError> {
+        let old = self
+            .get_mut(index)
+            .ok_or(ReplaceError::InvalidIndex(index))?;
+        if slots(old) != slots(&new) {
+            return Err(ReplaceError::SlotsChanged(index));
+        }
+        Ok(std::mem::replace(old, new))
+    }
+
+    ///
+    /// Returns the index of a Utf8 constant holding `text`, appending one if the pool has none.
+    ///
+    /// Fails as [`ConstantPool::push`] does if there is no room for a new constant
+    pub fn intern_utf8(&mut self, text: &JStr) -> Result<u16, WriteError> {
+        let existing = self
+            .entries()
+            .find(|(_, constant)| matches!(constant, Constant::Utf8(st) if **st == *text))
+            .map(|(index, _)| index);
+        match existing {
+            Some(index) => Ok(index),
+            None => self.push(Constant::Utf8(text.to_owned())),
+        }
+    }
+
+    ///
+    /// Resolves a Utf8 constant
+    pub fn utf8(&self, index: u16) -> Option<&JStr> {
+        match self.get(index)? {
+            Constant::Utf8(st) => Some(st),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Resolves a Class constant to the name it refers to
+    pub fn class_name(&self, index: u16) -> Option<&JStr> {
+        match self.get(index)? {
+            Constant::Class(name) => self.utf8(*name),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Resolves a Package constant to the internal name of the package, such as `java/lang`
+    pub fn package_name(&self, index: u16) -> Option<&JStr> {
+        match self.get(index)? {
+            Constant::Package(name) => self.utf8(*name),
+            _ => None,
+        }
+    }
+
+    ///
+    /// Resolves a NameAndType constant to its name and descriptor
+    pub fn name_and_type(&self, index: u16) -> Option<(&JStr, &JStr)> {
+        match self.get(index)? {
+            Constant::NameAndType { name, descriptor } => {
+                Some((self.utf8(*name)?, self.utf8(*descriptor)?))
+            }
+            _ => None,
+        }
+    }
+
+    ///
+    /// Resolves a FieldRef, MethodRef, or InterfaceMethodRef constant to its class name, member name, and descriptor
+    pub fn member_ref(&self, index: u16) -> Option<(&JStr, &JStr, &JStr)> {
+        match self.get(index)? {
+            Constant::FieldRef {
+                class,
+                name_and_type,
+            }
+            | Constant::MethodRef {
+                class,
+                name_and_type,
+            }
+            | Constant::InterfaceMethodRef {
+                class,
+                name_and_type,
+            } => {
+                let (name, descriptor) = self.name_and_type(*name_and_type)?;
+                Some((self.class_name(*class)?, name, descriptor))
+            }
+            _ => None,
+        }
+    }
+
+    ///
+    /// Resolves a MethodHandle constant to its kind and the field or method it refers to, as with [`Constant::resolve_method_handle`]
+    pub fn method_handle(&self, index: u16) -> Option<ResolvedMethodHandle<'_>> {
+        self.get(index)?.resolve_method_handle(self)
+    }
+}
+
+/// The number of slots `constant` takes in the pool, where the second slot of a Long or Double takes none of its own
+fn slots(constant: &Constant) -> u8 {
+    match constant {
+        Constant::Long(_) | Constant::Double(_) => 2,
+        Constant::LongOrDoubleHigh => 0,
+        _ => 1,
+    }
+}
+
+///
+/// The error returned by [`ConstantPool::replace`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ReplaceError {
+    /// There is no constant at the index
+    InvalidIndex(u16),
+    /// The replacement takes a different number of slots than the constant at the index
+    SlotsChanged(u16),
+}
+
+impl Display for ReplaceError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplaceError::InvalidIndex(index) => write!(f, "no constant at index #{}", index),
+            ReplaceError::SlotsChanged(index) => write!(
+                f,
+                "replacing constant #{} would change the number of slots it takes",
+                index
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ReplaceError {}
+
+///
+/// A constant pool index where 0 means there is none, as in the `catch_type` of an exception handler, the `outer_class_info` and `inner_name` of an inner class,
+/// the method of an EnclosingMethod attribute, the name of a method parameter, or the version of a module
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct CpIndex(pub u16);
+
+impl CpIndex {
+    pub const NONE: CpIndex = CpIndex(0);
+
+    ///
+    /// Returns the index, or None if there is none
+    pub fn get(self) -> Option<NonZeroU16> {
+        NonZeroU16::new(self.0)
+    }
+
+    ///
+    /// Resolves the index with `f`, which is only called if there is an index.
+    ///
+    /// Returns `Some(None)` if there is no index, and None if there is one but `f` cannot resolve it
+    pub fn resolve<T>(self, f: impl FnOnce(u16) -> Option<T>) -> Option<Option<T>> {
+        match self.get() {
+            None => Some(None),
+            Some(idx) => f(idx.get()).map(Some),
+        }
+    }
+
+    ///
+    /// Resolves a Utf8 constant, returning None if there is no index or it does not resolve
+    pub fn utf8(self, pool: &ConstantPool) -> Option<&JStr> {
+        pool.utf8(self.get()?.get())
+    }
+
+    ///
+    /// Resolves a Class constant to the name it refers to, returning None if there is no index or it does not resolve
+    pub fn class_name(self, pool: &ConstantPool) -> Option<&JStr> {
+        pool.class_name(self.get()?.get())
+    }
+}
+
+impl From<u16> for CpIndex {
+    fn from(idx: u16) -> Self {
+        CpIndex(idx)
+    }
+}
+
+impl From<CpIndex> for u16 {
+    fn from(idx: CpIndex) -> Self {
+        idx.0
+    }
+}
+
+impl From<Vec<Constant>> for ConstantPool {
+    fn from(consts: Vec<Constant>) -> Self {
+        Self(consts)
+    }
+}
+
+impl<'a> IntoIterator for &'a ConstantPool {
+    type Item = &'a Constant;
+    type IntoIter = std::slice::Iter<'a, Constant>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut ConstantPool {
+    type Item = &'a mut Constant;
+    type IntoIter = std::slice::IterMut<'a, Constant>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{class::Attribute, testutil};
+
+    #[test]
+    fn entries_skip_second_slots() {
+        let mut pool = ConstantPool::new();
+        assert_eq!(pool.push(Constant::Int(1)).unwrap(), 1);
+        assert_eq!(pool.push(Constant::Double(0.5)).unwrap(), 2);
+        assert_eq!(
+            pool.push(Constant::Utf8(testutil::jstring("a"))).unwrap(),
+            4
+        );
+        assert_eq!(pool.push(Constant::Long(7)).unwrap(), 5);
+        assert_eq!(pool.push(Constant::Float(2.0)).unwrap(), 7);
+        assert_eq!(pool.count(), 8);
+        assert_eq!(pool.iter().count(), 7);
+
+        let indices: Vec<u16> = pool.entries().map(|(index, _)| index).collect();
+        assert_eq!(indices, [1, 2, 4, 5, 7]);
+        assert_eq!(pool.real_entries(), 5);
+        assert!(matches!(pool.entries().nth(1), Some((2, Constant::Double(v))) if *v == 0.5));
+        assert!(matches!(pool.get(3), Some(Constant::LongOrDoubleHigh)));
+        for (index, constant) in pool.entries() {
+            assert!(std::ptr::eq(pool.get(index).unwrap(), constant));
+        }
+    }
+
+    #[test]
+    fn cp_index_zero_is_none() {
+        let mut pool = ConstantPool::new();
+        let name = pool
+            .push(Constant::Utf8(testutil::jstring("java/lang/Object")))
+            .unwrap();
+        let class = pool.push(Constant::Class(name)).unwrap();
+
+        assert_eq!(CpIndex::NONE.get(), None);
+        assert_eq!(CpIndex::default(), CpIndex::NONE);
+        assert_eq!(CpIndex(0).utf8(&pool), None);
+        assert_eq!(CpIndex(0).class_name(&pool), None);
+        assert_eq!(
+            CpIndex(0).resolve(|_| -> Option<()> { panic!("resolved index 0") }),
+            Some(None)
+        );
+
+        assert_eq!(CpIndex(name).get().map(NonZeroU16::get), Some(name));
+        assert_eq!(
+            CpIndex(name).utf8(&pool).unwrap(),
+            &*testutil::jstring("java/lang/Object")
+        );
+        assert_eq!(CpIndex(class).class_name(&pool), CpIndex(name).utf8(&pool));
+        assert_eq!(CpIndex(class).resolve(|idx| pool.utf8(idx)), None);
+        assert_eq!(u16::from(CpIndex::from(class)), class);
+    }
+
+    #[test]
+    fn index_zero_resolves_to_none_where_it_is_used() {
+        // The anonymous class has no outer class or simple name, and is enclosed by a method
+        let anonymous = testutil::read(testutil::FIXTURE_ANONYMOUS);
+        let inner = anonymous
+            .inner_classes()
+            .into_iter()
+            .find(|inner| inner.inner_class.unwrap() == &*testutil::jstring("Fixture$1"))
+            .unwrap();
+        assert_eq!((inner.outer_class, inner.inner_name), (None, None));
+
+        let mut enclosing = anonymous
+            .attributes
+            .iter()
+            .find(|attr| matches!(attr, Attribute::EnclosingMethod { .. }))
+            .unwrap()
+            .clone();
+        assert!(enclosing
+            .enclosing_method(&anonymous.consts)
+            .unwrap()
+            .1
+            .is_some());
+        if let Attribute::EnclosingMethod { method, .. } = &mut enclosing {
+            *method = 0;
+        }
+        let (class, method) = enclosing.enclosing_method(&anonymous.consts).unwrap();
+        assert_eq!(class, &*testutil::jstring("Fixture"));
+        assert_eq!(method, None);
+
+        // The finally block catches everything
+        let fixture = testutil::read(testutil::FIXTURE);
+        let code = testutil::method(&fixture, "handlers").code().unwrap();
+        assert!(code
+            .exceptions
+            .iter()
+            .any(|handler| handler.catch_type == 0));
+        for (handler, ty) in code
+            .exceptions
+            .iter()
+            .zip(code.handler_types(&fixture.consts))
+        {
+            assert_eq!(ty.is_none(), handler.catch_type == 0);
+        }
+
+        // javac leaves out the version of the module and of java.base when compiling with --release
+        let module = testutil::read(testutil::MODULE_INFO);
+        assert_eq!(CpIndex(module.supercl).class_name(&module.consts), None);
+        let info = module
+            .attributes
+            .iter()
+            .find_map(|attr| match attr {
+                Attribute::Module(info) => Some(info),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(info.version, 0);
+        assert_eq!(CpIndex(info.version).utf8(&module.consts), None);
+        assert_eq!(CpIndex(info.requires[0].version).utf8(&module.consts), None);
+    }
+
+    #[test]
+    fn count_includes_the_second_slot_of_a_double() {
+        let mut pool = ConstantPool::new();
+        assert_eq!((pool.count(), pool.real_entries()), (1, 0));
+        pool.push(Constant::Utf8(testutil::jstring("HALF")))
+            .unwrap();
+        let double = pool.push(Constant::Double(0.5)).unwrap();
+        pool.push(Constant::Int(3)).unwrap();
+        // constant_pool_count is one more than the highest index, which is one more than the number of constants
+        assert_eq!(pool.real_entries(), 3);
+        assert_eq!(pool.count() as usize - 1, pool.real_entries() + 1);
+        assert_eq!(pool.count(), 5);
+        assert!(pool.get(double + 1).is_some());
+        assert!(pool.get(pool.count()).is_none());
+
+        let class = testutil::read(testutil::FIXTURE);
+        let wide = class
+            .consts
+            .entries()
+            .filter(|(_, constant)| matches!(constant, Constant::Long(_) | Constant::Double(_)))
+            .count();
+        assert_eq!(
+            class.consts.count() as usize - 1,
+            class.consts.real_entries() + wide
+        );
+        // The count as written in the class file, after the magic and version
+        assert_eq!(
+            &testutil::FIXTURE[8..10],
+            class.consts.count().to_be_bytes()
+        );
+    }
+
+    #[test]
+    fn replace_keeps_the_number_of_slots() {
+        let mut pool = ConstantPool::new();
+        let text = pool.push(Constant::Utf8(testutil::jstring("old"))).unwrap();
+        let int = pool.push(Constant::Int(1)).unwrap();
+        let long = pool.push(Constant::Long(2)).unwrap();
+        let after = pool.push(Constant::Int(3)).unwrap();
+
+        let old = pool
+            .replace(text, Constant::Utf8(testutil::jstring("new")))
+            .unwrap();
+        assert!(matches!(old, Constant::Utf8(st) if st == testutil::jstring("old")));
+        assert_eq!(pool.utf8(text).unwrap(), &*testutil::jstring("new"));
+        assert!(matches!(
+            pool.replace(long, Constant::Double(2.0)),
+            Ok(Constant::Long(2))
+        ));
+
+        assert_eq!(
+            pool.replace(int, Constant::Long(1)).unwrap_err(),
+            ReplaceError::SlotsChanged(int)
+        );
+        assert_eq!(
+            pool.replace(long, Constant::Int(2)).unwrap_err(),
+            ReplaceError::SlotsChanged(long)
+        );
+        assert_eq!(
+            pool.replace(long + 1, Constant::Int(2)).unwrap_err(),
+            ReplaceError::SlotsChanged(long + 1)
+        );
+        assert_eq!(
+            pool.replace(0, Constant::Int(0)).unwrap_err(),
+            ReplaceError::InvalidIndex(0)
+        );
+        assert_eq!(
+            pool.replace(after + 1, Constant::Int(0)).unwrap_err(),
+            ReplaceError::InvalidIndex(after + 1)
+        );
+        // Nothing shifted
+        assert!(matches!(pool.get(int), Some(Constant::Int(1))));
+        assert!(matches!(pool.get(after), Some(Constant::Int(3))));
+    }
+
+    #[test]
+    fn push_fails_when_the_pool_is_full() {
+        let mut pool = ConstantPool::from_constants(vec![Constant::Int(0); MAX_SLOTS - 2]);
+        assert_eq!(pool.push(Constant::Long(1)).unwrap(), 65533);
+        assert_eq!(pool.count(), u16::MAX);
+        let full = pool.as_slice().len();
+
+        assert!(matches!(
+            pool.push(Constant::Int(1)),
+            Err(WriteError::ConstantPoolOverflow { slots: 65535 })
+        ));
+        assert!(matches!(
+            pool.intern_utf8(&testutil::jstring("new")),
+            Err(WriteError::ConstantPoolOverflow { .. })
+        ));
+        assert_eq!(pool.as_slice().len(), full);
+
+        // A Long or Double needs room for both slots
+        let mut pool = ConstantPool::from_constants(vec![Constant::Int(0); MAX_SLOTS - 1]);
+        assert!(matches!(
+            pool.push(Constant::Double(1.0)),
+            Err(WriteError::ConstantPoolOverflow { slots: 65535 })
+        ));
+        assert_eq!(pool.push(Constant::Int(1)).unwrap(), 65534);
+        assert!(pool.intern_utf8(&testutil::jstring("x")).is_err());
+    }
+
+    #[test]
+    fn entries_stop_at_the_last_index() {
+        let pool = ConstantPool::from_constants(vec![Constant::Int(0); 70000]);
+        assert_eq!(pool.entries().count(), usize::from(u16::MAX));
+        assert_eq!(pool.entries().last().unwrap().0, u16::MAX);
+    }
+}
@@ -0,0 +1,344 @@
+use std::io::Read;
+
+use crate::{
+    parse::{ParseError, ParseOptions},
+    string::JString,
+};
+
+/// The most memory a single table is allocated before any of its items are read
+const MAX_PREALLOC_BYTES: usize = 1 << 20;
+
+/// Allocates space for `declared` items, capped to the number of items of at least `per_item_min` bytes that fit in the rest of the input.
+///
+/// The length of a stream is not known up front, so the allocation is also capped to [`MAX_PREALLOC_BYTES`].
+/// This preallocates exactly for well-formed input, without letting a forged count allocate far more than the input could fill
+pub(crate) fn reserve_checked<T>(
+    declared: usize,
+    remaining_bytes: usize,
+    per_item_min: usize,
+) -> Vec<T> {
+    let max_items = MAX_PREALLOC_BYTES / std::mem::size_of::<T>().max(1);
+    Vec::with_capacity(declared.min(remaining_bytes / per_item_min).min(max_items))
+}
+
+///
+/// Reads the big-endian primitives of the class file format, keeping track of the position in the input and enforcing [`ParseOptions::max_bytes`]
+pub(crate) struct ClassReader<'a, R> {
+    pub(crate) inner: R,
+    pub(crate) pos: usize,
+    /// The position the input is known to end at
+    end: usize,
+    /// What is being read, as reported by [`ParseError::UnexpectedEof`]
+    expected: &'static str,
+    pub(crate) opts: &'a ParseOptions,
+}
+
+impl<'a, R: Read> ClassReader<'a, R> {
+    pub(crate) fn new(inner: R, opts: &'a ParseOptions) -> Self {
+        Self {
+            inner,
+            pos: 0,
+            end: opts.max_bytes,
+            expected: "magic",
+            opts,
+        }
+    }
+
+    pub(crate) fn with_len(inner: R, opts: &'a ParseOptions, len: usize) -> Self {
+        Self {
+            inner,
+            pos: 0,
+            end: len,
+            expected: "attribute info",
+            opts,
+        }
+    }
+
+    pub(crate) fn remaining(&self) -> usize {
+        self.end.saturating_sub(self.pos)
+    }
+
+    /// Names what the following reads are for, in case the input ends before they finish
+    pub(crate) fn expect(&mut self, expected: &'static str) -> &mut Self {
+        self.expected = expected;
+        self
+    }
+
+    fn unexpected_eof(&self, at: usize) -> ParseError {
+        ParseError::UnexpectedEof {
+            expected: self.expected,
+            at: at as u64,
+        }
+    }
+
+    fn advance(&mut self, n: usize) -> Result<(), ParseError> {
+        let pos = self.pos.saturating_add(n);
+        if pos > self.opts.max_bytes {
+            return Err(ParseError::LimitExceeded {
+                limit: "max_bytes",
+                value: pos,
+            });
+        }
+        self.pos = pos;
+        Ok(())
+    }
+
+    pub(crate) fn read_array<const N: usize>(&mut self) -> Result<[u8; N], ParseError> {
+        let at = self.pos;
+        self.advance(N)?;
+        let mut buf = [0; N];
+        match self.inner.read_exact(&mut buf) {
+            Ok(()) => Ok(buf),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => Err(self.unexpected_eof(at)),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8, ParseError> {
+        self.read_array().map(u8::from_be_bytes)
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16, ParseError> {
+        self.read_array().map(u16::from_be_bytes)
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32, ParseError> {
+        self.read_array().map(u32::from_be_bytes)
+    }
+
+    pub(crate) fn read_u64(&mut self) -> Result<u64, ParseError> {
+        self.read_array().map(u64::from_be_bytes)
+    }
+
+    pub(crate) fn read_i32(&mut self) -> Result<i32, ParseError> {
+        self.read_array().map(i32::from_be_bytes)
+    }
+
+    pub(crate) fn read_i64(&mut self) -> Result<i64, ParseError> {
+        self.read_array().map(i64::from_be_bytes)
+    }
+
+    /// Reads a float from its exact bit pattern, so NaN payloads are preserved
+    pub(crate) fn read_f32(&mut self) -> Result<f32, ParseError> {
+        self.read_u32().map(f32::from_bits)
+    }
+
+    /// Reads a double from its exact bit pattern, so NaN payloads are preserved
+    pub(crate) fn read_f64(&mut self) -> Result<f64, ParseError> {
+        self.read_u64().map(f64::from_bits)
+    }
+
+    /// Reads `n` bytes, growing the buffer as the data arrives rather than trusting `n` up front
+    pub(crate) fn read_bytes(&mut self, n: usize) -> Result<Vec<u8>, ParseError> {
+        let at = self.pos;
+        self.advance(n)?;
+        let mut buf = Vec::new();
+        (&mut self.inner).take(n as u64).read_to_end(&mut buf)?;
+        if buf.len() < n {
+            return Err(self.unexpected_eof(at));
+        }
+        Ok(buf)
+    }
+
+    /// Reads `len` bytes of Modified UTF-8. Invalid text is reported as [`ParseError::InvalidUtf8`] with an index of 0, for the caller to fill in
+    pub(crate) fn read_utf8(&mut self, len: usize) -> Result<JString, ParseError> {
+        let bytes = self.read_bytes(len)?;
+        JString::from_modified_utf8(bytes).map_err(|e| ParseError::InvalidUtf8 {
+            index: 0,
+            err: e.modified_utf8_error(),
+        })
+    }
+
+    pub(crate) fn read_u16s(&mut self) -> Result<Vec<u16>, ParseError> {
+        let count = self.read_u16()?;
+        self.table(count as usize, 2, Self::read_u16)
+    }
+
+    /// Reads `count` items, each taking at least `per_item_min` bytes
+    pub(crate) fn table<T>(
+        &mut self,
+        count: usize,
+        per_item_min: usize,
+        mut read_item: impl FnMut(&mut Self) -> Result<T, ParseError>,
+    ) -> Result<Vec<T>, ParseError> {
+        let mut items = reserve_checked(count, self.remaining(), per_item_min);
+        for _ in 0..count {
+            items.push(read_item(self)?);
+        }
+        Ok(items)
+    }
+
+    /// Reads a u16 count, checking it against one of the limits in [`ParseOptions`]
+    pub(crate) fn limited_count(
+        &mut self,
+        limit: &'static str,
+        max: usize,
+    ) -> Result<u16, ParseError> {
+        let count = self.read_u16()?;
+        if count as usize > max {
+            return Err(ParseError::LimitExceeded {
+                limit,
+                value: count as usize,
+            });
+        }
+        Ok(count)
+    }
+}
+
+impl<'b> ClassReader<'_, &'b [u8]> {
+    /// Reads `n` bytes without copying them out of the input
+    pub(crate) fn read_slice(&mut self, n: usize) -> Result<&'b [u8], ParseError> {
+        let at = self.pos;
+        self.advance(n)?;
+        if self.inner.len() < n {
+            return Err(self.unexpected_eof(at));
+        }
+        let (head, tail) = self.inner.split_at(n);
+        self.inner = tail;
+        Ok(head)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::class::Constant;
+
+    #[test]
+    fn reserve_checked_trusts_plausible_counts() {
+        let consts = reserve_checked::<Constant>(300, 4096, 3);
+        assert_eq!(consts.capacity(), 300);
+        let consts = reserve_checked::<Constant>(300, 30, 3);
+        assert_eq!(consts.capacity(), 10);
+    }
+
+    #[test]
+    fn reserve_checked_caps_forged_counts() {
+        let consts = reserve_checked::<Constant>(usize::MAX, usize::MAX, 3);
+        assert!(consts.capacity() * std::mem::size_of::<Constant>() <= MAX_PREALLOC_BYTES);
+        let bytes = reserve_checked::<u8>(usize::MAX, usize::MAX, 1);
+        assert_eq!(bytes.capacity(), MAX_PREALLOC_BYTES);
+    }
+
+    #[test]
+    fn forged_count_in_stream_is_bounded() {
+        // A constant_pool_count of 65535, read from a stream whose length is unknown
+        let opts = ParseOptions::default();
+        let mut r = ClassReader::new(&[0xffu8, 0xff][..], &opts);
+        let count = r.read_u16().unwrap();
+        let consts = reserve_checked::<Constant>(count as usize, r.remaining(), 3);
+        assert!(consts.capacity() * std::mem::size_of::<Constant>() <= MAX_PREALLOC_BYTES);
+    }
+
+    #[test]
+    fn primitives_are_big_endian() {
+        let opts = ParseOptions::default();
+        let mut input = vec![0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07];
+        input.extend_from_slice(&u64::MAX.to_be_bytes());
+        input.extend_from_slice(&(-2i32).to_be_bytes());
+        input.extend_from_slice(&(-3i64).to_be_bytes());
+        input.extend_from_slice(&1.5f32.to_be_bytes());
+        input.extend_from_slice(&(-0.25f64).to_be_bytes());
+        input.extend_from_slice(&[0, 2, 0xab, 0xcd, 0x12, 0x34]);
+        input.extend_from_slice(b"hi\xc0\x80");
+        let mut r = ClassReader::new(&input[..], &opts);
+        assert_eq!(r.read_u8().unwrap(), 0x01);
+        assert_eq!(r.read_u16().unwrap(), 0x0203);
+        assert_eq!(r.read_u32().unwrap(), 0x04050607);
+        assert_eq!(r.read_u64().unwrap(), u64::MAX);
+        assert_eq!(r.read_i32().unwrap(), -2);
+        assert_eq!(r.read_i64().unwrap(), -3);
+        assert_eq!(r.read_f32().unwrap(), 1.5);
+        assert_eq!(r.read_f64().unwrap(), -0.25);
+        assert_eq!(r.read_u16s().unwrap(), [0xabcd, 0x1234]);
+        assert_eq!(
+            r.read_utf8(4).unwrap().jchars().collect::<Vec<_>>(),
+            [0x68, 0x69, 0]
+        );
+        assert_eq!(r.pos, input.len());
+        assert!(matches!(
+            r.expect("more").read_u8(),
+            Err(ParseError::UnexpectedEof { expected: "more", at }) if at == input.len() as u64
+        ));
+    }
+
+    #[test]
+    fn every_primitive_reports_eof_where_it_started() {
+        let opts = ParseOptions::default();
+        let input = [0xffu8; 2];
+        type Read<'a> = fn(&mut ClassReader<'a, &'a [u8]>) -> Result<(), ParseError>;
+        let reads: [(&str, Read); 9] = [
+            ("u16", |r| r.read_u16().map(drop)),
+            ("u32", |r| r.read_u32().map(drop)),
+            ("u64", |r| r.read_u64().map(drop)),
+            ("i32", |r| r.read_i32().map(drop)),
+            ("i64", |r| r.read_i64().map(drop)),
+            ("f32", |r| r.read_f32().map(drop)),
+            ("f64", |r| r.read_f64().map(drop)),
+            ("bytes", |r| r.read_bytes(7).map(drop)),
+            ("utf8", |r| r.read_utf8(7).map(drop)),
+        ];
+        for (name, read) in reads {
+            let mut r = ClassReader::new(&input[..], &opts);
+            r.read_u8().unwrap();
+            r.expect(name);
+            match read(&mut r) {
+                Err(ParseError::UnexpectedEof { expected, at }) => {
+                    assert_eq!((expected, at), (name, 1))
+                }
+                other => panic!("{}: expected UnexpectedEof, got {:?}", name, other),
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_utf8_is_reported() {
+        let opts = ParseOptions::default();
+        let mut r = ClassReader::new(&b"a\x00"[..], &opts);
+        assert!(matches!(
+            r.read_utf8(2),
+            Err(ParseError::InvalidUtf8 { index: 0, .. })
+        ));
+    }
+
+    #[test]
+    fn max_bytes_is_enforced_before_reading() {
+        let opts = ParseOptions {
+            max_bytes: 3,
+            ..ParseOptions::default()
+        };
+        let mut r = ClassReader::new(&[1u8, 2, 3, 4][..], &opts);
+        assert_eq!(r.read_u16().unwrap(), 0x0102);
+        assert!(matches!(
+            r.read_u16(),
+            Err(ParseError::LimitExceeded {
+                limit: "max_bytes",
+                value: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn with_len_bounds_the_remaining_input() {
+        let opts = ParseOptions::default();
+        let mut r = ClassReader::with_len(&[0u8; 8][..], &opts, 6);
+        assert_eq!(r.remaining(), 6);
+        r.read_u32().unwrap();
+        assert_eq!(r.remaining(), 2);
+        assert_eq!(r.limited_count("max_things", 0).unwrap(), 0);
+        assert_eq!(r.remaining(), 0);
+    }
+
+    #[test]
+    fn limited_count_rejects_large_counts() {
+        let opts = ParseOptions::default();
+        let mut r = ClassReader::new(&[0u8, 5][..], &opts);
+        assert!(matches!(
+            r.limited_count("max_things", 4),
+            Err(ParseError::LimitExceeded {
+                limit: "max_things",
+                value: 5
+            })
+        ));
+    }
+}
@@ -0,0 +1,660 @@
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+};
+
+use crate::{
+    bytecode::{DecodeError, Instruction, Instructions},
+    class::{
+        Annotation, Attribute, Availability, ClassFile, Constant, ElementValue, StackMapFrame,
+        TypeAnnotation, VerificationInfo,
+    },
+    string::JStr,
+};
+
+///
+/// Where in a class file a constant pool index was found
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Location {
+    /// In the constant at the given index
+    Constant(u16),
+    /// In `this_class`, `super_class`, `interfaces`, or the attributes of the class
+    Class,
+    /// In the field at the given position in `fields`, or its attributes
+    Field(u16),
+    /// In the method at the given position in `methods`, or its attributes
+    Method(u16),
+    /// In the operand of the instruction at `pc` in the code of the given method
+    Code { method: u16, pc: u32 },
+}
+
+///
+/// What a constant pool index stored in a class file is expected to refer to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum RefKind {
+    /// A Utf8 constant used as a name or as text
+    Utf8,
+    /// A Utf8 constant holding a field or method descriptor
+    Descriptor,
+    /// A Utf8 constant holding a generic signature
+    Signature,
+    /// A Utf8 constant named by a Class constant: an internal class name or an array descriptor
+    ClassName,
+    Class,
+    NameAndType,
+    FieldRef,
+    /// A MethodRef or InterfaceMethodRef
+    MethodRef,
+    /// The FieldRef, MethodRef, or InterfaceMethodRef of a MethodHandle constant
+    MethodHandleRef,
+    MethodHandle,
+    /// An Int, Float, Long, Double, or String constant, as used by ConstantValue and annotation elements
+    ConstantValue,
+    /// Any loadable constant, as used by bootstrap arguments
+    Loadable,
+    /// A loadable constant other than a Long or Double, as used by `ldc` and `ldc_w`
+    NarrowLoadable,
+    /// A Long, Double, or Dynamic constant, as used by `ldc2_w`
+    WideLoadable,
+    InvokeDynamic,
+    Module,
+    Package,
+    /// An index whose kind is not known to lcjvm
+    Any,
+}
+
+macro_rules! ref_visitor {
+    ($($mut:ident)?; $for_each:ident, $code:ident, $attrs:ident, $attr:ident, $annots:ident, $annot:ident, $value:ident, $type_annots:ident) => {
+        fn $annot<F: FnMut(Location, RefKind, &$($mut)? u16)>(annot: &$($mut)? Annotation, loc: Location, f: &mut F) {
+            f(loc, RefKind::Descriptor, &$($mut)? annot.class);
+            for elem in &$($mut)? annot.elements {
+                f(loc, RefKind::Utf8, &$($mut)? elem.name);
+                $value(&$($mut)? elem.value, loc, f);
+            }
+        }
+
+        fn $annots<F: FnMut(Location, RefKind, &$($mut)? u16)>(annots: &$($mut)? [Annotation], loc: Location, f: &mut F) {
+            for annot in annots {
+                $annot(annot, loc, f);
+            }
+        }
+
+        fn $type_annots<F: FnMut(Location, RefKind, &$($mut)? u16)>(
+            annots: &$($mut)? [TypeAnnotation], loc: Location, f: &mut F) {
+            for annot in annots {
+                $annot(&$($mut)? annot.annotation, loc, f);
+            }
+        }
+
+        fn $value<F: FnMut(Location, RefKind, &$($mut)? u16)>(value: &$($mut)? ElementValue, loc: Location, f: &mut F) {
+            match value {
+                ElementValue::Byte(idx)
+                | ElementValue::Char(idx)
+                | ElementValue::Double(idx)
+                | ElementValue::Float(idx)
+                | ElementValue::Int(idx)
+                | ElementValue::Long(idx)
+                | ElementValue::Short(idx)
+                | ElementValue::Boolean(idx) => f(loc, RefKind::ConstantValue, idx),
+                ElementValue::String(idx) => f(loc, RefKind::Utf8, idx),
+                ElementValue::Enum {
+                    type_name,
+                    const_name,
+                } => {
+                    f(loc, RefKind::Descriptor, type_name);
+                    f(loc, RefKind::Utf8, const_name);
+                }
+                ElementValue::Class(idx) => f(loc, RefKind::Descriptor, idx),
+                ElementValue::Annotation(annot) => $annot(annot, loc, f),
+                ElementValue::Array(values) => {
+                    for value in values {
+                        $value(value, loc, f);
+                    }
+                }
+            }
+        }
+
+        fn $attr<F: FnMut(Location, RefKind, &$($mut)? u16)>(attr: &$($mut)? Attribute, loc: Location, f: &mut F) {
+            match attr {
+                Attribute::ConstantValue(idx) => f(loc, RefKind::ConstantValue, idx),
+                Attribute::Code(code) => {
+                    for ex in &$($mut)? code.exceptions {
+                        if ex.catch_type != 0 {
+                            f(loc, RefKind::Class, &$($mut)? ex.catch_type);
+                        }
+                    }
+                    $code(&$($mut)? code.code, loc, f);
+                    $attrs(&$($mut)? code.attributes, loc, f);
+                }
+                Attribute::StackMapTable(frames) => {
+                    for frame in frames {
+                        match frame {
+                            StackMapFrame::SameLocals1StackFrame { info, .. }
+                            | StackMapFrame::SameLocals1StackFrameExtended { info, .. } => {
+                                if let VerificationInfo::Object { class } = info {
+                                    f(loc, RefKind::Class, class);
+                                }
+                            }
+                            StackMapFrame::Append { items, .. } => {
+                                for info in items {
+                                    if let VerificationInfo::Object { class } = info {
+                                        f(loc, RefKind::Class, class);
+                                    }
+                                }
+                            }
+                            StackMapFrame::Full { locals, stack, .. } => {
+                                for infos in [locals, stack] {
+                                    for info in infos {
+                                        if let VerificationInfo::Object { class } = info {
+                                            f(loc, RefKind::Class, class);
+                                        }
+                                    }
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                Attribute::Exceptions(classes)
+                | Attribute::NestMembers(classes)
+                | Attribute::PermittedSubclasses(classes) => {
+                    for class in classes {
+                        f(loc, RefKind::Class, class);
+                    }
+                }
+                Attribute::InnerClasses(classes) => {
+                    for class in classes {
+                        f(loc, RefKind::Class, &$($mut)? class.inner_class_info);
+                        if class.outer_class_info != 0 {
+                            f(loc, RefKind::Class, &$($mut)? class.outer_class_info);
+                        }
+                        if class.inner_name != 0 {
+                            f(loc, RefKind::Utf8, &$($mut)? class.inner_name);
+                        }
+                    }
+                }
+                Attribute::EnclosingMethod { class, method } => {
+                    f(loc, RefKind::Class, class);
+                    if *method != 0 {
+                        f(loc, RefKind::NameAndType, method);
+                    }
+                }
+                Attribute::Signature(idx) => f(loc, RefKind::Signature, idx),
+                Attribute::SourceFile(idx) | Attribute::LangItem(idx) => f(loc, RefKind::Utf8, idx),
+                Attribute::LocalVariableTable(vars) => {
+                    for var in vars {
+                        f(loc, RefKind::Utf8, &$($mut)? var.name);
+                        f(loc, RefKind::Descriptor, &$($mut)? var.descriptor);
+                    }
+                }
+                Attribute::LocalVariableTypeTable(vars) => {
+                    for var in vars {
+                        f(loc, RefKind::Utf8, &$($mut)? var.name);
+                        f(loc, RefKind::Signature, &$($mut)? var.signature);
+                    }
+                }
+                Attribute::RuntimeVisibleAnnotations(annots)
+                | Attribute::RuntimeInvisibleAnnotations(annots) => $annots(annots, loc, f),
+                Attribute::RuntimeVisibleParameterAnnotations(params)
+                | Attribute::RuntimeInvisibleParameterAnnotations(params) => {
+                    for annots in params {
+                        $annots(annots, loc, f);
+                    }
+                }
+                Attribute::RuntimeVisibleTypeAnnotations(annots)
+                | Attribute::RuntimeInvisibleTypeAnnotations(annots) => $type_annots(annots, loc, f),
+                Attribute::AnnotationDefault(value) => $value(value, loc, f),
+                Attribute::BootstrapMethods(methods) => {
+                    for method in methods {
+                        f(loc, RefKind::MethodHandle, &$($mut)? method.href);
+                        for arg in &$($mut)? method.args {
+                            f(loc, RefKind::Loadable, arg);
+                        }
+                    }
+                }
+                Attribute::MethodParameters(params) => {
+                    for param in params {
+                        if param.name != 0 {
+                            f(loc, RefKind::Utf8, &$($mut)? param.name);
+                        }
+                    }
+                }
+                Attribute::Module(module) => {
+                    f(loc, RefKind::Module, &$($mut)? module.name);
+                    if module.version != 0 {
+                        f(loc, RefKind::Utf8, &$($mut)? module.version);
+                    }
+                    for req in &$($mut)? module.requires {
+                        f(loc, RefKind::Module, &$($mut)? req.requires);
+                        if req.version != 0 {
+                            f(loc, RefKind::Utf8, &$($mut)? req.version);
+                        }
+                    }
+                    for exports in [&$($mut)? module.exports, &$($mut)? module.opens] {
+                        for export in exports {
+                            f(loc, RefKind::Package, &$($mut)? export.exports);
+                            for to in &$($mut)? export.to {
+                                f(loc, RefKind::Module, to);
+                            }
+                        }
+                    }
+                    for uses in &$($mut)? module.uses {
+                        f(loc, RefKind::Class, uses);
+                    }
+                    for provides in &$($mut)? module.provides {
+                        f(loc, RefKind::Class, &$($mut)? provides.provides);
+                        for with in &$($mut)? provides.with {
+                            f(loc, RefKind::Class, with);
+                        }
+                    }
+                }
+                Attribute::ModulePackage(packages) => {
+                    for package in packages {
+                        f(loc, RefKind::Package, package);
+                    }
+                }
+                Attribute::ModuleMainClass(idx) | Attribute::NestHost(idx) => {
+                    f(loc, RefKind::Class, idx)
+                }
+                Attribute::Record(components) => {
+                    for component in components {
+                        f(loc, RefKind::Utf8, &$($mut)? component.name);
+                        f(loc, RefKind::Descriptor, &$($mut)? component.descriptor);
+                        $attrs(&$($mut)? component.attributes, loc, f);
+                    }
+                }
+                Attribute::Availability(Availability::Unstable { feature, .. }) => {
+                    f(loc, RefKind::Utf8, feature)
+                }
+                Attribute::FillNativeMethod(idx) => f(loc, RefKind::Any, idx),
+                Attribute::Unresolved { name, .. } => f(loc, RefKind::Utf8, name),
+                Attribute::Synthetic
+                | Attribute::SourceDebugExtension(_)
+                | Attribute::LineNumberTable(_)
+                | Attribute::Deprecated
+                | Attribute::Availability(_) => {}
+            }
+        }
+
+        fn $attrs<F: FnMut(Location, RefKind, &$($mut)? u16)>(attrs: &$($mut)? [Attribute], loc: Location, f: &mut F) {
+            for attr in attrs {
+                $attr(attr, loc, f);
+            }
+        }
+
+        fn $for_each<F: FnMut(Location, RefKind, &$($mut)? u16)>(class: &$($mut)? ClassFile, mut f: F) {
+            let f = &mut f;
+            for (idx, constant) in (&$($mut)? class.consts).into_iter().enumerate() {
+                let loc = Location::Constant(idx as u16 + 1);
+                match constant {
+                    Constant::Class(name) => f(loc, RefKind::ClassName, name),
+                    Constant::String(idx) | Constant::Module(idx) | Constant::Package(idx) => {
+                        f(loc, RefKind::Utf8, idx)
+                    }
+                    Constant::FieldRef {
+                        class,
+                        name_and_type,
+                    }
+                    | Constant::MethodRef {
+                        class,
+                        name_and_type,
+                    }
+                    | Constant::InterfaceMethodRef {
+                        class,
+                        name_and_type,
+                    } => {
+                        f(loc, RefKind::Class, class);
+                        f(loc, RefKind::NameAndType, name_and_type);
+                    }
+                    Constant::NameAndType { name, descriptor } => {
+                        f(loc, RefKind::Utf8, name);
+                        f(loc, RefKind::Descriptor, descriptor);
+                    }
+                    Constant::MethodHandle { reference, .. } => {
+                        f(loc, RefKind::MethodHandleRef, reference)
+                    }
+                    Constant::MethodType(desc) => f(loc, RefKind::Descriptor, desc),
+                    Constant::Dynamic { name_and_type, .. }
+                    | Constant::InvokeDynamic { name_and_type, .. } => {
+                        f(loc, RefKind::NameAndType, name_and_type)
+                    }
+                    Constant::Utf8(_)
+                    | Constant::Int(_)
+                    | Constant::Float(_)
+                    | Constant::Long(_)
+                    | Constant::Double(_)
+                    | Constant::LongOrDoubleHigh => {}
+                }
+            }
+            let loc = Location::Class;
+            f(loc, RefKind::Class, &$($mut)? class.this);
+            if class.supercl != 0 {
+                f(loc, RefKind::Class, &$($mut)? class.supercl);
+            }
+            for interface in &$($mut)? class.interfaces {
+                f(loc, RefKind::Class, interface);
+            }
+            for (idx, field) in (&$($mut)? class.fields).into_iter().enumerate() {
+                let loc = Location::Field(idx as u16);
+                f(loc, RefKind::Utf8, &$($mut)? field.name);
+                f(loc, RefKind::Descriptor, &$($mut)? field.descriptor);
+                $attrs(&$($mut)? field.attributes, loc, f);
+            }
+            for (idx, method) in (&$($mut)? class.methods).into_iter().enumerate() {
+                let loc = Location::Method(idx as u16);
+                f(loc, RefKind::Utf8, &$($mut)? method.name);
+                f(loc, RefKind::Descriptor, &$($mut)? method.descriptor);
+                $attrs(&$($mut)? method.attributes, loc, f);
+            }
+            $attrs(&$($mut)? class.attributes, loc, f);
+        }
+    };
+}
+
+fn code_ref_kind(insn: &Instruction) -> Option<(RefKind, u16)> {
+    let kind = match insn {
+        Instruction::Ldc(_) => RefKind::NarrowLoadable,
+        Instruction::Ldc2W(_) => RefKind::WideLoadable,
+        Instruction::GetStatic(_)
+        | Instruction::PutStatic(_)
+        | Instruction::GetField(_)
+        | Instruction::PutField(_) => RefKind::FieldRef,
+        Instruction::InvokeVirtual(_)
+        | Instruction::InvokeSpecial(_)
+        | Instruction::InvokeStatic(_)
+        | Instruction::InvokeInterface { .. } => RefKind::MethodRef,
+        Instruction::InvokeDynamic(_) => RefKind::InvokeDynamic,
+        _ => RefKind::Class,
+    };
+    Some((kind, insn.constant_index()?))
+}
+
+///
+/// Bytecode is visited up to the first instruction that cannot be decoded
+fn visit_code<F: FnMut(Location, RefKind, &u16)>(code: &[u8], loc: Location, f: &mut F) {
+    let method = match loc {
+        Location::Method(method) => method,
+        _ => return,
+    };
+    for (pc, insn) in Instructions::new(code).map_while(Result::ok) {
+        if let Some((kind, idx)) = code_ref_kind(&insn) {
+            f(Location::Code { method, pc }, kind, &idx);
+        }
+    }
+}
+
+fn visit_code_mut<F: FnMut(Location, RefKind, &mut u16)>(
+    code: &mut [u8],
+    loc: Location,
+    f: &mut F,
+) {
+    let method = match loc {
+        Location::Method(method) => method,
+        _ => return,
+    };
+    let insns: Vec<_> = Instructions::new(code).map_while(Result::ok).collect();
+    for (pc, insn) in insns {
+        if let Some((kind, mut idx)) = code_ref_kind(&insn) {
+            f(Location::Code { method, pc }, kind, &mut idx);
+            let pc = pc as usize;
+            if code[pc] == 0x12 {
+                code[pc + 1] =
+                    u8::try_from(idx).expect("ldc operand rewritten to an index above 255");
+            } else {
+                code[pc + 1..pc + 3].copy_from_slice(&idx.to_be_bytes());
+            }
+        }
+    }
+}
+
+ref_visitor!(; visit_class, visit_code, visit_attributes, visit_attribute, visit_annotations, visit_annotation, visit_element_value, visit_type_annotations);
+ref_visitor!(mut; visit_class_mut, visit_code_mut, visit_attributes_mut, visit_attribute_mut, visit_annotations_mut, visit_annotation_mut, visit_element_value_mut, visit_type_annotations_mut);
+
+impl ClassFile {
+    ///
+    /// Calls `f` with every constant pool index stored in the class, including the operands of bytecode instructions, along with where it was found and what it should refer to.
+    ///
+    /// Optional indices that are 0 are skipped. The bootstrap method indices of Dynamic and InvokeDynamic constants are not constant pool indices, and are not visited
+    pub(crate) fn for_each_ref<F: FnMut(Location, RefKind, &u16)>(&self, f: F) {
+        visit_class(self, f)
+    }
+
+    ///
+    /// Like [`ClassFile::for_each_ref`], but allows the indices to be rewritten.
+    ///
+    /// # Panics
+    /// Panics if the operand of an `ldc` instruction is rewritten to an index that does not fit in a byte
+    pub(crate) fn for_each_ref_mut<F: FnMut(Location, RefKind, &mut u16)>(&mut self, f: F) {
+        visit_class_mut(self, f)
+    }
+
+    ///
+    /// Rebuilds the pool from the constants at the indices in `order`, and redirects every reference to them. References to indices not in `order` are left as they are.
+    ///
+    /// The operands of `ldc` instructions are placed first, keeping their relative order, so that they still fit in a byte
+    pub(crate) fn reorder_constants(&mut self, order: &[u16]) {
+        let mut narrow = HashSet::new();
+        for method in &self.methods {
+            for attr in &method.attributes {
+                if let Attribute::Code(code) = attr {
+                    for (pc, insn) in Instructions::new(&code.code).map_while(Result::ok) {
+                        if let (0x12, Instruction::Ldc(idx)) = (code.code[pc as usize], insn) {
+                            narrow.insert(idx);
+                        }
+                    }
+                }
+            }
+        }
+        let (mut first, rest): (Vec<u16>, Vec<u16>) =
+            order.iter().copied().partition(|idx| narrow.contains(idx));
+        first.extend(rest);
+        self.rebuild_constants(&first);
+    }
+
+    ///
+    /// Like [`ClassFile::reorder_constants`], but places the constants exactly in the order given
+    fn rebuild_constants(&mut self, order: &[u16]) {
+        let old = std::mem::take(&mut self.consts);
+        let mut remap = HashMap::new();
+        for &idx in order {
+            match old.get(idx) {
+                None | Some(Constant::LongOrDoubleHigh) => {}
+                Some(_) if remap.contains_key(&idx) => {}
+                Some(constant) => {
+                    // Each constant of the old pool is copied at most once, so the new pool is no larger
+                    let new = self
+                        .consts
+                        .push(constant.clone())
+                        .expect("rebuilt pool is no larger than the old one");
+                    remap.insert(idx, new);
+                }
+            }
+        }
+        self.for_each_ref_mut(|_, _, idx| {
+            if let Some(&new) = remap.get(idx) {
+                *idx = new;
+            }
+        });
+    }
+
+    ///
+    /// Removes the constants that nothing in the class refers to, directly or through other constants, and redirects every reference to the constants that remain.
+    ///
+    /// The Utf8 constants naming the attributes of the class are kept, since they are needed to write it. The remaining constants keep their relative order.
+    ///
+    /// Returns an error, leaving the pool unchanged, if the bytecode of a method cannot be decoded, since the constants it refers to cannot all be found
+    pub fn garbage_collect_constants(&mut self) -> Result<(), DecodeError> {
+        fn attribute_names(attrs: &[Attribute], names: &mut HashSet<&'static str>) {
+            for attr in attrs {
+                names.extend(attr.name());
+                match attr {
+                    Attribute::Code(code) => attribute_names(&code.attributes, names),
+                    Attribute::Record(components) => {
+                        for component in components {
+                            attribute_names(&component.attributes, names);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        for method in &self.methods {
+            for attr in &method.attributes {
+                if let Attribute::Code(code) = attr {
+                    Instructions::new(&code.code).try_for_each(|insn| insn.map(drop))?;
+                }
+            }
+        }
+
+        let len = self.consts.as_slice().len();
+        let mut children = vec![Vec::new(); len];
+        let mut stack = Vec::new();
+        self.for_each_ref(|loc, _, &idx| match loc {
+            Location::Constant(owner) => children[owner as usize - 1].push(idx),
+            _ => stack.push(idx),
+        });
+
+        let mut names = HashSet::new();
+        attribute_names(&self.attributes, &mut names);
+        for member in self.fields.iter().map(|f| &f.attributes) {
+            attribute_names(member, &mut names);
+        }
+        for member in self.methods.iter().map(|m| &m.attributes) {
+            attribute_names(member, &mut names);
+        }
+        for name in names {
+            let name = JStr::from_str(name).unwrap();
+            // The writer looks up attribute names by content, using the first match
+            stack.extend(
+                self.consts
+                    .entries()
+                    .find(|(_, constant)| matches!(constant, Constant::Utf8(st) if **st == *name))
+                    .map(|(idx, _)| idx),
+            );
+        }
+
+        let mut reachable = vec![false; len];
+        while let Some(idx) = stack.pop() {
+            if let Some(slot) = (idx as usize).checked_sub(1).filter(|&slot| slot < len) {
+                if !reachable[slot] {
+                    reachable[slot] = true;
+                    stack.extend_from_slice(&children[slot]);
+                }
+            }
+        }
+        let order: Vec<u16> = (1..=len as u16)
+            .filter(|&idx| reachable[idx as usize - 1])
+            .collect();
+        self.rebuild_constants(&order);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    fn find_utf8(class: &ClassFile, text: &str) -> Option<u16> {
+        class
+            .consts
+            .entries()
+            .find(|(_, constant)| matches!(constant, Constant::Utf8(st) if st.to_string() == text))
+            .map(|(idx, _)| idx)
+    }
+
+    #[test]
+    fn unused_constants_are_removed_and_references_follow() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        // Only the SourceFile attribute refers to its name and to the Utf8 naming it
+        let unused = find_utf8(&class, "Fixture.java").unwrap();
+        let source_file = find_utf8(&class, "SourceFile").unwrap();
+        class
+            .attributes
+            .retain(|attr| !matches!(attr, Attribute::SourceFile(_)));
+        let count = class.consts.count();
+        let long = class
+            .consts
+            .entries()
+            .find_map(|(idx, constant)| match constant {
+                Constant::Long(v) => Some((idx, *v)),
+                _ => None,
+            })
+            .unwrap();
+        let (long_idx, long) = long;
+        let this = class.consts.class_name(class.this).unwrap().to_owned();
+        let method_names: Vec<String> = class
+            .methods
+            .iter()
+            .map(|method| testutil::utf8(&class, method.name))
+            .collect();
+
+        class.garbage_collect_constants().unwrap();
+
+        assert_eq!(find_utf8(&class, "Fixture.java"), None);
+        assert_eq!(find_utf8(&class, "SourceFile"), None);
+        assert_eq!(class.consts.count(), count - 2);
+        assert_eq!(class.consts.class_name(class.this).unwrap(), &*this);
+        let names: Vec<String> = class
+            .methods
+            .iter()
+            .map(|method| testutil::utf8(&class, method.name))
+            .collect();
+        assert_eq!(names, method_names);
+        assert_eq!(class.validate_references(), Ok(()));
+
+        // The Long keeps its second slot, and moved down if it came after the removed constants
+        let (new_long_idx, _) = class
+            .consts
+            .entries()
+            .find(|(_, constant)| matches!(constant, Constant::Long(v) if *v == long))
+            .unwrap();
+        assert!(matches!(
+            class.consts.get(new_long_idx + 1),
+            Some(Constant::LongOrDoubleHigh)
+        ));
+        assert_eq!(
+            new_long_idx,
+            long_idx - (long_idx > unused) as u16 - (long_idx > source_file) as u16
+        );
+
+        let bytes = class.write_to_vec().unwrap();
+        testutil::assert_roundtrip(&bytes);
+    }
+
+    #[test]
+    fn referenced_constants_survive() {
+        for bytes in testutil::ALL {
+            let mut class = testutil::read(bytes);
+            let count = class.consts.count();
+            class.garbage_collect_constants().unwrap();
+            // javac does not emit unused constants
+            assert_eq!(class.consts.count(), count);
+            assert_eq!(class.write_to_vec().unwrap(), *bytes);
+        }
+    }
+
+    #[test]
+    fn undecodable_code_keeps_the_pool() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        let only_in_code = class.consts.push(Constant::Int(12345)).unwrap();
+        let code = class
+            .methods
+            .iter_mut()
+            .find_map(|method| method.code_mut())
+            .unwrap();
+        let pc = code.code.len() as u32;
+        // An invalid opcode, followed by an ldc_w of a constant nothing else refers to
+        code.code.push(0xff);
+        code.code.push(0x13);
+        code.code.extend_from_slice(&only_in_code.to_be_bytes());
+        let before = class.write_to_vec().unwrap();
+
+        assert_eq!(
+            class.garbage_collect_constants(),
+            Err(DecodeError::InvalidOpcode { pc, opcode: 0xff })
+        );
+        assert_eq!(class.write_to_vec().unwrap(), before);
+    }
+}
@@ -0,0 +1,523 @@
+use std::fmt::{Display, Formatter};
+
+use crate::string::{JStr, JString};
+
+///
+/// A type in a generic signature
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TypeSignature<'a> {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    Class(ClassTypeSignature<'a>),
+    /// A reference to a type variable, by name
+    TypeVariable(&'a JStr),
+    Array(Box<TypeSignature<'a>>),
+}
+
+///
+/// A class or interface type, such as `Ljava/util/Map<TK;TV;>.Entry<TK;TV;>;`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ClassTypeSignature<'a> {
+    /// The outermost class, whose name includes the package
+    pub outer: SimpleClassTypeSignature<'a>,
+    /// Member classes of `outer`, by simple name
+    pub inner: Vec<SimpleClassTypeSignature<'a>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct SimpleClassTypeSignature<'a> {
+    pub name: &'a JStr,
+    pub type_args: Vec<TypeArgument<'a>>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TypeArgument<'a> {
+    /// `*`
+    Any,
+    Exact(TypeSignature<'a>),
+    /// `+`, written `? extends` in java
+    Extends(TypeSignature<'a>),
+    /// `-`, written `? super` in java
+    Super(TypeSignature<'a>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TypeParameter<'a> {
+    pub name: &'a JStr,
+    pub class_bound: Option<TypeSignature<'a>>,
+    pub interface_bounds: Vec<TypeSignature<'a>>,
+}
+
+///
+/// The signature of a class, from its Signature attribute
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ClassSignature<'a> {
+    pub type_params: Vec<TypeParameter<'a>>,
+    pub superclass: ClassTypeSignature<'a>,
+    pub interfaces: Vec<ClassTypeSignature<'a>>,
+}
+
+///
+/// The signature of a method. A `ret` of None is `void`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct MethodSignature<'a> {
+    pub type_params: Vec<TypeParameter<'a>>,
+    pub params: Vec<TypeSignature<'a>>,
+    pub ret: Option<TypeSignature<'a>>,
+    pub throws: Vec<TypeSignature<'a>>,
+}
+
+///
+/// The signature of a field, record component, or local variable
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct FieldSignature<'a> {
+    pub ty: TypeSignature<'a>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    UnexpectedEnd,
+    /// An unexpected byte was found at the given position
+    InvalidChar {
+        pos: usize,
+        byte: u8,
+    },
+    /// The signature continued past the end of what was parsed
+    TrailingData {
+        pos: usize,
+    },
+}
+
+impl Display for SignatureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SignatureError::UnexpectedEnd => f.write_str("unexpected end of signature"),
+            SignatureError::InvalidChar { pos, byte } => {
+                write!(f, "unexpected byte {:#04x} at {}", byte, pos)
+            }
+            SignatureError::TrailingData { pos } => {
+                write!(f, "unexpected data after signature at {}", pos)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+struct SignatureParser<'a> {
+    sig: &'a JStr,
+    pos: usize,
+}
+
+impl<'a> SignatureParser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.sig.as_bytes().get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Result<u8, SignatureError> {
+        let b = self.peek().ok_or(SignatureError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), SignatureError> {
+        let pos = self.pos;
+        match self.next()? {
+            b if b == expected => Ok(()),
+            byte => Err(SignatureError::InvalidChar { pos, byte }),
+        }
+    }
+
+    /// Reads up to (but not including) the first byte in `terminators`, which must be ASCII
+    fn identifier(&mut self, terminators: &[u8]) -> Result<&'a JStr, SignatureError> {
+        let start = self.pos;
+        let len = self.sig.as_bytes()[start..]
+            .iter()
+            .position(|b| terminators.contains(b))
+            .ok_or(SignatureError::UnexpectedEnd)?;
+        if len == 0 {
+            return Err(SignatureError::InvalidChar {
+                pos: start,
+                byte: self.sig.as_bytes()[start],
+            });
+        }
+        self.pos += len;
+        // SAFETY:
+        // The identifier is delimited by ASCII bytes (or the start of the string), so it starts and ends on character boundaries
+        Ok(unsafe { JStr::from_modified_utf8_unchecked(&self.sig.as_bytes()[start..start + len]) })
+    }
+
+    fn type_args(&mut self) -> Result<Vec<TypeArgument<'a>>, SignatureError> {
+        let mut args = Vec::new();
+        if self.peek() != Some(b'<') {
+            return Ok(args);
+        }
+        self.pos += 1;
+        loop {
+            args.push(match self.peek() {
+                Some(b'*') => {
+                    self.pos += 1;
+                    TypeArgument::Any
+                }
+                Some(b'+') => {
+                    self.pos += 1;
+                    TypeArgument::Extends(self.reference_type()?)
+                }
+                Some(b'-') => {
+                    self.pos += 1;
+                    TypeArgument::Super(self.reference_type()?)
+                }
+                _ => TypeArgument::Exact(self.reference_type()?),
+            });
+            if self.peek() == Some(b'>') {
+                self.pos += 1;
+                break Ok(args);
+            }
+        }
+    }
+
+    fn simple_class_type(&mut self) -> Result<SimpleClassTypeSignature<'a>, SignatureError> {
+        let name = self.identifier(b"<.;")?;
+        let type_args = self.type_args()?;
+        Ok(SimpleClassTypeSignature { name, type_args })
+    }
+
+    fn class_type(&mut self) -> Result<ClassTypeSignature<'a>, SignatureError> {
+        self.expect(b'L')?;
+        let outer = self.simple_class_type()?;
+        let mut inner = Vec::new();
+        while self.peek() == Some(b'.') {
+            self.pos += 1;
+            inner.push(self.simple_class_type()?);
+        }
+        self.expect(b';')?;
+        Ok(ClassTypeSignature { outer, inner })
+    }
+
+    fn reference_type(&mut self) -> Result<TypeSignature<'a>, SignatureError> {
+        let pos = self.pos;
+        match self.peek().ok_or(SignatureError::UnexpectedEnd)? {
+            b'L' => Ok(TypeSignature::Class(self.class_type()?)),
+            b'T' => {
+                self.pos += 1;
+                let name = self.identifier(b";")?;
+                self.pos += 1;
+                Ok(TypeSignature::TypeVariable(name))
+            }
+            b'[' => {
+                self.pos += 1;
+                Ok(TypeSignature::Array(Box::new(self.java_type()?)))
+            }
+            byte => Err(SignatureError::InvalidChar { pos, byte }),
+        }
+    }
+
+    fn java_type(&mut self) -> Result<TypeSignature<'a>, SignatureError> {
+        let ty = match self.peek().ok_or(SignatureError::UnexpectedEnd)? {
+            b'B' => TypeSignature::Byte,
+            b'C' => TypeSignature::Char,
+            b'D' => TypeSignature::Double,
+            b'F' => TypeSignature::Float,
+            b'I' => TypeSignature::Int,
+            b'J' => TypeSignature::Long,
+            b'S' => TypeSignature::Short,
+            b'Z' => TypeSignature::Boolean,
+            _ => return self.reference_type(),
+        };
+        self.pos += 1;
+        Ok(ty)
+    }
+
+    fn type_params(&mut self) -> Result<Vec<TypeParameter<'a>>, SignatureError> {
+        let mut params = Vec::new();
+        if self.peek() != Some(b'<') {
+            return Ok(params);
+        }
+        self.pos += 1;
+        loop {
+            let name = self.identifier(b":")?;
+            self.pos += 1;
+            let class_bound = match self.peek() {
+                Some(b':') | Some(b'>') => None,
+                _ => Some(self.reference_type()?),
+            };
+            let mut interface_bounds = Vec::new();
+            while self.peek() == Some(b':') {
+                self.pos += 1;
+                interface_bounds.push(self.reference_type()?);
+            }
+            params.push(TypeParameter {
+                name,
+                class_bound,
+                interface_bounds,
+            });
+            if self.peek() == Some(b'>') {
+                self.pos += 1;
+                break Ok(params);
+            }
+        }
+    }
+
+    fn finish(&self) -> Result<(), SignatureError> {
+        if self.pos == self.sig.len() {
+            Ok(())
+        } else {
+            Err(SignatureError::TrailingData { pos: self.pos })
+        }
+    }
+}
+
+fn encode_type_args(args: &[TypeArgument], out: &mut Vec<u8>) {
+    if args.is_empty() {
+        return;
+    }
+    out.push(b'<');
+    for arg in args {
+        match arg {
+            TypeArgument::Any => out.push(b'*'),
+            TypeArgument::Exact(ty) => ty.encode(out),
+            TypeArgument::Extends(ty) => {
+                out.push(b'+');
+                ty.encode(out);
+            }
+            TypeArgument::Super(ty) => {
+                out.push(b'-');
+                ty.encode(out);
+            }
+        }
+    }
+    out.push(b'>');
+}
+
+fn encode_type_params(params: &[TypeParameter], out: &mut Vec<u8>) {
+    if params.is_empty() {
+        return;
+    }
+    out.push(b'<');
+    for param in params {
+        out.extend_from_slice(param.name.as_bytes());
+        out.push(b':');
+        if let Some(bound) = &param.class_bound {
+            bound.encode(out);
+        }
+        for bound in &param.interface_bounds {
+            out.push(b':');
+            bound.encode(out);
+        }
+    }
+    out.push(b'>');
+}
+
+fn rename_in_type_args<'a>(args: &mut [TypeArgument<'a>], from: &JStr, to: &'a JStr) {
+    for arg in args {
+        match arg {
+            TypeArgument::Any => {}
+            TypeArgument::Exact(ty) | TypeArgument::Extends(ty) | TypeArgument::Super(ty) => {
+                ty.rename_class(from, to)
+            }
+        }
+    }
+}
+
+fn rename_in_type_params<'a>(params: &mut [TypeParameter<'a>], from: &JStr, to: &'a JStr) {
+    for param in params {
+        for bound in param
+            .class_bound
+            .iter_mut()
+            .chain(param.interface_bounds.iter_mut())
+        {
+            bound.rename_class(from, to);
+        }
+    }
+}
+
+fn into_jstring(bytes: Vec<u8>) -> JString {
+    // SAFETY:
+    // Signatures are encoded from ASCII punctuation and the JStrs they contain, split on character boundaries
+    unsafe { JString::from_modified_utf8_unchecked(bytes) }
+}
+
+impl<'a> TypeSignature<'a> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            TypeSignature::Byte => out.push(b'B'),
+            TypeSignature::Char => out.push(b'C'),
+            TypeSignature::Double => out.push(b'D'),
+            TypeSignature::Float => out.push(b'F'),
+            TypeSignature::Int => out.push(b'I'),
+            TypeSignature::Long => out.push(b'J'),
+            TypeSignature::Short => out.push(b'S'),
+            TypeSignature::Boolean => out.push(b'Z'),
+            TypeSignature::Class(class) => class.encode(out),
+            TypeSignature::TypeVariable(name) => {
+                out.push(b'T');
+                out.extend_from_slice(name.as_bytes());
+                out.push(b';');
+            }
+            TypeSignature::Array(elem) => {
+                out.push(b'[');
+                elem.encode(out);
+            }
+        }
+    }
+
+    ///
+    /// Replaces every reference to the class `from` with `to`
+    pub fn rename_class(&mut self, from: &JStr, to: &'a JStr) {
+        match self {
+            TypeSignature::Class(class) => class.rename_class(from, to),
+            TypeSignature::Array(elem) => elem.rename_class(from, to),
+            _ => {}
+        }
+    }
+}
+
+impl<'a> ClassTypeSignature<'a> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(b'L');
+        out.extend_from_slice(self.outer.name.as_bytes());
+        encode_type_args(&self.outer.type_args, out);
+        for inner in &self.inner {
+            out.push(b'.');
+            out.extend_from_slice(inner.name.as_bytes());
+            encode_type_args(&inner.type_args, out);
+        }
+        out.push(b';');
+    }
+
+    ///
+    /// Replaces every reference to the class `from` with `to`.
+    ///
+    /// Only the outermost class is matched against `from`, since member classes are named relative to it
+    pub fn rename_class(&mut self, from: &JStr, to: &'a JStr) {
+        if self.outer.name == from {
+            self.outer.name = to;
+        }
+        rename_in_type_args(&mut self.outer.type_args, from, to);
+        for inner in &mut self.inner {
+            rename_in_type_args(&mut inner.type_args, from, to);
+        }
+    }
+}
+
+impl<'a> ClassSignature<'a> {
+    pub fn parse(sig: &'a JStr) -> Result<Self, SignatureError> {
+        let mut parser = SignatureParser { sig, pos: 0 };
+        let type_params = parser.type_params()?;
+        let superclass = parser.class_type()?;
+        let mut interfaces = Vec::new();
+        while parser.peek().is_some() {
+            interfaces.push(parser.class_type()?);
+        }
+        Ok(Self {
+            type_params,
+            superclass,
+            interfaces,
+        })
+    }
+
+    pub fn to_signature(&self) -> JString {
+        let mut out = Vec::new();
+        encode_type_params(&self.type_params, &mut out);
+        self.superclass.encode(&mut out);
+        for interface in &self.interfaces {
+            interface.encode(&mut out);
+        }
+        into_jstring(out)
+    }
+
+    pub fn rename_class(&mut self, from: &JStr, to: &'a JStr) {
+        rename_in_type_params(&mut self.type_params, from, to);
+        self.superclass.rename_class(from, to);
+        for interface in &mut self.interfaces {
+            interface.rename_class(from, to);
+        }
+    }
+}
+
+impl<'a> MethodSignature<'a> {
+    pub fn parse(sig: &'a JStr) -> Result<Self, SignatureError> {
+        let mut parser = SignatureParser { sig, pos: 0 };
+        let type_params = parser.type_params()?;
+        parser.expect(b'(')?;
+        let mut params = Vec::new();
+        while parser.peek() != Some(b')') {
+            params.push(parser.java_type()?);
+        }
+        parser.pos += 1;
+        let ret = if parser.peek() == Some(b'V') {
+            parser.pos += 1;
+            None
+        } else {
+            Some(parser.java_type()?)
+        };
+        let mut throws = Vec::new();
+        while parser.peek() == Some(b'^') {
+            parser.pos += 1;
+            throws.push(parser.reference_type()?);
+        }
+        parser.finish()?;
+        Ok(Self {
+            type_params,
+            params,
+            ret,
+            throws,
+        })
+    }
+
+    pub fn to_signature(&self) -> JString {
+        let mut out = Vec::new();
+        encode_type_params(&self.type_params, &mut out);
+        out.push(b'(');
+        for param in &self.params {
+            param.encode(&mut out);
+        }
+        out.push(b')');
+        match &self.ret {
+            Some(ret) => ret.encode(&mut out),
+            None => out.push(b'V'),
+        }
+        for throws in &self.throws {
+            out.push(b'^');
+            throws.encode(&mut out);
+        }
+        into_jstring(out)
+    }
+
+    pub fn rename_class(&mut self, from: &JStr, to: &'a JStr) {
+        rename_in_type_params(&mut self.type_params, from, to);
+        for ty in self
+            .params
+            .iter_mut()
+            .chain(self.ret.iter_mut())
+            .chain(self.throws.iter_mut())
+        {
+            ty.rename_class(from, to);
+        }
+    }
+}
+
+impl<'a> FieldSignature<'a> {
+    pub fn parse(sig: &'a JStr) -> Result<Self, SignatureError> {
+        let mut parser = SignatureParser { sig, pos: 0 };
+        let ty = parser.reference_type()?;
+        parser.finish()?;
+        Ok(Self { ty })
+    }
+
+    pub fn to_signature(&self) -> JString {
+        let mut out = Vec::new();
+        self.ty.encode(&mut out);
+        into_jstring(out)
+    }
+
+    pub fn rename_class(&mut self, from: &JStr, to: &'a JStr) {
+        self.ty.rename_class(from, to);
+    }
+}
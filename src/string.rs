@@ -15,9 +15,12 @@ macro_rules! debug_unreachable {
 
 use std::{
     borrow::{Borrow, BorrowMut, Cow},
+    convert::TryFrom,
     fmt::{Display, Formatter, Write},
     iter::{Copied, Enumerate, FusedIterator},
     ops::{Deref, DerefMut},
+    rc::Rc,
+    sync::Arc,
 };
 
 /// Represents a Slice of a String encoded in [Modified UTF-8](https://docs.oracle.com/en/java/javase/15/docs/api/java.base/java/io/DataInput.html#modified-utf-8).
@@ -32,6 +35,40 @@ mod sealed {
     pub trait Sealed {}
 }
 
+///
+/// A pattern that can be searched for in a [`JStr`]: a `char`, a `&JStr`, or a `&JString`.
+///
+/// Patterns are matched by their Modified UTF-8 encoding. An empty pattern never matches. This trait is sealed
+pub trait Pattern: sealed::Sealed + Copy {
+    #[doc(hidden)]
+    fn with_bytes<R>(self, f: impl FnOnce(&[u8]) -> R) -> R;
+}
+
+impl sealed::Sealed for char {}
+
+impl Pattern for char {
+    fn with_bytes<R>(self, f: impl FnOnce(&[u8]) -> R) -> R {
+        f(JStr::encode_char(self, &mut [0; 6]).as_bytes())
+    }
+}
+
+impl sealed::Sealed for &JStr {}
+
+impl Pattern for &JStr {
+    fn with_bytes<R>(self, f: impl FnOnce(&[u8]) -> R) -> R {
+        f(self.as_bytes())
+    }
+}
+
+impl sealed::Sealed for &JString {}
+
+impl Pattern for &JString {
+    fn with_bytes<R>(self, f: impl FnOnce(&[u8]) -> R) -> R {
+        f(self.as_bytes())
+    }
+}
+
+#[derive(Clone, Debug)]
 pub struct Bytes<'a>(Copied<std::slice::Iter<'a, u8>>);
 
 impl<'a> Iterator for Bytes<'a> {
@@ -76,6 +113,7 @@ impl<'a> DoubleEndedIterator for Bytes<'a> {
     }
 }
 
+#[derive(Clone, Debug)]
 pub struct JCharIndecies<'a>(Enumerate<Bytes<'a>>);
 
 #[allow(unreachable_code)]
@@ -124,6 +162,7 @@ impl<'a> Iterator for JCharIndecies<'a> {
 
 ///
 /// An iterator over a &ModifiedUtf8Str that produces u16s that are valid java characters
+#[derive(Clone, Debug)]
 pub struct JChars<'a>(Bytes<'a>);
 
 #[allow(unreachable_code)]
@@ -173,38 +212,389 @@ impl<'a> Iterator for JChars<'a> {
     }
 }
 
+impl<'a> JChars<'a> {
+    ///
+    /// The number of bytes of the string that have not been decoded yet
+    pub fn remaining_bytes(&self) -> usize {
+        self.0.len()
+    }
+}
+
 impl<'a> FusedIterator for JChars<'a> {}
 
-pub struct Chars<'a>(JChars<'a>);
+///
+/// A cursor over the jchars of a &JStr that can look at the next jchar without consuming it, for hand-written parsers.
+///
+/// Like [`JChars`], a char outside the Basic Multilingual Plane is seen as the two halves of its surrogate pair
+#[derive(Clone)]
+pub struct JCharCursor<'a> {
+    st: &'a JStr,
+    inner: JChars<'a>,
+}
+
+impl<'a> JCharCursor<'a> {
+    pub fn new(st: &'a JStr) -> Self {
+        Self {
+            st,
+            inner: st.jchars(),
+        }
+    }
+
+    ///
+    /// Returns the next jchar without consuming it
+    pub fn peek(&self) -> Option<u16> {
+        self.inner.clone().next()
+    }
+
+    ///
+    /// Consumes and returns the next jchar
+    pub fn advance(&mut self) -> Option<u16> {
+        self.inner.next()
+    }
+
+    ///
+    /// The byte offset in the string of the next jchar
+    pub fn position(&self) -> usize {
+        self.st.len() - self.inner.remaining_bytes()
+    }
+
+    ///
+    /// The part of the string that has not been consumed
+    pub fn rest(&self) -> &'a JStr {
+        // SAFETY:
+        // The cursor only stops between jchars, which are boundaries of valid Modified UTF-8
+        unsafe { JStr::from_modified_utf8_unchecked(&self.st.0[self.position()..]) }
+    }
+}
+
+///
+/// An iterator over the chars of a &JStr.
+///
+/// Surrogate pairs are combined into a single char. Unpaired surrogates, which Modified UTF-8 can represent but a char cannot, are replaced by U+FFFD
+#[derive(Clone, Debug)]
+pub struct Chars<'a> {
+    inner: JChars<'a>,
+}
+
+impl<'a> Chars<'a> {
+    fn new(inner: JChars<'a>) -> Self {
+        Self { inner }
+    }
+
+    ///
+    /// The number of bytes of the string that have not been decoded yet
+    pub fn remaining_bytes(&self) -> usize {
+        self.inner.remaining_bytes()
+    }
+}
 
 impl<'a> Iterator for Chars<'a> {
     type Item = char;
-    #[allow(unreachable_code)]
+
     fn next(&mut self) -> Option<char> {
-        let mut val = self.0.next()? as u32;
-        if let 0xd800..=0xdbff = val {
-            val = 0x10000
-                + ((val & 0x3ff) << 10)
-                + self.0.next().unwrap_or_else(|| {
-                    debug_unreachable!("Unexpected EOF in JStr");
-                    // SAFETY:
-                    // ModifiedUtf8Str is valid Modified Utf-8, so a multibyte character will have sufficient continuation bytes
-                    // Thus this line will never execute because self.0.next() will return Some.
-                    unsafe { core::hint::unreachable_unchecked() }
-                }) as u32
-                & 0x3fff;
+        let unit = self.inner.next()?;
+        if let 0xd800..=0xdbff = unit {
+            // Only consume the next code unit if it completes the pair
+            let mut ahead = self.inner.clone();
+            if let Some(low @ 0xdc00..=0xdfff) = ahead.next() {
+                self.inner = ahead;
+                let val = 0x10000 + (((unit & 0x3ff) as u32) << 10) + (low & 0x3ff) as u32;
+                return Some(char::from_u32(val).unwrap_or(char::REPLACEMENT_CHARACTER));
+            }
+            return Some(char::REPLACEMENT_CHARACTER);
         }
-
-        Some(<char>::from_u32(val).unwrap())
+        Some(char::from_u32(unit as u32).unwrap_or(char::REPLACEMENT_CHARACTER))
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
-        let (lo, hi) = self.0.size_hint();
+        let (lo, hi) = self.inner.size_hint();
+
+        (lo.div_ceil(2), hi)
+    }
+}
+
+impl<'a> FusedIterator for Chars<'a> {}
+
+/// The state shared by [`EscapeDefault`] and [`EscapeUnicode`]
+#[derive(Clone)]
+struct Escape<'a> {
+    inner: std::char::DecodeUtf16<JChars<'a>>,
+    /// The escape of the last char read, which is at most `\u{10ffff}`
+    buf: [u8; 10],
+    pos: u8,
+    len: u8,
+    /// Whether every char is escaped as `\u{...}`
+    unicode: bool,
+}
+
+impl<'a> Escape<'a> {
+    fn new(st: &'a JStr, unicode: bool) -> Self {
+        Self {
+            inner: std::char::decode_utf16(st.jchars()),
+            buf: [0; 10],
+            pos: 0,
+            len: 0,
+            unicode,
+        }
+    }
+}
+
+impl<'a> Iterator for Escape<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        if self.pos == self.len {
+            let next = self.inner.next()?;
+            let unicode = self.unicode;
+            let mut len = 0;
+            let mut push = |c: char| {
+                self.buf[len] = c as u8;
+                len += 1;
+            };
+            match next {
+                Ok(c) if unicode => c.escape_unicode().for_each(push),
+                Ok(c) => c.escape_default().for_each(push),
+                Err(e) => {
+                    // Surrogates always have 4 hex digits
+                    let unit = e.unpaired_surrogate();
+                    "\\u{".chars().for_each(&mut push);
+                    (0..4)
+                        .rev()
+                        .map(|i| std::char::from_digit((unit >> (i * 4)) as u32 & 0xf, 16).unwrap())
+                        .for_each(&mut push);
+                    push('}');
+                }
+            }
+            self.pos = 0;
+            self.len = len as u8;
+        }
+        let c = self.buf[self.pos as usize] as char;
+        self.pos += 1;
+        Some(c)
+    }
+}
+
+///
+/// An iterator over the chars of a &JStr escaped like [`str::escape_default`], producing only printable ASCII. Returned by [`JStr::escape_default`].
+///
+/// Unpaired surrogates are escaped by their code unit, such as `\u{d800}`, rather than being replaced
+#[derive(Clone)]
+pub struct EscapeDefault<'a>(Escape<'a>);
+
+impl<'a> Iterator for EscapeDefault<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        self.0.next()
+    }
+}
+
+impl<'a> FusedIterator for EscapeDefault<'a> {}
+
+impl<'a> Display for EscapeDefault<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.clone().try_for_each(|c| f.write_char(c))
+    }
+}
+
+///
+/// An iterator over the chars of a &JStr, each escaped as `\u{...}` like [`str::escape_unicode`]. Returned by [`JStr::escape_unicode`].
+///
+/// Unpaired surrogates are escaped by their code unit, such as `\u{d800}`
+#[derive(Clone)]
+pub struct EscapeUnicode<'a>(Escape<'a>);
+
+impl<'a> Iterator for EscapeUnicode<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        self.0.next()
+    }
+}
+
+impl<'a> FusedIterator for EscapeUnicode<'a> {}
+
+impl<'a> Display for EscapeUnicode<'a> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        self.clone().try_for_each(|c| f.write_char(c))
+    }
+}
+
+fn find_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return None;
+    }
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+fn rfind_bytes(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    if needle.is_empty() {
+        return None;
+    }
+    haystack
+        .windows(needle.len())
+        .rposition(|window| window == needle)
+}
+
+///
+/// An iterator over the parts of a &JStr separated by a [`Pattern`], from the start. Returned by [`JStr::split`]
+pub struct Split<'a, P> {
+    rest: Option<&'a JStr>,
+    pat: P,
+}
+
+impl<'a, P: Pattern> Iterator for Split<'a, P> {
+    type Item = &'a JStr;
+
+    fn next(&mut self) -> Option<&'a JStr> {
+        let rest = self.rest?;
+        match self
+            .pat
+            .with_bytes(|pat| rest.split_around(pat, find_bytes))
+        {
+            Some((part, tail)) => {
+                self.rest = Some(tail);
+                Some(part)
+            }
+            None => self.rest.take(),
+        }
+    }
+}
+
+impl<'a, P: Pattern> FusedIterator for Split<'a, P> {}
+
+///
+/// Like [`Split`], but each part keeps the separator that ends it. Returned by [`JStr::split_inclusive`]
+pub struct SplitInclusive<'a, P> {
+    rest: &'a JStr,
+    pat: P,
+}
+
+impl<'a, P: Pattern> Iterator for SplitInclusive<'a, P> {
+    type Item = &'a JStr;
+
+    fn next(&mut self) -> Option<&'a JStr> {
+        let rest = self.rest;
+        if rest.0.is_empty() {
+            return None;
+        }
+        let end = self
+            .pat
+            .with_bytes(|pat| find_bytes(&rest.0, pat).map(|start| start + pat.len()))
+            .unwrap_or(rest.0.len());
+        let (part, tail) = rest.0.split_at(end);
+        // SAFETY:
+        // The pattern is valid Modified UTF-8, so the end of a match of it is on a character boundary
+        unsafe {
+            self.rest = JStr::from_modified_utf8_unchecked(tail);
+            Some(JStr::from_modified_utf8_unchecked(part))
+        }
+    }
+}
+
+impl<'a, P: Pattern> FusedIterator for SplitInclusive<'a, P> {}
+
+///
+/// An iterator over the parts of a &JStr separated by a [`Pattern`], from the end. Returned by [`JStr::rsplit`]
+pub struct RSplit<'a, P> {
+    rest: Option<&'a JStr>,
+    pat: P,
+}
+
+impl<'a, P: Pattern> Iterator for RSplit<'a, P> {
+    type Item = &'a JStr;
+
+    fn next(&mut self) -> Option<&'a JStr> {
+        let rest = self.rest?;
+        match self
+            .pat
+            .with_bytes(|pat| rest.split_around(pat, rfind_bytes))
+        {
+            Some((head, part)) => {
+                self.rest = Some(head);
+                Some(part)
+            }
+            None => self.rest.take(),
+        }
+    }
+}
+
+impl<'a, P: Pattern> FusedIterator for RSplit<'a, P> {}
+
+///
+/// Like [`RSplit`], but yields at most `n` parts, the last of which is the rest of the string. Returned by [`JStr::rsplitn`]
+pub struct RSplitN<'a, P> {
+    inner: RSplit<'a, P>,
+    n: usize,
+}
+
+impl<'a, P: Pattern> Iterator for RSplitN<'a, P> {
+    type Item = &'a JStr;
+
+    fn next(&mut self) -> Option<&'a JStr> {
+        match self.n {
+            0 => None,
+            1 => {
+                self.n = 0;
+                self.inner.rest.take()
+            }
+            _ => {
+                self.n -= 1;
+                self.inner.next()
+            }
+        }
+    }
+}
+
+impl<'a, P: Pattern> FusedIterator for RSplitN<'a, P> {}
+
+///
+/// An iterator over a &JStr that produces subslices of a bounded byte length, split on character boundaries
+pub struct Chunks<'a> {
+    rest: &'a JStr,
+    max: usize,
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = &'a JStr;
+
+    fn next(&mut self) -> Option<&'a JStr> {
+        if self.rest.len() == 0 {
+            return None;
+        }
+
+        let mut end = self.max.min(self.rest.len());
+        while !self.rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        if end == 0 {
+            // The first character is longer than `max`, so yield it whole
+            end = 1;
+            while !self.rest.is_char_boundary(end) {
+                end += 1;
+            }
+        }
+
+        let (chunk, rest) = self.rest.0.split_at(end);
+        // SAFETY:
+        // Both halves are split on a character boundary of a valid JStr, so they are both valid Modified UTF-8
+        unsafe {
+            self.rest = JStr::from_modified_utf8_unchecked(rest);
+            Some(JStr::from_modified_utf8_unchecked(chunk))
+        }
+    }
 
-        (lo / 2, hi)
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.rest.len();
+        // Each chunk is at most `max` bytes (or a single character of at most 6 bytes), and at least 1 byte
+        (len.div_ceil(self.max.max(6)), Some(len))
     }
 }
 
+impl<'a> FusedIterator for Chunks<'a> {}
+
 #[derive(Clone, Copy, Debug)]
 pub struct ModifiedUtf8Error {
     pos: usize,
@@ -212,78 +602,181 @@ pub struct ModifiedUtf8Error {
 }
 
 impl ModifiedUtf8Error {
+    ///
+    /// The length of the valid prefix of the input, which is also where the invalid char starts
     pub fn valid_up_to(&self) -> usize {
         self.pos
     }
 
+    ///
+    /// The number of bytes to skip past `valid_up_to` to resume decoding at the next char, like [`std::str::Utf8Error::error_len`].
+    ///
+    /// None if the input ends in the middle of a char. Unpaired surrogates are valid Modified UTF-8, so they are never the cause of the error
     pub fn error_len(&self) -> Option<usize> {
         self.len
     }
 }
 
-fn validate_modified_utf8(x: &[u8]) -> Result<(), ModifiedUtf8Error> {
-    let mut iter = x.into_iter().enumerate();
-    let mut pair_start = None;
-    while let Some((pos, b)) = iter.next() {
-        if *b == 0 {
-            if let Some((_, pos)) = pair_start {
-                return Err(ModifiedUtf8Error { pos, len: Some(3) }); // Ensure Erroneous unpaired surrogates are detected first
+impl Display for ModifiedUtf8Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.len {
+            Some(_) => write!(f, "invalid Modified UTF-8 at byte {}", self.pos),
+            None => write!(f, "incomplete Modified UTF-8 char at byte {}", self.pos),
+        }
+    }
+}
+
+impl std::error::Error for ModifiedUtf8Error {}
+
+///
+/// Which of the JVM's rules for the contents of a Utf8 constant was broken, as reported by [`JStr::from_jvm_utf8`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum JvmUtf8ErrorKind {
+    /// A raw `00` byte. NUL is encoded as `C0 80` instead
+    Nul,
+    /// A byte of `F0` or above, which would start a 4-byte UTF-8 sequence. Chars outside the Basic Multilingual Plane are encoded as a surrogate pair instead
+    FourByteLead,
+    /// A continuation byte where a char should start
+    UnexpectedContinuation,
+    /// A char is missing some of its continuation bytes
+    Truncated,
+    /// A high surrogate not followed by a low surrogate, or a low surrogate not preceded by a high surrogate
+    UnpairedSurrogate,
+}
+
+///
+/// The error returned by [`JStr::from_jvm_utf8`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct JvmUtf8Error {
+    pos: usize,
+    kind: JvmUtf8ErrorKind,
+}
+
+impl JvmUtf8Error {
+    ///
+    /// The offset of the first byte of the offending char. Everything before it is valid
+    pub fn valid_up_to(&self) -> usize {
+        self.pos
+    }
+
+    pub fn kind(&self) -> JvmUtf8ErrorKind {
+        self.kind
+    }
+}
+
+impl Display for JvmUtf8Error {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let reason = match self.kind {
+            JvmUtf8ErrorKind::Nul => "raw NUL byte (NUL must be encoded as C0 80)",
+            JvmUtf8ErrorKind::FourByteLead => {
+                "byte of F0 or above (chars outside the Basic Multilingual Plane must be encoded as a surrogate pair)"
             }
-            return Err(ModifiedUtf8Error { pos, len: Some(1) });
-        } else if *b & 0xc0 == 0x80 {
-            if let Some((_, pos)) = pair_start {
-                return Err(ModifiedUtf8Error { pos, len: Some(3) }); // Same here
+            JvmUtf8ErrorKind::UnexpectedContinuation => "unexpected continuation byte",
+            JvmUtf8ErrorKind::Truncated => "incomplete multi-byte char",
+            JvmUtf8ErrorKind::UnpairedSurrogate => "unpaired surrogate",
+        };
+        write!(f, "invalid Utf8 constant at byte {}: {}", self.pos, reason)
+    }
+}
+
+impl std::error::Error for JvmUtf8Error {}
+
+///
+/// Checks the stricter rules of [`JStr::from_jvm_utf8`], once the bytes are known to be valid Modified UTF-8
+fn validate_surrogate_pairs(x: &[u8]) -> Result<(), JvmUtf8Error> {
+    let is_high = |pos: usize| x[pos] == 0xed && x[pos + 1] & 0xf0 == 0xa0;
+    let is_low = |pos: usize| x[pos] == 0xed && x[pos + 1] & 0xf0 == 0xb0;
+    let mut pos = 0;
+    while pos < x.len() {
+        let len = match x[pos] {
+            0x00..=0x7f => 1,
+            0xc0..=0xdf => 2,
+            _ => 3,
+        };
+        if len == 3 && is_high(pos) {
+            if pos + 3 < x.len() && is_low(pos + 3) {
+                pos += 6;
+                continue;
             }
-            return Err(ModifiedUtf8Error { pos, len: Some(1) });
-        } else if *b & 0xe0 == 0xc0 {
-            if let Some((_, pos)) = pair_start {
-                return Err(ModifiedUtf8Error { pos, len: Some(3) });
+        } else if !(len == 3 && is_low(pos)) {
+            pos += len;
+            continue;
+        }
+        return Err(JvmUtf8Error {
+            pos,
+            kind: JvmUtf8ErrorKind::UnpairedSurrogate,
+        });
+    }
+    Ok(())
+}
+
+///
+/// The number of bytes [`JStr::encode_char`] writes for `c`: 1 for ASCII other than NUL, 2 for NUL and up to U+07FF, 3 for the rest of the Basic Multilingual Plane, and 6 for a surrogate pair
+pub const fn char_modified_utf8_len(c: char) -> usize {
+    match c as u32 {
+        0x01..=0x7f => 1,
+        0x00 | 0x80..=0x7ff => 2,
+        0x800..=0xffff => 3,
+        _ => 6,
+    }
+}
+
+fn encode_jchar(unit: u16, out: &mut Vec<u8>) {
+    if unit == 0 {
+        out.extend_from_slice(&[0xc0, 0x80]); // Modified Utf-8 uses 2 bytes to encode the Null Character
+    } else if unit < 0x80 {
+        out.push(unit as u8);
+    } else if unit < 0x800 {
+        out.extend_from_slice(&[((unit >> 6) | 0xc0) as u8, (unit & 0x3f | 0x80) as u8]);
+    } else {
+        out.extend_from_slice(&[
+            ((unit >> 12) | 0xe0) as u8,
+            ((unit >> 6) & 0x3f | 0x80) as u8,
+            (unit & 0x3f | 0x80) as u8,
+        ]);
+    }
+}
+
+///
+/// Surrogates need not be paired: like a Java String, Modified UTF-8 can hold any sequence of UTF-16 code units, and javac emits lone surrogates for string constants that contain them.
+/// So there is no span of a broken pair to report. A high surrogate followed by ASCII, by another high surrogate, or by the end of the input is valid here, and only a surrogate whose own 3 bytes are cut short is an error, like any other char.
+/// [`JStr::from_jvm_utf8`] is the one that rejects the lone surrogate, at its first byte
+fn validate_modified_utf8(x: &[u8]) -> Result<(), ModifiedUtf8Error> {
+    const ONES: u64 = 0x0101_0101_0101_0101;
+    const HIGH_BITS: u64 = 0x8080_8080_8080_8080;
+
+    let mut pos = 0;
+    while pos < x.len() {
+        // Skip runs of ASCII a word at a time. A word is only skipped if none of its bytes are NUL either,
+        // which subtracting 1 from each byte detects once no high bits are set
+        while let Some(word) = x.get(pos..pos + 8) {
+            let word = u64::from_ne_bytes(<[u8; 8]>::try_from(word).unwrap());
+            if word & HIGH_BITS != 0 || word.wrapping_sub(ONES) & HIGH_BITS != 0 {
+                break;
             }
-            let (_, cont) = iter.next().ok_or(ModifiedUtf8Error {
-                pos: pos + 1,
-                len: None,
-            })?;
+            pos += 8;
+        }
+        let b = match x.get(pos) {
+            Some(b) => *b,
+            None => break,
+        };
+        let conts = if b == 0 || b & 0xc0 == 0x80 || b & 0xf0 == 0xf0 {
+            return Err(ModifiedUtf8Error { pos, len: Some(1) });
+        } else if b & 0xe0 == 0xc0 {
+            1
+        } else if b & 0xf0 == 0xe0 {
+            2
+        } else {
+            0
+        };
+        for n in 1..=conts {
+            let cont = x.get(pos + n).ok_or(ModifiedUtf8Error { pos, len: None })?;
+            // The byte that is not a continuation byte may start the next char, so only the bytes before it are invalid
             if *cont & 0xc0 != 0x80 {
-                return Err(ModifiedUtf8Error { pos, len: Some(2) });
+                return Err(ModifiedUtf8Error { pos, len: Some(n) });
             }
-        } else if *b & 0xf0 == 0xe0 {
-            let (_, cont1) = iter.next().ok_or(ModifiedUtf8Error {
-                pos: pos + 1,
-                len: None,
-            })?;
-            if *cont1 & 0xc0 != 0x80 {
-                return Err(ModifiedUtf8Error { pos, len: Some(3) });
-            }
-            let (_, cont2) = iter.next().ok_or(ModifiedUtf8Error {
-                pos: pos + 2,
-                len: None,
-            })?;
-            if *cont2 & 0xc0 != 0x80 {
-                return Err(ModifiedUtf8Error { pos, len: Some(3) });
-            }
-            let val = ((*b & 0xf) as u16) << 12
-                | ((*cont1 & 0x3f) as u16) << 6
-                | ((*cont2 & 0x3f) as u16);
-            if let 0xd800..=0xdbff = val {
-                if let Some((_, pos)) = pair_start {
-                    return Err(ModifiedUtf8Error { pos, len: Some(3) });
-                }
-                pair_start = Some((val, pos));
-            } else if let 0xdc00..=0xdfff = val {
-                if let Some((high, pos)) = pair_start {
-                    let val = 0x10000 + (((high as u32) & 0x3fff) << 10) + ((val as u32) & 0x3fff);
-                    if <char>::from_u32(val).is_none() {
-                        return Err(ModifiedUtf8Error { pos, len: Some(6) });
-                    }
-                }
-            } else if let Some((_, pos)) = pair_start {
-                return Err(ModifiedUtf8Error { pos, len: Some(3) });
-            }
-        } else if *b & 0xf0 == 0xf0 {
-            return Err(ModifiedUtf8Error { pos, len: Some(1) });
-        } else if let Some((_, pos)) = pair_start {
-            return Err(ModifiedUtf8Error { pos, len: Some(3) });
         }
+        pos += conts + 1;
     }
 
     Ok(())
@@ -300,6 +793,35 @@ impl JStr {
         Ok(unsafe { Self::from_modified_utf8_unchecked(x) })
     }
 
+    ///
+    /// Converts bytes holding the contents of a Utf8 constant, enforcing every rule the JVM has for them, and explaining which rule was broken on failure.
+    ///
+    /// Unlike [`JStr::from_modified_utf8`], this also rejects unpaired surrogates. javac emits them for string literals that contain them, so the parser does not use this
+    pub fn from_jvm_utf8(x: &[u8]) -> Result<&Self, JvmUtf8Error> {
+        if let Err(e) = validate_modified_utf8(x) {
+            let pos = e.valid_up_to();
+            return Err(match e.error_len() {
+                None => JvmUtf8Error {
+                    pos,
+                    kind: JvmUtf8ErrorKind::Truncated,
+                },
+                Some(_) => JvmUtf8Error {
+                    pos,
+                    kind: match x[pos] {
+                        0 => JvmUtf8ErrorKind::Nul,
+                        0xf0..=0xff => JvmUtf8ErrorKind::FourByteLead,
+                        0x80..=0xbf => JvmUtf8ErrorKind::UnexpectedContinuation,
+                        _ => JvmUtf8ErrorKind::Truncated,
+                    },
+                },
+            });
+        }
+        validate_surrogate_pairs(x)?;
+        // SAFETY:
+        // validation performed above, so x is valid Modified UTF-8
+        Ok(unsafe { Self::from_modified_utf8_unchecked(x) })
+    }
+
     pub fn from_modified_utf8_mut(x: &mut [u8]) -> Result<&mut Self, ModifiedUtf8Error> {
         validate_modified_utf8(x)?;
         // SAFETY:
@@ -352,60 +874,352 @@ impl JStr {
     }
 
     pub fn chars(&self) -> Chars {
-        Chars(self.jchars())
+        Chars::new(self.jchars())
     }
 
     pub fn jchars(&self) -> JChars {
         JChars(self.bytes())
     }
 
-    pub fn is_ascii(&self) -> bool {
-        self.bytes().all(|b| b < 0x80)
+    ///
+    /// Escapes each char like [`char::escape_default`], giving a printable ASCII representation that can be embedded in Java or Rust source.
+    ///
+    /// Unpaired surrogates are escaped as `\u{d800}` and so on, so no information is lost
+    pub fn escape_default(&self) -> EscapeDefault<'_> {
+        EscapeDefault(Escape::new(self, false))
     }
 
-    pub fn make_ascii_lowercase(&mut self) {
-        for b in &mut self.0 {
-            if 0x40 < *b && *b < 0x5b {
-                *b |= 0x20;
-            }
-        }
+    ///
+    /// Escapes every char as `\u{...}` like [`char::escape_unicode`], including ASCII. A surrogate pair is escaped as the char it encodes.
+    ///
+    /// Unpaired surrogates are escaped as `\u{d800}` and so on, so no information is lost
+    pub fn escape_unicode(&self) -> EscapeUnicode<'_> {
+        EscapeUnicode(Escape::new(self, true))
     }
 
-    pub fn make_ascii_uppercase(&mut self) {
-        for b in &mut self.0 {
-            if 0x60 < *b && *b < 0x7b {
-                *b &= !0x20;
+    ///
+    /// Whether the two strings hold the same sequence of chars, even if they encode them differently.
+    ///
+    /// Byte equality (`==`) is stricter: like the JVM, JStr accepts overlong encodings, such as `C1 81` for `A`, which decode and display as the same chars as the shortest encoding.
+    /// Unpaired surrogates are compared by their value, so they only equal the same unpaired surrogate
+    pub fn eq_scalars(&self, other: &JStr) -> bool {
+        self.jchars().eq(other.jchars())
+    }
+
+    ///
+    /// Compares two strings exactly like java's `String.compareTo`: by UTF-16 code unit, returning the difference of the first pair of units that differ, or else the difference in length.
+    ///
+    /// This differs from the `Ord` impl, which compares bytes, in how NUL sorts: it is the lowest code unit, but encoded as `C0 80`
+    pub fn java_compare_to(&self, other: &JStr) -> i32 {
+        let mut this = self.jchars();
+        let mut other = other.jchars();
+        loop {
+            match (this.next(), other.next()) {
+                (Some(a), Some(b)) if a != b => return a as i32 - b as i32,
+                (Some(_), Some(_)) => {}
+                (Some(_), None) => return this.count() as i32 + 1,
+                (None, Some(_)) => return -(other.count() as i32 + 1),
+                (None, None) => return 0,
             }
         }
     }
 
-    pub fn encode_char(c: char, bytes: &mut [u8; 6]) -> &JStr {
+    ///
+    /// Checks whether `idx` is the start of a character (or the end of the string).
+    ///
+    /// The low half of a surrogate pair is not considered a boundary, since it belongs to the same character as the high half
+    pub fn is_char_boundary(&self, idx: usize) -> bool {
+        if idx == 0 || idx == self.len() {
+            true
+        } else if idx > self.len() || self.0[idx] & 0xc0 == 0x80 {
+            false
+        } else {
+            // A low surrogate is encoded as ED B0..BF .., and is only part of a pair if it directly follows a high surrogate (ED A0..AF ..)
+            !(idx >= 3
+                && self.0[idx] == 0xed
+                && self.0[idx + 1] & 0xf0 == 0xb0
+                && self.0[idx - 3] == 0xed
+                && self.0[idx - 2] & 0xf0 == 0xa0)
+        }
+    }
+
+    ///
+    /// Returns an iterator over the byte offsets at which each character of the string starts, in order.
+    ///
+    /// This yields 0 for a non-empty string, but never `self.len()`. A surrogate pair is one character, so the offset of its low half is not yielded
+    pub fn char_boundaries(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.len()).filter(move |&idx| self.is_char_boundary(idx))
+    }
+
+    ///
+    /// Returns an iterator over subslices of `self` that are at most `max` bytes long, each ending on a character boundary.
+    ///
+    /// A chunk may be shorter than `max` when the next character does not fit. If a single character is longer than `max`, it is yielded as a chunk on its own.
+    ///
+    /// Panics if `max` is 0
+    pub fn chunks_by_bytes(&self, max: usize) -> Chunks<'_> {
+        assert!(max != 0, "chunk size must be non-zero");
+        Chunks { rest: self, max }
+    }
+
+    ///
+    /// Encodes a sequence of UTF-16 code units into `buf`, and borrows the result as a JStr.
+    ///
+    /// Any previous contents of `buf` are discarded. Lone surrogates are encoded individually, as with [`JString::from_jchar`]
+    pub fn from_jchars<'buf>(units: &[u16], buf: &'buf mut Vec<u8>) -> &'buf JStr {
+        buf.clear();
+        for &unit in units {
+            encode_jchar(unit, buf);
+        }
+        // SAFETY:
+        // buf only contains the Modified UTF-8 encoding of units
+        unsafe { Self::from_modified_utf8_unchecked(buf) }
+    }
+
+    ///
+    /// Counts the characters in the string, without decoding them.
+    ///
+    /// This counts the same characters as `self.chars().count()`, so a surrogate pair counts as one character.
+    pub fn count_chars(&self) -> usize {
+        let leads = self.0.iter().filter(|&&b| b & 0xc0 != 0x80).count();
+        // A high surrogate (ED A0..AF ..) followed by a low surrogate (ED B0..BF ..) has two lead bytes, but is a single char
+        let pairs = self
+            .0
+            .windows(5)
+            .filter(|w| w[0] == 0xed && w[1] & 0xf0 == 0xa0 && w[3] == 0xed && w[4] & 0xf0 == 0xb0)
+            .count();
+        leads - pairs
+    }
+
+    fn replace_ascii_byte(&self, from: u8, to: u8) -> Cow<'_, JStr> {
+        if self.0.contains(&from) {
+            let bytes = self
+                .0
+                .iter()
+                .map(|&b| if b == from { to } else { b })
+                .collect();
+            // SAFETY:
+            // Replacing one ASCII byte with another cannot affect multibyte characters, whose bytes are all >= 0x80
+            Cow::Owned(unsafe { JString::from_modified_utf8_unchecked(bytes) })
+        } else {
+            Cow::Borrowed(self)
+        }
+    }
+
+    ///
+    /// Converts an internal class name (`java/lang/Object`) to a binary name (`java.lang.Object`), borrowing if there is nothing to replace
+    pub fn to_binary_name(&self) -> Cow<'_, JStr> {
+        self.replace_ascii_byte(b'/', b'.')
+    }
+
+    ///
+    /// Converts a binary class name (`java.lang.Object`) to an internal name (`java/lang/Object`), borrowing if there is nothing to replace
+    pub fn to_internal_name(&self) -> Cow<'_, JStr> {
+        self.replace_ascii_byte(b'.', b'/')
+    }
+
+    pub fn is_ascii(&self) -> bool {
+        self.bytes().all(|b| b < 0x80)
+    }
+
+    pub fn make_ascii_lowercase(&mut self) {
+        for b in &mut self.0 {
+            if 0x40 < *b && *b < 0x5b {
+                *b |= 0x20;
+            }
+        }
+    }
+
+    pub fn make_ascii_uppercase(&mut self) {
+        for b in &mut self.0 {
+            if 0x60 < *b && *b < 0x7b {
+                *b &= !0x20;
+            }
+        }
+    }
+
+    ///
+    /// Encodes `c` into `bytes`, returning the part of `bytes` that was written.
+    ///
+    /// NUL is encoded as 2 bytes, and a char outside the Basic Multilingual Plane as the 6 byte encoding of its surrogate pair
+    pub fn encode_char(c: char, bytes: &mut [u8; 6]) -> &JStr {
         let x = c as u32;
-        if x < 0x80 {
+        if x != 0 && x < 0x80 {
             bytes[0] = x as u8;
             unsafe { Self::from_modified_utf8_unchecked(&bytes[..1]) }
         } else if x < 0x800 {
             bytes[0] = 0xc0 | ((x >> 6) & 0x1f) as u8;
             bytes[1] = 0x80 | (x & 0x3f) as u8;
             unsafe { Self::from_modified_utf8_unchecked(&bytes[..2]) }
-        } else if x < 0x80000 {
+        } else if x < 0x10000 {
             bytes[0] = 0xe0 | ((x >> 12) & 0xf) as u8;
             bytes[1] = 0x80 | ((x >> 6) & 0x3f) as u8;
             bytes[2] = 0x80 | (x & 0x3f) as u8;
             unsafe { Self::from_modified_utf8_unchecked(&bytes[..3]) }
         } else {
-            let mut u16 @ [h, w] = [0; 2];
-            c.encode_utf16(&mut u16);
+            let mut units = [0; 2];
+            c.encode_utf16(&mut units);
+            let [h, w] = units;
             bytes[0] = 0xe0 | ((h >> 12) & 0xf) as u8;
             bytes[1] = 0x80 | ((h >> 6) & 0x3f) as u8;
             bytes[2] = 0x80 | (h & 0x3f) as u8;
             bytes[3] = 0xe0 | ((w >> 12) & 0xf) as u8;
             bytes[4] = 0x80 | ((w >> 6) & 0x3f) as u8;
             bytes[5] = 0x80 | (w & 0x3f) as u8;
-            unsafe { Self::from_modified_utf8_unchecked(&bytes[..3]) }
+            unsafe { Self::from_modified_utf8_unchecked(&bytes[..6]) }
+        }
+    }
+
+    ///
+    /// Encodes `c` like [`JStr::encode_char`], appending it to `out`
+    pub fn encode_char_to_vec(c: char, out: &mut Vec<u8>) {
+        for &mut unit in c.encode_utf16(&mut [0; 2]) {
+            encode_jchar(unit, out);
+        }
+    }
+
+    ///
+    /// Encodes `c` like [`JStr::encode_char`], returning a buffer and the number of bytes of it that were written
+    pub fn encode_char_array(c: char) -> ([u8; 6], usize) {
+        let mut bytes = [0; 6];
+        let len = Self::encode_char(c, &mut bytes).0.len();
+        (bytes, len)
+    }
+
+    ///
+    /// Whether this is a valid unqualified name of a field, local variable, or formal parameter: a non-empty name containing none of `.`, `;`, `[`, or `/`
+    pub fn is_valid_unqualified_name(&self) -> bool {
+        !self.0.is_empty()
+            && !self
+                .0
+                .iter()
+                .any(|b| matches!(b, b'.' | b';' | b'[' | b'/'))
+    }
+
+    ///
+    /// Whether this is a valid method name: `<init>`, `<clinit>`, or an unqualified name that also does not contain `<` or `>`
+    pub fn is_valid_method_name(&self) -> bool {
+        matches!(&self.0, b"<init>" | b"<clinit>")
+            || (self.is_valid_unqualified_name()
+                && !self.0.iter().any(|b| matches!(b, b'<' | b'>')))
+    }
+
+    ///
+    /// Whether this is a valid class or interface name in internal form, such as `java/lang/Object`: unqualified names separated by `/`.
+    ///
+    /// Array types, which Class constants name by their descriptor, are not class names
+    pub fn is_valid_class_name(&self) -> bool {
+        self.0
+            .split(|&b| b == b'/')
+            .all(|part| !part.is_empty() && !part.iter().any(|b| matches!(b, b'.' | b';' | b'[')))
+    }
+
+    ///
+    /// Removes every leading and trailing occurrence of `pat`. A `&JStr` pattern is removed as a whole, as many times as it repeats
+    pub fn trim_matches<P: Pattern>(&self, pat: P) -> &JStr {
+        pat.with_bytes(|pat| self.trim_start_bytes(pat).trim_end_bytes(pat))
+    }
+
+    ///
+    /// Removes every leading occurrence of `pat`
+    pub fn trim_start_matches<P: Pattern>(&self, pat: P) -> &JStr {
+        pat.with_bytes(|pat| self.trim_start_bytes(pat))
+    }
+
+    ///
+    /// Removes every trailing occurrence of `pat`
+    pub fn trim_end_matches<P: Pattern>(&self, pat: P) -> &JStr {
+        pat.with_bytes(|pat| self.trim_end_bytes(pat))
+    }
+
+    ///
+    /// Splits the string into the parts separated by `pat`
+    pub fn split<P: Pattern>(&self, pat: P) -> Split<'_, P> {
+        Split {
+            rest: Some(self),
+            pat,
         }
     }
 
+    ///
+    /// Splits the string into the parts separated by `pat`, keeping the separator at the end of each part.
+    ///
+    /// Unlike [`JStr::split`], a separator at the end of the string doesn't produce an empty last part
+    pub fn split_inclusive<P: Pattern>(&self, pat: P) -> SplitInclusive<'_, P> {
+        SplitInclusive { rest: self, pat }
+    }
+
+    ///
+    /// Splits the string into the parts separated by `pat`, starting from the end
+    pub fn rsplit<P: Pattern>(&self, pat: P) -> RSplit<'_, P> {
+        RSplit {
+            rest: Some(self),
+            pat,
+        }
+    }
+
+    ///
+    /// Splits the string into at most `n` parts separated by `pat`, starting from the end. The last part is whatever remains of the start of the string.
+    ///
+    /// For example, `rsplitn(2, '/')` splits an internal class name into its simple name and its package
+    pub fn rsplitn<P: Pattern>(&self, n: usize, pat: P) -> RSplitN<'_, P> {
+        RSplitN {
+            inner: self.rsplit(pat),
+            n,
+        }
+    }
+
+    ///
+    /// Splits the string around the match of `pat` found by `find`, which returns its byte position
+    fn split_around(
+        &self,
+        pat: &[u8],
+        find: fn(&[u8], &[u8]) -> Option<usize>,
+    ) -> Option<(&JStr, &JStr)> {
+        let start = find(&self.0, pat)?;
+        let (head, rest) = self.0.split_at(start);
+        // SAFETY:
+        // pat is valid Modified UTF-8, so a match of it starts and ends on a character boundary
+        unsafe {
+            Some((
+                Self::from_modified_utf8_unchecked(head),
+                Self::from_modified_utf8_unchecked(&rest[pat.len()..]),
+            ))
+        }
+    }
+
+    fn trim_start_bytes(&self, pat: &[u8]) -> &JStr {
+        let mut rest = &self.0;
+        if !pat.is_empty() {
+            while let Some(stripped) = rest.strip_prefix(pat) {
+                rest = stripped;
+            }
+        }
+        // SAFETY:
+        // pat is valid Modified UTF-8, so removing it from the start leaves a boundary between characters
+        unsafe { Self::from_modified_utf8_unchecked(rest) }
+    }
+
+    fn trim_end_bytes(&self, pat: &[u8]) -> &JStr {
+        let mut rest = &self.0;
+        if !pat.is_empty() {
+            while let Some(stripped) = rest.strip_suffix(pat) {
+                rest = stripped;
+            }
+        }
+        // SAFETY:
+        // pat is valid Modified UTF-8 and so starts with a lead byte, so removing it from the end leaves a boundary between characters
+        unsafe { Self::from_modified_utf8_unchecked(rest) }
+    }
+
+    ///
+    /// Borrows this string as a str, if its bytes are also valid UTF-8.
+    ///
+    /// This is the case unless the string contains a NUL, a char outside the Basic Multilingual Plane, or an unpaired surrogate, which Modified UTF-8 encodes differently or UTF-8 cannot encode at all
+    pub fn as_utf8(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
+
     pub fn into_str(&self) -> Cow<str> {
         match std::str::from_utf8(&self.0) {
             Ok(s) => Cow::Borrowed(s),
@@ -413,64 +1227,48 @@ impl JStr {
         }
     }
 
+    ///
+    /// Converts the string to an OsString, replacing unpaired surrogates with U+FFFD
+    #[cfg(feature = "path")]
+    pub fn to_os_string(&self) -> std::ffi::OsString {
+        self.into_str().into_owned().into()
+    }
+
+    ///
+    /// Converts an internal class name such as `com/foo/Bar` into a relative path such as `com/foo/Bar.class`, using the separator of the target OS, and replacing unpaired surrogates with U+FFFD.
+    ///
+    /// No `.` is added if `extension` is empty. The components are not checked, so a name containing `..` can refer outside of the directory the path is joined to
+    #[cfg(feature = "path")]
+    pub fn to_path(&self, extension: &str) -> std::path::PathBuf {
+        let mut path: std::path::PathBuf =
+            self.split('/').map(|part| part.to_os_string()).collect();
+        if !extension.is_empty() {
+            let mut name = path.file_name().unwrap_or_default().to_os_string();
+            name.push(".");
+            name.push(extension);
+            path.set_file_name(name);
+        }
+        path
+    }
+
     pub fn from_utf8_str(st: &str) -> Cow<JStr> {
         match Self::from_str(st) {
             Ok(st) => Cow::Borrowed(st),
             Err(e) => {
-                let mut bytes = st.as_bytes();
-                let mut vec = Vec::new();
-                let (prefix, rest) = bytes.split_at(e.valid_up_to());
-                vec.extend_from_slice(prefix);
-                bytes = rest;
-                loop {
-                    // SAFETY:
-                    // bytes is a valid UTF-8 string
-                    let c = unsafe { std::str::from_utf8_unchecked(bytes) }
-                        .chars()
-                        .next()
-                        .unwrap();
-                    bytes = &bytes[..c.len_utf8()];
-                    if c as u32 == 0 {
-                        vec.extend_from_slice(&[0xc0, 0x80]); // Modified Utf-8 uses 2 bytes to encode the Null Character
-                    } else {
-                        let mut utf16 = [0u16; 2];
-                        let utf16 = c.encode_utf16(&mut utf16);
-                        for &mut u in utf16 {
-                            if u < 0x80 {
-                                vec.push(u as u8);
-                            } else if u < 0x800 {
-                                vec.extend_from_slice(&[
-                                    ((u >> 6) | 0xc0) as u8,
-                                    (u & 0x3f | 0x80) as u8,
-                                ]);
-                            } else {
-                                vec.extend_from_slice(&[
-                                    ((u >> 12) | 0xe0) as u8,
-                                    ((u >> 6) & 0x3f | 0x80) as u8,
-                                    (u & 0x3f | 0x80) as u8,
-                                ])
-                            }
-                        }
-                    }
-
-                    if let Err(e) = self::validate_modified_utf8(bytes) {
-                        let (prefix, rest) = bytes.split_at(e.valid_up_to());
-                        vec.extend_from_slice(prefix);
-                        bytes = rest;
-                    } else {
-                        vec.extend_from_slice(bytes);
-                        break;
-                    }
+                // Everything before the first NUL or supplementary char is already valid Modified UTF-8
+                let (prefix, rest) = st.split_at(e.valid_up_to());
+                let mut vec = Vec::with_capacity(st.len() + 2);
+                vec.extend_from_slice(prefix.as_bytes());
+                for c in rest.chars() {
+                    Self::encode_char_to_vec(c, &mut vec);
                 }
 
                 // SAFETY:
-                // We have validated the contents inserted into `vec`
+                // We have encoded every char of `st` into `vec`
                 Cow::Owned(JString(vec))
             }
         }
     }
-
-    pub fn escape_debug(&self) -> EscapeDebug {}
 }
 
 impl AsRef<[u8]> for JStr {
@@ -491,6 +1289,38 @@ impl AsMut<JStr> for JStr {
     }
 }
 
+impl<'a> IntoIterator for &'a JStr {
+    type Item = char;
+    type IntoIter = Chars<'a>;
+
+    fn into_iter(self) -> Chars<'a> {
+        self.chars()
+    }
+}
+
+/// Decodes the sequence at the start of `rest`, which is valid Modified UTF-8 but not UTF-8, returning the char it decodes to and the bytes after it.
+///
+/// The sequence is either a 2 or 3 byte encoding, such as `C0 80` for NUL or an overlong encoding, or a surrogate.
+/// A high surrogate (ED A0..AF ..) directly followed by a low surrogate (ED B0..BF ..) is decoded as a pair, and lone surrogates become U+FFFD
+fn split_first_non_utf8(rest: &[u8]) -> (char, &[u8]) {
+    let len = if rest[0] & 0xe0 == 0xc0 {
+        2
+    } else if rest[0] == 0xed
+        && rest[1] & 0xf0 == 0xa0
+        && rest.get(3) == Some(&0xed)
+        && rest.get(4).is_some_and(|b| b & 0xf0 == 0xb0)
+    {
+        6
+    } else {
+        3
+    };
+    let (seq, tail) = rest.split_at(len);
+    let c = Chars::new(JChars(Bytes(seq.iter().copied())))
+        .next()
+        .unwrap();
+    (c, tail)
+}
+
 impl Display for JStr {
     fn fmt(&self, fmt: &mut Formatter<'_>) -> std::fmt::Result {
         let mut inner = self.as_bytes();
@@ -503,19 +1333,9 @@ impl Display for JStr {
                     // SAFETY:
                     // The prefix of an array passed to from_utf8 that returns an error, of length `e.valid_up_to()` is valid UTF-8
                     unsafe { std::str::from_utf8_unchecked(prefix) }.fmt(fmt)?;
-                    if rest[0] == 0xc0 && rest[1] == 0x80 {
-                        unsafe { char::from_u32_unchecked(0) }.fmt(fmt)?;
-                        inner = &rest[2..];
-                    } else {
-                        // We have a surrogate pair
-                        // Let's decode it
-                        let (char, tail) = rest.split_at(6);
-                        inner = tail;
-                        Chars(JChars(Bytes(char.iter().copied())))
-                            .next()
-                            .unwrap()
-                            .fmt(fmt)?;
-                    }
+                    let (c, tail) = split_first_non_utf8(rest);
+                    c.fmt(fmt)?;
+                    inner = tail;
                 }
             }
         }
@@ -538,25 +1358,9 @@ impl ::core::fmt::Debug for JStr {
                         &unsafe { std::str::from_utf8_unchecked(prefix) }.escape_debug(),
                         fmt,
                     )?;
-                    if rest[0] == 0xc0 && rest[1] == 0x80 {
-                        std::fmt::Display::fmt(
-                            &unsafe { char::from_u32_unchecked(0) }.escape_debug(),
-                            fmt,
-                        )?;
-                        inner = &rest[2..];
-                    } else {
-                        // We have a surrogate pair
-                        // Let's decode it
-                        let (char, tail) = rest.split_at(6);
-                        inner = tail;
-                        std::fmt::Display::fmt(
-                            &Chars(JChars(Bytes(char.iter().copied())))
-                                .next()
-                                .unwrap()
-                                .escape_debug(),
-                            fmt,
-                        )?;
-                    }
+                    let (c, tail) = split_first_non_utf8(rest);
+                    std::fmt::Display::fmt(&c.escape_debug(), fmt)?;
+                    inner = tail;
                 }
             }
         }
@@ -583,10 +1387,21 @@ impl FromModifiedUtf8Error {
     }
 }
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+///
+/// An owned string encoded in Modified UTF-8, analogous to String.
+///
+/// There is intentionally no `From<&str>`: a str containing a NUL or a char outside the Basic Multilingual Plane must be re-encoded, which should not happen implicitly.
+/// Use [`JStr::from_utf8_str`], which only allocates when re-encoding is needed, or `<&JStr>::try_from`, which only accepts a str that is already valid Modified UTF-8
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 pub struct JString(Vec<u8>);
 
 impl JString {
+    ///
+    /// Creates an empty string, for example to build one with `write!`
+    pub fn new() -> Self {
+        Self(Vec::new())
+    }
+
     pub fn from_modified_utf8(vec: Vec<u8>) -> Result<Self, FromModifiedUtf8Error> {
         if let Err(err) = self::validate_modified_utf8(&vec) {
             Err(FromModifiedUtf8Error { err, vec })
@@ -601,15 +1416,122 @@ impl JString {
         Self(vec)
     }
 
+    ///
+    /// Decodes arbitrary bytes as UTF-8, replacing invalid sequences with U+FFFD like [`String::from_utf8_lossy`], and re-encodes the text as Modified UTF-8
+    pub fn from_utf8_lossy(bytes: &[u8]) -> Self {
+        let mut st = JString::new();
+        // Writing to a JString never fails
+        let _ = st.write_str(&String::from_utf8_lossy(bytes));
+        st
+    }
+
     pub fn from_boxed_modified_utf8_str(st: Box<JStr>) -> Self {
         Self(Vec::from(unsafe {
             Box::from_raw(Box::into_raw(st) as *mut [u8])
         }))
     }
 
+    ///
+    /// Converts the string into an `Arc<JStr>`, so that it can be shared between threads without copying it again
+    pub fn into_arc(self) -> Arc<JStr> {
+        let arc: Arc<[u8]> = Arc::from(self.0);
+        // SAFETY:
+        // JStr is a transparent wrapper around [u8], and the bytes are valid Modified UTF-8
+        unsafe { Arc::from_raw(Arc::into_raw(arc) as *const JStr) }
+    }
+
+    ///
+    /// Converts the string into an `Rc<JStr>`
+    pub fn into_rc(self) -> Rc<JStr> {
+        let rc: Rc<[u8]> = Rc::from(self.0);
+        // SAFETY:
+        // As in `into_arc`
+        unsafe { Rc::from_raw(Rc::into_raw(rc) as *const JStr) }
+    }
+
+    ///
+    /// Converts the string into a C string holding its Modified UTF-8 bytes, as JNI functions such as `FindClass` expect.
+    ///
+    /// This cannot fail, since Modified UTF-8 never contains a `00` byte. A NUL in the string stays encoded as the two bytes `C0 80`, so it does not end the C string
+    pub fn into_c_string(self) -> std::ffi::CString {
+        // SAFETY:
+        // Valid Modified UTF-8 contains no 0 bytes
+        unsafe { std::ffi::CString::from_vec_unchecked(self.0) }
+    }
+
+    ///
+    /// Borrows the string as a JStr. Unlike `as_ref()` or `&*`, this never needs type annotations to pick a target
+    ///
+    /// ```
+    /// use lcjvm::string::{JStr, JString};
+    ///
+    /// let names = vec![JString::from('a'), JString::from('b')];
+    /// // A path to `AsRef::as_ref` would leave the target type for inference to guess
+    /// let borrowed: Vec<&JStr> = names.iter().map(JString::as_jstr).collect();
+    /// assert_eq!(borrowed[1].as_bytes(), b"b");
+    /// ```
+    pub fn as_jstr(&self) -> &JStr {
+        self
+    }
+
+    ///
+    /// Mutably borrows the string as a JStr. Unlike `as_mut()` or `&mut *`, this never needs type annotations to pick a target
+    pub fn as_mut_jstr(&mut self) -> &mut JStr {
+        self
+    }
+
+    ///
+    /// Encodes a single UTF-16 code unit, such as a java `char`.
+    ///
+    /// Unlike [`JStr::encode_char`], this can produce a lone surrogate, which Modified UTF-8 encodes as a 3 byte sequence of its own
+    pub fn from_jchar(unit: u16) -> JString {
+        let mut vec = Vec::with_capacity(3);
+        encode_jchar(unit, &mut vec);
+        // SAFETY:
+        // encode_jchar produces the Modified UTF-8 encoding of unit
+        unsafe { Self::from_modified_utf8_unchecked(vec) }
+    }
+
+    ///
+    /// Encodes a sequence of UTF-16 code units, such as the contents of a java `char[]`.
+    ///
+    /// Unlike [`String::from_utf16`], lone surrogates are kept rather than rejected, so this is the exact inverse of [`JString::encode_utf16`]
+    pub fn from_utf16_units(units: Vec<u16>) -> JString {
+        let mut vec = Vec::with_capacity(units.len());
+        JStr::from_jchars(&units, &mut vec);
+        // SAFETY:
+        // from_jchars filled vec with the Modified UTF-8 encoding of units
+        unsafe { Self::from_modified_utf8_unchecked(vec) }
+    }
+
+    ///
+    /// Decodes the string into UTF-16 code units, the inverse of [`JString::from_utf16_units`]
     pub fn encode_utf16(&self) -> Vec<u16> {
         self.jchars().collect()
     }
+
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.0.shrink_to_fit()
+    }
+
+    ///
+    /// Shortens the string to `new_len` bytes, like [`String::truncate`]. Does nothing if the string is not longer than that.
+    ///
+    /// Panics if `new_len` is not a char boundary, including the middle of a surrogate pair
+    pub fn truncate(&mut self, new_len: usize) {
+        if new_len < self.len() {
+            assert!(
+                self.is_char_boundary(new_len),
+                "{} is not a char boundary",
+                new_len
+            );
+            self.0.truncate(new_len)
+        }
+    }
 }
 
 impl Deref for JString {
@@ -646,12 +1568,66 @@ impl Borrow<JStr> for JString {
     }
 }
 
+///
+/// Allows maps keyed by JString to be queried with the Modified UTF-8 bytes of a key. JString hashes and compares exactly like its bytes
+impl Borrow<[u8]> for JString {
+    fn borrow(&self) -> &[u8] {
+        &self.0
+    }
+}
+
 impl BorrowMut<JStr> for JString {
     fn borrow_mut(&mut self) -> &mut JStr {
         self
     }
 }
 
+impl PartialEq<JStr> for JString {
+    fn eq(&self, other: &JStr) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl PartialEq<&JStr> for JString {
+    fn eq(&self, other: &&JStr) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl PartialEq<JString> for JStr {
+    fn eq(&self, other: &JString) -> bool {
+        self.0 == *other.0
+    }
+}
+
+impl PartialEq<JString> for &JStr {
+    fn eq(&self, other: &JString) -> bool {
+        self.0 == *other.0
+    }
+}
+
+///
+/// Iterates over the characters of the string, like [`JStr::chars`]
+///
+/// ```
+/// use lcjvm::string::{JStr, JString};
+///
+/// let st: JString = JStr::from_utf8_str("h\u{e9}\0!").into_owned();
+/// let mut chars = Vec::new();
+/// for c in &st {
+///     chars.push(c);
+/// }
+/// assert_eq!(chars, ['h', '\u{e9}', '\0', '!']);
+/// ```
+impl<'a> IntoIterator for &'a JString {
+    type Item = char;
+    type IntoIter = Chars<'a>;
+
+    fn into_iter(self) -> Chars<'a> {
+        self.chars()
+    }
+}
+
 impl ToOwned for JStr {
     type Owned = JString;
 
@@ -662,6 +1638,73 @@ impl ToOwned for JStr {
     }
 }
 
+impl From<char> for JString {
+    fn from(c: char) -> Self {
+        let mut vec = Vec::with_capacity(char_modified_utf8_len(c));
+        JStr::encode_char_to_vec(c, &mut vec);
+        Self(vec)
+    }
+}
+
+///
+/// Allows building a JString with `write!`. Each str is re-encoded as needed, so it may contain NULs and chars outside the Basic Multilingual Plane
+impl Write for JString {
+    fn write_str(&mut self, s: &str) -> std::fmt::Result {
+        match JStr::from_str(s) {
+            Ok(st) => self.0.extend_from_slice(st.as_bytes()),
+            Err(_) => {
+                for c in s.chars() {
+                    self.write_char(c)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn write_char(&mut self, c: char) -> std::fmt::Result {
+        JStr::encode_char_to_vec(c, &mut self.0);
+        Ok(())
+    }
+}
+
+impl From<JString> for Arc<JStr> {
+    fn from(st: JString) -> Self {
+        st.into_arc()
+    }
+}
+
+impl From<&JStr> for Arc<JStr> {
+    fn from(st: &JStr) -> Self {
+        st.to_owned().into_arc()
+    }
+}
+
+impl From<JString> for Rc<JStr> {
+    fn from(st: JString) -> Self {
+        st.into_rc()
+    }
+}
+
+impl From<&JStr> for Rc<JStr> {
+    fn from(st: &JStr) -> Self {
+        st.to_owned().into_rc()
+    }
+}
+
+impl From<Arc<JStr>> for JString {
+    fn from(st: Arc<JStr>) -> Self {
+        (*st).to_owned()
+    }
+}
+
+impl<'a> TryFrom<&'a str> for &'a JStr {
+    type Error = ModifiedUtf8Error;
+
+    fn try_from(st: &'a str) -> Result<Self, Self::Error> {
+        JStr::from_str(st)
+    }
+}
+
 impl core::fmt::Debug for JString {
     fn fmt(&self, f: &mut Formatter) -> core::fmt::Result {
         <JStr as core::fmt::Debug>::fmt(self, f)
@@ -673,3 +1716,797 @@ impl Display for JString {
         <JStr as Display>::fmt(self, f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    // "x\uD800y", as javac writes the string constant `LONE` in testdata/src/Fixture.java
+    const LONE: &[u8] = b"x\xed\xa0\x80y";
+
+    #[test]
+    fn lone_surrogate_is_modified_utf8() {
+        let st = JStr::from_modified_utf8(LONE).unwrap();
+        assert_eq!(st.jchars().collect::<Vec<_>>(), [0x78, 0xd800, 0x79]);
+        assert_eq!(
+            JStr::from_jvm_utf8(LONE).unwrap_err().kind(),
+            JvmUtf8ErrorKind::UnpairedSurrogate
+        );
+    }
+
+    #[test]
+    fn javac_emits_lone_surrogates() {
+        let class = testutil::read(testutil::FIXTURE);
+        assert!(class.utf8_constants().any(|(_, st)| st.as_bytes() == LONE));
+        testutil::assert_roundtrip(testutil::FIXTURE);
+    }
+
+    #[test]
+    fn chunks_do_not_split_surrogate_pairs() {
+        let st = JStr::from_utf8_str("a\u{1F600}b");
+        assert_eq!(st.len(), 8);
+        for max in 1..=8 {
+            let chunks: Vec<&JStr> = st.chunks_by_bytes(max).collect();
+            let joined: Vec<u8> = chunks.iter().flat_map(|c| c.as_bytes()).copied().collect();
+            assert_eq!(joined, st.as_bytes());
+            for chunk in chunks {
+                assert!(chunk.len() <= max || chunk.count_chars() == 1);
+                assert!(chunk.len() != 3 || chunk.as_bytes()[0] != 0xed);
+            }
+        }
+        let chunks: Vec<&[u8]> = st.chunks_by_bytes(4).map(JStr::as_bytes).collect();
+        assert_eq!(chunks, [&b"a"[..], &st.as_bytes()[1..7], b"b"]);
+    }
+
+    #[test]
+    fn name_conversion_borrows_when_unchanged() {
+        let internal = JStr::from_str("java/lang/String").unwrap();
+        let binary = JStr::from_str("java.lang.String").unwrap();
+        assert!(matches!(internal.to_binary_name(), Cow::Owned(ref st) if **st == *binary));
+        assert!(matches!(binary.to_internal_name(), Cow::Owned(ref st) if **st == *internal));
+        assert!(matches!(binary.to_binary_name(), Cow::Borrowed(st) if st == binary));
+        assert!(matches!(internal.to_internal_name(), Cow::Borrowed(st) if st == internal));
+
+        let simple = JStr::from_str("Main").unwrap();
+        assert!(matches!(simple.to_binary_name(), Cow::Borrowed(_)));
+        assert!(matches!(simple.to_internal_name(), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn count_chars_matches_chars() {
+        let mut lone = b"x".to_vec();
+        lone.extend_from_slice(LONE);
+        let strings = vec![
+            JStr::from_utf8_str("").into_owned(),
+            JStr::from_utf8_str("ascii").into_owned(),
+            JStr::from_utf8_str("a\0b\u{e9}\u{20ac}").into_owned(),
+            JStr::from_utf8_str("\u{1F600}\u{1F600}x").into_owned(),
+            JStr::from_modified_utf8(&lone).unwrap().to_owned(),
+        ];
+        for st in strings {
+            assert_eq!(st.count_chars(), st.chars().count());
+        }
+        assert_eq!(JStr::from_utf8_str("\u{1F600}").count_chars(), 1);
+    }
+
+    #[test]
+    fn from_jchar_encodes_single_units() {
+        assert_eq!(JString::from_jchar(0x41).as_bytes(), b"A");
+        assert_eq!(JString::from_jchar(0x20ac).as_bytes(), b"\xe2\x82\xac");
+        assert_eq!(JString::from_jchar(0xd800).as_bytes(), b"\xed\xa0\x80");
+        assert_eq!(JString::from_jchar(0).as_bytes(), b"\xc0\x80");
+    }
+
+    #[test]
+    fn from_jchars_encodes_into_buffer() {
+        let mut buf = b"stale".to_vec();
+        let st = JStr::from_jchars(&[0x41, 0x20ac, 0xd83d, 0xde00], &mut buf);
+        assert_eq!(st, &*JStr::from_utf8_str("A\u{20ac}\u{1F600}"));
+        assert_eq!(buf.len(), 10);
+        assert!(JStr::from_modified_utf8(&buf).is_ok());
+        assert_eq!(JStr::from_jvm_utf8(&buf).map(JStr::len), Ok(10));
+    }
+
+    #[test]
+    fn unpaired_surrogates_decode_to_replacement_chars() {
+        const R: char = char::REPLACEMENT_CHARACTER;
+        let cases: Vec<(&[u8], Vec<char>)> = vec![
+            (b"\xed\xa0\x80A", vec![R, 'A']),
+            (b"\xed\xa0\x80\xed\xa0\x80", vec![R, R]),
+            (
+                b"\xed\xa0\x80\xed\xa0\x80\xed\xb0\x80",
+                vec![R, '\u{10000}'],
+            ),
+            (b"A\xed\xa0\x80", vec!['A', R]),
+            (b"\xed\xb0\x80A", vec![R, 'A']),
+        ];
+        for (bytes, chars) in cases {
+            let st = JStr::from_modified_utf8(bytes).unwrap();
+            assert_eq!(st.chars().collect::<Vec<_>>(), chars);
+            assert_eq!(st.into_str().chars().collect::<Vec<_>>(), chars);
+        }
+    }
+
+    #[test]
+    fn as_utf8_borrows_only_valid_utf8() {
+        let ascii = JStr::from_str("java/lang/Object").unwrap();
+        assert_eq!(ascii.as_utf8(), Some("java/lang/Object"));
+        let bmp = JStr::from_utf8_str("caf\u{e9}");
+        assert_eq!(bmp.as_utf8(), Some("caf\u{e9}"));
+
+        let nul = JStr::from_utf8_str("a\0b");
+        assert_eq!(nul.as_bytes(), b"a\xc0\x80b");
+        assert_eq!(nul.as_utf8(), None);
+        assert_eq!(JStr::from_utf8_str("\u{1F600}").as_utf8(), None);
+        assert_eq!(JStr::from_modified_utf8(LONE).unwrap().as_utf8(), None);
+    }
+
+    #[test]
+    fn conversions_from_char_and_str() {
+        let astral = JString::from('\u{1F600}');
+        assert_eq!(astral.as_bytes(), b"\xed\xa0\xbd\xed\xb8\x80");
+        assert_eq!(astral.chars().collect::<Vec<_>>(), ['\u{1F600}']);
+        assert_eq!(JString::from('\0').as_bytes(), b"\xc0\x80");
+
+        let ok = <&JStr>::try_from("java/lang/Object").unwrap();
+        assert_eq!(ok.as_bytes(), b"java/lang/Object");
+        let err = <&JStr>::try_from("a\0b").unwrap_err();
+        assert_eq!((err.valid_up_to(), err.error_len()), (1, Some(1)));
+    }
+
+    #[test]
+    fn write_macro_builds_modified_utf8() {
+        use std::fmt::Write;
+
+        let (param, ret) = ("Ljava/lang/String;", 'V');
+        let mut desc = JString::new();
+        write!(desc, "({}I)", param).unwrap();
+        write!(desc, "{}", ret).unwrap();
+        assert_eq!(desc.as_bytes(), b"(Ljava/lang/String;I)V");
+
+        let mut st = JString::new();
+        let emoji = '\u{1F600}';
+        write!(st, "a\0{}", emoji).unwrap();
+        assert_eq!(st.as_bytes(), b"a\xc0\x80\xed\xa0\xbd\xed\xb8\x80");
+        assert!(JStr::from_jvm_utf8(st.as_bytes()).is_ok());
+    }
+
+    #[test]
+    fn char_boundaries_are_char_starts() {
+        // 1, 2, 3, and 6 byte chars, then a lone high surrogate and a lone low surrogate
+        let mut bytes = JStr::from_utf8_str("a\u{e9}\u{20ac}\u{1F600}")
+            .as_bytes()
+            .to_vec();
+        bytes.extend_from_slice(b"\xed\xa0\x80x\xed\xb0\x80");
+        let st = JStr::from_modified_utf8(&bytes).unwrap();
+        let boundaries: Vec<usize> = st.char_boundaries().collect();
+        assert_eq!(boundaries, [0, 1, 3, 6, 12, 15, 16]);
+
+        let mut start = 0;
+        let mut starts = Vec::new();
+        for c in st.chars() {
+            starts.push(start);
+            start += match c {
+                char::REPLACEMENT_CHARACTER => 3,
+                c => char_modified_utf8_len(c),
+            };
+        }
+        assert_eq!(boundaries, starts);
+        assert!(JStr::from_str("")
+            .unwrap()
+            .char_boundaries()
+            .next()
+            .is_none());
+    }
+
+    #[test]
+    fn java_compare_to_matches_java() {
+        let cmp =
+            |a: &str, b: &str| JStr::from_utf8_str(a).java_compare_to(&JStr::from_utf8_str(b));
+        // The results of String.compareTo in Java
+        assert_eq!(cmp("apple", "banana"), -1);
+        assert_eq!(cmp("banana", "apple"), 1);
+        assert_eq!(cmp("abc", "ab"), 1);
+        assert_eq!(cmp("", "hello"), -5);
+        assert_eq!(cmp("same", "same"), 0);
+        assert_eq!(cmp("a", "\u{e9}"), 0x61 - 0xe9);
+        assert_eq!(cmp("\u{ffff}", "\u{1F600}"), 0xffff - 0xd83d);
+
+        // NUL is encoded as C0 80, so it sorts after U+0001 by bytes but before it in UTF-16
+        let (nul, one) = (JStr::from_utf8_str("\0"), JStr::from_utf8_str("\u{1}"));
+        assert!(nul.as_bytes() > one.as_bytes());
+        assert_eq!(nul.java_compare_to(&one), -1);
+        assert_eq!(cmp("a\0", "a"), 1);
+    }
+
+    #[test]
+    fn name_validity() {
+        let name = |s: &str| JStr::from_utf8_str(s).into_owned();
+        assert!(name("<init>").is_valid_method_name());
+        assert!(name("<clinit>").is_valid_method_name());
+        assert!(name("<init>").is_valid_unqualified_name());
+        assert!(!name("<foo>").is_valid_method_name());
+        assert!(name("toString").is_valid_method_name());
+
+        assert!(!name("a;b").is_valid_unqualified_name());
+        assert!(!name("a;b").is_valid_method_name());
+        assert!(!name("a;b").is_valid_class_name());
+        assert!(!name("").is_valid_unqualified_name());
+
+        assert!(name("java/lang/Object").is_valid_class_name());
+        assert!(!name("java/lang/Object").is_valid_unqualified_name());
+        assert!(!name("java.lang.Object").is_valid_class_name());
+        assert!(!name("java//Object").is_valid_class_name());
+        assert!(!name("/Object").is_valid_class_name());
+        assert!(!name("[Ljava/lang/Object;").is_valid_class_name());
+    }
+
+    #[test]
+    fn into_arc_derefs_to_the_original_content() {
+        let st = JStr::from_modified_utf8(LONE).unwrap().to_owned();
+        let arc = st.clone().into_arc();
+        assert_eq!(&*arc, &*st);
+        assert_eq!(arc.as_bytes(), LONE);
+
+        let shared = Arc::clone(&arc);
+        let from_thread = std::thread::spawn(move || shared.jchars().collect::<Vec<_>>())
+            .join()
+            .unwrap();
+        assert_eq!(from_thread, [0x78, 0xd800, 0x79]);
+
+        assert_eq!(JString::from(arc), st);
+        assert_eq!(&*st.clone().into_rc(), &*st);
+        assert_eq!(&*Arc::<JStr>::from(&*st), &*st);
+    }
+
+    #[test]
+    fn display_and_debug_decode_overlong_and_surrogate_sequences() {
+        let cases: [(&[u8], &str); 6] = [
+            (b"\xc1\x81", "A"),
+            (b"\xc1\x81BC", "ABC"),
+            (b"x\xe0\x81\x81\xc0\x80y", "xA\0y"),
+            (b"x\xed\xa0\x80y", "x\u{fffd}y"),
+            (b"\xed\xb0\x80\xed\xa0\x80", "\u{fffd}\u{fffd}"),
+            (b"\xed\xa0\xbd\xed\xb8\x80!", "\u{1f600}!"),
+        ];
+        for (bytes, expected) in cases {
+            let st = JStr::from_modified_utf8(bytes).unwrap();
+            assert_eq!(st.to_string(), expected);
+            assert_eq!(format!("{:?}", st), format!("{:?}", expected));
+        }
+    }
+
+    #[test]
+    fn eq_scalars_ignores_overlong_encodings() {
+        let short = JStr::from_modified_utf8(b"xAy").unwrap();
+        let overlong = JStr::from_modified_utf8(b"x\xc1\x81y").unwrap();
+        assert_ne!(short, overlong);
+        assert!(short.eq_scalars(overlong));
+        assert!(overlong.eq_scalars(short));
+        assert_eq!(overlong.to_string(), short.to_string());
+        assert_eq!(format!("{:?}", overlong), "\"xAy\"");
+
+        // U+1F600 as a surrogate pair
+        let pair = JStr::from_modified_utf8(b"\xed\xa0\xbd\xed\xb8\x80").unwrap();
+        assert!(pair.eq_scalars(pair));
+
+        let lone = JStr::from_modified_utf8(LONE).unwrap();
+        assert!(lone.eq_scalars(lone));
+        assert!(!lone.eq_scalars(short));
+        assert!(!short.eq_scalars(JStr::from_modified_utf8(b"xA").unwrap()));
+    }
+
+    #[test]
+    fn shrink_after_truncate() {
+        let mut st = JString::from_utf8_lossy(b"java/lang/Object");
+        st.truncate(9);
+        assert_eq!(st.as_bytes(), b"java/lang");
+        st.shrink_to_fit();
+        assert!(st.capacity() < 16);
+        assert_eq!(st.as_bytes(), b"java/lang");
+        st.truncate(20);
+        assert_eq!(st.len(), 9);
+    }
+
+    #[test]
+    #[should_panic(expected = "not a char boundary")]
+    fn truncate_inside_a_surrogate_pair() {
+        let mut st = JStr::from_modified_utf8(b"\xed\xa0\xbd\xed\xb8\x80")
+            .unwrap()
+            .to_owned();
+        st.truncate(3);
+    }
+
+    #[test]
+    fn lossy_ingest_replaces_invalid_utf8() {
+        let st = JString::from_utf8_lossy(b"ab\xffc\xe2\x82");
+        assert_eq!(st.chars().collect::<String>(), "ab\u{fffd}c\u{fffd}");
+        assert_eq!(st.as_bytes(), "ab\u{fffd}c\u{fffd}".as_bytes());
+
+        let st = JString::from_utf8_lossy("x\0\u{1f600}".as_bytes());
+        assert_eq!(st.as_bytes(), b"x\xc0\x80\xed\xa0\xbd\xed\xb8\x80");
+    }
+
+    #[test]
+    fn cloned_iterators_advance_independently() {
+        let st = JStr::from_modified_utf8(b"a\xed\xa0\xbd\xed\xb8\x80b").unwrap();
+        let mut chars = st.chars();
+        assert_eq!(chars.next(), Some('a'));
+        let mut branch = chars.clone();
+        assert_eq!(branch.next(), Some('\u{1f600}'));
+        assert_eq!(branch.next(), Some('b'));
+        assert_eq!(branch.next(), None);
+        assert_eq!(chars.collect::<String>(), "\u{1f600}b");
+
+        let mut jchars = st.jchars();
+        let branch = jchars.clone();
+        assert_eq!(jchars.next(), Some(0x61));
+        assert_eq!(branch.count(), 4);
+
+        let mut bytes = st.bytes();
+        let branch = bytes.clone();
+        bytes.next();
+        assert_eq!(branch.len(), st.len());
+        assert!(format!("{:?}", bytes).starts_with("Bytes"));
+    }
+
+    #[test]
+    fn remaining_bytes_decrease_by_each_encoding() {
+        // 'a' takes 1 byte, NUL 2, U+00E9 2, a surrogate pair 6, and U+20AC 3
+        let st = JStr::from_modified_utf8(b"a\xc0\x80\xc3\xa9\xed\xa0\xbd\xed\xb8\x80\xe2\x82\xac")
+            .unwrap();
+        let mut chars = st.chars();
+        let mut remaining = vec![chars.remaining_bytes()];
+        while chars.next().is_some() {
+            remaining.push(chars.remaining_bytes());
+        }
+        assert_eq!(remaining, [14, 13, 11, 9, 3, 0]);
+
+        // JChars yields each half of the pair separately
+        let mut jchars = st.jchars();
+        let mut remaining = vec![jchars.remaining_bytes()];
+        while jchars.next().is_some() {
+            remaining.push(jchars.remaining_bytes());
+        }
+        assert_eq!(remaining, [14, 13, 11, 9, 6, 3, 0]);
+    }
+
+    #[test]
+    fn utf16_round_trip_keeps_lone_surrogates() {
+        let units = [0x78, 0xd800, 0x79, 0xd83d, 0xde00, 0, 0xdc00];
+        assert!(String::from_utf16(&units).is_err());
+        let st = JString::from_utf16_units(units.to_vec());
+        assert_eq!(st.encode_utf16(), units);
+        assert_eq!(&st.as_bytes()[..5], LONE);
+
+        // A lone high surrogate at the end
+        let st = JString::from_utf16_units(vec![0x61, 0xd800]);
+        assert_eq!(st.as_bytes(), b"a\xed\xa0\x80");
+        assert_eq!(st.encode_utf16(), [0x61, 0xd800]);
+        assert!(JString::from_utf16_units(Vec::new()).as_bytes().is_empty());
+    }
+
+    #[test]
+    fn unpaired_high_surrogate_error_spans() {
+        const HIGH: &[u8] = b"\xed\xa0\xbd";
+
+        for (bytes, lone_at) in [
+            // Followed by ASCII
+            ([HIGH, b"A"].concat(), 0),
+            // Followed by another high surrogate, which is then followed by its low half
+            ([HIGH, HIGH, b"\xed\xb8\x80"].concat(), 0),
+            // At the end of the input
+            ([b"ab", HIGH].concat(), 2),
+        ] {
+            assert!(JStr::from_modified_utf8(&bytes).is_ok(), "{:x?}", bytes);
+            let err = JStr::from_jvm_utf8(&bytes).unwrap_err();
+            assert_eq!(err.kind(), JvmUtf8ErrorKind::UnpairedSurrogate);
+            assert_eq!(err.valid_up_to(), lone_at, "{:x?}", bytes);
+        }
+
+        // A high surrogate cut short by ASCII: only its first two bytes are skipped, since the A starts the next char
+        let err = JStr::from_modified_utf8(b"x\xed\xa0A").unwrap_err();
+        assert_eq!((err.valid_up_to(), err.error_len()), (1, Some(2)));
+        // Cut short by another high surrogate
+        let err = JStr::from_modified_utf8(&[b"\xed\xa0", HIGH].concat()).unwrap_err();
+        assert_eq!((err.valid_up_to(), err.error_len()), (0, Some(2)));
+        // Cut short by the end of the input
+        let err = JStr::from_modified_utf8(b"ab\xed\xa0").unwrap_err();
+        assert_eq!((err.valid_up_to(), err.error_len()), (2, None));
+        let err = JStr::from_modified_utf8(&[HIGH, b"\xed"].concat()).unwrap_err();
+        assert_eq!((err.valid_up_to(), err.error_len()), (3, None));
+    }
+
+    #[test]
+    fn into_c_string_keeps_java_nul_encoded() {
+        let name = JStr::from_modified_utf8(b"a\xc0\x80b").unwrap().to_owned();
+        assert_eq!(name.jchars().collect::<Vec<_>>(), [0x61, 0, 0x62]);
+        let c = name.into_c_string();
+        assert_eq!(c.as_bytes(), b"a\xc0\x80b");
+        assert_eq!(c.as_bytes().len(), 4);
+        assert_eq!(c.as_bytes_with_nul(), b"a\xc0\x80b\0");
+    }
+
+    #[test]
+    fn trim_matches_chars_and_strings() {
+        let st = |s: &str| JStr::from_utf8_str(s).into_owned();
+        assert_eq!(
+            st(";;Ljava/lang/Object;;").trim_matches(';'),
+            &*st("Ljava/lang/Object")
+        );
+        assert_eq!(st(";a;").trim_start_matches(';'), &*st("a;"));
+        assert_eq!(st(";a;").trim_end_matches(';'), &*st(";a"));
+        assert_eq!(st(";;;").trim_matches(';'), &*st(""));
+
+        // A string pattern is removed whole, as many times as it repeats
+        let ab = st("ab");
+        assert_eq!(st("ababXabab").trim_matches(&*ab), &*st("X"));
+        assert_eq!(st("ababXaba").trim_matches(&*ab), &*st("Xaba"));
+        assert_eq!(st("ababXabab").trim_start_matches(&ab), &*st("Xabab"));
+        assert_eq!(st("abXabab").trim_end_matches(&*ab), &*st("abX"));
+        assert_eq!(st("aXb").trim_matches(&*ab), &*st("aXb"));
+        assert_eq!(st("ab").trim_matches(&*st("")), &*st("ab"));
+
+        // Characters are matched by their encoding, including NUL and supplementary characters
+        assert_eq!(st("\0x\0").trim_matches('\0'), &*st("x"));
+        assert_eq!(
+            st("\u{1F600}x\u{1F600}").trim_matches('\u{1F600}'),
+            &*st("x")
+        );
+    }
+
+    #[test]
+    fn char_modified_utf8_len_matches_encode_char() {
+        for (c, len) in [
+            ('\0', 2),
+            ('a', 1),
+            ('\u{7f}', 1),
+            ('\u{80}', 2),
+            ('\u{7ff}', 2),
+            ('\u{800}', 3),
+            ('\u{ffff}', 3),
+            ('\u{10000}', 6),
+            ('\u{1F600}', 6),
+        ] {
+            assert_eq!(char_modified_utf8_len(c), len, "{:?}", c);
+            assert_eq!(JStr::encode_char(c, &mut [0; 6]).len(), len, "{:?}", c);
+        }
+    }
+
+    #[test]
+    fn encode_char_variants_agree() {
+        for (c, expected) in [
+            ('\0', &b"\xc0\x80"[..]),
+            ('a', b"a"),
+            ('\u{80}', b"\xc2\x80"),
+            ('\u{7ff}', b"\xdf\xbf"),
+            ('\u{800}', b"\xe0\xa0\x80"),
+            ('\u{ffff}', b"\xef\xbf\xbf"),
+            ('\u{10000}', b"\xed\xa0\x80\xed\xb0\x80"),
+            ('\u{1f600}', b"\xed\xa0\xbd\xed\xb8\x80"),
+            ('\u{10ffff}', b"\xed\xaf\xbf\xed\xbf\xbf"),
+        ] {
+            assert_eq!(
+                JStr::encode_char(c, &mut [0; 6]).as_bytes(),
+                expected,
+                "{:?}",
+                c
+            );
+
+            let (array, len) = JStr::encode_char_array(c);
+            assert_eq!(&array[..len], expected, "{:?}", c);
+
+            let mut vec = b"x".to_vec();
+            JStr::encode_char_to_vec(c, &mut vec);
+            assert_eq!(&vec[1..], expected, "{:?}", c);
+
+            // A supplementary char decodes back to itself rather than to two replacement chars
+            let decoded = JStr::from_modified_utf8(expected).unwrap();
+            assert_eq!(decoded.chars().collect::<Vec<_>>(), [c]);
+        }
+    }
+
+    #[test]
+    fn jstring_equals_jstr_across_ownership() {
+        let owned = JStr::from_modified_utf8(LONE).unwrap().to_owned();
+        let borrowed: &JStr = JStr::from_modified_utf8(LONE).unwrap();
+        assert!(owned == borrowed);
+        assert!(borrowed == owned);
+        assert!(owned == *borrowed);
+        assert!(*borrowed == owned);
+        assert_eq!(owned, borrowed);
+        assert_eq!(borrowed, owned);
+
+        let other = JStr::from_utf8_str("xy");
+        let other: &JStr = &other;
+        assert!(owned != other);
+        assert!(other != owned);
+
+        // In generic code, such as searching a collection of owned strings for a borrowed one
+        let names = [testutil::jstring("a"), owned.clone()];
+        assert!(names.iter().any(|name| *name == borrowed));
+        assert_eq!(names.iter().position(|name| borrowed == *name), Some(1));
+    }
+
+    #[test]
+    fn rsplitn_extracts_the_simple_name() {
+        let st = |s: &str| JStr::from_utf8_str(s).into_owned();
+        let strings =
+            |parts: Vec<&JStr>| parts.into_iter().map(|p| p.to_string()).collect::<Vec<_>>();
+
+        let name = st("java/util/Map$Entry");
+        assert_eq!(
+            strings(name.rsplitn(2, '/').collect()),
+            ["Map$Entry", "java/util"]
+        );
+        assert_eq!(name.rsplitn(2, '/').next().unwrap(), &*st("Map$Entry"));
+        assert_eq!(
+            strings(name.rsplit('/').collect()),
+            ["Map$Entry", "util", "java"]
+        );
+        assert_eq!(
+            strings(name.rsplitn(1, '/').collect()),
+            ["java/util/Map$Entry"]
+        );
+        assert_eq!(name.rsplitn(0, '/').count(), 0);
+
+        // A class in the unnamed package
+        let simple = st("Fixture");
+        assert_eq!(strings(simple.rsplitn(2, '/').collect()), ["Fixture"]);
+        assert_eq!(
+            strings(st("a//b/").rsplit('/').collect()),
+            ["", "b", "", "a"]
+        );
+        assert_eq!(
+            strings(st("x::y::z").rsplitn(2, &*st("::")).collect()),
+            ["z", "x::y"]
+        );
+    }
+
+    #[test]
+    fn split_inclusive_keeps_separators() {
+        let st = |s: &str| JStr::from_utf8_str(s).into_owned();
+        let strings =
+            |parts: Vec<&JStr>| parts.into_iter().map(|p| p.to_string()).collect::<Vec<_>>();
+
+        // An SMAP section, whose last line ends with a newline
+        let smap = st("SMAP\nFoo.java\nKotlin\n");
+        assert_eq!(
+            strings(smap.split_inclusive('\n').collect()),
+            ["SMAP\n", "Foo.java\n", "Kotlin\n"]
+        );
+        assert_eq!(
+            strings(st("a\n\nb").split_inclusive('\n').collect()),
+            ["a\n", "\n", "b"]
+        );
+        assert_eq!(
+            strings(st("no newline").split_inclusive('\n').collect()),
+            ["no newline"]
+        );
+        assert_eq!(st("").split_inclusive('\n').count(), 0);
+        assert_eq!(
+            strings(st("*S Kotlin*E").split_inclusive(&*st("*")).collect()),
+            ["*", "S Kotlin*", "E"]
+        );
+    }
+
+    ///
+    /// `validate_modified_utf8` without the ASCII fast path, one byte at a time
+    fn validate_bytewise(x: &[u8]) -> Result<(), ModifiedUtf8Error> {
+        let mut pos = 0;
+        while let Some(&b) = x.get(pos) {
+            let conts = match b {
+                0 | 0x80..=0xbf | 0xf0..=0xff => {
+                    return Err(ModifiedUtf8Error { pos, len: Some(1) })
+                }
+                0xc0..=0xdf => 1,
+                0xe0..=0xef => 2,
+                _ => 0,
+            };
+            for n in 1..=conts {
+                match x.get(pos + n) {
+                    None => return Err(ModifiedUtf8Error { pos, len: None }),
+                    Some(cont) if cont & 0xc0 != 0x80 => {
+                        return Err(ModifiedUtf8Error { pos, len: Some(n) })
+                    }
+                    Some(_) => {}
+                }
+            }
+            pos += conts + 1;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn ascii_fast_path_reports_exact_positions() {
+        let errors: [&[u8]; 7] = [
+            b"\0",
+            b"\x80",
+            b"\xf0\x80\x80",
+            b"\xc3",
+            b"\xe2\x82",
+            b"\xe2(\xac",
+            b"\xed\xa0\x80\0",
+        ];
+        for error in errors {
+            for before in 0..20 {
+                for after in [0, 1, 7, 8, 9, 17] {
+                    let mut bytes = vec![b'a'; before];
+                    bytes.extend_from_slice(error);
+                    bytes.resize(bytes.len() + after, b'z');
+                    let fast = validate_modified_utf8(&bytes).unwrap_err();
+                    let slow = validate_bytewise(&bytes).unwrap_err();
+                    assert_eq!(
+                        (fast.valid_up_to(), fast.error_len()),
+                        (slow.valid_up_to(), slow.error_len()),
+                        "{:?}",
+                        bytes
+                    );
+                    assert!(fast.valid_up_to() >= before);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ascii_fast_path_on_large_input() {
+        // Mostly ASCII, as in a typical constant pool, with a multi-byte char and NUL every so often
+        let mut bytes = Vec::new();
+        for i in 0..100_000 {
+            bytes.extend_from_slice(b"java/lang/Object");
+            if i % 97 == 0 {
+                bytes.extend_from_slice("\u{e9}\u{20ac}".as_bytes());
+            }
+            if i % 101 == 0 {
+                bytes.extend_from_slice(b"\xc0\x80");
+            }
+        }
+        assert!(validate_modified_utf8(&bytes).is_ok());
+        assert!(validate_bytewise(&bytes).is_ok());
+
+        let end = bytes.len();
+        bytes.push(0);
+        assert_eq!(
+            validate_modified_utf8(&bytes).unwrap_err().valid_up_to(),
+            end
+        );
+    }
+
+    #[test]
+    fn map_keyed_by_jstring_is_queried_with_bytes() {
+        use std::collections::{BTreeMap, HashMap};
+
+        let mut map = HashMap::new();
+        map.insert(testutil::jstring("java/lang/Object"), 1);
+        map.insert(JStr::from_modified_utf8(LONE).unwrap().to_owned(), 2);
+        map.insert(testutil::jstring("a\0b"), 3);
+        assert_eq!(map.get(&b"java/lang/Object"[..]), Some(&1));
+        assert_eq!(map.get(LONE), Some(&2));
+        assert_eq!(map.get(&b"a\xc0\x80b"[..]), Some(&3));
+        // A NUL encoded as one byte is not the same key
+        assert_eq!(map.get(&b"a\0b"[..]), None);
+        assert_eq!(map.get(&*testutil::jstring("a\0b")), Some(&3));
+
+        let sorted: BTreeMap<_, _> = map.into_iter().collect();
+        assert_eq!(sorted.get(&b"java/lang/Object"[..]), Some(&1));
+    }
+
+    #[test]
+    fn cursor_peeks_and_advances_across_a_surrogate_pair() {
+        let st = JStr::from_utf8_str("a\u{1F600}\0b");
+        let mut cursor = JCharCursor::new(&st);
+        assert_eq!((cursor.peek(), cursor.position()), (Some(0x61), 0));
+        assert_eq!(cursor.advance(), Some(0x61));
+
+        // Each half of the pair is a jchar of its own, 3 bytes long
+        assert_eq!((cursor.peek(), cursor.position()), (Some(0xd83d), 1));
+        assert_eq!(cursor.peek(), Some(0xd83d));
+        assert_eq!(cursor.advance(), Some(0xd83d));
+        assert_eq!((cursor.peek(), cursor.position()), (Some(0xde00), 4));
+        assert_eq!(cursor.rest().as_bytes(), b"\xed\xb8\x80\xc0\x80b");
+        assert_eq!(cursor.advance(), Some(0xde00));
+
+        assert_eq!((cursor.advance(), cursor.position()), (Some(0), 9));
+        assert_eq!((cursor.advance(), cursor.position()), (Some(0x62), 10));
+        assert_eq!(cursor.position(), st.len());
+        assert_eq!((cursor.peek(), cursor.advance()), (None, None));
+        assert!(cursor.rest().as_bytes().is_empty());
+    }
+
+    #[test]
+    fn escape_default_matches_str() {
+        let ascii: String = (0u8..0x80).map(char::from).collect();
+        for text in [
+            &*ascii,
+            "tab\there \"quoted\" 'single' \\",
+            "caf\u{e9} \u{20ac}\u{1F600}",
+        ] {
+            let st = JStr::from_utf8_str(text);
+            assert_eq!(
+                st.escape_default().to_string(),
+                text.escape_default().to_string()
+            );
+            assert_eq!(
+                st.escape_unicode().to_string(),
+                text.escape_unicode().to_string()
+            );
+            assert!(st.escape_default().all(|c| c.is_ascii()));
+        }
+
+        let lone = JStr::from_modified_utf8(LONE).unwrap();
+        assert_eq!(lone.escape_default().to_string(), "x\\u{d800}y");
+        assert_eq!(lone.escape_unicode().to_string(), "\\u{78}\\u{d800}\\u{79}");
+        let low = JStr::from_modified_utf8(b"\xed\xb0\x80").unwrap();
+        assert_eq!(low.escape_default().to_string(), "\\u{dc00}");
+    }
+
+    #[test]
+    fn escape_unicode_matches_char_escapes() {
+        let text = "a\0\u{e9}\u{20ac}\u{1F600}\n";
+        let expected: String = text.chars().flat_map(char::escape_unicode).collect();
+        let st = JStr::from_utf8_str(text);
+        assert_eq!(st.escape_unicode().to_string(), expected);
+        assert_eq!(st.escape_unicode().collect::<String>(), expected);
+        assert_eq!(expected, "\\u{61}\\u{0}\\u{e9}\\u{20ac}\\u{1f600}\\u{a}");
+
+        // A lone surrogate between a pair and ASCII
+        let mixed = JString::from_utf16_units(vec![0xd83d, 0xde00, 0xdbff, 0x41]);
+        assert_eq!(
+            mixed.escape_unicode().to_string(),
+            "\\u{1f600}\\u{dbff}\\u{41}"
+        );
+    }
+
+    #[cfg(feature = "path")]
+    #[test]
+    fn internal_name_to_path() {
+        use std::path::{Path, MAIN_SEPARATOR};
+
+        let name = testutil::jstring("com/foo/Bar");
+        let path = name.to_path("class");
+        assert_eq!(path, Path::new("com").join("foo").join("Bar.class"));
+        assert_eq!(
+            path.to_str().unwrap(),
+            format!("com{0}foo{0}Bar.class", MAIN_SEPARATOR)
+        );
+        assert_eq!(path.components().count(), 3);
+
+        assert_eq!(
+            testutil::jstring("Fixture").to_path("class"),
+            Path::new("Fixture.class")
+        );
+        assert_eq!(name.to_path(""), Path::new("com").join("foo").join("Bar"));
+        assert_eq!(
+            testutil::jstring("Fixture$Inner").to_path("java"),
+            Path::new("Fixture$Inner.java")
+        );
+        // Unpaired surrogates cannot be in an OsString on every platform
+        let lone = JStr::from_modified_utf8(LONE).unwrap();
+        assert_eq!(lone.to_os_string(), "x\u{fffd}y");
+    }
+
+    #[test]
+    fn jvm_utf8_rejects_four_byte_sequences_clearly() {
+        // U+1F600 in standard UTF-8, which the JVM requires as a surrogate pair
+        let err = JStr::from_jvm_utf8(b"ab\xf0\x9f\x98\x80").unwrap_err();
+        assert_eq!(err.kind(), JvmUtf8ErrorKind::FourByteLead);
+        assert_eq!(err.valid_up_to(), 2);
+        assert_eq!(
+            err.to_string(),
+            "invalid Utf8 constant at byte 2: byte of F0 or above (chars outside the Basic Multilingual Plane must be encoded as a surrogate pair)"
+        );
+        assert!(JStr::from_jvm_utf8(JStr::from_utf8_str("ab\u{1F600}").as_bytes()).is_ok());
+
+        for (bytes, kind, pos) in [
+            (&b"a\0"[..], JvmUtf8ErrorKind::Nul, 1),
+            (b"\xff", JvmUtf8ErrorKind::FourByteLead, 0),
+            (b"a\x80", JvmUtf8ErrorKind::UnexpectedContinuation, 1),
+            (b"a\xe2\x82", JvmUtf8ErrorKind::Truncated, 1),
+            (b"a\xed\xb0\x80", JvmUtf8ErrorKind::UnpairedSurrogate, 1),
+        ] {
+            let err = JStr::from_jvm_utf8(bytes).unwrap_err();
+            assert_eq!((err.kind(), err.valid_up_to()), (kind, pos), "{:?}", bytes);
+        }
+    }
+}
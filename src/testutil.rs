@@ -0,0 +1,70 @@
+// Fixtures and helpers shared by the unit tests
+//
+// The fixtures in `testdata` are compiled by `javac --release 16 -g -parameters` from the sources in `testdata/src`.
+// `module-info.class` is taken from a jar created with `jar --main-class com.example.app.Main`, which adds its ModuleMainClass and ModulePackages attributes
+
+use crate::{
+    class::{ClassFile, FieldInfo, MethodInfo},
+    string::{JStr, JString},
+};
+
+pub(crate) const FIXTURE: &[u8] = include_bytes!("../testdata/Fixture.class");
+pub(crate) const FIXTURE_INNER: &[u8] = include_bytes!("../testdata/Fixture$Inner.class");
+pub(crate) const FIXTURE_ANONYMOUS: &[u8] = include_bytes!("../testdata/Fixture$1.class");
+pub(crate) const IFACE: &[u8] = include_bytes!("../testdata/Iface.class");
+pub(crate) const POINT: &[u8] = include_bytes!("../testdata/Point.class");
+pub(crate) const MODULE_INFO: &[u8] = include_bytes!("../testdata/module-info.class");
+pub(crate) const USES: &[u8] = include_bytes!("../testdata/Uses.class");
+
+pub(crate) const ALL: &[&[u8]] = &[
+    FIXTURE,
+    FIXTURE_INNER,
+    FIXTURE_ANONYMOUS,
+    IFACE,
+    POINT,
+    MODULE_INFO,
+    USES,
+];
+
+pub(crate) fn read(bytes: &[u8]) -> ClassFile {
+    ClassFile::read(&mut &bytes[..]).unwrap()
+}
+
+///
+/// Asserts that reading and writing `bytes` reproduces them exactly
+pub(crate) fn assert_roundtrip(bytes: &[u8]) {
+    let written = read(bytes).write_to_vec().unwrap();
+    assert!(written == bytes, "class file did not round-trip");
+}
+
+///
+/// Encodes `st` as Modified UTF-8
+pub(crate) fn jstring(st: &str) -> JString {
+    JStr::from_utf8_str(st).into_owned()
+}
+
+///
+/// The text of the Utf8 constant at `index`, which must exist
+pub(crate) fn utf8(class: &ClassFile, index: u16) -> String {
+    class.consts.utf8(index).unwrap().to_string()
+}
+
+///
+/// The first method named `name`
+pub(crate) fn method<'a>(class: &'a ClassFile, name: &str) -> &'a MethodInfo {
+    class
+        .methods
+        .iter()
+        .find(|method| utf8(class, method.name) == name)
+        .unwrap()
+}
+
+///
+/// The field named `name`
+pub(crate) fn field<'a>(class: &'a ClassFile, name: &str) -> &'a FieldInfo {
+    class
+        .fields
+        .iter()
+        .find(|field| utf8(class, field.name) == name)
+        .unwrap()
+}
@@ -0,0 +1,818 @@
+use std::{
+    collections::HashSet,
+    fmt::{Display, Formatter},
+};
+
+use crate::{
+    class::{consts, Attribute, ClassFile, Constant, StackMapFrame},
+    flags::MethodAccessFlags,
+    pool::ConstantPool,
+    refs::{Location, RefKind},
+    string::{JStr, JString},
+};
+
+///
+/// A way in which a class file breaks the rules of the JVMS, found by one of the `validate_*` methods of [`ClassFile`]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum ValidationError {
+    /// A constant pool index does not refer to a constant of the expected kind
+    InvalidReference {
+        location: Location,
+        index: u16,
+        expected: RefKind,
+    },
+    /// A constant pool index refers to the unusable second slot of a Long or Double
+    PhantomReference { location: Location, index: u16 },
+    /// A class other than `java/lang/Object` has a `super_class` of 0
+    MissingSuperclass,
+    /// `java/lang/Object` has a superclass
+    ObjectHasSuperclass,
+    /// The superclass of an interface is not `java/lang/Object`
+    InterfaceSuperclass,
+    /// A class with `ACC_MODULE` set breaks one of the rules for module-info classes
+    InvalidModuleClass(&'static str),
+    /// The method at the given position in `methods` is abstract or native, but has a Code attribute
+    UnexpectedCode { method: u16 },
+    /// The method at the given position in `methods` is neither abstract nor native, but has no Code attribute
+    MissingCode { method: u16 },
+    /// The method at the given position in `methods` has more than one Code attribute
+    DuplicateCode { method: u16 },
+    /// A frame of the StackMapTable of the method at the given position in `methods` is not at the start of an instruction, as when its offset runs past the end of the code
+    InvalidStackMapOffset { method: u16, offset: u32 },
+    /// A chop frame of the StackMapTable of the method at the given position in `methods` removes a number of locals other than 1 to 3
+    InvalidChopFrame { method: u16, chop: u8 },
+    /// The minor version is not allowed with the major version: the preview minor is only allowed from Java 11, and from Java 12 every other minor version must be 0
+    InvalidMinorVersion { major: u16, minor: u16 },
+    /// The method at the given position in `methods` of an interface breaks one of the rules for interface methods
+    InvalidInterfaceMethod { method: u16, reason: &'static str },
+    /// More than one field, or more than one method, has this name and descriptor
+    DuplicateMember { name: JString, descriptor: JString },
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ValidationError::InvalidReference {
+                location,
+                index,
+                expected,
+            } => write!(
+                f,
+                "{:?}: constant #{} is not a valid {:?} reference",
+                location, index, expected
+            ),
+            ValidationError::PhantomReference { location, index } => write!(
+                f,
+                "{:?}: constant #{} is the second slot of a Long or Double",
+                location, index
+            ),
+            ValidationError::MissingSuperclass => {
+                f.write_str("only java/lang/Object may have no superclass")
+            }
+            ValidationError::ObjectHasSuperclass => {
+                f.write_str("java/lang/Object must not have a superclass")
+            }
+            ValidationError::InterfaceSuperclass => {
+                f.write_str("the superclass of an interface must be java/lang/Object")
+            }
+            ValidationError::InvalidModuleClass(reason) => {
+                write!(f, "invalid module-info class: {}", reason)
+            }
+            ValidationError::UnexpectedCode { method } => write!(
+                f,
+                "method {} is abstract or native, but has a Code attribute",
+                method
+            ),
+            ValidationError::MissingCode { method } => {
+                write!(f, "method {} has no Code attribute", method)
+            }
+            ValidationError::DuplicateCode { method } => {
+                write!(f, "method {} has more than one Code attribute", method)
+            }
+            ValidationError::InvalidStackMapOffset { method, offset } => write!(
+                f,
+                "method {}: stack map frame at offset {} is not at the start of an instruction",
+                method, offset
+            ),
+            ValidationError::InvalidChopFrame { method, chop } => write!(
+                f,
+                "method {}: stack map frame chops {} locals, but must chop 1 to 3",
+                method, chop
+            ),
+            ValidationError::InvalidMinorVersion { major, minor } => {
+                write!(f, "invalid class file version {}.{}", major, minor)
+            }
+            ValidationError::InvalidInterfaceMethod { method, reason } => {
+                write!(f, "invalid interface method {}: {}", method, reason)
+            }
+            ValidationError::DuplicateMember { name, descriptor } => {
+                write!(f, "duplicate member {} {}", name, descriptor)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+const OBJECT: &[u8] = b"java/lang/Object";
+
+///
+/// Whether `constant` is of a kind that may be referred to as `kind`
+fn is_valid_ref(kind: RefKind, constant: &Constant) -> bool {
+    match kind {
+        RefKind::Utf8 | RefKind::Descriptor | RefKind::Signature | RefKind::ClassName => {
+            matches!(constant, Constant::Utf8(_))
+        }
+        RefKind::Class => matches!(constant, Constant::Class(_)),
+        RefKind::NameAndType => matches!(constant, Constant::NameAndType { .. }),
+        RefKind::FieldRef => matches!(constant, Constant::FieldRef { .. }),
+        RefKind::MethodRef => matches!(
+            constant,
+            Constant::MethodRef { .. } | Constant::InterfaceMethodRef { .. }
+        ),
+        RefKind::MethodHandleRef => matches!(
+            constant,
+            Constant::FieldRef { .. }
+                | Constant::MethodRef { .. }
+                | Constant::InterfaceMethodRef { .. }
+        ),
+        RefKind::MethodHandle => matches!(constant, Constant::MethodHandle { .. }),
+        RefKind::ConstantValue => matches!(
+            constant,
+            Constant::Int(_)
+                | Constant::Float(_)
+                | Constant::Long(_)
+                | Constant::Double(_)
+                | Constant::String(_)
+        ),
+        RefKind::Loadable => {
+            is_valid_ref(RefKind::NarrowLoadable, constant)
+                || matches!(constant, Constant::Long(_) | Constant::Double(_))
+        }
+        RefKind::NarrowLoadable => matches!(
+            constant,
+            Constant::Int(_)
+                | Constant::Float(_)
+                | Constant::String(_)
+                | Constant::Class(_)
+                | Constant::MethodHandle { .. }
+                | Constant::MethodType(_)
+                | Constant::Dynamic { .. }
+        ),
+        RefKind::WideLoadable => matches!(
+            constant,
+            Constant::Long(_) | Constant::Double(_) | Constant::Dynamic { .. }
+        ),
+        RefKind::InvokeDynamic => matches!(constant, Constant::InvokeDynamic { .. }),
+        RefKind::Module => matches!(constant, Constant::Module(_)),
+        RefKind::Package => matches!(constant, Constant::Package(_)),
+        RefKind::Any => !matches!(constant, Constant::LongOrDoubleHigh),
+    }
+}
+
+///
+/// Checks a single reference, distinguishing references to the second slot of a Long or Double from other invalid references
+fn check_ref(
+    consts: &ConstantPool,
+    location: Location,
+    expected: RefKind,
+    index: u16,
+) -> Option<ValidationError> {
+    match consts.get(index) {
+        Some(Constant::LongOrDoubleHigh) => {
+            Some(ValidationError::PhantomReference { location, index })
+        }
+        Some(constant) if is_valid_ref(expected, constant) => None,
+        _ => Some(ValidationError::InvalidReference {
+            location,
+            index,
+            expected,
+        }),
+    }
+}
+
+fn into_result(errors: Vec<ValidationError>) -> Result<(), Vec<ValidationError>> {
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+impl ClassFile {
+    ///
+    /// Runs every validator: [`ClassFile::validate_version`], [`ClassFile::validate_references`], [`ClassFile::validate_this_and_super`], [`ClassFile::validate_interface_methods`],
+    /// [`ClassFile::validate_code_attributes`], and [`ClassFile::validate_stack_map_tables`], and checks for members that [`ClassFile::find_duplicate_members`] reports.
+    ///
+    /// All of the errors are reported, in that order. An error found by more than one validator is reported once
+    pub fn validate(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        let results = vec![
+            self.validate_version(),
+            self.validate_references(),
+            self.validate_this_and_super(),
+            self.validate_interface_methods(),
+            self.validate_code_attributes(),
+            self.validate_stack_map_tables(),
+        ];
+        let duplicates = self
+            .find_duplicate_members()
+            .into_iter()
+            .map(|(name, descriptor)| ValidationError::DuplicateMember { name, descriptor });
+        for error in results
+            .into_iter()
+            .filter_map(Result::err)
+            .flatten()
+            .chain(duplicates)
+        {
+            if !errors.contains(&error) {
+                errors.push(error);
+            }
+        }
+        into_result(errors)
+    }
+
+    ///
+    /// Checks that every constant pool index in the class, including those in bytecode, refers to a constant of the kind expected where it is used.
+    ///
+    /// An index of the second slot of a Long or Double is reported as a [`ValidationError::PhantomReference`]
+    pub fn validate_references(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        self.for_each_ref(|location, expected, &index| {
+            errors.extend(check_ref(&self.consts, location, expected, index));
+        });
+        into_result(errors)
+    }
+
+    ///
+    /// Like [`ClassFile::validate_references`], but checks the references in parallel. The errors are reported in the same order
+    #[cfg(feature = "rayon")]
+    pub fn validate_references_parallel(&self) -> Result<(), Vec<ValidationError>> {
+        use rayon::prelude::*;
+
+        let mut refs = Vec::new();
+        self.for_each_ref(|location, expected, &index| refs.push((location, expected, index)));
+        let errors = refs
+            .into_par_iter()
+            .filter_map(|(location, expected, index)| {
+                check_ref(&self.consts, location, expected, index)
+            })
+            .collect();
+        into_result(errors)
+    }
+
+    ///
+    /// Checks that `this_class` and `super_class` agree with each other and with the access flags:
+    /// only `java/lang/Object` has no superclass, interfaces extend `java/lang/Object`, and module-info classes have the shape the JVMS requires of them
+    pub fn validate_this_and_super(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        let this = match self.consts.class_name(self.this) {
+            Some(this) => this,
+            None => {
+                return Err(vec![ValidationError::InvalidReference {
+                    location: Location::Class,
+                    index: self.this,
+                    expected: RefKind::Class,
+                }])
+            }
+        };
+        let supercl = if self.supercl == 0 {
+            None
+        } else {
+            match self.consts.class_name(self.supercl) {
+                Some(supercl) => Some(supercl),
+                None => {
+                    return Err(vec![ValidationError::InvalidReference {
+                        location: Location::Class,
+                        index: self.supercl,
+                        expected: RefKind::Class,
+                    }])
+                }
+            }
+        };
+
+        if self.acc & consts::ACC_MODULE != 0 {
+            if this.as_bytes() != b"module-info" {
+                errors.push(ValidationError::InvalidModuleClass(
+                    "this_class must be module-info",
+                ));
+            }
+            if supercl.is_some() {
+                errors.push(ValidationError::InvalidModuleClass("super_class must be 0"));
+            }
+            if self.acc != consts::ACC_MODULE {
+                errors.push(ValidationError::InvalidModuleClass(
+                    "no other access flags may be set",
+                ));
+            }
+            if !self.version_at_least(consts::JAVA_9) {
+                errors.push(ValidationError::InvalidModuleClass(
+                    "major version must be at least 53",
+                ));
+            }
+            if !self.interfaces.is_empty() || !self.fields.is_empty() || !self.methods.is_empty() {
+                errors.push(ValidationError::InvalidModuleClass(
+                    "must not have interfaces, fields, or methods",
+                ));
+            }
+            return into_result(errors);
+        }
+
+        match supercl {
+            None if this.as_bytes() != OBJECT => errors.push(ValidationError::MissingSuperclass),
+            Some(_) if this.as_bytes() == OBJECT => {
+                errors.push(ValidationError::ObjectHasSuperclass)
+            }
+            Some(supercl)
+                if self.acc & consts::ACC_INTERFACE != 0 && supercl.as_bytes() != OBJECT =>
+            {
+                errors.push(ValidationError::InterfaceSuperclass)
+            }
+            _ => {}
+        }
+        into_result(errors)
+    }
+
+    ///
+    /// Checks that abstract and native methods have no Code attribute, and that every other method has exactly one
+    pub fn validate_code_attributes(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        for (method, info) in (0u16..).zip(&self.methods) {
+            let flags = MethodAccessFlags::from_bits_truncate(info.acc);
+            let code = info
+                .attributes
+                .iter()
+                .filter(|attr| matches!(attr, Attribute::Code(_)))
+                .count();
+            if flags.intersects(MethodAccessFlags::ABSTRACT | MethodAccessFlags::NATIVE) {
+                if code != 0 {
+                    errors.push(ValidationError::UnexpectedCode { method });
+                }
+            } else if code == 0 {
+                errors.push(ValidationError::MissingCode { method });
+            } else if code > 1 {
+                errors.push(ValidationError::DuplicateCode { method });
+            }
+        }
+        into_result(errors)
+    }
+
+    ///
+    /// Checks that every frame of a StackMapTable is at the start of an instruction of its code, and that every chop frame removes 1 to 3 locals.
+    ///
+    /// Because each frame is at least one byte past the one before it, this also finds tables that are out of order, which can only be expressed by offsets that run past the end of the code.
+    /// Code that cannot be decoded is considered up to the first error
+    pub fn validate_stack_map_tables(&self) -> Result<(), Vec<ValidationError>> {
+        let mut errors = Vec::new();
+        for (method, info) in (0u16..).zip(&self.methods) {
+            for code in info.attributes.iter().filter_map(|attr| match attr {
+                Attribute::Code(code) => Some(code),
+                _ => None,
+            }) {
+                errors.extend(
+                    code.attributes
+                        .iter()
+                        .filter_map(|attr| match attr {
+                            Attribute::StackMapTable(frames) => Some(frames),
+                            _ => None,
+                        })
+                        .flatten()
+                        .filter_map(|frame| match frame {
+                            StackMapFrame::ChopFrame { chop, .. } if !(1..=3).contains(chop) => {
+                                Some(ValidationError::InvalidChopFrame {
+                                    method,
+                                    chop: *chop,
+                                })
+                            }
+                            _ => None,
+                        }),
+                );
+                let offsets = code.stack_map_offsets();
+                if offsets.is_empty() {
+                    continue;
+                }
+                let starts: HashSet<u32> = code
+                    .instructions()
+                    .map_while(Result::ok)
+                    .map(|(pc, _)| pc)
+                    .collect();
+                errors.extend(
+                    offsets
+                        .into_iter()
+                        .filter(|offset| !starts.contains(offset))
+                        .map(|offset| ValidationError::InvalidStackMapOffset { method, offset }),
+                );
+            }
+        }
+        into_result(errors)
+    }
+
+    ///
+    /// Checks that the minor version is allowed with the major version.
+    ///
+    /// Before Java 11, the minor version [`consts::PREVIEW_FEATURES`] is reported, since there were no preview features to use, but every other minor version is allowed.
+    /// Java 11 allows any minor version. From Java 12, only 0 and [`consts::PREVIEW_FEATURES`] are allowed, as JVMS §4.1 requires
+    pub fn validate_version(&self) -> Result<(), Vec<ValidationError>> {
+        let valid = if self.version_at_least(consts::JAVA_12) {
+            self.min == 0 || self.is_preview()
+        } else {
+            self.version_at_least(consts::JAVA_11) || !self.is_preview()
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(vec![ValidationError::InvalidMinorVersion {
+                major: self.maj,
+                minor: self.min,
+            }])
+        }
+    }
+
+    ///
+    /// Checks the access flags of the methods of an interface against the rules of its version:
+    /// before Java 8, every method other than `<clinit>` must be public and abstract, and from Java 8 static, private, and default methods are allowed, but each method must still be exactly one of public or private.
+    /// No interface method may be protected, final, synchronized, or native, and from Java 7 `<clinit>` must be static. Interfaces have no `<init>` methods.
+    ///
+    /// Classes that are not interfaces always pass
+    pub fn validate_interface_methods(&self) -> Result<(), Vec<ValidationError>> {
+        if self.acc & consts::ACC_INTERFACE == 0 {
+            return Ok(());
+        }
+        let mut errors = Vec::new();
+        for (method, info) in (0u16..).zip(&self.methods) {
+            let flags = MethodAccessFlags::from_bits_truncate(info.acc);
+            let mut error =
+                |reason| errors.push(ValidationError::InvalidInterfaceMethod { method, reason });
+            match self.consts.utf8(info.name).map(JStr::as_bytes) {
+                Some(b"<clinit>") => {
+                    if self.version_at_least(consts::JAVA_7)
+                        && !flags.contains(MethodAccessFlags::STATIC)
+                    {
+                        error("<clinit> must be static");
+                    }
+                    continue;
+                }
+                Some(b"<init>") => error("interfaces must not have constructors"),
+                _ => {}
+            }
+            if flags.intersects(
+                MethodAccessFlags::PROTECTED
+                    | MethodAccessFlags::FINAL
+                    | MethodAccessFlags::SYNCHRONIZED
+                    | MethodAccessFlags::NATIVE,
+            ) {
+                error("must not be protected, final, synchronized, or native");
+            }
+            if self.version_at_least(consts::JAVA_8) {
+                if flags.contains(MethodAccessFlags::PUBLIC)
+                    == flags.contains(MethodAccessFlags::PRIVATE)
+                {
+                    error("must be exactly one of public or private");
+                }
+            } else if !flags.contains(MethodAccessFlags::PUBLIC | MethodAccessFlags::ABSTRACT) {
+                error("must be public and abstract before Java 8");
+            }
+        }
+        into_result(errors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil;
+
+    #[test]
+    fn validate_reports_every_problem() {
+        for bytes in testutil::ALL {
+            assert_eq!(testutil::read(bytes).validate(), Ok(()));
+        }
+
+        let mut class = testutil::read(testutil::FIXTURE);
+        class.maj = 45;
+        class.min = consts::PREVIEW_FEATURES;
+        class.supercl = 0;
+        let old = class
+            .methods
+            .iter()
+            .position(|method| class.consts.utf8(method.name).unwrap().as_bytes() == b"old")
+            .unwrap();
+        class.methods[old]
+            .attributes
+            .retain(|attr| !matches!(attr, Attribute::Code(_)));
+        class.methods.push(class.methods[old].clone());
+        let duplicate = class.methods.len() as u16 - 1;
+
+        assert_eq!(
+            class.validate(),
+            Err(vec![
+                ValidationError::InvalidMinorVersion {
+                    major: 45,
+                    minor: consts::PREVIEW_FEATURES
+                },
+                ValidationError::MissingSuperclass,
+                ValidationError::MissingCode { method: old as u16 },
+                ValidationError::MissingCode { method: duplicate },
+                ValidationError::DuplicateMember {
+                    name: testutil::jstring("old"),
+                    descriptor: testutil::jstring("()V"),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn default_methods_need_java_8() {
+        let mut iface = testutil::read(testutil::IFACE);
+        let consts = &iface.consts;
+        iface.methods.retain(|method| {
+            matches!(
+                consts.utf8(method.name).map(JStr::as_bytes),
+                Some(b"run") | Some(b"twice")
+            )
+        });
+        assert_eq!(iface.methods.len(), 2);
+        let twice = iface
+            .methods
+            .iter()
+            .position(|method| iface.consts.utf8(method.name).unwrap().as_bytes() == b"twice")
+            .unwrap() as u16;
+
+        iface.maj = consts::JAVA_8;
+        assert_eq!(iface.validate_interface_methods(), Ok(()));
+        iface.maj = consts::JAVA_7;
+        assert_eq!(
+            iface.validate_interface_methods(),
+            Err(vec![ValidationError::InvalidInterfaceMethod {
+                method: twice,
+                reason: "must be public and abstract before Java 8"
+            }])
+        );
+
+        // The static and private methods are rejected as well
+        let mut iface = testutil::read(testutil::IFACE);
+        assert_eq!(iface.validate_interface_methods(), Ok(()));
+        iface.maj = consts::JAVA_7;
+        let errors = iface.validate_interface_methods().unwrap_err();
+        assert_eq!(errors.len(), iface.methods.len() - 1);
+    }
+
+    #[test]
+    fn preview_minor_needs_java_11() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        class.maj = 61;
+        class.min = consts::PREVIEW_FEATURES;
+        assert!(class.is_preview());
+        assert_eq!(class.validate_version(), Ok(()));
+        class.maj = consts::JAVA_11;
+        assert_eq!(class.validate_version(), Ok(()));
+
+        // Only from Java 12 must other minor versions be 0
+        class.min = 1;
+        assert_eq!(class.validate_version(), Ok(()));
+        class.maj = consts::JAVA_12;
+        assert_eq!(
+            class.validate_version(),
+            Err(vec![ValidationError::InvalidMinorVersion {
+                major: consts::JAVA_12,
+                minor: 1
+            }])
+        );
+
+        class.min = consts::PREVIEW_FEATURES;
+        class.maj = 45;
+        assert_eq!(
+            class.validate_version(),
+            Err(vec![ValidationError::InvalidMinorVersion {
+                major: 45,
+                minor: consts::PREVIEW_FEATURES
+            }])
+        );
+        class.min = 3;
+        assert!(!class.is_preview());
+        assert_eq!(class.validate_version(), Ok(()));
+    }
+
+    #[test]
+    fn only_object_may_have_no_superclass() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        assert_eq!(class.validate_this_and_super(), Ok(()));
+        let object = class.supercl;
+        class.supercl = 0;
+        assert_eq!(
+            class.validate_this_and_super(),
+            Err(vec![ValidationError::MissingSuperclass])
+        );
+
+        // The same class, renamed to java/lang/Object
+        class.this = object;
+        assert_eq!(class.validate_this_and_super(), Ok(()));
+        class.supercl = object;
+        assert_eq!(
+            class.validate_this_and_super(),
+            Err(vec![ValidationError::ObjectHasSuperclass])
+        );
+    }
+
+    #[test]
+    fn interface_superclass_must_be_object() {
+        let mut iface = testutil::read(testutil::IFACE);
+        assert_eq!(iface.validate_this_and_super(), Ok(()));
+        iface.supercl = iface.this;
+        assert_eq!(
+            iface.validate_this_and_super(),
+            Err(vec![ValidationError::InterfaceSuperclass])
+        );
+    }
+
+    #[test]
+    fn module_info_shape() {
+        let mut module = testutil::read(testutil::MODULE_INFO);
+        assert_eq!(module.validate_this_and_super(), Ok(()));
+        module.acc |= consts::ACC_PUBLIC;
+        module.maj = consts::JAVA_8;
+        assert_eq!(
+            module.validate_this_and_super(),
+            Err(vec![
+                ValidationError::InvalidModuleClass("no other access flags may be set"),
+                ValidationError::InvalidModuleClass("major version must be at least 53"),
+            ])
+        );
+    }
+
+    ///
+    /// The fixture class, with a method name, a field descriptor, and an `ldc` operand pointing at the wrong kinds of constant
+    fn corrupt_class() -> ClassFile {
+        let mut class = testutil::read(testutil::FIXTURE);
+        let long = class
+            .consts
+            .entries()
+            .find(|(_, constant)| matches!(constant, Constant::Long(_)))
+            .unwrap()
+            .0;
+        class.methods[1].name = class.this;
+        class.fields[0].descriptor = long + 1;
+        let string = class
+            .consts
+            .entries()
+            .find(|(_, constant)| matches!(constant, Constant::String(_)))
+            .unwrap()
+            .0;
+        class
+            .consts
+            .replace(string, Constant::Utf8(testutil::jstring("io")))
+            .unwrap();
+        class
+    }
+
+    #[test]
+    fn corrupt_references_are_reported() {
+        let class = corrupt_class();
+        let errors = class.validate_references().unwrap_err();
+        assert!(errors.contains(&ValidationError::PhantomReference {
+            location: Location::Field(0),
+            index: class.fields[0].descriptor,
+        }));
+        assert!(errors.iter().any(|err| matches!(
+            err,
+            ValidationError::InvalidReference {
+                location: Location::Method(1),
+                expected: RefKind::Utf8,
+                ..
+            }
+        )));
+        assert!(errors.iter().any(|err| matches!(
+            err,
+            ValidationError::InvalidReference {
+                location: Location::Code { .. },
+                ..
+            }
+        )));
+    }
+
+    #[test]
+    fn code_attribute_iff_concrete() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        assert_eq!(class.validate_code_attributes(), Ok(()));
+        let iface = testutil::read(testutil::IFACE);
+        assert_eq!(iface.validate_code_attributes(), Ok(()));
+
+        let code = class.methods[0].code().unwrap().clone();
+        // An abstract method with code, a concrete method without, and a concrete method with two
+        class.methods[0].acc |= consts::ACC_ABSTRACT;
+        class.methods[1]
+            .attributes
+            .retain(|attr| !matches!(attr, Attribute::Code(_)));
+        class.methods[2]
+            .attributes
+            .push(Attribute::Code(code.clone()));
+        assert_eq!(
+            class.validate_code_attributes(),
+            Err(vec![
+                ValidationError::UnexpectedCode { method: 0 },
+                ValidationError::MissingCode { method: 1 },
+                ValidationError::DuplicateCode { method: 2 },
+            ])
+        );
+
+        let mut iface = iface;
+        let abstract_method = iface
+            .methods
+            .iter()
+            .position(|method| method.acc & consts::ACC_ABSTRACT != 0)
+            .unwrap();
+        iface.methods[abstract_method]
+            .attributes
+            .push(Attribute::Code(code));
+        assert_eq!(
+            iface.validate_code_attributes(),
+            Err(vec![ValidationError::UnexpectedCode {
+                method: abstract_method as u16
+            }])
+        );
+        iface.methods[abstract_method].acc ^= consts::ACC_ABSTRACT | consts::ACC_NATIVE;
+        assert_eq!(
+            iface.validate_code_attributes(),
+            Err(vec![ValidationError::UnexpectedCode {
+                method: abstract_method as u16
+            }])
+        );
+    }
+
+    #[test]
+    fn out_of_order_stack_map_is_flagged() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        assert_eq!(class.validate_stack_map_tables(), Ok(()));
+        let method = class
+            .methods
+            .iter()
+            .position(|method| testutil::utf8(&class, method.name) == "loop")
+            .unwrap();
+        let code = class.methods[method].code_mut().unwrap();
+        let offsets = code.stack_map_offsets();
+        assert_eq!(offsets.len(), 2);
+        let (first, second) = (offsets[0], offsets[1]);
+
+        // The frames in the wrong order: going back to the first needs a negative delta, which wraps around past the end of the code
+        let backwards = (first as u16).wrapping_sub(second as u16 + 1);
+        for attr in &mut code.attributes {
+            if let Attribute::StackMapTable(frames) = attr {
+                *frames = vec![
+                    StackMapFrame::SameExtended {
+                        offset_delta: second as u16,
+                    },
+                    StackMapFrame::SameExtended {
+                        offset_delta: backwards,
+                    },
+                ];
+            }
+        }
+        assert_eq!(code.stack_map_offsets(), [second, first + 0x10000]);
+        assert_eq!(
+            class.validate_stack_map_tables(),
+            Err(vec![ValidationError::InvalidStackMapOffset {
+                method: method as u16,
+                offset: first + 0x10000,
+            }])
+        );
+    }
+
+    #[test]
+    fn string_pointing_at_high_half_of_long() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        let long = class
+            .consts
+            .entries()
+            .find(|(_, constant)| matches!(constant, Constant::Long(_)))
+            .unwrap()
+            .0;
+        let string = class
+            .consts
+            .entries()
+            .find(|(_, constant)| matches!(constant, Constant::String(_)))
+            .unwrap()
+            .0;
+        class
+            .consts
+            .replace(string, Constant::String(long + 1))
+            .unwrap();
+        assert_eq!(
+            class.validate_references(),
+            Err(vec![ValidationError::PhantomReference {
+                location: Location::Constant(string),
+                index: long + 1,
+            }])
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn parallel_references_match_sequential() {
+        let class = testutil::read(testutil::FIXTURE);
+        assert_eq!(class.validate_references_parallel(), Ok(()));
+        let class = corrupt_class();
+        let sequential = class.validate_references();
+        assert!(sequential.is_err());
+        assert_eq!(class.validate_references_parallel(), sequential);
+    }
+}
@@ -0,0 +1,1078 @@
+use std::{
+    fmt::{Display, Formatter},
+    io::Write,
+};
+
+use crate::{
+    class::{
+        consts, Annotation, Attribute, Availability, ClassFile, CodeAttribute, Constant,
+        ElementValue, LocalVariableLocationInfo, StackMapFrame, TypeAnnotation,
+        TypeAnnotationTarget, TypePathSegment, VerificationInfo,
+    },
+    pool::MAX_SLOTS,
+    string::JStr,
+    writer::ClassWriter,
+};
+
+///
+/// How the constant pool is ordered when a class is written
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub enum ConstantOrder {
+    /// The constants are written in the order they are stored in the [`ClassFile`], so a class that was read and not modified is written byte for byte as it was read
+    #[default]
+    Preserve,
+    /// The constants are written in the order given by [`ClassFile::canonicalize_constant_pool`], so classes that differ only in the order of their pools are written identically
+    Canonical,
+}
+
+///
+/// Options controlling how a class file is written
+#[derive(Clone, Debug, Default)]
+pub struct WriteOptions {
+    pub constant_order: ConstantOrder,
+}
+
+#[derive(Debug)]
+pub enum WriteError {
+    Io(std::io::Error),
+    /// A Utf8 constant is longer than the 65535 bytes its u16 length can describe
+    Utf8TooLong {
+        len: usize,
+    },
+    /// A table has more entries than its count field can describe
+    TooManyEntries {
+        what: &'static str,
+        len: usize,
+    },
+    /// The constant pool has no Utf8 entry for the name of an attribute being written
+    MissingAttributeName(&'static str),
+    /// The constant pool has more than the 65534 slots that can be indexed, counting both slots of each Long and Double
+    ConstantPoolOverflow {
+        slots: usize,
+    },
+    /// A chop frame of a StackMapTable removes a number of locals other than 1 to 3, which its frame type cannot encode
+    InvalidChopFrame {
+        chop: u8,
+    },
+    /// An append frame of a StackMapTable adds a number of locals other than 1 to 3, which its frame type cannot encode
+    InvalidAppendFrame {
+        len: usize,
+    },
+    /// A same or same_locals_1_stack_item frame of a StackMapTable has an offset delta of 64 or more, which its frame type cannot encode
+    FrameOffsetTooLarge {
+        offset_delta: u8,
+    },
+}
+
+impl Display for WriteError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriteError::Io(e) => e.fmt(f),
+            WriteError::Utf8TooLong { len } => write!(
+                f,
+                "Utf8 constant is {} bytes long, but at most 65535 bytes can be encoded",
+                len
+            ),
+            WriteError::TooManyEntries { what, len } => {
+                write!(f, "too many {} to encode ({})", what, len)
+            }
+            WriteError::MissingAttributeName(name) => write!(
+                f,
+                "constant pool does not contain the attribute name {:?}",
+                name
+            ),
+            WriteError::ConstantPoolOverflow { slots } => write!(
+                f,
+                "constant pool has {} slots, but at most 65534 can be indexed",
+                slots
+            ),
+            WriteError::InvalidChopFrame { chop } => write!(
+                f,
+                "stack map frame chops {} locals, but only 1 to 3 can be encoded",
+                chop
+            ),
+            WriteError::InvalidAppendFrame { len } => write!(
+                f,
+                "stack map frame appends {} locals, but only 1 to 3 can be encoded",
+                len
+            ),
+            WriteError::FrameOffsetTooLarge { offset_delta } => write!(
+                f,
+                "stack map frame has offset delta {}, but its frame type can only encode 0 to 63",
+                offset_delta
+            ),
+        }
+    }
+}
+
+impl std::error::Error for WriteError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            WriteError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for WriteError {
+    fn from(e: std::io::Error) -> Self {
+        WriteError::Io(e)
+    }
+}
+
+fn write_constant(out: &mut ClassWriter<Vec<u8>>, constant: &Constant) -> Result<(), WriteError> {
+    match constant {
+        Constant::Utf8(st) => {
+            if st.len() > u16::MAX as usize {
+                return Err(WriteError::Utf8TooLong { len: st.len() });
+            }
+            out.write_u8(1)?;
+            out.write_u16(st.len() as u16)?;
+            out.write_bytes(st.as_bytes())?;
+        }
+        Constant::Int(v) => {
+            out.write_u8(3)?;
+            out.write_i32(*v)?;
+        }
+        Constant::Float(v) => {
+            out.write_u8(4)?;
+            out.write_f32(*v)?;
+        }
+        Constant::Long(v) => {
+            out.write_u8(5)?;
+            out.write_i64(*v)?;
+        }
+        Constant::Double(v) => {
+            out.write_u8(6)?;
+            out.write_f64(*v)?;
+        }
+        Constant::LongOrDoubleHigh => {} // The second slot of a Long or Double has no representation of its own
+        Constant::Class(name) => {
+            out.write_u8(7)?;
+            out.write_u16(*name)?;
+        }
+        Constant::String(st) => {
+            out.write_u8(8)?;
+            out.write_u16(*st)?;
+        }
+        Constant::FieldRef {
+            class,
+            name_and_type,
+        } => {
+            out.write_u8(9)?;
+            out.write_u16(*class)?;
+            out.write_u16(*name_and_type)?;
+        }
+        Constant::MethodRef {
+            class,
+            name_and_type,
+        } => {
+            out.write_u8(10)?;
+            out.write_u16(*class)?;
+            out.write_u16(*name_and_type)?;
+        }
+        Constant::InterfaceMethodRef {
+            class,
+            name_and_type,
+        } => {
+            out.write_u8(11)?;
+            out.write_u16(*class)?;
+            out.write_u16(*name_and_type)?;
+        }
+        Constant::NameAndType { name, descriptor } => {
+            out.write_u8(12)?;
+            out.write_u16(*name)?;
+            out.write_u16(*descriptor)?;
+        }
+        Constant::MethodHandle { kind, reference } => {
+            out.write_u8(15)?;
+            out.write_u8(*kind)?;
+            out.write_u16(*reference)?;
+        }
+        Constant::MethodType(descriptor) => {
+            out.write_u8(16)?;
+            out.write_u16(*descriptor)?;
+        }
+        Constant::Dynamic {
+            bootstrap_attrs,
+            name_and_type,
+        } => {
+            out.write_u8(17)?;
+            out.write_u16(*bootstrap_attrs)?;
+            out.write_u16(*name_and_type)?;
+        }
+        Constant::InvokeDynamic {
+            bootstrap_attrs,
+            name_and_type,
+        } => {
+            out.write_u8(18)?;
+            out.write_u16(*bootstrap_attrs)?;
+            out.write_u16(*name_and_type)?;
+        }
+        Constant::Module(name) => {
+            out.write_u8(19)?;
+            out.write_u16(*name)?;
+        }
+        Constant::Package(name) => {
+            out.write_u8(20)?;
+            out.write_u16(*name)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_verification_info(
+    out: &mut ClassWriter<Vec<u8>>,
+    info: &VerificationInfo,
+) -> Result<(), WriteError> {
+    match info {
+        VerificationInfo::Top => out.write_u8(0)?,
+        VerificationInfo::Integer => out.write_u8(1)?,
+        VerificationInfo::Float => out.write_u8(2)?,
+        VerificationInfo::Double => out.write_u8(3)?,
+        VerificationInfo::Long => out.write_u8(4)?,
+        VerificationInfo::Null => out.write_u8(5)?,
+        VerificationInfo::UninitializedThis => out.write_u8(6)?,
+        VerificationInfo::Object { class } => {
+            out.write_u8(7)?;
+            out.write_u16(*class)?;
+        }
+        VerificationInfo::Uninitialized { offset } => {
+            out.write_u8(8)?;
+            out.write_u16(*offset)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_verification_infos(
+    out: &mut ClassWriter<Vec<u8>>,
+    infos: &[VerificationInfo],
+) -> Result<(), WriteError> {
+    out.write_u16_len("verification types", infos.len())?;
+    for info in infos {
+        write_verification_info(out, info)?;
+    }
+    Ok(())
+}
+
+fn write_stack_map_frame(
+    out: &mut ClassWriter<Vec<u8>>,
+    frame: &StackMapFrame,
+) -> Result<(), WriteError> {
+    match frame {
+        StackMapFrame::Same { offset_delta }
+        | StackMapFrame::SameLocals1StackFrame { offset_delta, .. }
+            if *offset_delta >= 64 =>
+        {
+            return Err(WriteError::FrameOffsetTooLarge {
+                offset_delta: *offset_delta,
+            });
+        }
+        StackMapFrame::Same { offset_delta } => out.write_u8(*offset_delta)?,
+        StackMapFrame::SameLocals1StackFrame { offset_delta, info } => {
+            out.write_u8(64 + *offset_delta)?;
+            write_verification_info(out, info)?;
+        }
+        StackMapFrame::SameLocals1StackFrameExtended { offset_delta, info } => {
+            out.write_u8(247)?;
+            out.write_u16(*offset_delta)?;
+            write_verification_info(out, info)?;
+        }
+        StackMapFrame::ChopFrame { chop, offset_delta } => {
+            if !(1..=3).contains(chop) {
+                return Err(WriteError::InvalidChopFrame { chop: *chop });
+            }
+            out.write_u8(251 - *chop)?;
+            out.write_u16(*offset_delta)?;
+        }
+        StackMapFrame::SameExtended { offset_delta } => {
+            out.write_u8(251)?;
+            out.write_u16(*offset_delta)?;
+        }
+        StackMapFrame::Append {
+            offset_delta,
+            items,
+        } => {
+            if !(1..=3).contains(&items.len()) {
+                return Err(WriteError::InvalidAppendFrame { len: items.len() });
+            }
+            out.write_u8(251 + items.len() as u8)?;
+            out.write_u16(*offset_delta)?;
+            for info in items {
+                write_verification_info(out, info)?;
+            }
+        }
+        StackMapFrame::Full {
+            offset_delta,
+            locals,
+            stack,
+        } => {
+            out.write_u8(255)?;
+            out.write_u16(*offset_delta)?;
+            write_verification_infos(out, locals)?;
+            write_verification_infos(out, stack)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_element_value(
+    out: &mut ClassWriter<Vec<u8>>,
+    value: &ElementValue,
+) -> Result<(), WriteError> {
+    match value {
+        ElementValue::Byte(idx) => {
+            out.write_u8(b'B')?;
+            out.write_u16(*idx)?;
+        }
+        ElementValue::Char(idx) => {
+            out.write_u8(b'C')?;
+            out.write_u16(*idx)?;
+        }
+        ElementValue::Double(idx) => {
+            out.write_u8(b'D')?;
+            out.write_u16(*idx)?;
+        }
+        ElementValue::Float(idx) => {
+            out.write_u8(b'F')?;
+            out.write_u16(*idx)?;
+        }
+        ElementValue::Int(idx) => {
+            out.write_u8(b'I')?;
+            out.write_u16(*idx)?;
+        }
+        ElementValue::Long(idx) => {
+            out.write_u8(b'J')?;
+            out.write_u16(*idx)?;
+        }
+        ElementValue::Short(idx) => {
+            out.write_u8(b'S')?;
+            out.write_u16(*idx)?;
+        }
+        ElementValue::Boolean(idx) => {
+            out.write_u8(b'Z')?;
+            out.write_u16(*idx)?;
+        }
+        ElementValue::String(idx) => {
+            out.write_u8(b's')?;
+            out.write_u16(*idx)?;
+        }
+        ElementValue::Enum {
+            type_name,
+            const_name,
+        } => {
+            out.write_u8(b'e')?;
+            out.write_u16(*type_name)?;
+            out.write_u16(*const_name)?;
+        }
+        ElementValue::Class(idx) => {
+            out.write_u8(b'c')?;
+            out.write_u16(*idx)?;
+        }
+        ElementValue::Annotation(annotation) => {
+            out.write_u8(b'@')?;
+            write_annotation(out, annotation)?;
+        }
+        ElementValue::Array(values) => {
+            out.write_u8(b'[')?;
+            out.write_u16_len("array element values", values.len())?;
+            for value in values {
+                write_element_value(out, value)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_annotation(
+    out: &mut ClassWriter<Vec<u8>>,
+    annotation: &Annotation,
+) -> Result<(), WriteError> {
+    out.write_u16(annotation.class)?;
+    out.write_u16_len("annotation elements", annotation.elements.len())?;
+    for element in &annotation.elements {
+        out.write_u16(element.name)?;
+        write_element_value(out, &element.value)?;
+    }
+    Ok(())
+}
+
+fn write_annotations(
+    out: &mut ClassWriter<Vec<u8>>,
+    annotations: &[Annotation],
+) -> Result<(), WriteError> {
+    out.write_u16_len("annotations", annotations.len())?;
+    for annotation in annotations {
+        write_annotation(out, annotation)?;
+    }
+    Ok(())
+}
+
+fn write_parameter_annotations(
+    out: &mut ClassWriter<Vec<u8>>,
+    params: &[Vec<Annotation>],
+) -> Result<(), WriteError> {
+    out.write_u8_len("annotated parameters", params.len())?;
+    for annotations in params {
+        write_annotations(out, annotations)?;
+    }
+    Ok(())
+}
+
+fn write_local_variable_locations(
+    out: &mut ClassWriter<Vec<u8>>,
+    table: &[LocalVariableLocationInfo],
+) -> Result<(), WriteError> {
+    out.write_u16_len("local variable locations", table.len())?;
+    for loc in table {
+        out.write_u16(loc.start_pc)?;
+        out.write_u16(loc.length)?;
+        out.write_u16(loc.index)?;
+    }
+    Ok(())
+}
+
+fn write_type_annotation(
+    out: &mut ClassWriter<Vec<u8>>,
+    annotation: &TypeAnnotation,
+) -> Result<(), WriteError> {
+    match &annotation.target {
+        TypeAnnotationTarget::ClassTypeParameter(param) => {
+            out.write_u8(0x00)?;
+            out.write_u8(*param)?;
+        }
+        TypeAnnotationTarget::MethodTypeParameter(param) => {
+            out.write_u8(0x01)?;
+            out.write_u8(*param)?;
+        }
+        TypeAnnotationTarget::SuperClass(idx) => {
+            out.write_u8(0x10)?;
+            out.write_u16(*idx)?;
+        }
+        TypeAnnotationTarget::ClassTypeParameterBound { param, bound } => {
+            out.write_u8(0x11)?;
+            out.write_u8(*param)?;
+            out.write_u8(*bound)?;
+        }
+        TypeAnnotationTarget::MethodTypeParameterBound { param, bound } => {
+            out.write_u8(0x12)?;
+            out.write_u8(*param)?;
+            out.write_u8(*bound)?;
+        }
+        TypeAnnotationTarget::FieldType => out.write_u8(0x13)?,
+        TypeAnnotationTarget::MethodReturnType => out.write_u8(0x14)?,
+        TypeAnnotationTarget::RecieverType => out.write_u8(0x15)?,
+        TypeAnnotationTarget::FormalParameterType(param) => {
+            out.write_u8(0x16)?;
+            out.write_u8(*param)?;
+        }
+        TypeAnnotationTarget::ThrowsType(idx) => {
+            out.write_u8(0x17)?;
+            out.write_u16(*idx)?;
+        }
+        TypeAnnotationTarget::LocalVariableType(table) => {
+            out.write_u8(0x40)?;
+            write_local_variable_locations(out, table)?;
+        }
+        TypeAnnotationTarget::ResourceVariableType(table) => {
+            out.write_u8(0x41)?;
+            write_local_variable_locations(out, table)?;
+        }
+        TypeAnnotationTarget::CatchParameterType(idx) => {
+            out.write_u8(0x42)?;
+            out.write_u16(*idx)?;
+        }
+        TypeAnnotationTarget::InstanceOfType(offset) => {
+            out.write_u8(0x43)?;
+            out.write_u16(*offset)?;
+        }
+        TypeAnnotationTarget::NewType(offset) => {
+            out.write_u8(0x44)?;
+            out.write_u16(*offset)?;
+        }
+        TypeAnnotationTarget::NewReferenceType(offset) => {
+            out.write_u8(0x45)?;
+            out.write_u16(*offset)?;
+        }
+        TypeAnnotationTarget::MethodReferenceType(offset) => {
+            out.write_u8(0x46)?;
+            out.write_u16(*offset)?;
+        }
+        TypeAnnotationTarget::CastType { offset, type_var } => {
+            out.write_u8(0x47)?;
+            out.write_u16(*offset)?;
+            out.write_u8(*type_var)?;
+        }
+        TypeAnnotationTarget::GenericConstructorTypeArgument { offset, type_var } => {
+            out.write_u8(0x48)?;
+            out.write_u16(*offset)?;
+            out.write_u8(*type_var)?;
+        }
+        TypeAnnotationTarget::GenericMethodTypeArgument { offset, type_var } => {
+            out.write_u8(0x49)?;
+            out.write_u16(*offset)?;
+            out.write_u8(*type_var)?;
+        }
+        TypeAnnotationTarget::GenericConstructorReferenceTypeArgument { offset, type_var } => {
+            out.write_u8(0x4a)?;
+            out.write_u16(*offset)?;
+            out.write_u8(*type_var)?;
+        }
+        TypeAnnotationTarget::GenericMethodReferenceTypeArgument { offset, type_var } => {
+            out.write_u8(0x4b)?;
+            out.write_u16(*offset)?;
+            out.write_u8(*type_var)?;
+        }
+    }
+
+    out.write_u8_len("type path segments", annotation.path.len())?;
+    for segment in &annotation.path {
+        match segment {
+            TypePathSegment::Array => {
+                out.write_u8(0)?;
+                out.write_u8(0)?;
+            }
+            TypePathSegment::NestedType => {
+                out.write_u8(1)?;
+                out.write_u8(0)?;
+            }
+            TypePathSegment::Wildcard => {
+                out.write_u8(2)?;
+                out.write_u8(0)?;
+            }
+            TypePathSegment::ParameterizedType(arg) => {
+                out.write_u8(3)?;
+                out.write_u8(*arg)?;
+            }
+        }
+    }
+
+    write_annotation(out, &annotation.annotation)
+}
+
+fn write_type_annotations(
+    out: &mut ClassWriter<Vec<u8>>,
+    annotations: &[TypeAnnotation],
+) -> Result<(), WriteError> {
+    out.write_u16_len("type annotations", annotations.len())?;
+    for annotation in annotations {
+        write_type_annotation(out, annotation)?;
+    }
+    Ok(())
+}
+
+struct ClassEncoder<'a> {
+    class: &'a ClassFile,
+}
+
+impl<'a> ClassEncoder<'a> {
+    fn attribute_name_index(&self, name: &'static str) -> Result<u16, WriteError> {
+        let jname = JStr::from_str(name).unwrap();
+        self.class
+            .consts
+            .iter()
+            .position(|c| matches!(c, Constant::Utf8(st) if **st == *jname))
+            .map(|pos| (pos + 1) as u16)
+            .ok_or(WriteError::MissingAttributeName(name))
+    }
+
+    fn write_code(
+        &self,
+        out: &mut ClassWriter<Vec<u8>>,
+        code: &CodeAttribute,
+    ) -> Result<(), WriteError> {
+        out.write_u16(code.max_stack)?;
+        out.write_u16(code.max_locals)?;
+        out.write_u32_len("bytes of code", code.code.len())?;
+        out.write_bytes(&code.code)?;
+        out.write_u16_len("exception handlers", code.exceptions.len())?;
+        for handler in &code.exceptions {
+            out.write_u16(handler.start_pc)?;
+            out.write_u16(handler.end_pc)?;
+            out.write_u16(handler.handler_pc)?;
+            out.write_u16(handler.catch_type)?;
+        }
+        self.write_attributes(out, &code.attributes)
+    }
+
+    fn write_attribute_body(
+        &self,
+        out: &mut ClassWriter<Vec<u8>>,
+        attr: &Attribute,
+    ) -> Result<(), WriteError> {
+        match attr {
+            Attribute::ConstantValue(idx)
+            | Attribute::Signature(idx)
+            | Attribute::SourceFile(idx)
+            | Attribute::ModuleMainClass(idx)
+            | Attribute::NestHost(idx)
+            | Attribute::LangItem(idx)
+            | Attribute::FillNativeMethod(idx) => out.write_u16(*idx)?,
+            Attribute::Code(code) => self.write_code(out, code)?,
+            Attribute::StackMapTable(frames) => {
+                out.write_u16_len("stack map frames", frames.len())?;
+                for frame in frames {
+                    write_stack_map_frame(out, frame)?;
+                }
+            }
+            Attribute::Exceptions(classes) => out.write_u16s("exceptions", classes)?,
+            Attribute::InnerClasses(classes) => {
+                out.write_u16_len("inner classes", classes.len())?;
+                for class in classes {
+                    out.write_u16(class.inner_class_info)?;
+                    out.write_u16(class.outer_class_info)?;
+                    out.write_u16(class.inner_name)?;
+                    out.write_u16(class.inner_flags)?;
+                }
+            }
+            Attribute::EnclosingMethod { class, method } => {
+                out.write_u16(*class)?;
+                out.write_u16(*method)?;
+            }
+            Attribute::Synthetic | Attribute::Deprecated => {}
+            Attribute::SourceDebugExtension(st) => out.write_bytes(st.as_bytes())?,
+            Attribute::LineNumberTable(lines) => {
+                out.write_u16_len("line numbers", lines.len())?;
+                for line in lines {
+                    out.write_u16(line.start_pc)?;
+                    out.write_u16(line.line_number)?;
+                }
+            }
+            Attribute::LocalVariableTable(vars) => {
+                out.write_u16_len("local variables", vars.len())?;
+                for var in vars {
+                    out.write_u16(var.start_pc)?;
+                    out.write_u16(var.length)?;
+                    out.write_u16(var.name)?;
+                    out.write_u16(var.descriptor)?;
+                    out.write_u16(var.index)?;
+                }
+            }
+            Attribute::LocalVariableTypeTable(vars) => {
+                out.write_u16_len("local variable types", vars.len())?;
+                for var in vars {
+                    out.write_u16(var.start_pc)?;
+                    out.write_u16(var.length)?;
+                    out.write_u16(var.name)?;
+                    out.write_u16(var.signature)?;
+                    out.write_u16(var.index)?;
+                }
+            }
+            Attribute::RuntimeVisibleAnnotations(annotations)
+            | Attribute::RuntimeInvisibleAnnotations(annotations) => {
+                write_annotations(out, annotations)?
+            }
+            Attribute::RuntimeVisibleParameterAnnotations(params)
+            | Attribute::RuntimeInvisibleParameterAnnotations(params) => {
+                write_parameter_annotations(out, params)?
+            }
+            Attribute::RuntimeVisibleTypeAnnotations(annotations)
+            | Attribute::RuntimeInvisibleTypeAnnotations(annotations) => {
+                write_type_annotations(out, annotations)?
+            }
+            Attribute::AnnotationDefault(value) => write_element_value(out, value)?,
+            Attribute::BootstrapMethods(methods) => {
+                out.write_u16_len("bootstrap methods", methods.len())?;
+                for method in methods {
+                    out.write_u16(method.href)?;
+                    out.write_u16s("bootstrap arguments", &method.args)?;
+                }
+            }
+            Attribute::MethodParameters(params) => {
+                out.write_u8_len("method parameters", params.len())?;
+                for param in params {
+                    out.write_u16(param.name)?;
+                    out.write_u16(param.access)?;
+                }
+            }
+            Attribute::Module(module) => {
+                out.write_u16(module.name)?;
+                out.write_u16(module.access)?;
+                out.write_u16(module.version)?;
+                out.write_u16_len("module requires", module.requires.len())?;
+                for require in &module.requires {
+                    out.write_u16(require.requires)?;
+                    out.write_u16(require.flags)?;
+                    out.write_u16(require.version)?;
+                }
+                for (what, exports) in [
+                    ("module exports", &module.exports),
+                    ("module opens", &module.opens),
+                ] {
+                    out.write_u16_len(what, exports.len())?;
+                    for export in exports {
+                        out.write_u16(export.exports)?;
+                        out.write_u16(export.flags)?;
+                        out.write_u16s(what, &export.to)?;
+                    }
+                }
+                out.write_u16s("module uses", &module.uses)?;
+                out.write_u16_len("module provides", module.provides.len())?;
+                for provide in &module.provides {
+                    out.write_u16(provide.provides)?;
+                    out.write_u16s("module provides", &provide.with)?;
+                }
+            }
+            Attribute::ModulePackage(packages) => out.write_u16s("module packages", packages)?,
+            Attribute::NestMembers(classes) => out.write_u16s("nest members", classes)?,
+            Attribute::Record(components) => {
+                out.write_u16_len("record components", components.len())?;
+                for component in components {
+                    out.write_u16(component.name)?;
+                    out.write_u16(component.descriptor)?;
+                    self.write_attributes(out, &component.attributes)?;
+                }
+            }
+            Attribute::PermittedSubclasses(classes) => {
+                out.write_u16s("permitted subclasses", classes)?
+            }
+            Attribute::Availability(availability) => {
+                let (kind, val, default) = match availability {
+                    Availability::From { ver, default } => (0, *ver, *default),
+                    Availability::Removed { ver, default } => (1, *ver, *default),
+                    Availability::Unstable { feature, default } => (2, *feature, *default),
+                };
+                out.write_u8(kind)?;
+                out.write_u16(val)?;
+                out.write_u8(default as u8)?;
+            }
+            Attribute::Unresolved { content, .. } => out.write_bytes(content)?,
+        }
+        Ok(())
+    }
+
+    fn write_attribute(
+        &self,
+        out: &mut ClassWriter<Vec<u8>>,
+        attr: &Attribute,
+    ) -> Result<(), WriteError> {
+        let name = match (attr, attr.name()) {
+            (Attribute::Unresolved { name, .. }, _) => *name,
+            (_, Some(name)) => self.attribute_name_index(name)?,
+            (_, None) => unreachable!("only Unresolved attributes have no name"),
+        };
+        let mut body = ClassWriter::new(Vec::new());
+        self.write_attribute_body(&mut body, attr)?;
+        let body = body.into_inner();
+        out.write_u16(name)?;
+        out.write_u32_len("bytes in attribute", body.len())?;
+        out.write_bytes(&body)?;
+        Ok(())
+    }
+
+    fn write_attributes(
+        &self,
+        out: &mut ClassWriter<Vec<u8>>,
+        attrs: &[Attribute],
+    ) -> Result<(), WriteError> {
+        out.write_u16_len("attributes", attrs.len())?;
+        for attr in attrs {
+            self.write_attribute(out, attr)?;
+        }
+        Ok(())
+    }
+
+    fn write_class(&self, out: &mut ClassWriter<Vec<u8>>) -> Result<(), WriteError> {
+        let class = self.class;
+        out.write_u32(consts::MAGIC)?;
+        out.write_u16(class.min)?;
+        out.write_u16(class.maj)?;
+        let slots = class.consts.as_slice().len();
+        if slots > MAX_SLOTS {
+            return Err(WriteError::ConstantPoolOverflow { slots });
+        }
+        out.write_u16(slots as u16 + 1)?;
+        for constant in &class.consts {
+            write_constant(out, constant)?;
+        }
+        out.write_u16(class.acc)?;
+        out.write_u16(class.this)?;
+        out.write_u16(class.supercl)?;
+        out.write_u16s("interfaces", &class.interfaces)?;
+        out.write_u16_len("fields", class.fields.len())?;
+        for field in &class.fields {
+            out.write_u16(field.acc)?;
+            out.write_u16(field.name)?;
+            out.write_u16(field.descriptor)?;
+            self.write_attributes(out, &field.attributes)?;
+        }
+        out.write_u16_len("methods", class.methods.len())?;
+        for method in &class.methods {
+            out.write_u16(method.acc)?;
+            out.write_u16(method.name)?;
+            out.write_u16(method.descriptor)?;
+            self.write_attributes(out, &method.attributes)?;
+        }
+        self.write_attributes(out, &class.attributes)
+    }
+}
+
+impl ClassFile {
+    ///
+    /// Writes the class in the class file format.
+    ///
+    /// `consts` is written as-is, so it must already contain the names of every attribute being written.
+    pub fn write<W: Write>(&self, w: &mut W) -> Result<(), WriteError> {
+        self.write_with_options(w, &WriteOptions::default())
+    }
+
+    ///
+    /// Writes the class in the class file format, ordering the constant pool as given by `opts`.
+    ///
+    /// With [`ConstantOrder::Canonical`], the class is reordered in a copy, leaving `self` untouched
+    pub fn write_with_options<W: Write>(
+        &self,
+        w: &mut W,
+        opts: &WriteOptions,
+    ) -> Result<(), WriteError> {
+        w.write_all(&self.encode(opts)?)?;
+        Ok(())
+    }
+
+    ///
+    /// Writes the class in the class file format into a new buffer, like [`ClassFile::write`]
+    pub fn write_to_vec(&self) -> Result<Vec<u8>, WriteError> {
+        self.encode(&WriteOptions::default())
+    }
+
+    ///
+    /// The SHA-256 digest of the class written with [`ConstantOrder::Canonical`], so classes that differ only in the order of their constant pools have the same digest
+    #[cfg(feature = "sha2")]
+    pub fn sha256(&self) -> Result<[u8; 32], WriteError> {
+        use sha2::{Digest, Sha256};
+
+        let bytes = self.encode(&WriteOptions {
+            constant_order: ConstantOrder::Canonical,
+        })?;
+        Ok(Sha256::digest(&bytes).into())
+    }
+
+    fn encode(&self, opts: &WriteOptions) -> Result<Vec<u8>, WriteError> {
+        let canonical;
+        let class = match opts.constant_order {
+            ConstantOrder::Preserve => self,
+            ConstantOrder::Canonical => {
+                let mut class = self.clone();
+                class.canonicalize_constant_pool();
+                canonical = class;
+                &canonical
+            }
+        };
+        let mut out = ClassWriter::new(Vec::new());
+        ClassEncoder { class }.write_class(&mut out)?;
+        Ok(out.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ConstantOrder, WriteError, WriteOptions};
+    use crate::{
+        class::{Attribute, ClassFile, Constant, StackMapFrame, VerificationInfo},
+        string::JStr,
+        testutil,
+    };
+
+    #[test]
+    fn javac_fixtures_roundtrip() {
+        for bytes in testutil::ALL {
+            testutil::assert_roundtrip(bytes);
+        }
+    }
+
+    #[test]
+    fn roundtrip_is_stable() {
+        for bytes in testutil::ALL {
+            let once = testutil::read(bytes).write_to_vec().unwrap();
+            let twice = testutil::read(&once).write_to_vec().unwrap();
+            assert_eq!(once, twice);
+        }
+    }
+
+    #[test]
+    fn utf8_longer_than_65535_bytes_is_rejected() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        let long = JStr::from_modified_utf8(&[b'a'; 70000]).unwrap().to_owned();
+        class.consts.push(Constant::Utf8(long)).unwrap();
+        match class.write_to_vec() {
+            Err(WriteError::Utf8TooLong { len: 70000 }) => {}
+            other => panic!("expected Utf8TooLong, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn oversized_constant_pool_is_rejected() {
+        let mut class = testutil::read(testutil::FIXTURE);
+        let mut constants = class.consts.clone().into_constants();
+        constants.resize(crate::pool::MAX_SLOTS + 1, Constant::Int(0));
+        class.consts = constants.into();
+        match class.write_to_vec() {
+            Err(WriteError::ConstantPoolOverflow { slots: 65535 }) => {}
+            other => panic!("expected ConstantPoolOverflow, got {:?}", other),
+        }
+    }
+
+    fn with_stack_map(frames: Vec<StackMapFrame>) -> ClassFile {
+        let mut class = testutil::read(testutil::FIXTURE);
+        let code = class
+            .methods
+            .iter_mut()
+            .find_map(|method| method.code_mut())
+            .unwrap();
+        code.attributes
+            .retain(|attr| !matches!(attr, Attribute::StackMapTable(_)));
+        code.attributes.push(Attribute::StackMapTable(frames));
+        class
+    }
+
+    fn stack_map(class: &ClassFile) -> &[StackMapFrame] {
+        class
+            .methods
+            .iter()
+            .find_map(|method| method.code())
+            .unwrap()
+            .attributes
+            .iter()
+            .find_map(|attr| match attr {
+                Attribute::StackMapTable(frames) => Some(&frames[..]),
+                _ => None,
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn chop_frames_roundtrip() {
+        for chop in 1..=3 {
+            let class = with_stack_map(vec![StackMapFrame::ChopFrame {
+                chop,
+                offset_delta: 7,
+            }]);
+            let read = testutil::read(&class.write_to_vec().unwrap());
+            match stack_map(&read) {
+                [StackMapFrame::ChopFrame {
+                    chop: read_chop,
+                    offset_delta: 7,
+                }] => assert_eq!(*read_chop, chop),
+                other => panic!("expected a chop frame, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn unencodable_frames_are_rejected() {
+        for chop in [0, 4] {
+            let class = with_stack_map(vec![StackMapFrame::ChopFrame {
+                chop,
+                offset_delta: 0,
+            }]);
+            match class.write_to_vec() {
+                Err(WriteError::InvalidChopFrame { chop: got }) => assert_eq!(got, chop),
+                other => panic!("expected InvalidChopFrame, got {:?}", other),
+            }
+        }
+        for len in [0, 4] {
+            let class = with_stack_map(vec![StackMapFrame::Append {
+                offset_delta: 0,
+                items: vec![VerificationInfo::Integer; len],
+            }]);
+            match class.write_to_vec() {
+                Err(WriteError::InvalidAppendFrame { len: got }) => assert_eq!(got, len),
+                other => panic!("expected InvalidAppendFrame, got {:?}", other),
+            }
+        }
+        for frame in [
+            StackMapFrame::Same { offset_delta: 64 },
+            StackMapFrame::SameLocals1StackFrame {
+                offset_delta: 64,
+                info: VerificationInfo::Integer,
+            },
+        ] {
+            match with_stack_map(vec![frame]).write_to_vec() {
+                Err(WriteError::FrameOffsetTooLarge { offset_delta: 64 }) => {}
+                other => panic!("expected FrameOffsetTooLarge, got {:?}", other),
+            }
+        }
+    }
+
+    fn write_with(class: &crate::class::ClassFile, constant_order: ConstantOrder) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        class
+            .write_with_options(&mut bytes, &WriteOptions { constant_order })
+            .unwrap();
+        bytes
+    }
+
+    #[test]
+    fn preserved_and_canonical_constant_order() {
+        let class = testutil::read(testutil::FIXTURE);
+        let preserved = write_with(&class, ConstantOrder::Preserve);
+        assert_eq!(preserved, testutil::FIXTURE);
+        assert_eq!(preserved, class.write_to_vec().unwrap());
+
+        let canonical = write_with(&class, ConstantOrder::Canonical);
+        assert_ne!(canonical, preserved);
+        assert_eq!(canonical.len(), preserved.len());
+        // The class itself is not reordered
+        assert_eq!(class.write_to_vec().unwrap(), preserved);
+
+        // The same constants in another order give the same canonical bytes, but different preserved ones
+        let mut reversed = class.clone();
+        let mut order: Vec<u16> = class.consts.entries().map(|(idx, _)| idx).collect();
+        order.reverse();
+        reversed.reorder_constants(&order);
+        assert_ne!(write_with(&reversed, ConstantOrder::Preserve), preserved);
+        assert_eq!(write_with(&reversed, ConstantOrder::Canonical), canonical);
+
+        // Both keep the second slots of Longs and Doubles
+        for bytes in [&preserved, &canonical] {
+            let read = testutil::read(bytes);
+            assert_eq!(read.validate_references(), Ok(()));
+            for (idx, constant) in read.consts.entries() {
+                if matches!(constant, Constant::Long(_) | Constant::Double(_)) {
+                    assert!(matches!(
+                        read.consts.get(idx + 1),
+                        Some(Constant::LongOrDoubleHigh)
+                    ));
+                }
+            }
+            assert_eq!(read.content_hash().unwrap(), class.content_hash().unwrap());
+        }
+    }
+
+    #[cfg(feature = "sha2")]
+    #[test]
+    fn sha256_ignores_constant_order() {
+        use crate::bytecode::Instruction;
+
+        let class = testutil::read(testutil::FIXTURE);
+        let digest = class.sha256().unwrap();
+        assert_eq!(testutil::read(testutil::FIXTURE).sha256().unwrap(), digest);
+
+        let mut reversed = class.clone();
+        let mut order: Vec<u16> = class.consts.entries().map(|(idx, _)| idx).collect();
+        order.reverse();
+        reversed.reorder_constants(&order);
+        assert_ne!(
+            reversed.write_to_vec().unwrap(),
+            class.write_to_vec().unwrap()
+        );
+        assert_eq!(reversed.sha256().unwrap(), digest);
+
+        // `loop` starts with iconst_0, which becomes iconst_1
+        let mut changed = class.clone();
+        let index = changed
+            .methods
+            .iter()
+            .position(|method| changed.consts.utf8(method.name).unwrap().as_bytes() == b"loop")
+            .unwrap();
+        let code = changed.methods[index].code_mut().unwrap();
+        assert_eq!(code.code[0], 0x03);
+        code.code[0] = 0x04;
+        assert!(matches!(
+            crate::bytecode::Instructions::new(&code.code).next(),
+            Some(Ok((0, Instruction::IConst(1))))
+        ));
+        assert_ne!(changed.sha256().unwrap(), digest);
+    }
+}
@@ -0,0 +1,174 @@
+use std::io::Write;
+
+use crate::write::WriteError;
+
+///
+/// Writes the big-endian primitives of the class file format, checking that counts and lengths fit in the fields that hold them
+pub(crate) struct ClassWriter<W> {
+    inner: W,
+}
+
+impl<W: Write> ClassWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        Self { inner }
+    }
+
+    pub(crate) fn into_inner(self) -> W {
+        self.inner
+    }
+
+    pub(crate) fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), WriteError> {
+        self.inner.write_all(bytes)?;
+        Ok(())
+    }
+
+    pub(crate) fn write_u8(&mut self, v: u8) -> Result<(), WriteError> {
+        self.write_bytes(&[v])
+    }
+
+    pub(crate) fn write_u16(&mut self, v: u16) -> Result<(), WriteError> {
+        self.write_bytes(&v.to_be_bytes())
+    }
+
+    pub(crate) fn write_u32(&mut self, v: u32) -> Result<(), WriteError> {
+        self.write_bytes(&v.to_be_bytes())
+    }
+
+    pub(crate) fn write_u64(&mut self, v: u64) -> Result<(), WriteError> {
+        self.write_bytes(&v.to_be_bytes())
+    }
+
+    pub(crate) fn write_i32(&mut self, v: i32) -> Result<(), WriteError> {
+        self.write_bytes(&v.to_be_bytes())
+    }
+
+    pub(crate) fn write_i64(&mut self, v: i64) -> Result<(), WriteError> {
+        self.write_bytes(&v.to_be_bytes())
+    }
+
+    /// Writes the exact bit pattern of a float, so NaN payloads are preserved
+    pub(crate) fn write_f32(&mut self, v: f32) -> Result<(), WriteError> {
+        self.write_u32(v.to_bits())
+    }
+
+    /// Writes the exact bit pattern of a double, so NaN payloads are preserved
+    pub(crate) fn write_f64(&mut self, v: f64) -> Result<(), WriteError> {
+        self.write_u64(v.to_bits())
+    }
+
+    pub(crate) fn write_u8_len(
+        &mut self,
+        what: &'static str,
+        len: usize,
+    ) -> Result<(), WriteError> {
+        if len > u8::MAX as usize {
+            return Err(WriteError::TooManyEntries { what, len });
+        }
+        self.write_u8(len as u8)
+    }
+
+    pub(crate) fn write_u16_len(
+        &mut self,
+        what: &'static str,
+        len: usize,
+    ) -> Result<(), WriteError> {
+        if len > u16::MAX as usize {
+            return Err(WriteError::TooManyEntries { what, len });
+        }
+        self.write_u16(len as u16)
+    }
+
+    pub(crate) fn write_u32_len(
+        &mut self,
+        what: &'static str,
+        len: usize,
+    ) -> Result<(), WriteError> {
+        if len > u32::MAX as usize {
+            return Err(WriteError::TooManyEntries { what, len });
+        }
+        self.write_u32(len as u32)
+    }
+
+    /// Writes a u16 count followed by the values
+    pub(crate) fn write_u16s(
+        &mut self,
+        what: &'static str,
+        vals: &[u16],
+    ) -> Result<(), WriteError> {
+        self.write_u16_len(what, vals.len())?;
+        for &v in vals {
+            self.write_u16(v)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn primitives_are_big_endian() {
+        let mut w = ClassWriter::new(Vec::new());
+        w.write_u8(0x01).unwrap();
+        w.write_u16(0x0203).unwrap();
+        w.write_u32(0x04050607).unwrap();
+        w.write_u64(0x08090a0b0c0d0e0f).unwrap();
+        w.write_i32(-2).unwrap();
+        w.write_i64(-3).unwrap();
+        w.write_f32(1.5).unwrap();
+        w.write_f64(-0.25).unwrap();
+        w.write_u16s("values", &[0xabcd, 0x1234]).unwrap();
+        w.write_bytes(b"hi").unwrap();
+
+        let mut expected = (1..=15).collect::<Vec<u8>>();
+        expected.extend_from_slice(&[0xff, 0xff, 0xff, 0xfe]);
+        expected.extend_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfd]);
+        expected.extend_from_slice(&[0x3f, 0xc0, 0, 0]);
+        expected.extend_from_slice(&[0xbf, 0xd0, 0, 0, 0, 0, 0, 0]);
+        expected.extend_from_slice(&[0, 2, 0xab, 0xcd, 0x12, 0x34]);
+        expected.extend_from_slice(b"hi");
+        assert_eq!(w.into_inner(), expected);
+    }
+
+    #[test]
+    fn lengths_must_fit_their_field() {
+        let mut w = ClassWriter::new(Vec::new());
+        w.write_u8_len("things", 255).unwrap();
+        w.write_u16_len("things", 65535).unwrap();
+        w.write_u32_len("things", 65536).unwrap();
+        assert_eq!(w.into_inner(), [255, 255, 255, 0, 1, 0, 0]);
+
+        let mut w = ClassWriter::new(Vec::new());
+        assert!(matches!(
+            w.write_u8_len("things", 256),
+            Err(WriteError::TooManyEntries {
+                what: "things",
+                len: 256
+            })
+        ));
+        assert!(matches!(
+            w.write_u16_len("things", 65536),
+            Err(WriteError::TooManyEntries {
+                what: "things",
+                len: 65536
+            })
+        ));
+        assert!(matches!(
+            w.write_u16s("values", &vec![0; 65536]),
+            Err(WriteError::TooManyEntries {
+                what: "values",
+                len: 65536
+            })
+        ));
+        assert!(w.into_inner().is_empty());
+    }
+
+    #[test]
+    fn io_errors_are_reported() {
+        let mut buf = [0u8; 1];
+        let mut w = ClassWriter::new(&mut buf[..]);
+        w.write_u8(1).unwrap();
+        assert!(matches!(w.write_u8(2), Err(WriteError::Io(_))));
+    }
+}
@@ -0,0 +1,94 @@
+import java.io.IOException;
+import java.util.ArrayList;
+import java.util.List;
+import java.util.function.Supplier;
+
+public class Fixture {
+    public static final int ANSWER = 42;
+    public static final String GREETING = "hello";
+    public static final long BIG = 1L << 40;
+    public static final double HALF = 0.5;
+    static final String NUL = "a\0b";
+    static final String EMOJI = "\uD83D\uDE00";
+    static final String LONE = "x\uD800y";
+
+    String name;
+
+    public class Inner {
+        int value() {
+            return ANSWER;
+        }
+    }
+
+    public Runnable anonymous() {
+        return new Runnable() {
+            public void run() {
+                System.out.println(name);
+            }
+        };
+    }
+
+    public Supplier<String> lambda(String suffix) {
+        return () -> name + suffix;
+    }
+
+    public static int loop(int n) {
+        int total = 0;
+        for (int i = 0; i < n; i++) {
+            total += i;
+        }
+        return total;
+    }
+
+    public static int classify(int x) {
+        switch (x) {
+            case 1: return 10;
+            case 2: return 20;
+            case 7: return 70;
+            default: return 0;
+        }
+    }
+
+    public void handlers() throws InterruptedException {
+        try {
+            io();
+        } catch (IOException e) {
+            name = "io";
+        } catch (IllegalStateException e) {
+            name = "state";
+        } finally {
+            name = name + "!";
+        }
+    }
+
+    public int guarded(int x) {
+        try {
+            if (x < 0) {
+                return -1;
+            }
+            io();
+        } catch (IOException e) {
+            return 1;
+        } catch (InterruptedException e) {
+            return 2;
+        }
+        return 0;
+    }
+
+    public void io() throws IOException, InterruptedException {
+        if (name == null) {
+            throw new IOException();
+        }
+    }
+
+    public List<String> names(List<String> input) {
+        List<String> out = new ArrayList<>(input);
+        out.add(name);
+        return out;
+    }
+
+    @Deprecated
+    public void old() {}
+
+    public void params(final int first, String second) {}
+}
@@ -0,0 +1,17 @@
+public interface Iface {
+    int CONSTANT = 1;
+
+    void run();
+
+    default int twice() {
+        return helper() * 2;
+    }
+
+    static Iface noop() {
+        return () -> {};
+    }
+
+    private int helper() {
+        return CONSTANT;
+    }
+}
@@ -0,0 +1,3 @@
+import java.util.List;
+
+public record Point(int x, List<String> tags) {}
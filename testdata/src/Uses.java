@@ -0,0 +1,16 @@
+import com.old.Foo;
+
+import java.util.List;
+
+public class Uses {
+    Foo foo;
+    List<Foo> all;
+
+    public Foo follow(Foo start) {
+        return start.next;
+    }
+
+    public Foo[] array() {
+        return new Foo[] { foo };
+    }
+}
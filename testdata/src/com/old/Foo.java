@@ -0,0 +1,5 @@
+package com.old;
+
+public class Foo {
+    public Foo next;
+}
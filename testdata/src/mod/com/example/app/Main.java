@@ -0,0 +1,7 @@
+package com.example.app;
+
+public class Main {
+    public static void main(String[] args) {
+        System.out.println(com.example.util.Util.NAME);
+    }
+}
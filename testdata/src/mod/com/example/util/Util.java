@@ -0,0 +1,5 @@
+package com.example.util;
+
+public class Util {
+    public static final String NAME = "util";
+}
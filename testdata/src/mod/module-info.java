@@ -0,0 +1,3 @@
+module com.example {
+    exports com.example.app;
+}